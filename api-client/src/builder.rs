@@ -0,0 +1,509 @@
+//! Building an [`ApiClient`] with custom middleware.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use http::header::{self, HeaderName, HeaderValue, InvalidHeaderValue};
+use http::Uri;
+use hyperdriver::client::SharedClientService;
+use hyperdriver::service::SharedService;
+use hyperdriver::Body;
+use tower::Layer as _;
+use tower_http::set_header::SetRequestHeaderLayer;
+
+use crate::authentication::{Authentication, AuthenticationLayer};
+use crate::proxy::{ProxyConfig, ProxyError, ProxyTransport};
+use crate::resolve::{Overrides, ResolveTransport};
+use crate::stats::{Counters, StatsLayer};
+use crate::tls::{TlsConfig, TlsError};
+#[cfg(unix)]
+use crate::unix::UnixTransport;
+use crate::{ApiClient, InnerClient};
+
+type ClientService = SharedClientService<Body, Body>;
+type BoxLayer = Box<dyn FnOnce(ClientService) -> ClientService + Send + Sync>;
+
+/// Which proxy a new hyperdriver client connects through.
+#[derive(Debug)]
+enum Proxy {
+    /// The proxy set in the environment, if any.
+    Env,
+
+    /// An explicitly configured proxy.
+    Config(ProxyConfig),
+
+    /// Always connect directly.
+    Disabled,
+}
+
+impl Proxy {
+    fn resolve(self) -> Option<ProxyConfig> {
+        match self {
+            Proxy::Env => ProxyConfig::from_env().unwrap_or_else(|error| {
+                tracing::warn!("Ignoring proxy from environment: {error}");
+                None
+            }),
+            Proxy::Config(proxy) => Some(proxy),
+            Proxy::Disabled => None,
+        }
+    }
+}
+
+/// How requests leave the client, below any added layers.
+enum Transport {
+    /// A new hyperdriver client.
+    Client {
+        tls: Option<rustls::ClientConfig>,
+        connect_timeout: Option<Duration>,
+        proxy: Proxy,
+        overrides: Overrides,
+    },
+
+    /// A new hyperdriver client which connects to a unix socket.
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+
+    /// A service provided by the caller.
+    Service(ClientService),
+}
+
+/// A builder for an [`ApiClient`], created with [`ApiClient::builder`].
+///
+/// Every client authenticates requests and records [`PoolStats`](crate::PoolStats).
+/// Service crates can set default headers, and add their own [`tower::Layer`]s
+/// below those, e.g. to retry or inspect requests to the service.
+///
+/// Clients connect through the proxy set by `HTTPS_PROXY` in the environment, if
+/// any, unless a proxy is set with [`ApiClientBuilder::with_proxy`].
+///
+/// ```
+/// # use api_client::{ApiClient, BearerAuth};
+/// let client = ApiClient::builder(
+///     "https://api.example.com/".parse().unwrap(),
+///     BearerAuth::new("token"),
+/// )
+/// .with_user_agent("example/1.0")
+/// .unwrap()
+/// .with_connect_timeout(std::time::Duration::from_secs(5))
+/// .build();
+/// ```
+pub struct ApiClientBuilder<A> {
+    base: Uri,
+    authentication: A,
+    transport: Transport,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    layers: Vec<BoxLayer>,
+    max_response_size: Option<usize>,
+}
+
+impl<A: fmt::Debug> fmt::Debug for ApiClientBuilder<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiClientBuilder")
+            .field("base", &self.base)
+            .field("authentication", &self.authentication)
+            .field("headers", &self.headers)
+            .field("layers", &self.layers.len())
+            .field("max_response_size", &self.max_response_size)
+            .finish()
+    }
+}
+
+impl<A> ApiClientBuilder<A>
+where
+    A: Authentication + Send + Sync + 'static,
+{
+    pub(crate) fn new(base: Uri, authentication: A) -> Self {
+        Self {
+            base,
+            authentication,
+            transport: Transport::Client {
+                tls: None,
+                connect_timeout: None,
+                proxy: Proxy::Env,
+                overrides: Overrides::default(),
+            },
+            headers: Vec::new(),
+            layers: Vec::new(),
+            max_response_size: None,
+        }
+    }
+
+    /// Replace the base URL which request endpoints are joined to.
+    pub fn with_base(mut self, base: Uri) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Send a header with every request, unless the request sets it itself.
+    ///
+    /// If the same header is set more than once, the last value is used.
+    pub fn with_header<V>(mut self, name: HeaderName, value: V) -> Self
+    where
+        V: Into<HeaderValue>,
+    {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Send a `User-Agent` header with every request, unless the request sets it itself.
+    pub fn with_user_agent(self, user_agent: &str) -> Result<Self, InvalidHeaderValue> {
+        let value = HeaderValue::from_str(user_agent)?;
+        Ok(self.with_header(header::USER_AGENT, value))
+    }
+
+    /// Use custom TLS settings, e.g. to present a client certificate to a service
+    /// which requires mutual TLS.
+    ///
+    /// This replaces any service set with [`ApiClientBuilder::with_inner_service`],
+    /// or socket set with `with_unix_socket`.
+    pub fn with_tls(mut self, tls: &TlsConfig) -> Result<Self, TlsError> {
+        let config = tls.client_config()?;
+        match &mut self.transport {
+            Transport::Client { tls, .. } => *tls = Some(config),
+            transport => {
+                *transport = Transport::Client {
+                    tls: Some(config),
+                    connect_timeout: None,
+                    proxy: Proxy::Env,
+                    overrides: Overrides::default(),
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    /// Give up on connecting to the service after `timeout`.
+    ///
+    /// This has no effect on a service set with [`ApiClientBuilder::with_inner_service`],
+    /// which manages its own connections.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        if let Transport::Client {
+            connect_timeout, ..
+        } = &mut self.transport
+        {
+            *connect_timeout = Some(timeout);
+        }
+        self
+    }
+
+    /// Fail to collect response bodies larger than `limit` bytes, rather than
+    /// buffering them in memory.
+    ///
+    /// Collecting an oversized body, e.g. with [`ResponseBodyExt::json`](crate::response::ResponseBodyExt::json)
+    /// or in a paginated stream, returns a [`BodyTooLarge`](crate::error::BodyTooLarge)
+    /// error. Bodies are not limited by default.
+    pub fn with_max_response_size(mut self, limit: usize) -> Self {
+        self.max_response_size = Some(limit);
+        self
+    }
+
+    /// Connect through a proxy, rather than the one set in the environment.
+    ///
+    /// This has no effect on a service set with [`ApiClientBuilder::with_inner_service`],
+    /// which manages its own connections.
+    pub fn with_proxy(mut self, config: ProxyConfig) -> Self {
+        if let Transport::Client { proxy, .. } = &mut self.transport {
+            *proxy = Proxy::Config(config);
+        }
+        self
+    }
+
+    /// Connect through the proxy set in the environment, returning an error if it
+    /// is invalid rather than connecting directly.
+    pub fn with_env_proxy(self) -> Result<Self, ProxyError> {
+        Ok(match ProxyConfig::from_env()? {
+            Some(config) => self.with_proxy(config),
+            None => self.without_proxy(),
+        })
+    }
+
+    /// Always connect directly, ignoring any proxy set in the environment.
+    pub fn without_proxy(mut self) -> Self {
+        if let Transport::Client { proxy, .. } = &mut self.transport {
+            *proxy = Proxy::Disabled;
+        }
+        self
+    }
+
+    /// Connect to `addr` for requests to `host`, rather than looking it up in DNS,
+    /// e.g. to send requests for `api.github.com` to a mock server in tests.
+    ///
+    /// The port in `addr` is used whatever the request's port, and connections
+    /// to overridden hosts never go through a proxy. Requests still use `host`
+    /// for the `Host` header and TLS.
+    ///
+    /// This has no effect on a service set with [`ApiClientBuilder::with_inner_service`],
+    /// which manages its own connections.
+    pub fn with_resolve(mut self, host: &str, addr: SocketAddr) -> Self {
+        if let Transport::Client { overrides, .. } = &mut self.transport {
+            overrides.insert(host, addr);
+        }
+        self
+    }
+
+    /// Send every request to a unix socket, e.g. to talk to a local daemon, rather
+    /// than connecting to the request's host.
+    ///
+    /// The base URL still sets the `Host` header, which some daemons check.
+    #[cfg(unix)]
+    pub fn with_unix_socket(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.transport = Transport::Unix(path.into());
+        self
+    }
+
+    /// Send requests through a service, rather than a new hyperdriver client, e.g.
+    /// to share a connection pool between clients, or to mock responses in tests.
+    pub fn with_inner_service<S>(mut self, inner: S) -> Self
+    where
+        S: tower::Service<
+                http::Request<Body>,
+                Response = http::Response<Body>,
+                Error = hyperdriver::client::Error,
+            > + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        self.transport = Transport::Service(SharedService::new(inner));
+        self
+    }
+
+    /// Add a layer of middleware between the client and the transport.
+    ///
+    /// Layers see requests after they are authenticated and default headers are
+    /// set. The first layer added is the outermost, so it sees each request first
+    /// and each response last.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: tower::Layer<ClientService> + Send + Sync + 'static,
+        L::Service: tower::Service<
+                http::Request<Body>,
+                Response = http::Response<Body>,
+                Error = hyperdriver::client::Error,
+            > + Clone
+            + Send
+            + Sync
+            + 'static,
+        <L::Service as tower::Service<http::Request<Body>>>::Future: Send + 'static,
+    {
+        self.layers.push(Box::new(move |inner| {
+            SharedService::new(layer.layer(inner))
+        }));
+        self
+    }
+
+    /// Build the client.
+    pub fn build(self) -> ApiClient<A> {
+        let transport = match self.transport {
+            Transport::Client {
+                tls,
+                connect_timeout,
+                proxy,
+                overrides,
+            } => {
+                let mut builder = hyperdriver::Client::build_tcp_http();
+                if connect_timeout.is_some() {
+                    builder.transport().connect_timeout = connect_timeout;
+                }
+
+                // TLS is negotiated over the proxy tunnel, so it wraps the proxy transport.
+                let proxy = proxy
+                    .resolve()
+                    .map(|proxy| ProxyTransport::new(proxy, connect_timeout));
+                if proxy.is_some() || !overrides.is_empty() {
+                    let builder = builder.with_transport(ResolveTransport::new(
+                        overrides,
+                        proxy,
+                        connect_timeout,
+                    ));
+                    match tls {
+                        Some(config) => builder.with_tls(config),
+                        None => builder.with_default_tls(),
+                    }
+                    .build_service()
+                } else {
+                    match tls {
+                        Some(config) => builder.with_tls(config),
+                        None => builder.with_default_tls(),
+                    }
+                    .build_service()
+                }
+            }
+            #[cfg(unix)]
+            Transport::Unix(path) => hyperdriver::Client::build_tcp_http()
+                .with_transport(UnixTransport::new(path))
+                .with_default_tls()
+                .build_service(),
+            Transport::Service(service) => service,
+        };
+
+        // Apply the last layer first, so that the first layer ends up outermost.
+        let inner = self
+            .layers
+            .into_iter()
+            .rev()
+            .fold(transport, |inner, layer| layer(inner));
+        let inner = self
+            .headers
+            .into_iter()
+            .fold(inner, |inner, (name, value)| {
+                SharedService::new(SetRequestHeaderLayer::if_not_present(name, value).layer(inner))
+            });
+
+        let authentication = Arc::new(ArcSwap::new(Arc::new(self.authentication)));
+        let stats = Arc::new(Counters::default());
+
+        let service = tower::ServiceBuilder::new()
+            .layer(SharedService::layer())
+            .layer(StatsLayer::new(stats.clone()))
+            .layer(AuthenticationLayer::new(authentication.clone()))
+            .service(inner);
+
+        ApiClient {
+            inner: Arc::new(InnerClient {
+                base: ArcSwap::new(Arc::new(self.base)),
+                inner: service,
+                authentication,
+                decoder: None,
+                max_response_size: self.max_response_size,
+                stats,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use http::header;
+    use tower::util::MapRequestLayer;
+
+    use crate::response::ResponseBodyExt as _;
+    use crate::{BearerAuth, Secret};
+
+    use super::*;
+
+    /// A layer which appends its name to the `x-layer` header.
+    fn tag(
+        name: &'static str,
+    ) -> MapRequestLayer<impl Fn(http::Request<Body>) -> http::Request<Body> + Clone> {
+        MapRequestLayer::new(move |mut req: http::Request<Body>| {
+            req.headers_mut()
+                .append("x-layer", http::HeaderValue::from_static(name));
+            req
+        })
+    }
+
+    #[tokio::test]
+    async fn layers_wrap_the_transport_in_order() {
+        let seen = Arc::new(Mutex::new(None));
+        let service = tower::service_fn({
+            let seen = seen.clone();
+            move |req: http::Request<Body>| {
+                *seen.lock().unwrap() = Some(req.headers().clone());
+                std::future::ready(Ok::<_, hyperdriver::client::Error>(http::Response::new(
+                    Body::empty(),
+                )))
+            }
+        });
+
+        let client = ApiClient::builder(
+            "http://example.com/".parse().unwrap(),
+            BearerAuth::new(Secret::from("secret garden")),
+        )
+        .with_inner_service(service)
+        .layer(tag("outer"))
+        .layer(tag("inner"))
+        .build();
+
+        client.get("thing").send().await.unwrap();
+
+        let headers = seen.lock().unwrap().take().unwrap();
+        let layers: Vec<_> = headers.get_all("x-layer").iter().collect();
+        assert_eq!(layers, vec!["outer", "inner"]);
+        assert!(headers.contains_key(header::AUTHORIZATION));
+        assert_eq!(client.pool_stats().requests, 1);
+    }
+
+    #[tokio::test]
+    async fn default_headers() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let service = tower::service_fn({
+            let seen = seen.clone();
+            move |req: http::Request<Body>| {
+                seen.lock().unwrap().push(req.headers().clone());
+                std::future::ready(Ok::<_, hyperdriver::client::Error>(http::Response::new(
+                    Body::empty(),
+                )))
+            }
+        });
+
+        let client = ApiClient::builder("http://example.com/".parse().unwrap(), ())
+            .with_base("http://example.org/".parse().unwrap())
+            .with_inner_service(service)
+            .with_user_agent("example/1.0")
+            .unwrap()
+            .with_header(header::ACCEPT, HeaderValue::from_static("text/plain"))
+            .with_header(header::ACCEPT, HeaderValue::from_static("application/json"))
+            .build();
+
+        client.get("thing").send().await.unwrap();
+        client
+            .get("thing")
+            .header(header::USER_AGENT, "override")
+            .send()
+            .await
+            .unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen[0][header::USER_AGENT], "example/1.0");
+        assert_eq!(seen[0][header::ACCEPT], "application/json");
+        assert_eq!(seen[1][header::USER_AGENT], "override");
+    }
+
+    #[tokio::test]
+    async fn max_response_size() {
+        let service = tower::service_fn(|req: http::Request<Body>| {
+            let body = if req.uri().path() == "/large" {
+                "a much longer body"
+            } else {
+                "short"
+            };
+            std::future::ready(Ok::<_, hyperdriver::client::Error>(http::Response::new(
+                Body::from(body),
+            )))
+        });
+
+        let client = ApiClient::builder("http://example.com/".parse().unwrap(), ())
+            .with_inner_service(service)
+            .with_max_response_size(8)
+            .build();
+
+        let response = client.get("small").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "short");
+
+        let response = client.get("large").send().await.unwrap();
+        let error = response.bytes().await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<crate::error::BodyTooLarge>(),
+            Some(&crate::error::BodyTooLarge { limit: 8 })
+        );
+
+        let req = http::Request::get("http://example.com/large")
+            .body(Body::empty())
+            .unwrap();
+        let response = client.execute(req).await.unwrap();
+        let error = crate::error::Error::ResponseBody(response.bytes().await.unwrap_err());
+        assert!(error.is_body_too_large());
+
+        // The limit can be lifted for a single response.
+        let response = client.get("large").send().await.unwrap();
+        let body = response.with_body_limit(None).bytes().await.unwrap();
+        assert_eq!(body.len(), 18);
+    }
+}
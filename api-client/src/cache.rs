@@ -0,0 +1,496 @@
+//! Conditional request cache for an [`ApiClient`](crate::ApiClient).
+//!
+//! Successful `GET` responses which carry an `ETag` or `Last-Modified` header are
+//! remembered by URI. Later requests for the same URI are sent with
+//! `If-None-Match` / `If-Modified-Since`, and a `304 Not Modified` reply is
+//! answered with the cached body. Many APIs (notably Github) don't count
+//! `304` responses against rate limits.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use http::{header, HeaderMap, Method, StatusCode, Uri};
+use http_body::Frame;
+use hyperdriver::Body;
+use tower::Layer;
+
+use crate::BoxFuture;
+
+/// Largest response body which will be cached by default.
+const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Number of responses kept by an in-memory cache by default.
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// A cached response, and the validators used to revalidate it.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl CachedResponse {
+    /// Create a cached response from the headers and body of a `200 OK` response.
+    pub fn new(headers: HeaderMap, body: Bytes) -> Self {
+        Self { headers, body }
+    }
+
+    /// The headers of the cached response, including its `ETag` or `Last-Modified` validators.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The body of the cached response.
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+
+    /// Conditional request headers for revalidating this entry.
+    fn conditions(&self) -> impl Iterator<Item = (header::HeaderName, &http::HeaderValue)> {
+        let etag = self
+            .headers
+            .get(header::ETAG)
+            .map(|value| (header::IF_NONE_MATCH, value));
+        let last_modified = self
+            .headers
+            .get(header::LAST_MODIFIED)
+            .map(|value| (header::IF_MODIFIED_SINCE, value));
+        etag.into_iter().chain(last_modified)
+    }
+
+    fn response(&self, fresh: &HeaderMap) -> http::Response<Body> {
+        let mut response = http::Response::new(Body::from(self.body.clone()));
+        *response.headers_mut() = self.headers.clone();
+
+        // Headers on the 304 (e.g. rate limit counters) are newer than the cached ones.
+        for (name, value) in fresh {
+            if name != header::CONTENT_LENGTH && name != header::TRANSFER_ENCODING {
+                response.headers_mut().insert(name, value.clone());
+            }
+        }
+        response
+    }
+}
+
+/// Where a [`ResponseCache`] keeps its responses, keyed by request URI.
+///
+/// Stores should treat failures as misses, so a broken cache only costs extra requests.
+pub trait CacheStore: fmt::Debug + Send + Sync + 'static {
+    /// Get the cached response for a URI.
+    fn get<'a>(&'a self, uri: &'a Uri) -> BoxFuture<'a, Option<CachedResponse>>;
+
+    /// Store the response for a URI, replacing any previous response.
+    fn put<'a>(&'a self, uri: &'a Uri, response: CachedResponse) -> BoxFuture<'a, ()>;
+
+    /// Forget the cached response for a URI.
+    fn remove<'a>(&'a self, uri: &'a Uri) -> BoxFuture<'a, ()>;
+}
+
+#[derive(Debug, Default)]
+struct MemoryEntries {
+    entries: HashMap<Uri, (CachedResponse, u64)>,
+    clock: u64,
+}
+
+/// An in-memory [`CacheStore`], which evicts the least recently used response
+/// once it holds `capacity` responses.
+///
+/// The store is cheap to clone, and clones share entries.
+#[derive(Debug, Clone)]
+pub struct MemoryCacheStore {
+    entries: Arc<Mutex<MemoryEntries>>,
+    capacity: usize,
+}
+
+impl Default for MemoryCacheStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl MemoryCacheStore {
+    /// Create an empty store which holds up to `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Default::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Number of cached responses.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().entries.len()
+    }
+
+    /// Check if the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all cached responses.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().entries.clear();
+    }
+}
+
+impl CacheStore for MemoryCacheStore {
+    fn get<'a>(&'a self, uri: &'a Uri) -> BoxFuture<'a, Option<CachedResponse>> {
+        let mut memory = self.entries.lock().unwrap();
+        memory.clock += 1;
+        let clock = memory.clock;
+        let cached = memory.entries.get_mut(uri).map(|(response, used)| {
+            *used = clock;
+            response.clone()
+        });
+        Box::pin(std::future::ready(cached))
+    }
+
+    fn put<'a>(&'a self, uri: &'a Uri, response: CachedResponse) -> BoxFuture<'a, ()> {
+        let mut memory = self.entries.lock().unwrap();
+        memory.clock += 1;
+        let clock = memory.clock;
+
+        if !memory.entries.contains_key(uri) && memory.entries.len() >= self.capacity {
+            let oldest = memory
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(uri, _)| uri.clone());
+            if let Some(oldest) = oldest {
+                tracing::trace!(uri = %oldest, "Evicting cached response");
+                memory.entries.remove(&oldest);
+            }
+        }
+        memory.entries.insert(uri.clone(), (response, clock));
+        Box::pin(std::future::ready(()))
+    }
+
+    fn remove<'a>(&'a self, uri: &'a Uri) -> BoxFuture<'a, ()> {
+        self.entries.lock().unwrap().entries.remove(uri);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+/// A cache of `GET` responses, revalidated with conditional requests.
+///
+/// Responses are kept in memory by default, see [`ResponseCache::with_store`]
+/// to keep them elsewhere. The cache is cheap to clone, and clones share entries.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    store: Arc<dyn CacheStore>,
+    max_body_size: usize,
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResponseCache {
+    /// Create an empty in-memory cache, which holds up to 1024 responses.
+    pub fn new() -> Self {
+        Self::with_store(MemoryCacheStore::default())
+    }
+
+    /// Create a cache which keeps responses in `store`.
+    pub fn with_store<S: CacheStore>(store: S) -> Self {
+        Self {
+            store: Arc::new(store),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+        }
+    }
+
+    /// Don't cache response bodies larger than this many bytes (1MiB by default).
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+
+    /// Forget the cached response for a URI.
+    pub async fn invalidate(&self, uri: &Uri) {
+        self.store.remove(uri).await;
+    }
+}
+
+/// A layer which revalidates `GET` requests against a [`ResponseCache`].
+#[derive(Debug, Clone)]
+pub(crate) struct CacheLayer {
+    cache: ResponseCache,
+}
+
+impl CacheLayer {
+    pub(crate) fn new(cache: ResponseCache) -> Self {
+        Self { cache }
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = CacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CacheService {
+            inner,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+/// A service which revalidates `GET` requests against a [`ResponseCache`].
+#[derive(Debug, Clone)]
+pub(crate) struct CacheService<S> {
+    inner: S,
+    cache: ResponseCache,
+}
+
+impl<S> tower::Service<http::Request<Body>> for CacheService<S>
+where
+    S: tower::Service<http::Request<Body>, Response = http::Response<Body>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = S::Error;
+    type Future = crate::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if req.method() != Method::GET {
+            return Box::pin(inner.call(req));
+        }
+
+        // Callers which send their own conditions get the 304 back.
+        let conditional = req.headers().contains_key(header::IF_NONE_MATCH)
+            || req.headers().contains_key(header::IF_MODIFIED_SINCE);
+        let uri = req.uri().clone();
+
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let cached = if conditional {
+                None
+            } else {
+                cache.store.get(&uri).await
+            };
+
+            if let Some(cached) = &cached {
+                for (name, value) in cached.conditions() {
+                    req.headers_mut().insert(name, value.clone());
+                }
+            }
+
+            let response = inner.call(req).await?;
+
+            if response.status() == StatusCode::NOT_MODIFIED {
+                if let Some(cached) = cached {
+                    tracing::trace!(%uri, "Serving cached response");
+                    return Ok(cached.response(response.headers()));
+                }
+                return Ok(response);
+            }
+
+            if conditional || response.status() != StatusCode::OK {
+                return Ok(response);
+            }
+
+            let validated = response.headers().contains_key(header::ETAG)
+                || response.headers().contains_key(header::LAST_MODIFIED);
+            if !validated {
+                cache.invalidate(&uri).await;
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let body = Body::new(CachingBody {
+                inner: body,
+                buffer: Some(BytesMut::new()),
+                headers: parts.headers.clone(),
+                uri: Some(uri),
+                storing: None,
+                cache,
+            });
+            Ok(http::Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// A response body which is stored in the cache once it has been read to the end.
+#[pin_project::pin_project]
+struct CachingBody<B> {
+    #[pin]
+    inner: B,
+
+    /// Body read so far, or `None` if the body is too large to cache.
+    buffer: Option<BytesMut>,
+    headers: HeaderMap,
+    uri: Option<Uri>,
+
+    /// Storing the complete body, which finishes before the end of the body is returned.
+    storing: Option<BoxFuture<'static, ()>>,
+    cache: ResponseCache,
+}
+
+impl<B: fmt::Debug> fmt::Debug for CachingBody<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingBody")
+            .field("inner", &self.inner)
+            .field("uri", &self.uri)
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<B> http_body::Body for CachingBody<B>
+where
+    B: http_body::Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        if let Some(storing) = this.storing.as_mut() {
+            futures::ready!(storing.as_mut().poll(cx));
+            *this.storing = None;
+            return Poll::Ready(None);
+        }
+
+        let frame = futures::ready!(this.inner.poll_frame(cx));
+
+        match &frame {
+            Some(Ok(frame)) => {
+                if let (Some(buffer), Some(data)) = (this.buffer.as_mut(), frame.data_ref()) {
+                    if buffer.len() + data.len() > this.cache.max_body_size {
+                        *this.buffer = None;
+                    } else {
+                        buffer.extend_from_slice(data);
+                    }
+                }
+            }
+            Some(Err(_)) => *this.buffer = None,
+            None => {
+                if let (Some(buffer), Some(uri)) = (this.buffer.take(), this.uri.take()) {
+                    let store = this.cache.store.clone();
+                    let response =
+                        CachedResponse::new(std::mem::take(this.headers), buffer.freeze());
+                    let mut storing: BoxFuture<'static, ()> =
+                        Box::pin(async move { store.put(&uri, response).await });
+                    if storing.as_mut().poll(cx).is_pending() {
+                        *this.storing = Some(storing);
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        // Keep polling until the end so that the body is stored.
+        self.buffer.is_none() && self.storing.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::response::{ResponseBodyExt as _, ResponseExt as _};
+    use crate::{ApiClient, BearerAuth, Secret};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn revalidate_with_etag() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let service = tower::service_fn(move |req: http::Request<Body>| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            let matched = req
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .is_some_and(|etag| etag == "\"v1\"");
+            let response = if matched {
+                http::Response::builder()
+                    .status(StatusCode::NOT_MODIFIED)
+                    .body(Body::empty())
+            } else {
+                http::Response::builder()
+                    .header(header::ETAG, "\"v1\"")
+                    .body(Body::from("frobulator"))
+            };
+            std::future::ready(Ok::<_, hyperdriver::client::Error>(response.unwrap()))
+        });
+
+        let store = MemoryCacheStore::default();
+        let client = ApiClient::builder(
+            "http://example.com/".parse().unwrap(),
+            BearerAuth::new(Secret::from("secret garden")),
+        )
+        .with_inner_service(service)
+        .build()
+        .with_response_cache(ResponseCache::with_store(store.clone()));
+
+        let response = client.get("thing").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "frobulator");
+        assert_eq!(store.len(), 1);
+
+        let response = client.get("thing").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "frobulator");
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+
+        // Callers which send their own validators see the 304.
+        let response = client
+            .get("thing")
+            .header(header::IF_NONE_MATCH, "\"v1\"")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        store.clear();
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn memory_store_evicts_least_recently_used() {
+        let store = MemoryCacheStore::new(2);
+        let response = CachedResponse::new(HeaderMap::new(), Bytes::from_static(b"body"));
+        let (a, b, c): (Uri, Uri, Uri) = (
+            "http://example.com/a".parse().unwrap(),
+            "http://example.com/b".parse().unwrap(),
+            "http://example.com/c".parse().unwrap(),
+        );
+
+        store.put(&a, response.clone()).await;
+        store.put(&b, response.clone()).await;
+        assert!(store.get(&a).await.is_some());
+
+        store.put(&c, response.clone()).await;
+        assert_eq!(store.len(), 2);
+        assert!(store.get(&a).await.is_some());
+        assert!(store.get(&b).await.is_none());
+        assert!(store.get(&c).await.is_some());
+    }
+}
@@ -0,0 +1,70 @@
+//! Deprecated items, kept for one release after they are replaced.
+//!
+//! When a public item is renamed or redesigned, the old item moves here with a
+//! `#[deprecated(since = "...", note = "...")]` attribute naming its replacement,
+//! and is re-exported from its old path with `#[doc(hidden)]`, so existing code
+//! keeps compiling (with a warning) until the following release removes it.
+//!
+//! Deprecated in this release:
+//!
+//! - `ApiClient::new_with_tls`, replaced by [`ApiClientBuilder::with_tls`].
+//! - `ApiClient::new_with_inner_service`, replaced by
+//!   [`ApiClientBuilder::with_inner_service`].
+//! - `ApiClient::new_bearer_auth`, replaced by [`ApiClient::new`] with a
+//!   [`BearerAuth`].
+//!
+//! These are inherent methods, so they stay on [`ApiClient`], hidden from its
+//! documentation, and are implemented here.
+
+use http::Uri;
+use hyperdriver::Body;
+
+use crate::tls::{TlsConfig, TlsError};
+use crate::{ApiClient, ApiClientBuilder, Authentication, BearerAuth, Secret};
+
+impl<A> ApiClient<A>
+where
+    A: Authentication + Send + Sync + 'static,
+{
+    /// Create a new API Client with custom TLS settings.
+    #[doc(hidden)]
+    #[deprecated(since = "0.1.0", note = "use `ApiClient::builder(..).with_tls(..)`")]
+    pub fn new_with_tls(base: Uri, authentication: A, tls: &TlsConfig) -> Result<Self, TlsError> {
+        Ok(Self::builder(base, authentication).with_tls(tls)?.build())
+    }
+
+    /// Create a new API Client which makes HTTP requests with an inner service.
+    #[doc(hidden)]
+    #[deprecated(
+        since = "0.1.0",
+        note = "use `ApiClient::builder(..).with_inner_service(..)`"
+    )]
+    pub fn new_with_inner_service<S>(base: Uri, authentication: A, inner: S) -> Self
+    where
+        S: tower::Service<
+                http::Request<Body>,
+                Response = http::Response<Body>,
+                Error = hyperdriver::client::Error,
+            > + Clone
+            + Send
+            + Sync
+            + 'static,
+        S::Future: Send + 'static,
+    {
+        Self::builder(base, authentication)
+            .with_inner_service(inner)
+            .build()
+    }
+}
+
+impl ApiClient<BearerAuth> {
+    /// Create a new API Client with a Bearer token authentication method.
+    #[doc(hidden)]
+    #[deprecated(
+        since = "0.1.0",
+        note = "use `ApiClient::new(base, BearerAuth::new(token))`"
+    )]
+    pub fn new_bearer_auth<K: Into<Secret>>(base: Uri, token: K) -> Self {
+        Self::new(base, BearerAuth::new(token.into()))
+    }
+}
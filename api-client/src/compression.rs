@@ -0,0 +1,63 @@
+//! Compression of request bodies, for APIs which accept a `Content-Encoding`.
+
+use std::io::Write as _;
+
+use http::HeaderValue;
+
+/// An encoding used to compress request bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// `Content-Encoding: gzip`
+    Gzip,
+
+    /// `Content-Encoding: zstd`
+    Zstd,
+}
+
+impl Compression {
+    /// The value of the `Content-Encoding` header for this encoding.
+    pub fn content_encoding(&self) -> HeaderValue {
+        match self {
+            Compression::Gzip => HeaderValue::from_static("gzip"),
+            Compression::Zstd => HeaderValue::from_static("zstd"),
+        }
+    }
+
+    /// Compress a body held in memory.
+    pub(crate) fn compress(&self, body: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder
+                    .write_all(body)
+                    .and_then(|_| encoder.finish())
+                    .expect("gzip into memory")
+            }
+            Compression::Zstd => zstd::bulk::compress(body, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .expect("zstd into memory"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+
+    use super::*;
+
+    #[test]
+    fn compress_roundtrip() {
+        let body = br#"{"records": ["frobulator", "frobulator", "frobulator"]}"#;
+
+        let gzip = Compression::Gzip.compress(body);
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(gzip.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+
+        let zstd = Compression::Zstd.compress(body);
+        assert_eq!(zstd::decode_all(zstd.as_slice()).unwrap(), body);
+    }
+}
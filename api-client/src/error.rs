@@ -1,7 +1,9 @@
 //! Error types for API Clients
 use std::fmt;
+use std::marker::PhantomData;
 
 use http::StatusCode;
+use serde::de::DeserializeOwned;
 use thiserror::Error;
 
 use crate::response::{Response, ResponseBodyExt as _, ResponseExt as _};
@@ -12,11 +14,23 @@ type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
 /// An error occured while sending or recieving an HTTP request
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// An HTTP response error occured
     #[error(transparent)]
     Response(#[from] HttpResponseError),
 
+    /// The server returned an error response, decoded by the client's [`ErrorDecoder`]
+    #[error("HTTP {status} response: {source}")]
+    Api {
+        /// The HTTP status code of the response
+        status: StatusCode,
+
+        /// The service-specific error decoded from the response body
+        #[source]
+        source: BoxError,
+    },
+
     /// An error occured while recieving the response body
     #[error("Error reading response body: {0}")]
     ResponseBody(#[source] BoxError),
@@ -34,6 +48,103 @@ pub enum Error {
     QuerySerialization(#[from] crate::uri::QueryError),
 }
 
+impl Error {
+    /// The HTTP status code of the error response, if this error came from one.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Response(error) => Some(error.status),
+            Error::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Get the decoded service error, if it has type `E`.
+    pub fn api_error<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        match self {
+            Error::Api { source, .. } => source.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Take the decoded service error, if it has type `E`, or return this error unchanged.
+    pub fn into_api_error<E: std::error::Error + 'static>(self) -> Result<E, Self> {
+        match self {
+            Error::Api { status, source } => match source.downcast() {
+                Ok(error) => Ok(*error),
+                Err(source) => Err(Error::Api { status, source }),
+            },
+            error => Err(error),
+        }
+    }
+
+    /// Check if the response body was larger than the client's limit.
+    pub fn is_body_too_large(&self) -> bool {
+        match self {
+            Error::ResponseBody(source) => source.is::<BodyTooLarge>(),
+            _ => false,
+        }
+    }
+}
+
+/// A response body was larger than the limit set with
+/// [`ApiClientBuilder::with_max_response_size`](crate::ApiClientBuilder::with_max_response_size).
+///
+/// Returned, boxed, when collecting the body, e.g. with
+/// [`ResponseBodyExt::json`](crate::response::ResponseBodyExt::json).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("Response body is larger than the limit of {limit} bytes")]
+pub struct BodyTooLarge {
+    /// The maximum size of the response body, in bytes
+    pub limit: usize,
+}
+
+/// Decodes the body of an error response into a service-specific error type.
+///
+/// Set on a client with [`ApiClient::with_error_decoder`](crate::ApiClient::with_error_decoder),
+/// and used by [`RequestBuilder::send_checked`](crate::RequestBuilder::send_checked) and
+/// paginated streams.
+pub trait ErrorDecoder: fmt::Debug + Send + Sync + 'static {
+    /// Decode an error response body, returning `None` if it isn't a recognized error.
+    fn decode(&self, status: StatusCode, body: &[u8]) -> Option<BoxError>;
+}
+
+/// An [`ErrorDecoder`] which deserializes error responses as JSON.
+pub struct JsonErrorDecoder<E> {
+    error: PhantomData<fn() -> E>,
+}
+
+impl<E> JsonErrorDecoder<E> {
+    /// Create a new JSON error decoder
+    pub fn new() -> Self {
+        Self { error: PhantomData }
+    }
+}
+
+impl<E> Default for JsonErrorDecoder<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> fmt::Debug for JsonErrorDecoder<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("JsonErrorDecoder")
+            .field(&std::any::type_name::<E>())
+            .finish()
+    }
+}
+
+impl<E> ErrorDecoder for JsonErrorDecoder<E>
+where
+    E: DeserializeOwned + std::error::Error + Send + Sync + 'static,
+{
+    fn decode(&self, _status: StatusCode, body: &[u8]) -> Option<BoxError> {
+        serde_json::from_slice::<E>(body)
+            .ok()
+            .map(|error| Box::new(error) as BoxError)
+    }
+}
+
 /// A server returned an error response
 #[derive(Debug, Clone)]
 pub struct HttpResponseError {
@@ -0,0 +1,235 @@
+//! Incremental parsing of a top-level JSON array from a response body.
+//!
+//! Elements are parsed with a [`serde_json::StreamDeserializer`] as soon as
+//! enough of the body has arrived, and the consumed part of the buffer is then
+//! dropped, so neither the whole body nor the whole array is held in memory.
+
+use std::pin::Pin;
+
+use bytes::Buf as _;
+use futures::stream::{BoxStream, StreamExt as _};
+use http_body_util::BodyExt as _;
+use serde::de::DeserializeOwned;
+use tower::BoxError;
+
+/// Where the parser is within the array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the opening `[`.
+    Start,
+
+    /// After the opening `[`, where the array may be empty.
+    First,
+
+    /// After a `,`, where an element must follow.
+    Element,
+
+    /// After an element, where a `,` or `]` must follow.
+    Next,
+
+    /// After the closing `]`, or an error.
+    Done,
+}
+
+/// One step of parsing the buffered body.
+enum Step<T> {
+    Element(T),
+    Incomplete,
+    Done,
+}
+
+struct JsonArray<B> {
+    body: Pin<Box<B>>,
+    buffer: Vec<u8>,
+    position: usize,
+    state: State,
+    eof: bool,
+}
+
+impl<B> JsonArray<B>
+where
+    B: http_body::Body,
+    B::Error: Into<BoxError>,
+{
+    fn new(body: B) -> Self {
+        Self {
+            body: Box::pin(body),
+            buffer: Vec::new(),
+            position: 0,
+            state: State::Start,
+            eof: false,
+        }
+    }
+
+    async fn next<T: DeserializeOwned>(&mut self) -> Option<Result<T, BoxError>> {
+        loop {
+            match self.step() {
+                Ok(Step::Element(value)) => return Some(Ok(value)),
+                Ok(Step::Done) => return None,
+                Ok(Step::Incomplete) if self.eof => {
+                    self.state = State::Done;
+                    return Some(Err("unexpected end of JSON array".into()));
+                }
+                Ok(Step::Incomplete) => match self.body.frame().await {
+                    Some(Ok(frame)) => {
+                        if let Ok(mut data) = frame.into_data() {
+                            while data.has_remaining() {
+                                let chunk = data.chunk();
+                                let len = chunk.len();
+                                self.buffer.extend_from_slice(chunk);
+                                data.advance(len);
+                            }
+                        }
+                    }
+                    Some(Err(error)) => {
+                        self.state = State::Done;
+                        return Some(Err(error.into()));
+                    }
+                    None => self.eof = true,
+                },
+                Err(error) => {
+                    self.state = State::Done;
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+
+    fn step<T: DeserializeOwned>(&mut self) -> Result<Step<T>, BoxError> {
+        loop {
+            if self.state == State::Done {
+                return Ok(Step::Done);
+            }
+
+            while self
+                .buffer
+                .get(self.position)
+                .is_some_and(u8::is_ascii_whitespace)
+            {
+                self.position += 1;
+            }
+
+            let Some(&next) = self.buffer.get(self.position) else {
+                return Ok(Step::Incomplete);
+            };
+
+            match (self.state, next) {
+                (State::Start, b'[') => self.state = State::First,
+                (State::Start, _) => return Err("expected a JSON array".into()),
+                (State::First, b']') | (State::Next, b']') => self.state = State::Done,
+                (State::Next, b',') => self.state = State::Element,
+                (State::Next, other) => {
+                    return Err(format!(
+                        "expected ',' or ']' in JSON array, found {:?}",
+                        other as char
+                    )
+                    .into())
+                }
+                (State::First, _) | (State::Element, _) => return self.element(),
+                (State::Done, _) => unreachable!("checked above"),
+            }
+            self.position += 1;
+        }
+    }
+
+    fn element<T: DeserializeOwned>(&mut self) -> Result<Step<T>, BoxError> {
+        let mut values =
+            serde_json::Deserializer::from_slice(&self.buffer[self.position..]).into_iter::<T>();
+
+        match values.next() {
+            Some(Ok(value)) => {
+                let end = self.position + values.byte_offset();
+
+                // A value which runs to the end of the buffer might be a number
+                // which continues in the next frame.
+                if end == self.buffer.len() && !self.eof {
+                    return Ok(Step::Incomplete);
+                }
+
+                self.position = end;
+                self.state = State::Next;
+                if self.position > self.buffer.len() / 2 {
+                    self.buffer.drain(..self.position);
+                    self.position = 0;
+                }
+                Ok(Step::Element(value))
+            }
+            Some(Err(error)) if error.is_eof() => Ok(Step::Incomplete),
+            Some(Err(error)) => Err(error.into()),
+            None => Ok(Step::Incomplete),
+        }
+    }
+}
+
+/// Stream the elements of a top-level JSON array in a body.
+pub(crate) fn json_array<T, B>(body: B) -> BoxStream<'static, Result<T, BoxError>>
+where
+    T: DeserializeOwned + Send + 'static,
+    B: http_body::Body + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    futures::stream::unfold(JsonArray::new(body), |mut array| async move {
+        let item = array.next().await?;
+        Some((item, array))
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::TryStreamExt as _;
+    use http_body::Frame;
+    use http_body_util::StreamBody;
+
+    use super::*;
+
+    fn chunked(
+        chunks: &[&'static str],
+    ) -> StreamBody<BoxStream<'static, Result<Frame<Bytes>, BoxError>>> {
+        let frames: Vec<Result<_, BoxError>> = chunks
+            .iter()
+            .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+            .collect();
+        StreamBody::new(futures::stream::iter(frames).boxed())
+    }
+
+    #[tokio::test]
+    async fn parse_chunked_array() {
+        let body = chunked(&[
+            " [ {\"name\": \"fro",
+            "b\"}, 12",
+            "34 ,",
+            "5, {\"name\":\"ulator\"}",
+            "]",
+        ]);
+        let values: Vec<serde_json::Value> = json_array(body).try_collect().await.unwrap();
+        assert_eq!(
+            values,
+            vec![
+                serde_json::json!({"name": "frob"}),
+                serde_json::json!(1234),
+                serde_json::json!(5),
+                serde_json::json!({"name": "ulator"}),
+            ]
+        );
+
+        let values: Vec<u32> = json_array(chunked(&["[", " ]"]))
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(values.is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_arrays() {
+        let result: Result<Vec<u32>, _> = json_array(chunked(&["{}"])).try_collect().await;
+        assert!(result.is_err());
+
+        let result: Result<Vec<u32>, _> = json_array(chunked(&["[1, 2"])).try_collect().await;
+        assert!(result.is_err());
+
+        let result: Result<Vec<u32>, _> = json_array(chunked(&["[1 2]"])).try_collect().await;
+        assert!(result.is_err());
+    }
+}
@@ -17,23 +17,50 @@ use tower::ServiceExt;
 
 mod adapt;
 mod authentication;
+mod builder;
+mod cache;
+#[doc(hidden)]
+pub mod compat;
+#[cfg(feature = "compression")]
+mod compression;
 pub mod error;
+mod json_array;
+pub mod mock;
 mod paginate;
+mod proxy;
 pub mod request;
+mod resolve;
 pub mod response;
 mod retry;
+mod sse;
+mod stats;
+mod tls;
+#[cfg(unix)]
+mod unix;
 pub mod uri;
 
 pub use self::adapt::AdaptClientIncomingLayer;
 pub use self::authentication::{
     basic_auth, Authentication, AuthenticationLayer, AuthenticationService, BasicAuth, BearerAuth,
 };
-pub use self::error::Error;
-pub use self::paginate::{Paginated, PaginatedData, PaginationInfo, Paginator};
+pub use self::builder::ApiClientBuilder;
+use self::cache::CacheLayer;
+pub use self::cache::{CacheStore, CachedResponse, MemoryCacheStore, ResponseCache};
+#[cfg(feature = "compression")]
+pub use self::compression::Compression;
+pub use self::error::{Error, ErrorDecoder, JsonErrorDecoder};
+pub use self::paginate::{
+    LinkHeaderPaginator, Paginated, PaginatedData, PaginatedList, PaginationInfo, Paginator,
+};
+pub use self::proxy::{ProxyConfig, ProxyError};
 pub use self::request::RequestBuilder;
 pub use self::request::RequestExt;
-use self::response::Response;
+use self::response::{Response, ResponseBodyExt as _, ResponseExt as _};
 pub use self::retry::{Attempts, Backoff};
+pub use self::sse::{Event, EventStream};
+use self::stats::Counters;
+pub use self::stats::PoolStats;
+pub use self::tls::{TlsConfig, TlsError};
 use self::uri::UriExtension as _;
 
 /// A boxed service used for API requests in the Client
@@ -48,6 +75,9 @@ struct InnerClient<A> {
     base: ArcSwap<Uri>,
     inner: hyperdriver::client::SharedClientService<Body, Body>,
     authentication: Arc<ArcSwap<A>>,
+    decoder: Option<Arc<dyn ErrorDecoder>>,
+    max_response_size: Option<usize>,
+    stats: Arc<Counters>,
 }
 
 /// A client for accessing APIs over HTTP / HTTPS
@@ -64,47 +94,55 @@ where
 {
     /// Create a new API Client from a base URL and an authentication method
     pub fn new(base: Uri, authentication: A) -> Self {
-        let authentication = Arc::new(ArcSwap::new(Arc::new(authentication)));
-        let inner = hyperdriver::Client::build_tcp_http()
-            .with_default_tls()
-            .layer(AuthenticationLayer::new(authentication.clone()))
-            .build_service();
+        Self::builder(base, authentication).build()
+    }
 
+    /// Create a new API Client which sends every request to a unix socket, e.g.
+    /// the API of a local daemon.
+    ///
+    /// Requests are sent to `http://localhost/`. Use [`ApiClientBuilder::with_unix_socket`]
+    /// for daemons which expect a different `Host` header.
+    #[cfg(unix)]
+    pub fn new_unix(path: impl Into<std::path::PathBuf>, authentication: A) -> Self {
+        Self::builder("http://localhost/".parse().unwrap(), authentication)
+            .with_unix_socket(path)
+            .build()
+    }
+
+    /// Start building an API Client, e.g. to add middleware layers.
+    pub fn builder(base: Uri, authentication: A) -> ApiClientBuilder<A> {
+        ApiClientBuilder::new(base, authentication)
+    }
+
+    /// Decode error responses with a service-specific [`ErrorDecoder`].
+    pub fn with_error_decoder<D: ErrorDecoder>(self, decoder: D) -> Self {
         ApiClient {
             inner: Arc::new(InnerClient {
-                base: ArcSwap::new(Arc::new(base)),
-                inner: SharedService::new(inner),
-                authentication,
+                base: ArcSwap::new(self.inner.base.load_full()),
+                inner: self.inner.inner.clone(),
+                authentication: self.inner.authentication.clone(),
+                decoder: Some(Arc::new(decoder)),
+                max_response_size: self.inner.max_response_size,
+                stats: self.inner.stats.clone(),
             }),
         }
     }
 
-    /// Create a new API Client from a base URL and an authentication method, as well as an inner service
-    /// which will be used to make the HTTP requests.
-    pub fn new_with_inner_service<S>(base: Uri, authentication: A, inner: S) -> Self
-    where
-        S: tower::Service<
-                http::Request<Body>,
-                Response = http::Response<Body>,
-                Error = hyperdriver::client::Error,
-            > + Clone
-            + Send
-            + Sync
-            + 'static,
-        S::Future: Send + 'static,
-    {
-        let authentication = Arc::new(ArcSwap::new(Arc::new(authentication)));
-
-        let service = tower::ServiceBuilder::new()
-            .layer(SharedService::layer())
-            .layer(AuthenticationLayer::new(authentication.clone()))
-            .service(inner);
+    /// Revalidate `GET` requests against a [`ResponseCache`], serving cached bodies
+    /// when the server responds with `304 Not Modified`.
+    ///
+    /// Clones of the cache share entries, so one cache can be shared between clients.
+    pub fn with_response_cache(self, cache: ResponseCache) -> Self {
+        let inner = tower::Layer::layer(&CacheLayer::new(cache), self.inner.inner.clone());
 
         ApiClient {
             inner: Arc::new(InnerClient {
-                base: ArcSwap::new(Arc::new(base)),
-                inner: service,
-                authentication,
+                base: ArcSwap::new(self.inner.base.load_full()),
+                inner: SharedService::new(inner),
+                authentication: self.inner.authentication.clone(),
+                decoder: self.inner.decoder.clone(),
+                max_response_size: self.inner.max_response_size,
+                stats: self.inner.stats.clone(),
             }),
         }
     }
@@ -124,19 +162,20 @@ where
         self.inner.authentication.as_ref().load()
     }
 
+    /// Connection statistics for requests made by this client.
+    ///
+    /// Clients created with [`ApiClient::with_error_decoder`] share statistics
+    /// with the client they were created from.
+    pub fn pool_stats(&self) -> PoolStats {
+        self.inner.stats.snapshot()
+    }
+
     /// Get the inner service used to make HTTP requests
     pub fn inner(&self) -> &hyperdriver::client::SharedClientService<Body, Body> {
         &self.inner.inner
     }
 }
 
-impl ApiClient<BearerAuth> {
-    /// Create a new API Client with a Bearer token authentication method
-    pub fn new_bearer_auth<K: Into<Secret>>(base: Uri, token: K) -> Self {
-        Self::new(base, BearerAuth::new(token.into()))
-    }
-}
-
 impl<A> ApiClient<A>
 where
     A: Authentication,
@@ -163,6 +202,12 @@ where
         RequestBuilder::new(self.clone(), url, Method::POST)
     }
 
+    /// Create a PATCH request builder for the client
+    pub fn patch(&self, endpoint: &str) -> RequestBuilder {
+        let url = self.join_endpoint(endpoint);
+        RequestBuilder::new(self.clone(), url, Method::PATCH)
+    }
+
     /// Create a DELETE request builder for the client
     pub fn delete(&self, endpoint: &str) -> RequestBuilder {
         let url = self.join_endpoint(endpoint);
@@ -180,114 +225,56 @@ where
             .oneshot(req)
             .await
             .map_err(Error::Request)?;
-        Ok(Response::new(parts, response))
+        Ok(Response::new(parts, response).with_body_limit(self.inner.max_response_size))
     }
-}
 
-/// A set of tools to help with testing API clients
-pub mod mock {
-    use bytes::Bytes;
-    use http::response;
-    use hyperdriver::Body;
-    use std::collections::HashMap;
-
-    /// A mock response for testing API clients
-    #[derive(Debug, Clone)]
-    pub struct MockResponse {
-        status: http::StatusCode,
-        headers: http::HeaderMap,
-        body: Vec<u8>,
+    /// Return an error for a response with a non-success status, using the client's
+    /// [`ErrorDecoder`] to decode the response body if one is set.
+    pub async fn error_for_status(&self, response: Response) -> Result<Response, Error> {
+        error_for_status(self.inner.decoder.as_deref(), response).await
     }
 
-    impl MockResponse {
-        /// Create a new mock response
-        pub fn new(status: http::StatusCode, headers: http::HeaderMap, body: Vec<u8>) -> Self {
-            Self {
-                status,
-                headers,
-                body,
-            }
-        }
+    /// Decode an error response body with the client's [`ErrorDecoder`].
+    pub(crate) fn decode_error(
+        &self,
+        status: http::StatusCode,
+        body: &[u8],
+    ) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        self.inner.decoder.as_ref()?.decode(status, body)
     }
+}
 
-    /// A mock service for testing API clients which returns pre-configured responses
-    /// based on the requested path.
-    #[derive(Debug, Default, Clone)]
-    pub struct MockService {
-        responses: HashMap<String, MockResponse>,
+pub(crate) async fn error_for_status(
+    decoder: Option<&dyn ErrorDecoder>,
+    response: Response,
+) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
     }
 
-    impl MockService {
-        /// Create a new mock service
-        pub fn new() -> Self {
-            Self {
-                responses: Default::default(),
-            }
-        }
-
-        /// Add a new response to the mock service
-        pub fn add(
-            &mut self,
-            path: &str,
-            status: http::StatusCode,
-            headers: http::HeaderMap,
-            body: Vec<u8>,
-        ) {
-            let response = MockResponse::new(status, headers, body);
-            self.responses.insert(path.to_owned(), response);
-        }
+    let body = response.bytes().await.map_err(Error::ResponseBody)?;
+    if let Some(source) = decoder.and_then(|decoder| decoder.decode(status, &body)) {
+        return Err(Error::Api { status, source });
     }
 
-    impl tower::Service<http::Request<Body>> for MockService {
-        type Response = http::Response<Body>;
-        type Error = hyperdriver::client::Error;
-        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
-
-        fn poll_ready(
-            &mut self,
-            _cx: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<Result<(), Self::Error>> {
-            std::task::Poll::Ready(Ok(()))
-        }
-
-        fn call(&mut self, req: http::Request<Body>) -> Self::Future {
-            let path = req.uri().path().to_owned();
-            let response = self.responses.get(&path).unwrap_or_else(|| {
-                panic!(
-                    "No response configured for path: {path}",
-                    path = req.uri().path()
-                )
-            });
-
-            let mut builder = response::Builder::new()
-                .status(response.status)
-                .version(http::Version::HTTP_11);
-
-            for (key, value) in response.headers.iter() {
-                builder = builder.header(key, value);
-            }
-
-            let response = builder
-                .body(hyperdriver::Body::from(Bytes::from(response.body.clone())))
-                .unwrap();
-
-            std::future::ready(Ok(response))
-        }
+    Err(error::HttpResponseError {
+        status,
+        message: String::from_utf8_lossy(&body).into_owned(),
     }
+    .into())
 }
 
 #[cfg(test)]
 mod test {
 
-    use self::response::ResponseExt as _;
-
     use super::*;
 
     #[test]
     fn extensions_produce_send_futures() {
-        let client = ApiClient::new_bearer_auth(
+        let client = ApiClient::new(
             "http://httpbin.org/get/".parse().unwrap(),
-            Secret::from("secret garden"),
+            BearerAuth::new(Secret::from("secret garden")),
         );
         let builder = client.get("frobulator");
 
@@ -307,13 +294,90 @@ mod test {
             b"frobulator".to_vec(),
         );
 
-        let client = ApiClient::new_with_inner_service(
+        let client = ApiClient::builder(
             "http://httpbin.org/get/".parse().unwrap(),
             BearerAuth::new(Secret::from("secret garden")),
-            mock,
-        );
+        )
+        .with_inner_service(mock)
+        .build();
 
         let response = client.get("").send().await.unwrap();
         assert_eq!(response.status(), http::StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn pool_stats() {
+        let mut mock = crate::mock::MockService::new();
+        mock.add(
+            "/get/",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            b"frobulator".to_vec(),
+        );
+
+        let client = ApiClient::builder(
+            "http://httpbin.org/get/".parse().unwrap(),
+            BearerAuth::new(Secret::from("secret garden")),
+        )
+        .with_inner_service(mock)
+        .build();
+        assert_eq!(client.pool_stats(), PoolStats::default());
+
+        let (first, second) = futures::join!(client.get("").send(), client.get("").send());
+        first.unwrap();
+        second.unwrap();
+
+        let stats = client.pool_stats();
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.http1, 2);
+        assert_eq!(stats.http2_ratio(), 0.0);
+    }
+
+    #[derive(Debug, thiserror::Error, serde::Deserialize)]
+    #[error("{message}")]
+    struct ServiceError {
+        message: String,
+    }
+
+    #[tokio::test]
+    async fn decode_error_responses() {
+        let mut mock = crate::mock::MockService::new();
+        mock.add(
+            "/missing",
+            http::StatusCode::NOT_FOUND,
+            http::HeaderMap::new(),
+            br#"{"message": "no such thing"}"#.to_vec(),
+        );
+        mock.add(
+            "/broken",
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+            http::HeaderMap::new(),
+            b"oops".to_vec(),
+        );
+
+        let client = ApiClient::builder(
+            "http://example.com/".parse().unwrap(),
+            BearerAuth::new(Secret::from("secret garden")),
+        )
+        .with_inner_service(mock)
+        .build()
+        .with_error_decoder(JsonErrorDecoder::<ServiceError>::new());
+
+        let error = client.get("missing").send_checked().await.unwrap_err();
+        assert_eq!(error.status(), Some(http::StatusCode::NOT_FOUND));
+        assert_eq!(
+            error.api_error::<ServiceError>().unwrap().message,
+            "no such thing"
+        );
+        let error = error.into_api_error::<ServiceError>().unwrap();
+        assert_eq!(error.to_string(), "no such thing");
+
+        let error = client.get("broken").send_checked().await.unwrap_err();
+        assert!(matches!(error, Error::Response(_)));
+        assert_eq!(
+            error.status(),
+            Some(http::StatusCode::INTERNAL_SERVER_ERROR)
+        );
+    }
 }
@@ -0,0 +1,396 @@
+//! A set of tools to help with testing API clients
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::{response, Method};
+use http_body_util::BodyExt as _;
+use hyperdriver::Body;
+
+use crate::BoxFuture;
+
+/// A mock response for testing API clients
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    status: http::StatusCode,
+    headers: http::HeaderMap,
+    body: Vec<u8>,
+    delay: Option<Duration>,
+}
+
+impl MockResponse {
+    /// Create a new mock response
+    pub fn new(status: http::StatusCode, headers: http::HeaderMap, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+            delay: None,
+        }
+    }
+
+    /// Wait before responding, e.g. to test timeouts.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn to_response(&self) -> http::Response<Body> {
+        let mut builder = response::Builder::new()
+            .status(self.status)
+            .version(http::Version::HTTP_11);
+
+        for (key, value) in self.headers.iter() {
+            builder = builder.header(key, value);
+        }
+
+        builder
+            .body(Body::from(Bytes::from(self.body.clone())))
+            .unwrap()
+    }
+}
+
+type MakeError = Arc<dyn Fn() -> hyperdriver::client::Error + Send + Sync>;
+
+/// What a mock service does for a request to a route.
+#[derive(Clone)]
+enum Reply {
+    Response(MockResponse),
+    Error(MakeError),
+}
+
+impl fmt::Debug for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Response(response) => f.debug_tuple("Response").field(response).finish(),
+            Reply::Error(_) => f.debug_tuple("Error").finish(),
+        }
+    }
+}
+
+/// The requests a mock response is returned for.
+///
+/// A route matches requests to its path, with any method and query unless those
+/// are set. When several routes match a request, the one with the most method
+/// and query constraints is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockRoute {
+    method: Option<Method>,
+    path: String,
+    query: Vec<(String, String)>,
+}
+
+impl MockRoute {
+    /// Match requests to a path.
+    pub fn new(path: &str) -> Self {
+        Self {
+            method: None,
+            path: path.to_owned(),
+            query: Vec::new(),
+        }
+    }
+
+    /// Only match requests with this method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Only match requests with this query parameter. Other parameters are ignored.
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    fn matches(&self, method: &Method, uri: &http::Uri) -> bool {
+        if self.method.as_ref().is_some_and(|m| m != method) || self.path != uri.path() {
+            return false;
+        }
+
+        let params: Vec<(String, String)> = uri
+            .query()
+            .and_then(|query| serde_urlencoded::from_str(query).ok())
+            .unwrap_or_default();
+        self.query.iter().all(|param| params.contains(param))
+    }
+
+    fn constraints(&self) -> usize {
+        usize::from(self.method.is_some()) + self.query.len()
+    }
+}
+
+impl From<&str> for MockRoute {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+/// A request received by a [`MockService`].
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    /// The request method
+    pub method: Method,
+
+    /// The request URI
+    pub uri: http::Uri,
+
+    /// The request headers
+    pub headers: http::HeaderMap,
+
+    /// The request body
+    pub body: Bytes,
+}
+
+impl MockRequest {
+    /// Deserialize the request body as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+#[derive(Debug)]
+struct Route {
+    route: MockRoute,
+    replies: VecDeque<Reply>,
+}
+
+impl Route {
+    /// The next reply for the route. The last reply is repeated once the others are used.
+    fn next_reply(&mut self) -> Option<Reply> {
+        if self.replies.len() > 1 {
+            self.replies.pop_front()
+        } else {
+            self.replies.front().cloned()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<MockRequest>,
+}
+
+impl State {
+    fn push(&mut self, route: MockRoute, reply: Reply) {
+        match self.routes.iter_mut().find(|r| r.route == route) {
+            Some(existing) => existing.replies.push_back(reply),
+            None => self.routes.push(Route {
+                route,
+                replies: VecDeque::from([reply]),
+            }),
+        }
+    }
+
+    fn reply(&mut self, method: &Method, uri: &http::Uri) -> Option<Reply> {
+        self.routes
+            .iter_mut()
+            .filter(|r| r.route.matches(method, uri))
+            // Prefer the first route added when several are equally specific.
+            .rev()
+            .max_by_key(|r| r.route.constraints())?
+            .next_reply()
+    }
+}
+
+/// A mock service for testing API clients which returns pre-configured responses
+/// based on the requested route, and records the requests it receives.
+///
+/// Clones share responses and recorded requests, so a test can keep a clone to
+/// inspect requests after passing the service to a client. Requests which match
+/// no route get a `404 Not Found` response.
+#[derive(Debug, Default, Clone)]
+pub struct MockService {
+    state: Arc<Mutex<State>>,
+}
+
+impl MockService {
+    /// Create a new mock service
+    pub fn new() -> Self {
+        Self {
+            state: Default::default(),
+        }
+    }
+
+    /// Add a new response to the mock service, for any request to `path`.
+    ///
+    /// This replaces any responses already set for the path.
+    pub fn add(
+        &mut self,
+        path: &str,
+        status: http::StatusCode,
+        headers: http::HeaderMap,
+        body: Vec<u8>,
+    ) {
+        let route = MockRoute::new(path);
+        let mut state = self.state.lock().unwrap();
+        state.routes.retain(|r| r.route != route);
+        state.push(
+            route,
+            Reply::Response(MockResponse::new(status, headers, body)),
+        );
+    }
+
+    /// Respond to requests matching a route.
+    ///
+    /// Responses added for the same route are returned in order, one per request,
+    /// and the last one is repeated once the others have been returned.
+    pub fn respond(&mut self, route: impl Into<MockRoute>, response: MockResponse) {
+        self.state
+            .lock()
+            .unwrap()
+            .push(route.into(), Reply::Response(response));
+    }
+
+    /// Fail requests matching a route with an error, e.g. to simulate a dropped
+    /// connection.
+    ///
+    /// Errors are sequenced with the responses for the route, as in [`MockService::respond`].
+    pub fn fail<F>(&mut self, route: impl Into<MockRoute>, error: F)
+    where
+        F: Fn() -> hyperdriver::client::Error + Send + Sync + 'static,
+    {
+        self.state
+            .lock()
+            .unwrap()
+            .push(route.into(), Reply::Error(Arc::new(error)));
+    }
+
+    /// The requests received so far, in the order they arrived.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl tower::Service<http::Request<Body>> for MockService {
+    type Response = http::Response<Body>;
+    type Error = hyperdriver::client::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        let state = self.state.clone();
+        Box::pin(async move {
+            let (parts, body) = req.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map(|collected| collected.to_bytes())
+                .unwrap_or_default();
+
+            let reply = {
+                let mut state = state.lock().unwrap();
+                state.requests.push(MockRequest {
+                    method: parts.method.clone(),
+                    uri: parts.uri.clone(),
+                    headers: parts.headers.clone(),
+                    body,
+                });
+                state.reply(&parts.method, &parts.uri)
+            };
+
+            match reply {
+                Some(Reply::Response(response)) => {
+                    if let Some(delay) = response.delay {
+                        tokio::time::sleep(delay).await;
+                    }
+                    Ok(response.to_response())
+                }
+                Some(Reply::Error(error)) => Err(error()),
+                None => {
+                    tracing::warn!("No mock response for {} {}", parts.method, parts.uri);
+                    Ok(MockResponse::new(
+                        http::StatusCode::NOT_FOUND,
+                        http::HeaderMap::new(),
+                        format!("No mock response for {} {}", parts.method, parts.uri.path())
+                            .into_bytes(),
+                    )
+                    .to_response())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::response::{ResponseBodyExt as _, ResponseExt as _};
+    use crate::ApiClient;
+
+    use super::*;
+
+    fn ok(body: &str) -> MockResponse {
+        MockResponse::new(
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            body.as_bytes().to_vec(),
+        )
+    }
+
+    #[tokio::test]
+    async fn routes_and_sequences() {
+        let mut mock = MockService::new();
+        mock.respond("/items", ok("any"));
+        mock.respond(MockRoute::new("/items").method(Method::POST), ok("created"));
+        mock.respond(MockRoute::new("/items").query("page", "2"), ok("first"));
+        mock.respond(MockRoute::new("/items").query("page", "2"), ok("again"));
+        mock.fail("/broken", || hyperdriver::client::Error::RequestTimeout);
+
+        let client = ApiClient::builder("http://example.com/".parse().unwrap(), ())
+            .with_inner_service(mock.clone())
+            .build();
+
+        let text = |response: crate::response::Response| response.text();
+        let get = |endpoint: &str, page: Option<&str>| {
+            let request = client.get(endpoint);
+            match page {
+                Some(page) => request
+                    .query(&[("page", page), ("per_page", "10")])
+                    .unwrap(),
+                None => request,
+            }
+            .send()
+        };
+
+        assert_eq!(
+            text(get("items", None).await.unwrap()).await.unwrap(),
+            "any"
+        );
+        for expected in ["first", "again", "again"] {
+            let response = get("items", Some("2")).await.unwrap();
+            assert_eq!(text(response).await.unwrap(), expected);
+        }
+
+        let response = client
+            .post("items")
+            .json(serde_json::json!({"name": "widget"}))
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(text(response).await.unwrap(), "created");
+
+        assert!(get("broken", None).await.is_err());
+        let response = get("missing", None).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::NOT_FOUND);
+
+        let requests = mock.requests();
+        assert_eq!(requests.len(), 7);
+        assert_eq!(requests[1].uri.query(), Some("page=2&per_page=10"));
+        assert_eq!(requests[4].method, Method::POST);
+        assert_eq!(
+            requests[4].json::<serde_json::Value>().unwrap(),
+            serde_json::json!({"name": "widget"})
+        );
+        assert_eq!(requests[6].uri.path(), "/missing");
+    }
+}
@@ -29,6 +29,14 @@ pub trait PaginationInfo {
         &self,
         req: http::Request<hyperdriver::Body>,
     ) -> Option<http::Request<hyperdriver::Body>>;
+
+    /// Update pagination information from the response headers.
+    ///
+    /// This is called after the response body has been deserialized, for APIs which
+    /// send pagination information in headers instead of the body.
+    fn update_from_headers(&mut self, headers: &http::HeaderMap) {
+        let _ = headers;
+    }
 }
 
 /// A trait for paginating responses from an API
@@ -73,6 +81,10 @@ where
     ) -> Option<http::Request<hyperdriver::Body>> {
         self.paginate.next(req)
     }
+
+    fn update_from_headers(&mut self, headers: &http::HeaderMap) {
+        self.paginate.update_from_headers(headers)
+    }
 }
 
 impl<T, P> Paginator for PaginatedData<T, P>
@@ -86,25 +98,180 @@ where
     }
 }
 
-type NextPageFuture<P> = BoxFuture<'static, Result<Option<P>, BoxError>>;
+/// A paginated response from an API which returns a bare JSON array of items,
+/// with pagination information provided by the paginator `P` (e.g. from headers).
+#[derive(Debug, Clone)]
+pub struct PaginatedList<T, P> {
+    /// The data returned in the response
+    pub data: Vec<T>,
+
+    /// Pagination information
+    pub paginate: P,
+}
+
+impl<'de, T, P> Deserialize<'de> for PaginatedList<T, P>
+where
+    T: Deserialize<'de>,
+    P: Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(Self {
+            data: Vec::deserialize(deserializer)?,
+            paginate: P::default(),
+        })
+    }
+}
+
+impl<T, P> PaginationInfo for PaginatedList<T, P>
+where
+    P: PaginationInfo,
+{
+    fn pages(&self) -> Option<usize> {
+        self.paginate.pages()
+    }
+
+    fn page(&self) -> Option<usize> {
+        self.paginate.page()
+    }
+
+    fn next(
+        &self,
+        req: http::Request<hyperdriver::Body>,
+    ) -> Option<http::Request<hyperdriver::Body>> {
+        self.paginate.next(req)
+    }
+
+    fn update_from_headers(&mut self, headers: &http::HeaderMap) {
+        self.paginate.update_from_headers(headers)
+    }
+}
+
+impl<T, P> Paginator for PaginatedList<T, P>
+where
+    P: PaginationInfo,
+{
+    type Item = T;
+
+    fn items(&mut self) -> Vec<Self::Item> {
+        std::mem::take(&mut self.data)
+    }
+}
+
+/// Pagination which follows the `rel="next"` URL in the `Link` header of each response,
+/// as described in [RFC 8288](https://www.rfc-editor.org/rfc/rfc8288).
+///
+/// This carries no information in the response body, so it can be flattened into
+/// [`PaginatedData`] or used with [`PaginatedList`] for APIs which return bare arrays.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LinkHeaderPaginator {
+    #[serde(skip)]
+    next: Option<http::Uri>,
+
+    #[serde(skip)]
+    page: Option<usize>,
+
+    #[serde(skip)]
+    pages: Option<usize>,
+}
+
+impl LinkHeaderPaginator {
+    /// Create a paginator from the `Link` headers of a response.
+    pub fn from_headers(headers: &http::HeaderMap) -> Self {
+        let mut paginator = Self::default();
+        paginator.update_from_headers(headers);
+        paginator
+    }
+
+    /// Find the URL for a link relation (e.g. `next` or `last`) in the `Link` headers.
+    pub fn link(headers: &http::HeaderMap, rel: &str) -> Option<http::Uri> {
+        headers
+            .get_all(http::header::LINK)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|link| {
+                let mut parts = link.split(';');
+                let url = parts.next()?.trim();
+                let url = url.strip_prefix('<')?.strip_suffix('>')?;
+                parts
+                    .any(|param| {
+                        param.trim().strip_prefix("rel=").is_some_and(|value| {
+                            value.trim_matches('"').split_whitespace().any(|r| r == rel)
+                        })
+                    })
+                    .then(|| url.parse().ok())
+                    .flatten()
+            })
+    }
+
+    /// The URL of the next page, if there is one.
+    pub fn next_link(&self) -> Option<&http::Uri> {
+        self.next.as_ref()
+    }
+}
 
-enum PaginatedStreamState<T, P> {
-    Query,
-    Buffered(VecDeque<T>),
-    Requesting(NextPageFuture<P>),
-    Done,
+/// Read the `page` query parameter from a URL.
+fn page_number(uri: &http::Uri) -> Option<usize> {
+    serde_urlencoded::from_str::<Vec<(String, String)>>(uri.query()?)
+        .ok()?
+        .into_iter()
+        .find_map(|(key, value)| (key == "page").then(|| value.parse().ok()).flatten())
 }
 
+impl PaginationInfo for LinkHeaderPaginator {
+    fn pages(&self) -> Option<usize> {
+        self.pages
+    }
+
+    fn page(&self) -> Option<usize> {
+        self.page
+    }
+
+    fn next(
+        &self,
+        mut req: http::Request<hyperdriver::Body>,
+    ) -> Option<http::Request<hyperdriver::Body>> {
+        *req.uri_mut() = self.next.clone()?;
+        Some(req)
+    }
+
+    fn update_from_headers(&mut self, headers: &http::HeaderMap) {
+        self.next = Self::link(headers, "next");
+        self.page = self
+            .next
+            .as_ref()
+            .and_then(page_number)
+            .map(|next| next.saturating_sub(1))
+            .or_else(|| {
+                Self::link(headers, "prev").and_then(|prev| page_number(&prev).map(|p| p + 1))
+            });
+        self.pages = Self::link(headers, "last")
+            .and_then(|last| page_number(&last))
+            .or(self.page.filter(|_| self.next.is_none()));
+    }
+}
+
+type NextPageFuture<P> = BoxFuture<'static, Result<Option<P>, BoxError>>;
+
 /// A stream of items which can be collected from a paginated API response.
 ///
 /// The type `A` should implement the `Authentication` trait, and the type `T` should be the type
 /// of item that is returned in the paginated response. The type `P` should implement the `Paginator`
 /// trait, and will be used to paginate the response.
+///
+/// By default each page is requested once the items from the previous page have been
+/// consumed. Use [`Paginated::prefetch`] to request pages ahead of the consumer.
 #[pin_project::pin_project]
 pub struct Paginated<A, T, P> {
     client: crate::ApiClient<A>,
     request: Option<http::Request<hyperdriver::Body>>,
-    state: PaginatedStreamState<T, P>,
+    pages: VecDeque<VecDeque<T>>,
+    requesting: Option<NextPageFuture<P>>,
+    error: Option<BoxError>,
+    prefetch: usize,
 }
 
 impl<A: fmt::Debug, T, P> fmt::Debug for Paginated<A, T, P> {
@@ -112,6 +279,7 @@ impl<A: fmt::Debug, T, P> fmt::Debug for Paginated<A, T, P> {
         f.debug_struct("Paginated")
             .field("client", &self.client)
             .field("request", &self.request)
+            .field("prefetch", &self.prefetch)
             .finish()
     }
 }
@@ -122,11 +290,83 @@ impl<A, T, P> Paginated<A, T, P> {
         Self {
             client,
             request: Some(request),
-            state: PaginatedStreamState::Query,
+            pages: VecDeque::new(),
+            requesting: None,
+            error: None,
+            prefetch: 0,
+        }
+    }
+
+    /// Request up to `pages` pages ahead of the page currently being consumed.
+    ///
+    /// Each page request still depends on the previous response, so pages are fetched
+    /// one at a time, but the next request is sent while buffered items are consumed.
+    /// Up to `pages + 1` pages of items may be held in memory.
+    pub fn prefetch(mut self, pages: usize) -> Self {
+        self.prefetch = pages;
+        self
+    }
+}
+
+/// Clone a request, so that it can be used to build the request for the following page.
+fn clone_request(
+    request: &http::Request<hyperdriver::Body>,
+) -> Option<http::Request<hyperdriver::Body>> {
+    let Some(body) = request.body().try_clone() else {
+        tracing::error!("Unable to clone the request body");
+        return None;
+    };
+
+    let mut builder = http::Request::builder()
+        .method(request.method())
+        .uri(request.uri());
+
+    if let Some(headers) = builder.headers_mut() {
+        *headers = request.headers().clone();
+    }
+
+    match builder.body(body) {
+        Ok(request) => Some(request),
+        Err(_) => {
+            tracing::error!("Unable to clone the request");
+            None
         }
     }
 }
 
+fn request_page<A, P>(
+    client: crate::ApiClient<A>,
+    request: http::Request<hyperdriver::Body>,
+) -> NextPageFuture<P>
+where
+    A: crate::Authentication + Send + Sync + 'static,
+    P: PaginationInfo + serde::de::DeserializeOwned + Send + 'static,
+{
+    tracing::trace!("Requesting next page: {:?}", request.uri());
+
+    Box::pin(async move {
+        let response = client.execute(request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await?;
+            if let Some(error) = client.decode_error(status, text.as_bytes()) {
+                return Err(error);
+            }
+
+            return Err(Box::new(PaginationError {
+                message: format!("{}: {}", status, text),
+                source: None,
+            }) as BoxError);
+        }
+
+        let headers = response.headers().clone();
+        let mut paginator: P = response.json().await?;
+        paginator.update_from_headers(&headers);
+        Ok(Some(paginator))
+    })
+}
+
 impl<A, T, P> futures::Stream for Paginated<A, T, P>
 where
     A: crate::Authentication + Send + Sync + 'static,
@@ -140,99 +380,193 @@ where
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Option<Self::Item>> {
         let this = self.project();
-        match this.state {
-            PaginatedStreamState::Query => {
-                let next_future = {
-                    let Some(request) = this.request.as_ref() else {
-                        tracing::trace!("No more pages to request, stream is done");
-                        *this.state = PaginatedStreamState::Done;
-                        return std::task::Poll::Ready(None);
-                    };
-
-                    let Some(body) = request.body().try_clone() else {
-                        tracing::error!("Unable to clone the request body");
-                        *this.state = PaginatedStreamState::Done;
-                        return std::task::Poll::Ready(None);
-                    };
-
-                    let builder = {
-                        let mut builder = http::Request::builder()
-                            .method(request.method())
-                            .uri(request.uri());
-
-                        if let Some(headers) = builder.headers_mut() {
-                            *headers = request.headers().clone();
+
+        loop {
+            if let Some(future) = this.requesting.as_mut() {
+                match future.poll_unpin(cx) {
+                    std::task::Poll::Ready(Ok(Some(mut paginator))) => {
+                        tracing::trace!(
+                            "Paginated request on page {} of {}",
+                            paginator.page().unwrap_or(0),
+                            paginator.pages().unwrap_or(0)
+                        );
+
+                        *this.requesting = None;
+                        let items = VecDeque::from(paginator.items());
+                        if !items.is_empty() {
+                            this.pages.push_back(items);
                         }
-                        builder.body(body)
-                    };
+                        if let Some(request) = this.request.take() {
+                            *this.request = paginator.next(request);
+                        }
+                    }
+                    std::task::Poll::Ready(Ok(None)) => {
+                        *this.requesting = None;
+                        *this.request = None;
+                    }
+                    std::task::Poll::Ready(Err(error)) => {
+                        // Items which were already fetched are yielded before the error.
+                        *this.requesting = None;
+                        *this.request = None;
+                        *this.error = Some(error);
+                    }
+                    std::task::Poll::Pending => {}
+                }
+            }
 
-                    let Ok(request) = builder else {
-                        tracing::error!("Unable to clone the request");
-                        *this.state = PaginatedStreamState::Done;
-                        return std::task::Poll::Ready(None);
-                    };
+            if this.requesting.is_none() && this.pages.len() <= *this.prefetch {
+                if let Some(request) = this.request.as_ref() {
+                    match clone_request(request) {
+                        Some(request) => {
+                            *this.requesting = Some(request_page(this.client.clone(), request));
+                            continue;
+                        }
+                        None => *this.request = None,
+                    }
+                }
+            }
 
-                    tracing::trace!("Requesting next page: {:?}", request.uri());
+            if let Some(page) = this.pages.front_mut() {
+                let item = page.pop_front();
+                if page.is_empty() {
+                    this.pages.pop_front();
+                }
+                if let Some(item) = item {
+                    return std::task::Poll::Ready(Some(Ok(item)));
+                }
+            }
 
-                    let client = this.client.clone();
+            if let Some(error) = this.error.take() {
+                return std::task::Poll::Ready(Some(Err(error)));
+            }
 
-                    Box::pin(async move {
-                        let response = client.execute(request).await?;
+            if this.requesting.is_some() {
+                return std::task::Poll::Pending;
+            }
 
-                        if !response.status().is_success() {
-                            let status = response.status();
-                            let text = response.text().await?;
-                            return Err(Box::new(PaginationError {
-                                message: format!("{}: {}", status, text),
-                                source: None,
-                            }) as BoxError);
-                        }
+            tracing::trace!("No more pages to request, stream is done");
+            return std::task::Poll::Ready(None);
+        }
+    }
+}
 
-                        Ok(Some(response.json().await?))
-                    })
-                };
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt as _;
+    use http::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn link_header() {
+        let mut headers = http::HeaderMap::new();
+        assert!(LinkHeaderPaginator::link(&headers, "next").is_none());
+
+        headers.insert(
+            http::header::LINK,
+            HeaderValue::from_static(
+                r#"<https://api.example.com/items?page=1>; rel="prev", <https://api.example.com/items?page=3>; rel="next", <https://api.example.com/items?page=5>; rel="last""#,
+            ),
+        );
+
+        let paginator = LinkHeaderPaginator::from_headers(&headers);
+        assert_eq!(
+            paginator.next_link().map(|uri| uri.to_string()).as_deref(),
+            Some("https://api.example.com/items?page=3")
+        );
+        assert_eq!(paginator.page(), Some(2));
+        assert_eq!(paginator.pages(), Some(5));
+
+        headers.insert(
+            http::header::LINK,
+            HeaderValue::from_static(
+                r#"<https://api.example.com/items?page=4>; rel="prev", <https://api.example.com/items?page=1>; rel="first""#,
+            ),
+        );
+        let paginator = LinkHeaderPaginator::from_headers(&headers);
+        assert!(paginator.next_link().is_none());
+        assert_eq!(paginator.page(), Some(5));
+        assert_eq!(paginator.pages(), Some(5));
+    }
 
-                *this.state = PaginatedStreamState::Requesting(next_future);
-                cx.waker().wake_by_ref();
-                std::task::Poll::Pending
-            }
-            PaginatedStreamState::Buffered(ref mut items) => {
-                if let Some(item) = items.pop_front() {
-                    std::task::Poll::Ready(Some(Ok(item)))
-                } else {
-                    tracing::trace!("Buffer is empty, requesting next page");
-                    *this.state = PaginatedStreamState::Query;
-                    cx.waker().wake_by_ref();
-                    std::task::Poll::Pending
-                }
-            }
-            PaginatedStreamState::Requesting(ref mut future) => match future.poll_unpin(cx) {
-                std::task::Poll::Ready(Ok(Some(mut paginator))) => {
-                    tracing::trace!(
-                        "Paginated request on page {} of {}",
-                        paginator.page().unwrap_or(0),
-                        paginator.pages().unwrap_or(0)
-                    );
-
-                    *this.state = PaginatedStreamState::Buffered(VecDeque::from(paginator.items()));
-                    if let Some(request) = this.request.take() {
-                        *this.request = paginator.next(request);
-                    }
-                    cx.waker().wake_by_ref();
-                    std::task::Poll::Pending
-                }
-                std::task::Poll::Ready(Ok(None)) => {
-                    *this.state = PaginatedStreamState::Done;
-                    std::task::Poll::Ready(None)
-                }
+    #[tokio::test]
+    async fn follow_link_headers() {
+        let mut mock = crate::mock::MockService::new();
+
+        let mut headers = http::HeaderMap::new();
+        headers.insert(
+            http::header::LINK,
+            HeaderValue::from_static(r#"<http://api.example.com/items/next?page=2>; rel="next""#),
+        );
+        mock.add(
+            "/items",
+            http::StatusCode::OK,
+            headers,
+            serde_json::to_vec(&[1, 2]).unwrap(),
+        );
+        mock.add(
+            "/items/next",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&[3]).unwrap(),
+        );
+
+        let client = crate::ApiClient::builder("http://api.example.com/".parse().unwrap(), ())
+            .with_inner_service(hyperdriver::service::SharedService::new(mock))
+            .build();
+        let request = client
+            .get("/items")
+            .body(hyperdriver::Body::empty())
+            .build()
+            .unwrap();
+
+        let items: Vec<u32> =
+            Paginated::<_, u32, PaginatedList<u32, LinkHeaderPaginator>>::new(client, request)
+                .try_collect()
+                .await
+                .unwrap();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
 
-                std::task::Poll::Ready(Err(error)) => {
-                    *this.state = PaginatedStreamState::Done;
-                    std::task::Poll::Ready(Some(Err(error)))
-                }
-                std::task::Poll::Pending => std::task::Poll::Pending,
-            },
-            PaginatedStreamState::Done => std::task::Poll::Ready(None),
+    #[tokio::test]
+    async fn prefetch_pages() {
+        let mut mock = crate::mock::MockService::new();
+
+        for (page, items) in [(1, [1, 2]), (2, [3, 4]), (3, [5, 6])] {
+            let mut headers = http::HeaderMap::new();
+            if page < 3 {
+                let link = format!(
+                    r#"<http://api.example.com/items/{next}>; rel="next""#,
+                    next = page + 1
+                );
+                headers.insert(http::header::LINK, link.parse().unwrap());
+            }
+            mock.add(
+                &format!("/items/{page}"),
+                http::StatusCode::OK,
+                headers,
+                serde_json::to_vec(&items).unwrap(),
+            );
         }
+
+        let client = crate::ApiClient::builder("http://api.example.com/".parse().unwrap(), ())
+            .with_inner_service(hyperdriver::service::SharedService::new(mock))
+            .build();
+        let request = client
+            .get("/items/1")
+            .body(hyperdriver::Body::empty())
+            .build()
+            .unwrap();
+
+        let mut stream =
+            Paginated::<_, u32, PaginatedList<u32, LinkHeaderPaginator>>::new(client, request)
+                .prefetch(1);
+
+        // The second page is requested as soon as the first arrives.
+        assert_eq!(stream.try_next().await.unwrap(), Some(1));
+        assert_eq!(stream.pages.len(), 2);
+
+        let rest: Vec<u32> = stream.try_collect().await.unwrap();
+        assert_eq!(rest, vec![2, 3, 4, 5, 6]);
     }
 }
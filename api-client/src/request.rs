@@ -1,5 +1,6 @@
 //! Request building utilities
 
+use std::sync::Arc;
 use std::time::Duration;
 
 use http::Uri;
@@ -9,7 +10,10 @@ use serde::Serialize;
 use tower::ServiceExt as _;
 
 use crate::basic_auth;
-use crate::error::Error;
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
+use crate::error::{Error, ErrorDecoder};
+use crate::sse::EventStream;
 
 use crate::uri::UriExtension;
 use crate::{response::Response, ApiClient};
@@ -107,13 +111,24 @@ impl RequestExt for http::request::Builder {
     }
 }
 
+/// The body of a request, held in memory when possible so that it can be compressed.
+#[derive(Debug)]
+enum RequestBody {
+    Bytes(bytes::Bytes),
+    Body(Body),
+}
+
 /// Builder for HTTP requests on an API client
 #[derive(Debug)]
 pub struct RequestBuilder {
     req: http::request::Builder,
     client: hyperdriver::client::SharedClientService<Body, Body>,
-    body: Option<Body>,
+    body: Option<RequestBody>,
     timeout: Option<Duration>,
+    decoder: Option<Arc<dyn ErrorDecoder>>,
+    max_response_size: Option<usize>,
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
 }
 
 impl RequestBuilder {
@@ -124,6 +139,10 @@ impl RequestBuilder {
             client: client.inner.inner.clone(),
             body: None,
             timeout: None,
+            decoder: client.inner.decoder.clone(),
+            max_response_size: client.inner.max_response_size,
+            #[cfg(feature = "compression")]
+            compression: None,
         }
     }
 
@@ -155,6 +174,11 @@ impl RequestBuilder {
         self
     }
 
+    /// The URI the request will be sent to
+    pub fn uri(&self) -> &Uri {
+        self.req.uri_ref().expect("request builder has a uri")
+    }
+
     /// Get a mutable reference to the headers of the request
     pub fn headers_mut(&mut self) -> Option<&mut http::header::HeaderMap> {
         self.req.headers_mut()
@@ -182,11 +206,31 @@ impl RequestBuilder {
     /// Set the body of the request
     pub fn body<B: Into<Body>>(self, body: B) -> Self {
         Self {
-            body: Some(body.into()),
+            body: Some(RequestBody::Body(body.into())),
             ..self
         }
     }
 
+    /// Set the body of the request from bytes held in memory.
+    ///
+    /// Unlike [`RequestBuilder::body`], these bodies can be compressed.
+    pub fn bytes<B: Into<bytes::Bytes>>(self, body: B) -> Self {
+        Self {
+            body: Some(RequestBody::Bytes(body.into())),
+            ..self
+        }
+    }
+
+    /// Compress the body of the request, and set the `Content-Encoding` header.
+    ///
+    /// Only bodies set with [`RequestBuilder::json`] or [`RequestBuilder::bytes`]
+    /// are compressed, since other bodies may be streamed.
+    #[cfg(feature = "compression")]
+    pub fn compress(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
     /// Set the body of the request as JSON
     pub fn json<D: Serialize>(self, body: D) -> Result<Self> {
         let body = bytes::Bytes::from(
@@ -194,7 +238,7 @@ impl RequestBuilder {
         );
 
         Ok(Self {
-            body: Some(Body::from(body)),
+            body: Some(RequestBody::Bytes(body)),
             req: self
                 .req
                 .header(http::header::CONTENT_TYPE, "application/json"),
@@ -204,28 +248,70 @@ impl RequestBuilder {
 
     /// Send the request and return the response
     pub async fn send(self) -> Result<Response, hyperdriver::client::Error> {
-        let req = self
-            .req
-            .body(self.body.unwrap_or_else(Body::empty))
-            .expect("valid request");
+        let timeout = self.timeout;
+        let limit = self.max_response_size;
+        let client = self.client.clone();
+        let req = self.build().expect("valid request");
 
         let parts = req.parts();
-        let future = self.client.oneshot(req);
+        let future = client.oneshot(req);
 
-        if let Some(timeout) = self.timeout {
+        let response = if let Some(timeout) = timeout {
             match tokio::time::timeout(timeout, future).await {
-                Ok(res) => Ok(res.map(|response| Response::new(parts, response))?),
-                Err(_) => Err(hyperdriver::client::Error::RequestTimeout),
+                Ok(res) => res?,
+                Err(_) => return Err(hyperdriver::client::Error::RequestTimeout),
             }
         } else {
-            Ok(future
-                .await
-                .map(|response| Response::new(parts, response))?)
-        }
+            future.await?
+        };
+
+        Ok(Response::new(parts, response).with_body_limit(limit))
+    }
+
+    /// Send the request, returning an error if the response was not successful.
+    ///
+    /// Error response bodies are decoded with the client's [`ErrorDecoder`], if one is set.
+    pub async fn send_checked(self) -> Result<Response, Error> {
+        let decoder = self.decoder.clone();
+        let response = self.send().await?;
+        crate::error_for_status(decoder.as_deref(), response).await
+    }
+
+    /// Send the request and stream the server-sent events in the response.
+    ///
+    /// The `Accept` header is set to `text/event-stream`, and an error is returned
+    /// if the response was not successful.
+    pub async fn event_stream(self) -> Result<EventStream, Error> {
+        let response = self
+            .header(http::header::ACCEPT, "text/event-stream")
+            .send_checked()
+            .await?;
+        let (_, _, body) = response.into_parts();
+        Ok(crate::sse::event_stream(body))
     }
 
     /// Build the request
     pub fn build(self) -> Result<http::Request<Body>, http::Error> {
-        self.req.body(self.body.unwrap_or_else(Body::empty))
+        let body = match self.body {
+            None => Body::empty(),
+            Some(RequestBody::Body(body)) => body,
+            Some(RequestBody::Bytes(bytes)) => {
+                #[cfg(feature = "compression")]
+                if let Some(compression) = self.compression {
+                    let compressed = bytes::Bytes::from(compression.compress(&bytes));
+                    return self
+                        .req
+                        .header(
+                            http::header::CONTENT_ENCODING,
+                            compression.content_encoding(),
+                        )
+                        .body(Body::from(compressed));
+                }
+
+                Body::from(bytes)
+            }
+        };
+
+        self.req.body(body)
     }
 }
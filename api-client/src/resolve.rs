@@ -0,0 +1,147 @@
+//! Overriding DNS resolution for some hosts, e.g. to point a client at a local
+//! mock server in tests without rewriting the URIs it requests.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tower::Service as _;
+
+use crate::proxy::{host_and_port, ProxyTransport};
+use crate::BoxFuture;
+
+/// Addresses to connect to for some hosts, instead of the ones found in DNS.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Overrides(HashMap<String, SocketAddr>);
+
+impl Overrides {
+    /// Connect to `addr` for every request to `host`, whatever the request's port.
+    pub(crate) fn insert(&mut self, host: &str, addr: SocketAddr) {
+        self.0.insert(host.to_ascii_lowercase(), addr);
+    }
+
+    fn get(&self, host: &str) -> Option<SocketAddr> {
+        self.0.get(&host.to_ascii_lowercase()).copied()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A hyperdriver transport which connects to overridden addresses directly, and
+/// to other hosts through a proxy, if one is set.
+#[derive(Debug, Clone)]
+pub(crate) struct ResolveTransport {
+    overrides: Arc<Overrides>,
+    proxy: Option<ProxyTransport>,
+    connect_timeout: Option<Duration>,
+}
+
+impl ResolveTransport {
+    pub(crate) fn new(
+        overrides: Overrides,
+        proxy: Option<ProxyTransport>,
+        connect_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            overrides: Arc::new(overrides),
+            proxy,
+            connect_timeout,
+        }
+    }
+}
+
+impl tower::Service<http::request::Parts> for ResolveTransport {
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<TcpStream, io::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, parts: http::request::Parts) -> Self::Future {
+        let (host, port) = match host_and_port(&parts.uri) {
+            Ok(authority) => authority,
+            Err(error) => return Box::pin(std::future::ready(Err(error))),
+        };
+
+        let addr = self.overrides.get(&host);
+        if addr.is_none() {
+            if let Some(proxy) = &mut self.proxy {
+                return proxy.call(parts);
+            }
+        }
+
+        let connect_timeout = self.connect_timeout;
+        Box::pin(async move {
+            let connect = async {
+                let stream = match (addr, host.parse::<IpAddr>()) {
+                    (Some(addr), _) => {
+                        tracing::trace!(%host, %addr, "Connecting to overridden address");
+                        TcpStream::connect(addr).await?
+                    }
+                    (None, Ok(address)) => {
+                        TcpStream::connect(SocketAddr::new(address, port)).await?
+                    }
+                    (None, Err(_)) => TcpStream::connect((host.as_str(), port)).await?,
+                };
+                stream.set_nodelay(true)?;
+                Ok(stream)
+            };
+
+            match connect_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, connect)
+                    .await
+                    .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))?,
+                None => connect.await,
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::TcpListener;
+
+    use crate::response::{ResponseBodyExt as _, ResponseExt as _};
+    use crate::ApiClient;
+
+    #[tokio::test]
+    async fn request_to_overridden_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let client = ApiClient::builder("http://api.example.com/".parse().unwrap(), ())
+            .without_proxy()
+            .with_resolve("API.example.com", addr)
+            .build();
+
+        let response = client.get("status").send().await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        let request = server.await.unwrap().to_ascii_lowercase();
+        assert!(request.starts_with("get /status http/1.1\r\n"));
+        assert!(request.contains("host: api.example.com\r\n"));
+    }
+}
@@ -9,22 +9,42 @@ mod futures {
     use std::pin::Pin;
     use std::task::{ready, Context, Poll};
 
-    use http_body_util::combinators::Collect;
-    use http_body_util::BodyExt as _;
+    use bytes::{Buf as _, BufMut as _, BytesMut};
     use pin_project::pin_project;
     use tower::BoxError;
 
+    use crate::error::BodyTooLarge;
+
     #[pin_project]
-    pub struct Bytes<Body = hyperdriver::Body>(#[pin] Collect<Body>)
+    pub struct Bytes<Body = hyperdriver::Body>
     where
-        Body: http_body::Body;
+        Body: http_body::Body,
+    {
+        #[pin]
+        body: Body,
+        buffer: BytesMut,
+        limit: Option<usize>,
+    }
+
+    impl<Body> Bytes<Body>
+    where
+        Body: http_body::Body,
+    {
+        pub(crate) fn new(body: Body, limit: Option<usize>) -> Self {
+            Self {
+                body,
+                buffer: BytesMut::new(),
+                limit,
+            }
+        }
+    }
 
     impl<Body> fmt::Debug for Bytes<Body>
     where
         Body: http_body::Body,
     {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            f.debug_struct("Bytes").finish()
+            f.debug_struct("Bytes").field("limit", &self.limit).finish()
         }
     }
 
@@ -36,8 +56,30 @@ mod futures {
         type Output = Result<bytes::Bytes, BoxError>;
 
         fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-            let collected = ready!(self.project().0.poll(cx)).map_err(Into::into)?;
-            Poll::Ready(Ok(collected.to_bytes()))
+            let mut this = self.project();
+            loop {
+                if let Some(limit) = *this.limit {
+                    // Fail early when the body says it will be too large, e.g. from
+                    // its Content-Length, rather than buffering up to the limit first.
+                    let remaining = this.body.size_hint().lower();
+                    if this.buffer.len() as u64 + remaining > limit as u64 {
+                        return Poll::Ready(Err(BodyTooLarge { limit }.into()));
+                    }
+                }
+
+                let Some(frame) = ready!(this.body.as_mut().poll_frame(cx)) else {
+                    return Poll::Ready(Ok(std::mem::take(this.buffer).freeze()));
+                };
+
+                if let Ok(data) = frame.map_err(Into::into)?.into_data() {
+                    if let Some(limit) = *this.limit {
+                        if this.buffer.len() + data.remaining() > limit {
+                            return Poll::Ready(Err(BodyTooLarge { limit }.into()));
+                        }
+                    }
+                    this.buffer.put(data);
+                }
+            }
         }
     }
 
@@ -46,7 +88,7 @@ mod futures {
         Body: http_body::Body,
     {
         fn from(body: Body) -> Self {
-            Self(body.collect())
+            Self::new(body, None)
         }
     }
 
@@ -227,6 +269,7 @@ pub struct Response {
     request: http::request::Parts,
     response: http::response::Parts,
     body: Body,
+    limit: Option<usize>,
 }
 
 impl Response {
@@ -238,9 +281,20 @@ impl Response {
             request,
             response,
             body,
+            limit: None,
         }
     }
 
+    /// Limit the size of the body collected by [`ResponseBodyExt::bytes`] and the
+    /// methods built on it, or remove the limit with `None`.
+    ///
+    /// Collecting a larger body fails with [`BodyTooLarge`](crate::error::BodyTooLarge).
+    /// Streaming the body, e.g. with [`Response::json_stream_array`], is not limited.
+    pub fn with_body_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
     /// Get the parts of the request that generated the response.
     pub fn into_parts(self) -> (http::request::Parts, http::response::Parts, Body) {
         (self.request, self.response, self.body)
@@ -251,6 +305,19 @@ impl Response {
         http::Response::from_parts(self.response, self.body)
     }
 
+    /// Stream the elements of a top-level JSON array in the response body, as they arrive.
+    ///
+    /// Unlike deserializing the whole body, only the unparsed part of the body is
+    /// buffered, so very large lists can be processed one element at a time.
+    pub fn json_stream_array<T>(
+        self,
+    ) -> futures::stream::BoxStream<'static, Result<T, tower::BoxError>>
+    where
+        T: serde::de::DeserializeOwned + Send + 'static,
+    {
+        crate::json_array::json_array(self.body)
+    }
+
     /// Convert the `Response` into an `HttpResponseError` instance.
     pub async fn into_error(self) -> HttpResponseError {
         HttpResponseError::from_response(self).await
@@ -272,11 +339,7 @@ impl ResponseBodyExt<hyperdriver::Body> for Response {
     }
 
     fn bytes(self) -> self::futures::Bytes {
-        self.body.into()
-    }
-
-    fn text(self) -> self::futures::Text {
-        self.body.into()
+        self::futures::Bytes::new(self.body, self.limit)
     }
 }
 
@@ -59,40 +59,46 @@ impl<E> Policy<http::Request<Body>, http::Response<Body>, E> for Backoff {
         req: &mut http::Request<Body>,
         result: &mut Result<http::Response<Body>, E>,
     ) -> Option<Self::Future> {
+        // Requests which may have had side effects are only retried when the
+        // server said it didn't process them, i.e. when they were rate limited.
+        let idempotent = req.method().is_idempotent();
         let backoff = self.increment()?;
-        match result {
+        let next = match result {
             Ok(res) => match res.status() {
-                StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => {
+                StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT if idempotent => {
                     tracing::debug!("retrying request to {} due to timeout", req.uri());
-                    Some(BackoffFuture::new(backoff))
+                    backoff
                 }
-                status if status.is_server_error() => {
+                status if status.is_server_error() && idempotent => {
                     tracing::debug!("retrying request to {} due to server error", req.uri());
-                    Some(BackoffFuture::new(backoff))
+                    backoff
                 }
                 StatusCode::TOO_MANY_REQUESTS => {
                     tracing::debug!("retrying request to {} due to rate limit", req.uri());
-                    Some(BackoffFuture::new(
-                        req.headers()
-                            .get(http::header::RETRY_AFTER)
-                            .and_then(|value| {
-                                value.to_str().ok().and_then(|value| {
-                                    value.parse::<u64>().ok().map(|value| {
-                                        let delay = std::time::Duration::from_secs(value);
-                                        self.rate_limited(delay)
-                                    })
-                                })
-                            })
-                            .unwrap_or(backoff),
-                    ))
+                    match rate_limit_delay(res) {
+                        Some(delay) if delay > self.max_delay => return None,
+                        Some(delay) => self.rate_limited(delay),
+                        None => backoff,
+                    }
+                }
+                StatusCode::FORBIDDEN => {
+                    // Some APIs (e.g. Github's secondary rate limits) use 403 with a Retry-After
+                    // or rate limit reset header to signal rate limiting, rather than 429.
+                    let delay = rate_limit_delay(res).filter(|delay| *delay <= self.max_delay)?;
+                    tracing::debug!("retrying request to {} due to rate limit", req.uri());
+                    self.rate_limited(delay)
                 }
-                _ => None,
+                _ => return None,
             },
-            Err(_) => {
+            Err(_) if idempotent => {
                 tracing::warn!("retrying request to {} due to error", req.uri());
-                Some(BackoffFuture::new(backoff))
+                backoff
             }
-        }
+            Err(_) => return None,
+        };
+
+        *self = next.clone();
+        Some(BackoffFuture::new(next))
     }
 
     fn clone_request(&mut self, req: &http::Request<Body>) -> Option<http::Request<Body>> {
@@ -100,6 +106,39 @@ impl<E> Policy<http::Request<Body>, http::Response<Body>, E> for Backoff {
     }
 }
 
+/// How long a rate limited response asked clients to wait.
+///
+/// This is the `Retry-After` header, in seconds, or, when the rate limit is used
+/// up (`x-ratelimit-remaining: 0`), the time until `x-ratelimit-reset`.
+fn rate_limit_delay(res: &http::Response<Body>) -> Option<std::time::Duration> {
+    retry_after(res).or_else(|| rate_limit_reset(res))
+}
+
+/// Parse the `Retry-After` header of a response, in seconds.
+fn retry_after(res: &http::Response<Body>) -> Option<std::time::Duration> {
+    let value = res.headers().get(http::header::RETRY_AFTER)?;
+    let seconds = value.to_str().ok()?.trim().parse::<u64>().ok()?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+/// The time until an exhausted rate limit resets, from the `x-ratelimit-reset`
+/// header, in seconds since the unix epoch.
+fn rate_limit_reset(res: &http::Response<Body>) -> Option<std::time::Duration> {
+    let header =
+        |name: &str| -> Option<u64> { res.headers().get(name)?.to_str().ok()?.trim().parse().ok() };
+
+    if header("x-ratelimit-remaining")? != 0 {
+        return None;
+    }
+
+    let reset =
+        std::time::UNIX_EPOCH + std::time::Duration::from_secs(header("x-ratelimit-reset")?);
+    let delay = reset
+        .duration_since(std::time::SystemTime::now())
+        .unwrap_or_default();
+    Some(delay.max(std::time::Duration::from_secs(1)))
+}
+
 fn try_clone_request(req: &http::Request<Body>) -> Option<http::Request<Body>> {
     let body = req.body().try_clone()?;
 
@@ -200,3 +239,122 @@ impl<E> Policy<http::Request<Body>, http::Response<Body>, E> for Attempts {
         try_clone_request(req)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn response(status: StatusCode, retry_after: Option<&str>) -> http::Response<Body> {
+        let mut builder = http::Response::builder().status(status);
+        if let Some(retry_after) = retry_after {
+            builder = builder.header(http::header::RETRY_AFTER, retry_after);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    fn retry_method(
+        policy: &mut Backoff,
+        method: http::Method,
+        res: http::Response<Body>,
+    ) -> Option<BackoffFuture> {
+        let mut req = http::Request::builder()
+            .method(method)
+            .uri("http://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        let mut result: Result<_, ()> = Ok(res);
+        policy.retry(&mut req, &mut result)
+    }
+
+    fn retry(policy: &mut Backoff, res: http::Response<Body>) -> Option<BackoffFuture> {
+        retry_method(policy, http::Method::GET, res)
+    }
+
+    #[tokio::test]
+    async fn backoff_rate_limits() {
+        let mut policy = Backoff::new(Duration::from_secs(1), 2, Duration::from_secs(60));
+
+        assert!(retry(&mut policy, response(StatusCode::FORBIDDEN, None)).is_none());
+        assert!(retry(&mut policy, response(StatusCode::FORBIDDEN, Some("3"))).is_some());
+        assert_eq!(policy.delay, Duration::from_secs(3));
+
+        assert!(retry(
+            &mut policy,
+            response(StatusCode::TOO_MANY_REQUESTS, Some("600"))
+        )
+        .is_none());
+
+        assert!(retry(&mut policy, response(StatusCode::BAD_GATEWAY, None)).is_some());
+        assert_eq!(policy.delay, Duration::from_secs(6));
+
+        policy.delay = Duration::from_secs(45);
+        assert!(retry(&mut policy, response(StatusCode::BAD_GATEWAY, None)).is_none());
+    }
+
+    #[tokio::test]
+    async fn backoff_until_rate_limit_reset() {
+        let mut policy = Backoff::new(Duration::from_secs(1), 2, Duration::from_secs(120));
+
+        let reset = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            + Duration::from_secs(30);
+        let exhausted = || {
+            http::Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .header("x-ratelimit-remaining", "0")
+                .header("x-ratelimit-reset", reset.as_secs().to_string())
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        assert!(retry_method(&mut policy, http::Method::POST, exhausted()).is_some());
+        assert!(policy.delay > Duration::from_secs(25), "{:?}", policy.delay);
+        assert!(
+            policy.delay <= Duration::from_secs(30),
+            "{:?}",
+            policy.delay
+        );
+
+        let remaining = http::Response::builder()
+            .status(StatusCode::FORBIDDEN)
+            .header("x-ratelimit-remaining", "10")
+            .header("x-ratelimit-reset", reset.as_secs().to_string())
+            .body(Body::empty())
+            .unwrap();
+        assert!(retry(&mut policy, remaining).is_none());
+    }
+
+    #[tokio::test]
+    async fn backoff_only_retries_idempotent_errors() {
+        let mut policy = Backoff::new(Duration::from_secs(1), 2, Duration::from_secs(60));
+
+        assert!(retry_method(
+            &mut policy,
+            http::Method::POST,
+            response(StatusCode::BAD_GATEWAY, None)
+        )
+        .is_none());
+        assert!(retry_method(
+            &mut policy,
+            http::Method::PUT,
+            response(StatusCode::BAD_GATEWAY, None)
+        )
+        .is_some());
+
+        let mut req = http::Request::post("http://example.com/")
+            .body(Body::empty())
+            .unwrap();
+        let mut result: Result<http::Response<Body>, ()> = Err(());
+        assert!(policy.retry(&mut req, &mut result).is_none());
+
+        assert!(retry_method(
+            &mut policy,
+            http::Method::POST,
+            response(StatusCode::TOO_MANY_REQUESTS, Some("1"))
+        )
+        .is_some());
+    }
+}
@@ -0,0 +1,230 @@
+//! Server-sent events, parsed from a `text/event-stream` response body.
+//!
+//! See the [HTML specification](https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation)
+//! for the format.
+
+use std::time::Duration;
+
+use bytes::Buf as _;
+use futures::stream::{BoxStream, StreamExt as _};
+use http_body_util::BodyExt as _;
+use tower::BoxError;
+
+/// A stream of server-sent events.
+pub type EventStream = BoxStream<'static, Result<Event, BoxError>>;
+
+/// A single server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    /// The event type, from the `event` field. Events without one have the type `message`.
+    pub event: String,
+
+    /// The event payload, from the `data` fields, joined with newlines.
+    pub data: String,
+
+    /// The last event ID sent by the server, from the `id` field.
+    pub id: Option<String>,
+
+    /// How long the server asked clients to wait before reconnecting, from the `retry` field.
+    pub retry: Option<Duration>,
+}
+
+impl Event {
+    /// Deserialize the payload of the event as JSON.
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_str(&self.data)
+    }
+}
+
+/// Accumulates lines into events.
+#[derive(Debug, Default)]
+struct EventParser {
+    buffer: Vec<u8>,
+    started: bool,
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl EventParser {
+    /// Take the next complete line from the buffer, without its line ending.
+    fn line(&mut self, eof: bool) -> Option<Vec<u8>> {
+        let end = self
+            .buffer
+            .iter()
+            .position(|&byte| byte == b'\n' || byte == b'\r')?;
+
+        let ending = match (self.buffer[end], self.buffer.get(end + 1)) {
+            (b'\r', Some(b'\n')) => 2,
+            // A trailing CR might be the first half of a CRLF.
+            (b'\r', None) if !eof => return None,
+            _ => 1,
+        };
+
+        let mut line: Vec<u8> = self.buffer.drain(..end + ending).collect();
+        line.truncate(end);
+        Some(line)
+    }
+
+    /// Parse buffered lines until an event is complete.
+    fn next_event(&mut self, eof: bool) -> Option<Event> {
+        if !self.started {
+            if self.buffer.len() < 3 && !eof {
+                return None;
+            }
+            if self.buffer.starts_with("\u{feff}".as_bytes()) {
+                self.buffer.drain(..3);
+            }
+            self.started = true;
+        }
+
+        while let Some(line) = self.line(eof) {
+            if line.is_empty() {
+                if let Some(event) = self.dispatch() {
+                    return Some(event);
+                }
+                continue;
+            }
+
+            let line = String::from_utf8_lossy(&line);
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (&*line, ""),
+            };
+
+            match field {
+                "event" => self.event = Some(value.to_owned()),
+                "data" => match &mut self.data {
+                    Some(data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => self.data = Some(value.to_owned()),
+                },
+                "id" if !value.contains('\0') => self.id = Some(value.to_owned()),
+                "retry" => {
+                    if let Ok(millis) = value.parse() {
+                        self.retry = Some(Duration::from_millis(millis));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// Finish the current event. Events without data are discarded.
+    ///
+    /// The event ID and retry delay persist between events.
+    fn dispatch(&mut self) -> Option<Event> {
+        let event = self.event.take();
+        let data = self.data.take()?;
+
+        Some(Event {
+            event: event
+                .filter(|event| !event.is_empty())
+                .unwrap_or_else(|| "message".to_owned()),
+            data,
+            id: self.id.clone(),
+            retry: self.retry,
+        })
+    }
+}
+
+/// Stream the server-sent events in a body.
+pub(crate) fn event_stream<B>(body: B) -> EventStream
+where
+    B: http_body::Body + Send + 'static,
+    B::Error: Into<BoxError>,
+{
+    let state = (Box::pin(body), EventParser::default(), false);
+    futures::stream::unfold(state, |(mut body, mut parser, mut eof)| async move {
+        loop {
+            if let Some(event) = parser.next_event(eof) {
+                return Some((Ok(event), (body, parser, eof)));
+            }
+
+            if eof {
+                return None;
+            }
+
+            match body.frame().await {
+                Some(Ok(frame)) => {
+                    if let Ok(mut data) = frame.into_data() {
+                        while data.has_remaining() {
+                            let chunk = data.chunk();
+                            let len = chunk.len();
+                            parser.buffer.extend_from_slice(chunk);
+                            data.advance(len);
+                        }
+                    }
+                }
+                Some(Err(error)) => return Some((Err(error.into()), (body, parser, true))),
+                None => eof = true,
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::TryStreamExt as _;
+    use http_body::Frame;
+    use http_body_util::StreamBody;
+
+    use super::*;
+
+    fn chunked(
+        chunks: &[&'static str],
+    ) -> StreamBody<BoxStream<'static, Result<Frame<Bytes>, BoxError>>> {
+        let frames: Vec<Result<_, BoxError>> = chunks
+            .iter()
+            .map(|chunk| Ok(Frame::data(Bytes::from_static(chunk.as_bytes()))))
+            .collect();
+        StreamBody::new(futures::stream::iter(frames).boxed())
+    }
+
+    #[tokio::test]
+    async fn parse_events() {
+        let body = chunked(&[
+            "\u{feff}: keep-alive\n\n",
+            "data: first\r",
+            "\ndata:  second\r\n\r\n",
+            "event: update\nid: 7\nretry: 1500\ndata: {\"id\"",
+            ": 1}\n\n",
+            "event: empty\n\n",
+            "data: unterminated",
+        ]);
+
+        let events: Vec<Event> = event_stream(body).try_collect().await.unwrap();
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    event: "message".into(),
+                    data: "first\n second".into(),
+                    id: None,
+                    retry: None,
+                },
+                Event {
+                    event: "update".into(),
+                    data: "{\"id\": 1}".into(),
+                    id: Some("7".into()),
+                    retry: Some(Duration::from_millis(1500)),
+                },
+            ]
+        );
+        assert_eq!(
+            events[1].json::<serde_json::Value>().unwrap(),
+            serde_json::json!({"id": 1})
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Connection statistics for an [`ApiClient`](crate::ApiClient).
+//!
+//! The connection pool lives inside hyperdriver, so these numbers are gathered
+//! from the requests which pass through the client: how many are in flight at
+//! once, and which HTTP version each response arrived over. Many concurrent
+//! requests with every response over HTTP/2 means requests are being multiplexed
+//! on shared connections.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tower::Layer;
+
+/// A snapshot of the connection statistics for a client.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Requests sent by the client.
+    pub requests: usize,
+
+    /// Requests waiting for a response.
+    pub in_flight: usize,
+
+    /// The most requests which have been in flight at once.
+    pub peak_in_flight: usize,
+
+    /// Responses received over HTTP/1.0 or HTTP/1.1.
+    pub http1: usize,
+
+    /// Responses received over HTTP/2.
+    pub http2: usize,
+
+    /// Requests which failed without a response, e.g. because a connection
+    /// could not be established.
+    pub failed: usize,
+}
+
+impl PoolStats {
+    /// The fraction of responses which arrived over HTTP/2.
+    pub fn http2_ratio(&self) -> f64 {
+        let responses = self.http1 + self.http2;
+        if responses == 0 {
+            return 0.0;
+        }
+        self.http2 as f64 / responses as f64
+    }
+}
+
+/// Shared counters, updated by [`StatsService`].
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    requests: AtomicUsize,
+    in_flight: AtomicUsize,
+    peak_in_flight: AtomicUsize,
+    http1: AtomicUsize,
+    http2: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl Counters {
+    pub(crate) fn snapshot(&self) -> PoolStats {
+        PoolStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            peak_in_flight: self.peak_in_flight.load(Ordering::Relaxed),
+            http1: self.http1.load(Ordering::Relaxed),
+            http2: self.http2.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn start(self: &Arc<Self>) -> InFlight {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::Relaxed) + 1;
+        self.peak_in_flight.fetch_max(in_flight, Ordering::Relaxed);
+        InFlight(self.clone())
+    }
+
+    /// Record the HTTP version of a response, or `None` if the request failed.
+    fn record(&self, version: Option<http::Version>) {
+        let counter = match version {
+            Some(http::Version::HTTP_2) => &self.http2,
+            Some(_) => &self.http1,
+            None => &self.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Marks a request as in flight until it is dropped.
+#[derive(Debug)]
+struct InFlight(Arc<Counters>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A layer which records [`PoolStats`] for the requests passing through it.
+#[derive(Debug, Clone)]
+pub(crate) struct StatsLayer {
+    counters: Arc<Counters>,
+}
+
+impl StatsLayer {
+    pub(crate) fn new(counters: Arc<Counters>) -> Self {
+        Self { counters }
+    }
+}
+
+impl<S> Layer<S> for StatsLayer {
+    type Service = StatsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        StatsService {
+            inner,
+            counters: self.counters.clone(),
+        }
+    }
+}
+
+/// A service which records [`PoolStats`] for the requests passing through it.
+#[derive(Debug, Clone)]
+pub(crate) struct StatsService<S> {
+    inner: S,
+    counters: Arc<Counters>,
+}
+
+impl<S, BIn, BOut> tower::Service<http::Request<BIn>> for StatsService<S>
+where
+    S: tower::Service<http::Request<BIn>, Response = http::Response<BOut>>,
+{
+    type Response = http::Response<BOut>;
+    type Error = S::Error;
+    type Future = StatsFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<BIn>) -> Self::Future {
+        StatsFuture {
+            guard: Some(self.counters.start()),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+/// Future returned by [`StatsService`].
+#[pin_project::pin_project]
+#[derive(Debug)]
+pub(crate) struct StatsFuture<F> {
+    #[pin]
+    inner: F,
+    guard: Option<InFlight>,
+}
+
+impl<F, B, E> Future for StatsFuture<F>
+where
+    F: Future<Output = Result<http::Response<B>, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = futures::ready!(this.inner.poll(cx));
+        if let Some(guard) = this.guard.take() {
+            guard
+                .0
+                .record(result.as_ref().ok().map(http::Response::version));
+        }
+        Poll::Ready(result)
+    }
+}
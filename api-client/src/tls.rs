@@ -0,0 +1,131 @@
+//! TLS settings for an [`ApiClient`](crate::ApiClient), including client certificates
+//! for mutual TLS.
+
+use std::fmt;
+use std::sync::Arc;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use secret::Secret;
+use thiserror::Error;
+
+/// Errors building a TLS configuration.
+#[derive(Debug, Error)]
+pub enum TlsError {
+    /// PEM data could not be read.
+    #[error("Invalid PEM: {0}")]
+    Pem(#[from] std::io::Error),
+
+    /// PEM data did not contain any certificates.
+    #[error("No certificates found in PEM")]
+    MissingCertificate,
+
+    /// PEM data did not contain a private key.
+    #[error("No private key found in PEM")]
+    MissingKey,
+
+    /// The certificates or key were rejected by rustls.
+    #[error("TLS: {0}")]
+    Rustls(#[from] rustls::Error),
+}
+
+/// A client certificate chain and the matching private key.
+struct ClientIdentity {
+    certificates: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+}
+
+/// TLS settings for an API client.
+///
+/// Certificates from the platform trust store are always trusted. Use
+/// [`TlsConfig::with_root_certificates`] to also trust a private CA, and
+/// [`TlsConfig::with_client_identity`] to present a client certificate to
+/// services which require mutual TLS.
+#[derive(Default)]
+pub struct TlsConfig {
+    identity: Option<ClientIdentity>,
+    roots: Vec<CertificateDer<'static>>,
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("client_identity", &self.identity.is_some())
+            .field("roots", &self.roots.len())
+            .finish()
+    }
+}
+
+impl TlsConfig {
+    /// Create TLS settings which only trust the platform trust store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Present a client certificate, from a PEM certificate chain and a PEM private key.
+    pub fn with_client_identity(
+        mut self,
+        certificates: &Secret,
+        key: &Secret,
+    ) -> Result<Self, TlsError> {
+        let certificates = parse_certificates(certificates.revealed())?;
+        let key = rustls_pemfile::private_key(&mut key.revealed().as_bytes())?
+            .ok_or(TlsError::MissingKey)?;
+
+        self.identity = Some(ClientIdentity { certificates, key });
+        Ok(self)
+    }
+
+    /// Also trust the CA certificates in a PEM bundle.
+    pub fn with_root_certificates(mut self, pem: &str) -> Result<Self, TlsError> {
+        self.roots.extend(parse_certificates(pem)?);
+        Ok(self)
+    }
+
+    /// Build the rustls configuration.
+    pub(crate) fn client_config(&self) -> Result<rustls::ClientConfig, TlsError> {
+        let mut roots = rustls::RootCertStore::empty();
+        let native = rustls_native_certs::load_native_certs();
+        for error in &native.errors {
+            tracing::warn!("Loading platform certificates: {error}");
+        }
+        roots.add_parsable_certificates(native.certs);
+        for root in &self.roots {
+            roots.add(root.clone())?;
+        }
+
+        let builder = rustls::ClientConfig::builder().with_root_certificates(Arc::new(roots));
+        let mut config = match &self.identity {
+            Some(identity) => builder
+                .with_client_auth_cert(identity.certificates.clone(), identity.key.clone_key())?,
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+fn parse_certificates(pem: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let certificates = rustls_pemfile::certs(&mut pem.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+    if certificates.is_empty() {
+        return Err(TlsError::MissingCertificate);
+    }
+    Ok(certificates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_pem_sections() {
+        let empty = Secret::from("");
+        assert!(matches!(
+            TlsConfig::new().with_client_identity(&empty, &empty),
+            Err(TlsError::MissingCertificate)
+        ));
+        assert!(matches!(
+            TlsConfig::new().with_root_certificates("not a certificate"),
+            Err(TlsError::MissingCertificate)
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+//! Speaking HTTP over a unix domain socket, for local daemons like `tailscaled`
+//! or `dockerd`.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::net::UnixStream;
+
+use crate::BoxFuture;
+
+/// A hyperdriver transport which connects every request to the same unix socket,
+/// whatever the request's host.
+#[derive(Debug, Clone)]
+pub(crate) struct UnixTransport {
+    path: Arc<PathBuf>,
+}
+
+impl UnixTransport {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path: Arc::new(path),
+        }
+    }
+}
+
+impl tower::Service<http::request::Parts> for UnixTransport {
+    type Response = UnixStream;
+    type Error = io::Error;
+    type Future = BoxFuture<'static, Result<UnixStream, io::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _parts: http::request::Parts) -> Self::Future {
+        let path = self.path.clone();
+        Box::pin(async move {
+            tracing::trace!(path = %path.display(), "Connecting to unix socket");
+            UnixStream::connect(path.as_path()).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::UnixListener;
+
+    use crate::response::{ResponseBodyExt as _, ResponseExt as _};
+    use crate::ApiClient;
+
+    #[tokio::test]
+    async fn request_over_unix_socket() {
+        let dir = std::env::temp_dir().join(format!("api-client-unix-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("api.sock");
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let client = ApiClient::new_unix(&path, ());
+        let response = client.get("status").send().await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("GET /status HTTP/1.1\r\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+//! Completion markers, which record that every entry in a book was uploaded.
+//!
+//! A marker is an empty entry named [`COMPLETION_MARKER`] in the book, written
+//! after the rest of the book. Books without one may be partial uploads, e.g.
+//! from a backup which crashed halfway, so restores should prefer
+//! [`Volume::latest_complete`] over [`Volume::latest`].
+
+use crate::{Book, Error, Volume};
+
+/// Name of the entry which marks a book as complete.
+pub const COMPLETION_MARKER: &str = "_SUCCESS";
+
+impl Book {
+    /// Check if the book has a completion marker.
+    ///
+    /// Like [`Book::contains`], this only consults the listing taken when the
+    /// volume was loaded.
+    pub fn is_complete(&self) -> bool {
+        self.volume
+            .paths()
+            .get(&self.epoch)
+            .is_some_and(|paths| paths.iter().any(|p| p == COMPLETION_MARKER))
+    }
+
+    /// Mark the book as complete, once all of its entries are uploaded.
+    pub async fn mark_complete(&self) -> Result<(), Error> {
+        self.entry(COMPLETION_MARKER)
+            .upload(&mut tokio::io::empty())
+            .await?;
+        tracing::debug!(volume = %self.volume.name(), epoch = %self.epoch, "Marked book complete");
+        Ok(())
+    }
+
+    /// Remove the completion marker, e.g. before replacing entries in the book.
+    pub async fn clear_complete(&self) -> Result<(), Error> {
+        self.entry(COMPLETION_MARKER).delete().await
+    }
+}
+
+impl Volume {
+    /// Get the latest book with a completion marker, skipping books which may
+    /// be partial uploads.
+    pub fn latest_complete(&self) -> Option<Book> {
+        self.paths()
+            .iter()
+            .rev()
+            .find(|(_, paths)| paths.iter().any(|p| p == COMPLETION_MARKER))
+            .map(|(epoch, _)| Book::new(self.clone(), *epoch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::{MemoryStorage, Storage};
+
+    use crate::{Bookshelf, Epoch};
+
+    #[tokio::test]
+    async fn latest_complete() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage, "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+
+        let epochs: Vec<Epoch> = ["20240101", "20240102", "20240103"]
+            .iter()
+            .map(|e| e.parse().unwrap())
+            .collect();
+        for epoch in &epochs {
+            volume
+                .book(*epoch)
+                .entry("data.tar")
+                .upload(&mut "data".as_bytes())
+                .await
+                .unwrap();
+        }
+        let volume = shelf.volume("backups").await.unwrap();
+        assert!(volume.latest_complete().is_none());
+
+        // The latest upload crashed before it was marked complete.
+        volume.book(epochs[0]).mark_complete().await.unwrap();
+        volume.book(epochs[1]).mark_complete().await.unwrap();
+
+        let volume = shelf.volume("backups").await.unwrap();
+        assert_eq!(volume.latest().unwrap().epoch(), epochs[2]);
+        assert!(!volume.book(epochs[2]).is_complete());
+
+        let book = volume.latest_complete().unwrap();
+        assert_eq!(book.epoch(), epochs[1]);
+        assert!(book.is_complete());
+
+        book.clear_complete().await.unwrap();
+        let volume = shelf.volume("backups").await.unwrap();
+        assert_eq!(volume.latest_complete().unwrap().epoch(), epochs[0]);
+    }
+}
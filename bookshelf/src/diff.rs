@@ -0,0 +1,171 @@
+//! Differences between the entries of two books in a volume.
+//!
+//! Entries present in both books are compared using their manifests when both
+//! books have one. Otherwise only the sizes reported by storage are compared, so
+//! entries with the same size can't be told apart without downloading them.
+
+use std::collections::BTreeSet;
+
+use camino::Utf8PathBuf;
+
+use crate::{Book, Epoch, Error, Volume};
+
+/// The differences between two books in a volume.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpochDiff {
+    /// Entries only in the newer book.
+    pub added: Vec<Utf8PathBuf>,
+
+    /// Entries only in the older book.
+    pub removed: Vec<Utf8PathBuf>,
+
+    /// Entries in both books whose contents differ.
+    pub changed: Vec<Utf8PathBuf>,
+
+    /// Entries in both books with matching checksums.
+    pub unchanged: Vec<Utf8PathBuf>,
+
+    /// Entries in both books with the same size, but no checksums to compare.
+    pub unverified: Vec<Utf8PathBuf>,
+}
+
+impl EpochDiff {
+    /// Check if the books are known to contain the same entries and contents.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.unverified.is_empty()
+    }
+}
+
+impl Volume {
+    /// List the entries added, removed and changed from the book at `from` to the book at `to`.
+    ///
+    /// This does not download any entries.
+    pub async fn diff(&self, from: Epoch, to: Epoch) -> Result<EpochDiff, Error> {
+        let from = self.book(from);
+        let to = self.book(to);
+
+        let before: BTreeSet<_> = from.list().into_iter().collect();
+        let after: BTreeSet<_> = to.list().into_iter().collect();
+
+        let mut diff = EpochDiff {
+            added: after.difference(&before).cloned().collect(),
+            removed: before.difference(&after).cloned().collect(),
+            ..Default::default()
+        };
+
+        let manifests = match (from.manifest().await?, to.manifest().await?) {
+            (Some(before), Some(after)) => Some((before, after)),
+            _ => None,
+        };
+
+        for path in before.intersection(&after) {
+            let checksums = manifests.as_ref().and_then(|(before, after)| {
+                Some((before.entries.get(path)?, after.entries.get(path)?))
+            });
+
+            match checksums {
+                Some((before, after)) if before == after => diff.unchanged.push(path.clone()),
+                Some(_) => diff.changed.push(path.clone()),
+                None if size(&from, path).await? != size(&to, path).await? => {
+                    diff.changed.push(path.clone())
+                }
+                None => diff.unverified.push(path.clone()),
+            }
+        }
+
+        tracing::debug!(
+            added = diff.added.len(),
+            removed = diff.removed.len(),
+            changed = diff.changed.len(),
+            "Compared {} to {}",
+            from.epoch(),
+            to.epoch()
+        );
+        Ok(diff)
+    }
+}
+
+async fn size(book: &Book, path: &Utf8PathBuf) -> Result<u64, Error> {
+    let entry = book.entry(path);
+    let metadata = book
+        .volume
+        .storage()
+        .metadata(book.volume.bucket(), entry.path())
+        .await?;
+    Ok(metadata.size)
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::{MemoryStorage, Storage};
+
+    use crate::{Bookshelf, Manifest};
+
+    use super::*;
+
+    async fn write(book: &Book, manifest: &mut Manifest, name: &str, contents: &str) {
+        let checksum = book
+            .entry(name)
+            .upload_with_checksum(&mut contents.as_bytes())
+            .await
+            .unwrap();
+        manifest.insert(name, checksum);
+    }
+
+    #[tokio::test]
+    async fn diff_epochs() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage.clone(), "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        let first: Epoch = "20200101".parse().unwrap();
+        let second: Epoch = "20200102".parse().unwrap();
+
+        let mut manifest = Manifest::new();
+        let book = volume.book(first);
+        write(&book, &mut manifest, "same.txt", "same").await;
+        write(&book, &mut manifest, "edited.txt", "before").await;
+        write(&book, &mut manifest, "grown.txt", "short").await;
+        write(&book, &mut manifest, "removed.txt", "gone").await;
+        book.write_manifest(&manifest).await.unwrap();
+
+        let mut manifest = Manifest::new();
+        let book = volume.book(second);
+        write(&book, &mut manifest, "same.txt", "same").await;
+        write(&book, &mut manifest, "edited.txt", "BEFORE").await;
+        write(&book, &mut manifest, "grown.txt", "much longer").await;
+        write(&book, &mut manifest, "added.txt", "new").await;
+
+        let volume = shelf.volume("backups").await.unwrap();
+
+        // Without both manifests, only sizes can be compared.
+        let diff = volume.diff(first, second).await.unwrap();
+        assert_eq!(diff.added, vec![Utf8PathBuf::from("added.txt")]);
+        assert_eq!(diff.removed, vec![Utf8PathBuf::from("removed.txt")]);
+        assert_eq!(diff.changed, vec![Utf8PathBuf::from("grown.txt")]);
+        assert_eq!(
+            diff.unverified,
+            vec![
+                Utf8PathBuf::from("edited.txt"),
+                Utf8PathBuf::from("same.txt")
+            ]
+        );
+        assert!(!diff.is_empty());
+
+        volume.book(second).write_manifest(&manifest).await.unwrap();
+        let diff = volume.diff(first, second).await.unwrap();
+        assert_eq!(
+            diff.changed,
+            vec![
+                Utf8PathBuf::from("edited.txt"),
+                Utf8PathBuf::from("grown.txt")
+            ]
+        );
+        assert_eq!(diff.unchanged, vec![Utf8PathBuf::from("same.txt")]);
+        assert!(diff.unverified.is_empty());
+
+        assert!(volume.diff(second, second).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+//! Short-lived cache of live existence checks.
+//!
+//! Listings are taken once and then reused, so entries uploaded or deleted by
+//! another process are not reflected in [`Entry::exists`](crate::Entry::exists).
+//! [`Entry::exists_live`](crate::Entry::exists_live) asks the storage backend
+//! instead, and remembers the answer, present or missing, for a short time so
+//! that repeated checks don't each make a request.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// How long an existence check is trusted by default.
+pub(crate) const DEFAULT_EXISTENCE_TTL: Duration = Duration::from_secs(5);
+
+/// Results of recent existence checks, by path within the bucket.
+#[derive(Debug, Clone)]
+pub(crate) struct ExistenceCache {
+    ttl: Duration,
+    paths: Arc<Mutex<HashMap<Utf8PathBuf, (bool, Instant)>>>,
+}
+
+impl Default for ExistenceCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_EXISTENCE_TTL)
+    }
+}
+
+impl ExistenceCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            paths: Default::default(),
+        }
+    }
+
+    /// Whether the path existed when last checked, if that was recent enough.
+    pub(crate) fn get(&self, path: &Utf8Path) -> Option<bool> {
+        let mut paths = self.paths.lock().unwrap();
+        match paths.get(path) {
+            Some((exists, checked)) if checked.elapsed() < self.ttl => Some(*exists),
+            Some(_) => {
+                paths.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Record whether the path exists.
+    pub(crate) fn insert(&self, path: &Utf8Path, exists: bool) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        self.paths
+            .lock()
+            .unwrap()
+            .insert(path.to_owned(), (exists, Instant::now()));
+    }
+
+    /// Forget all checks for paths under a prefix.
+    pub(crate) fn remove_prefix(&self, prefix: &Utf8Path) {
+        self.paths
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(prefix));
+    }
+}
@@ -2,19 +2,41 @@
 
 use std::{
     collections::{BTreeMap, BTreeSet},
-    sync::{Arc, Mutex},
+    sync::Arc,
+    time::Duration,
 };
 
 use camino::{Utf8Path, Utf8PathBuf};
-use storage::Storage;
+use storage::{ByteRange, Storage};
 use thiserror::Error;
 
+mod complete;
+pub mod diff;
 mod epoch;
+mod existence;
 pub mod expiration;
-
+pub mod library;
+mod listing;
+pub mod manifest;
+pub mod prune;
+mod restore;
+pub mod tiered;
+mod upload;
+
+pub use complete::COMPLETION_MARKER;
+pub use diff::EpochDiff;
 pub use epoch::{Epoch, EpochSelector, InvalidEpoch};
+pub use library::{Library, LibraryVolume};
+pub use manifest::{Checksum, Manifest, Verification};
+pub use prune::{PruneReport, Pruner};
+pub use tiered::{TieredBookshelf, TieredVolume, WritePolicy};
+pub use upload::EntryUpload;
+
+use existence::ExistenceCache;
+use listing::{Invalidator, VolumeCache};
 use tokio::io;
 use tracing::instrument;
+use upload::DEFAULT_UPLOAD_CONCURRENCY;
 
 /// Date type used to represent epochs.
 pub type Date = chrono::NaiveDate;
@@ -32,6 +54,50 @@ pub enum Error {
     /// An error occurred while interacting with the storage backend.
     #[error("Storage error: {0}")]
     Storage(#[from] storage::StorageError),
+
+    /// The book has no manifest to verify against.
+    #[error("Manifest {0} not found")]
+    MissingManifest(Utf8PathBuf),
+
+    /// The manifest could not be serialized or deserialized.
+    #[error("Manifest error: {0}")]
+    Manifest(#[from] serde_json::Error),
+
+    /// The book is too large to restore into scratch space.
+    #[error("Book is {size} bytes, exceeding the restore limit of {limit} bytes")]
+    RestoreLimit {
+        /// Total size of the entries in the manifest.
+        size: u64,
+
+        /// Maximum number of bytes to restore.
+        limit: u64,
+    },
+
+    /// The write policy of a tiered bookshelf names a tier which does not exist.
+    #[error("Tier {0} not found")]
+    UnknownTier(String),
+
+    /// A local directory could not be read for upload.
+    #[error("Failed to read {path}: {source}")]
+    ReadDir {
+        /// The path which could not be read.
+        path: Utf8PathBuf,
+
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// A local file could not be opened for upload.
+    #[error("Failed to open {path}: {source}")]
+    ReadFile {
+        /// The file which could not be opened.
+        path: Utf8PathBuf,
+
+        /// The underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
 }
 
 /// A set of volume objects that share a common prefix, storage
@@ -41,7 +107,9 @@ pub struct Bookshelf {
     storage: Storage,
     bucket: String,
     prefix: Option<Utf8PathBuf>,
-    volumes: Arc<Mutex<Option<Vec<Volume>>>>,
+    volumes: VolumeCache,
+    existence: ExistenceCache,
+    upload_concurrency: usize,
 }
 
 impl Bookshelf {
@@ -51,10 +119,39 @@ impl Bookshelf {
             storage,
             bucket,
             prefix,
-            volumes: Arc::new(Mutex::new(None)),
+            volumes: VolumeCache::default(),
+            existence: ExistenceCache::default(),
+            upload_concurrency: DEFAULT_UPLOAD_CONCURRENCY,
         }
     }
 
+    /// Set how long the list of volumes is cached before the bookshelf is listed again.
+    ///
+    /// By default the list is kept until it is invalidated, either by a write
+    /// through this bookshelf or by [`Bookshelf::refresh`].
+    pub fn with_volume_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.volumes = VolumeCache::new(Some(ttl));
+        self
+    }
+
+    /// Set how long live existence checks are cached, see [`Entry::exists_live`].
+    ///
+    /// The default is 5 seconds. A zero duration disables the cache, so every
+    /// check asks the storage backend.
+    pub fn with_existence_ttl(mut self, ttl: Duration) -> Self {
+        self.existence = ExistenceCache::new(ttl);
+        self.clear_volume_cache();
+        self
+    }
+
+    /// Limit the number of uploads in flight at once when uploading many entries,
+    /// see [`Book::upload_entries`] (8 by default).
+    pub fn with_upload_concurrency(mut self, concurrency: usize) -> Self {
+        self.upload_concurrency = concurrency.max(1);
+        self.clear_volume_cache();
+        self
+    }
+
     /// Set the prefix for the bookshelf.
     pub fn with_prefix(mut self, prefix: Utf8PathBuf) -> Self {
         self.prefix = Some(prefix);
@@ -87,18 +184,28 @@ impl Bookshelf {
     }
 
     fn clear_volume_cache(&self) {
-        let mut volumes = self.volumes.lock().unwrap();
-        *volumes = None;
+        self.volumes.clear();
+    }
+
+    /// List all volumes in the bookshelf, ignoring any cached list.
+    ///
+    /// Use this to see entries uploaded or deleted by another process, since
+    /// only writes through this bookshelf invalidate the cache.
+    pub async fn refresh(&self) -> Result<Vec<Volume>, Error> {
+        self.clear_volume_cache();
+        self.list().await
     }
 
     /// List all volumes in the bookshelf.
+    ///
+    /// The list is cached, and shared with clones of this bookshelf, see
+    /// [`Bookshelf::refresh`] and [`Bookshelf::with_volume_cache_ttl`].
     pub async fn list(&self) -> Result<Vec<Volume>, Error> {
-        {
-            if let Some(volumes) = self.volumes.lock().unwrap().as_ref() {
-                return Ok(volumes.clone());
-            }
+        if let Some(volumes) = self.volumes.get() {
+            return Ok(volumes);
         }
 
+        let generation = self.volumes.generation();
         let mut list = self
             .storage
             .list(&self.bucket, self.prefix.as_deref())
@@ -108,11 +215,7 @@ impl Bookshelf {
             .collect::<Vec<_>>();
         list.sort();
         let shelves = self.process_list(list.as_slice())?;
-
-        {
-            let mut volumes = self.volumes.lock().unwrap();
-            *volumes = Some(shelves.clone());
-        }
+        self.volumes.insert(shelves.clone(), generation);
 
         Ok(shelves)
     }
@@ -145,12 +248,8 @@ impl Bookshelf {
 
             let name = path.components().take(i).collect::<Utf8PathBuf>();
 
-            // The remainder is the suffix.
-            let suffix: Utf8PathBuf = path
-                .components()
-                .skip_while(|c| !matches!(c, camino::Utf8Component::Normal(_)))
-                .skip(1)
-                .collect();
+            // The remainder, after the epoch, is the suffix.
+            let suffix: Utf8PathBuf = path.components().skip(i + 1).collect();
 
             Some((name, epoch, suffix))
         });
@@ -166,15 +265,7 @@ impl Bookshelf {
 
         Ok(shelves
             .into_iter()
-            .map(|(name, paths)| {
-                Volume::new(
-                    self.storage.clone(),
-                    self.bucket.clone(),
-                    self.prefix.clone(),
-                    name,
-                    paths,
-                )
-            })
+            .map(|(name, paths)| Volume::new(self.volume_config(), name, paths))
             .collect())
     }
 
@@ -190,15 +281,26 @@ impl Bookshelf {
             .unwrap_or_else(|| {
                 self.clear_volume_cache();
                 tracing::trace!("Creating new bookshelf: {}", name);
-                Volume::new(
-                    self.storage.clone(),
-                    self.bucket.clone(),
-                    self.prefix.clone(),
-                    name.into(),
-                    BTreeMap::new(),
-                )
+                self.empty_volume(name.into())
             }))
     }
+
+    /// Create a volume with no books in this bookshelf.
+    fn empty_volume(&self, name: Utf8PathBuf) -> Volume {
+        Volume::new(self.volume_config(), name, BTreeMap::new())
+    }
+
+    /// Configuration shared by the volumes in this bookshelf.
+    fn volume_config(&self) -> VolumeConfig {
+        VolumeConfig {
+            storage: self.storage.clone(),
+            bucket: self.bucket.clone(),
+            prefix: self.prefix.clone(),
+            existence: self.existence.clone(),
+            listing: self.volumes.invalidator(),
+            upload_concurrency: self.upload_concurrency,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -206,6 +308,9 @@ struct VolumeConfig {
     storage: Storage,
     bucket: String,
     prefix: Option<Utf8PathBuf>,
+    existence: ExistenceCache,
+    listing: Invalidator,
+    upload_concurrency: usize,
 }
 
 impl PartialEq for VolumeConfig {
@@ -248,19 +353,7 @@ pub struct Volume {
 }
 
 impl Volume {
-    fn new(
-        storage: Storage,
-        bucket: String,
-        prefix: Option<Utf8PathBuf>,
-        name: Utf8PathBuf,
-        paths: Paths,
-    ) -> Self {
-        let config = VolumeConfig {
-            storage,
-            bucket,
-            prefix,
-        };
-
+    fn new(config: VolumeConfig, name: Utf8PathBuf, paths: Paths) -> Self {
         let inner = InnerVolume::new(config, paths, name);
 
         Self {
@@ -299,6 +392,12 @@ impl Volume {
         self.inner.config.prefix.as_deref()
     }
 
+    /// Note that entries in this volume were written, so that the bookshelf
+    /// lists its volumes again.
+    fn invalidate_listing(&self) {
+        self.inner.config.listing.invalidate();
+    }
+
     /// Get the paths indexed by epoch.
     fn paths(&self) -> &BTreeMap<Epoch, Vec<Utf8PathBuf>> {
         &self.inner.paths
@@ -382,6 +481,9 @@ impl Book {
     }
 
     /// Check if the book contains the given path.
+    ///
+    /// This only consults the listing taken when the volume was loaded, see
+    /// [`Book::contains_live`] to check the storage backend.
     pub async fn contains<P: AsRef<Utf8Path>>(&self, path: P) -> bool {
         self.volume
             .paths()
@@ -389,6 +491,11 @@ impl Book {
             .is_some_and(|paths| paths.iter().any(|p| p == path.as_ref()))
     }
 
+    /// Check if the book contains the given path in the storage backend now.
+    pub async fn contains_live<P: AsRef<Utf8Path>>(&self, path: P) -> Result<bool, Error> {
+        self.entry(path).exists_live().await
+    }
+
     /// Get an entry in the book, with download and upload methods.
     pub fn entry<P: AsRef<Utf8Path>>(&self, path: P) -> Entry {
         Entry::new(self.volume.clone(), self.epoch, path.as_ref())
@@ -396,25 +503,17 @@ impl Book {
 
     /// Delete all artifacts in the book.
     pub async fn delete(&self) -> Result<(), Error> {
-        let paths = self
+        let prefix = self.volume.path().join(self.epoch.to_path());
+        let deleted = self
             .volume
-            .paths()
-            .get(&self.epoch)
-            .cloned()
-            .unwrap_or_default();
-
-        let mut futures = Vec::with_capacity(paths.len());
-        for path in paths {
-            let path = self.volume.path().join(path);
-            futures.push(async move {
-                self.volume
-                    .storage()
-                    .delete(&self.volume.inner.config.bucket, &path)
-                    .await
-            });
-        }
+            .storage()
+            .delete_prefix(self.volume.bucket(), &prefix)
+            .await?;
+        self.volume.inner.config.existence.remove_prefix(&prefix);
+        self.volume.invalidate_listing();
+        tracing::debug!(%prefix, "Deleted {deleted} artifacts");
 
-        let _ = futures::future::try_join_all(futures).await?;
+        self.delete_manifest().await?;
         Ok(())
     }
 }
@@ -448,6 +547,9 @@ impl Entry {
     }
 
     /// Check if the artifact exists in cloud storage.
+    ///
+    /// This only consults the listing taken when the volume was loaded, see
+    /// [`Entry::exists_live`] to check the storage backend.
     pub fn exists(&self) -> bool {
         self.volume
             .paths()
@@ -455,6 +557,33 @@ impl Entry {
             .is_some_and(|paths| paths.iter().any(|p| self.path.ends_with(p)))
     }
 
+    /// Check if the artifact exists in cloud storage now, using the metadata
+    /// from the storage backend rather than the listing.
+    ///
+    /// Results, including missing artifacts, are cached for a short time, see
+    /// [`Bookshelf::with_existence_ttl`].
+    pub async fn exists_live(&self) -> Result<bool, Error> {
+        let cache = &self.volume.inner.config.existence;
+        if let Some(exists) = cache.get(&self.path) {
+            tracing::trace!(path = %self.path, exists, "Existence cache hit");
+            return Ok(exists);
+        }
+
+        let exists = match self
+            .volume
+            .storage()
+            .metadata(self.volume.bucket(), &self.path)
+            .await
+        {
+            Ok(_) => true,
+            Err(err) if err.is_not_found() => false,
+            Err(err) => return Err(err.into()),
+        };
+
+        cache.insert(&self.path, exists);
+        Ok(exists)
+    }
+
     /// Download the artifact to a writer.
     pub async fn download<'s, W>(&'s self, destination: &mut W) -> Result<(), Error>
     where
@@ -469,6 +598,26 @@ impl Entry {
             .map_err(Error::from)
     }
 
+    /// Download part of the artifact to a writer, e.g. the tail of a log archive.
+    ///
+    /// The range is clamped to the size of the artifact.
+    pub async fn download_range<'s, W>(
+        &'s self,
+        range: impl Into<ByteRange>,
+        destination: &mut W,
+    ) -> Result<(), Error>
+    where
+        W: io::AsyncWrite + Unpin + Send + Sync + 's,
+    {
+        let remote = self.path();
+
+        self.volume
+            .storage()
+            .download_range(&self.volume.inner.config.bucket, remote, range, destination)
+            .await
+            .map_err(Error::from)
+    }
+
     /// Upload the artifact from a reader.
     pub async fn upload<'s, R>(&'s self, source: &mut R) -> Result<(), Error>
     where
@@ -480,6 +629,8 @@ impl Entry {
             .storage()
             .upload(&self.volume.inner.config.bucket, remote, source)
             .await?;
+        self.volume.inner.config.existence.insert(remote, true);
+        self.volume.invalidate_listing();
         Ok(())
     }
 
@@ -491,6 +642,8 @@ impl Entry {
             .storage()
             .upload_file(&self.volume.inner.config.bucket, remote, source)
             .await?;
+        self.volume.inner.config.existence.insert(remote, true);
+        self.volume.invalidate_listing();
         Ok(())
     }
 
@@ -502,6 +655,8 @@ impl Entry {
             .storage()
             .delete(&self.volume.inner.config.bucket, remote)
             .await?;
+        self.volume.inner.config.existence.insert(remote, false);
+        self.volume.invalidate_listing();
         Ok(())
     }
 }
@@ -618,6 +773,134 @@ mod test {
         assert!(storage.list(bucket, None).await.unwrap().is_empty());
     }
 
+    #[tokio::test]
+    async fn entry_download_range() {
+        let bucket = "bucket";
+
+        let memory = MemoryStorage::new();
+        memory.create_bucket(bucket.to_string()).await;
+        let storage = Storage::new(memory);
+
+        let remote = "shelf/20200101/log";
+        let mut reader = std::io::Cursor::new("first line\nlast line\n");
+        storage
+            .upload(bucket, Utf8Path::new(remote), &mut reader)
+            .await
+            .unwrap();
+
+        let case = Bookshelf::new(storage, bucket.to_string(), None);
+        let entry = case
+            .volume("shelf")
+            .await
+            .unwrap()
+            .book(epoch!(2020 / 1 / 1))
+            .entry("log");
+
+        let mut tail = Vec::new();
+        entry
+            .download_range(ByteRange::Last(10), &mut tail)
+            .await
+            .unwrap();
+        assert_eq!(tail, b"last line\n");
+
+        let mut head = Vec::new();
+        entry.download_range(0..5, &mut head).await.unwrap();
+        assert_eq!(head, b"first");
+    }
+
+    #[tokio::test]
+    async fn entry_exists_live() {
+        let bucket = "bucket";
+
+        let memory = MemoryStorage::new();
+        memory.create_bucket(bucket.to_string()).await;
+        let storage = Storage::new(memory);
+
+        let case = Bookshelf::new(storage.clone(), bucket.to_string(), None);
+        let book = case
+            .volume("shelf")
+            .await
+            .unwrap()
+            .book(epoch!(2020 / 1 / 1));
+        let entry = book.entry("foo");
+        let remote = Utf8Path::new("shelf/20200101/foo");
+
+        // Uploaded after the listing was taken.
+        let mut reader = std::io::Cursor::new("foo");
+        storage.upload(bucket, remote, &mut reader).await.unwrap();
+        assert!(!entry.exists());
+        assert!(entry.exists_live().await.unwrap());
+        assert!(book.contains_live("foo").await.unwrap());
+
+        // Within the TTL, the cached result is used.
+        storage.delete(bucket, remote).await.unwrap();
+        assert!(entry.exists_live().await.unwrap());
+
+        // Deleting through the entry updates the cache.
+        entry.delete().await.unwrap();
+        assert!(!entry.exists_live().await.unwrap());
+
+        let uncached = case
+            .with_existence_ttl(Duration::ZERO)
+            .volume("shelf")
+            .await
+            .unwrap()
+            .book(epoch!(2020 / 1 / 1))
+            .entry("foo");
+        let mut reader = std::io::Cursor::new("foo");
+        storage.upload(bucket, remote, &mut reader).await.unwrap();
+        assert!(uncached.exists_live().await.unwrap());
+        storage.delete(bucket, remote).await.unwrap();
+        assert!(!uncached.exists_live().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn volume_cache_invalidation() {
+        let bucket = "bucket";
+
+        let memory = MemoryStorage::new();
+        memory.create_bucket(bucket.to_string()).await;
+        let storage = Storage::new(memory);
+        let names = |volumes: Vec<Volume>| {
+            volumes
+                .iter()
+                .map(|volume| volume.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let case = Bookshelf::new(storage.clone(), bucket.to_string(), None);
+        assert!(case.list().await.unwrap().is_empty());
+
+        // Writes through the bookshelf invalidate the cached list.
+        let book = case.volume("a").await.unwrap().book(epoch!(2020 / 1 / 1));
+        book.entry("foo")
+            .upload(&mut "foo".as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(names(case.list().await.unwrap()), vec!["a"]);
+
+        // Writes from elsewhere are only seen after a refresh.
+        let mut reader = std::io::Cursor::new("bar");
+        storage
+            .upload(bucket, Utf8Path::new("b/20200101/bar"), &mut reader)
+            .await
+            .unwrap();
+        assert_eq!(names(case.list().await.unwrap()), vec!["a"]);
+        assert_eq!(names(case.refresh().await.unwrap()), vec!["a", "b"]);
+
+        book.delete().await.unwrap();
+        assert_eq!(names(case.list().await.unwrap()), vec!["b"]);
+
+        // Or once the cache expires.
+        let uncached = case.with_volume_cache_ttl(Duration::ZERO);
+        assert_eq!(names(uncached.list().await.unwrap()), vec!["b"]);
+        storage
+            .delete(bucket, Utf8Path::new("b/20200101/bar"))
+            .await
+            .unwrap();
+        assert!(uncached.list().await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn bookshelf_no_prefix() {
         let bucket = "bucket";
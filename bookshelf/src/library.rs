@@ -0,0 +1,163 @@
+//! A read-only view across many bookshelves.
+//!
+//! Unlike a [`TieredBookshelf`](crate::TieredBookshelf), which merges its tiers
+//! into a single writable bookshelf, a [`Library`] keeps the volume from each
+//! bookshelf separate, and records which bookshelf it was found in. This lets
+//! tooling find every copy of a backup, e.g. on local disk and on B2, in one call.
+
+use camino::Utf8Path;
+
+use crate::{Bookshelf, Error, Volume};
+
+/// A set of named bookshelves, which may use different storage backends.
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    shelves: Vec<(String, Bookshelf)>,
+}
+
+impl Library {
+    /// Create an empty library.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a bookshelf to the library.
+    pub fn with_shelf<S: Into<String>>(mut self, name: S, shelf: Bookshelf) -> Self {
+        self.shelves.push((name.into(), shelf));
+        self
+    }
+
+    /// Get a bookshelf by name.
+    pub fn shelf(&self, name: &str) -> Option<&Bookshelf> {
+        self.shelves
+            .iter()
+            .find(|(shelf, _)| shelf == name)
+            .map(|(_, shelf)| shelf)
+    }
+
+    /// Iterate over the bookshelves, in the order they were added.
+    pub fn shelves(&self) -> impl Iterator<Item = (&str, &Bookshelf)> {
+        self.shelves
+            .iter()
+            .map(|(name, shelf)| (name.as_str(), shelf))
+    }
+
+    /// List the volumes in every bookshelf, sorted by name.
+    ///
+    /// A volume found in several bookshelves is listed once for each of them,
+    /// in the order the bookshelves were added. The bookshelves are listed
+    /// concurrently.
+    pub async fn volumes(&self) -> Result<Vec<LibraryVolume>, Error> {
+        let listed =
+            futures::future::try_join_all(self.shelves.iter().map(|(_, shelf)| shelf.list()))
+                .await?;
+
+        let mut volumes: Vec<LibraryVolume> = self
+            .shelves
+            .iter()
+            .zip(listed)
+            .flat_map(|((name, _), volumes)| {
+                volumes.into_iter().map(|volume| LibraryVolume {
+                    shelf: name.clone(),
+                    volume,
+                })
+            })
+            .collect();
+
+        // The sort is stable, so copies of a volume stay in bookshelf order.
+        volumes.sort_by(|a, b| a.volume.name().cmp(b.volume.name()));
+        Ok(volumes)
+    }
+
+    /// Find a volume by name in every bookshelf which contains it.
+    pub async fn volume(&self, name: &str) -> Result<Vec<LibraryVolume>, Error> {
+        let name = Utf8Path::new(name);
+        Ok(self
+            .volumes()
+            .await?
+            .into_iter()
+            .filter(|volume| volume.volume.name() == name)
+            .collect())
+    }
+}
+
+/// A volume in a [`Library`], and the bookshelf it was found in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryVolume {
+    shelf: String,
+    volume: Volume,
+}
+
+impl LibraryVolume {
+    /// The name of the bookshelf which contains the volume.
+    pub fn shelf(&self) -> &str {
+        &self.shelf
+    }
+
+    /// The volume.
+    pub fn volume(&self) -> &Volume {
+        &self.volume
+    }
+
+    /// Take the volume, discarding where it was found.
+    pub fn into_volume(self) -> Volume {
+        self.volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::{MemoryStorage, Storage};
+
+    use crate::Epoch;
+
+    use super::*;
+
+    async fn shelf(paths: &[&str]) -> Bookshelf {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        for path in paths {
+            let mut reader = std::io::Cursor::new("contents");
+            storage
+                .upload("bucket", Utf8Path::new(path), &mut reader)
+                .await
+                .unwrap();
+        }
+        Bookshelf::new(storage, "bucket".into(), None)
+    }
+
+    #[tokio::test]
+    async fn find_volumes_across_shelves() {
+        let hot = shelf(&["db/20200103/dump", "logs/20200103/app.log"]).await;
+        let cold = shelf(&["db/20200101/dump"]).await;
+        let library = Library::new()
+            .with_shelf("hot", hot)
+            .with_shelf("cold", cold);
+
+        let volumes: Vec<(String, String)> = library
+            .volumes()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.volume().name().to_string(), v.shelf().to_owned()))
+            .collect();
+        assert_eq!(
+            volumes,
+            vec![
+                ("db".to_owned(), "hot".to_owned()),
+                ("db".to_owned(), "cold".to_owned()),
+                ("logs".to_owned(), "hot".to_owned()),
+            ]
+        );
+
+        let copies = library.volume("db").await.unwrap();
+        assert_eq!(copies.len(), 2);
+        assert_eq!(copies[1].shelf(), "cold");
+        assert_eq!(
+            copies[1].volume().latest().unwrap().epoch(),
+            "20200101".parse::<Epoch>().unwrap()
+        );
+
+        assert!(library.volume("missing").await.unwrap().is_empty());
+        assert!(library.shelf("cold").is_some());
+    }
+}
@@ -0,0 +1,95 @@
+//! Cache of the volumes listed in a bookshelf.
+//!
+//! Listing a bookshelf lists every path under its prefix, so the volumes found are
+//! cached and shared by clones of the bookshelf. Uploads and deletes made through
+//! the bookshelf's entries and books invalidate the cache, while changes made
+//! elsewhere are only seen after [`Bookshelf::refresh`](crate::Bookshelf::refresh),
+//! or once the cache expires if it has a TTL.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::Volume;
+
+/// Volumes from a single listing of the bookshelf.
+#[derive(Debug)]
+struct Listing {
+    volumes: Vec<Volume>,
+    generation: u64,
+    listed: Instant,
+}
+
+/// The most recent listing of a bookshelf.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct VolumeCache {
+    ttl: Option<Duration>,
+    listing: Arc<Mutex<Option<Listing>>>,
+
+    /// Incremented whenever the cache is invalidated, so that listings started
+    /// before then are not used.
+    generation: Arc<AtomicU64>,
+}
+
+impl VolumeCache {
+    pub(crate) fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            ..Default::default()
+        }
+    }
+
+    /// The volumes from the last listing, if it is still valid.
+    pub(crate) fn get(&self) -> Option<Vec<Volume>> {
+        let listing = self.listing.lock().unwrap();
+        let listing = listing.as_ref()?;
+
+        if listing.generation != self.generation() {
+            return None;
+        }
+        if self.ttl.is_some_and(|ttl| listing.listed.elapsed() >= ttl) {
+            return None;
+        }
+        Some(listing.volumes.clone())
+    }
+
+    /// The current generation, to pass to [`VolumeCache::insert`] with a listing
+    /// started now.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Store the volumes from a listing started at `generation`.
+    pub(crate) fn insert(&self, volumes: Vec<Volume>, generation: u64) {
+        *self.listing.lock().unwrap() = Some(Listing {
+            volumes,
+            generation,
+            listed: Instant::now(),
+        });
+    }
+
+    /// Forget the last listing.
+    pub(crate) fn clear(&self) {
+        self.invalidator().invalidate();
+    }
+
+    /// A handle which invalidates this cache, held by volumes so that writes
+    /// through them are seen by the next listing.
+    pub(crate) fn invalidator(&self) -> Invalidator {
+        Invalidator(Arc::clone(&self.generation))
+    }
+}
+
+/// Invalidates a [`VolumeCache`], without keeping its volumes alive.
+#[derive(Debug, Clone)]
+pub(crate) struct Invalidator(Arc<AtomicU64>);
+
+impl Invalidator {
+    pub(crate) fn invalidate(&self) {
+        self.0.fetch_add(1, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,392 @@
+//! Content manifests for books, used to detect missing or corrupted entries.
+//!
+//! A manifest records the size and SHA-256 checksum of each entry in a book. It
+//! is stored next to the book's epoch directory (as `<epoch>.manifest.json`), so
+//! it is not itself listed as an entry in the book.
+
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use futures::{StreamExt as _, TryStreamExt as _};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{self, AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::{Book, Entry, Error};
+
+/// The size and checksum of a single entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checksum {
+    /// Size of the entry in bytes.
+    pub size: u64,
+
+    /// SHA-256 digest of the entry, hex encoded.
+    pub sha256: String,
+}
+
+/// A manifest of the entries in a book, keyed by their path within the book.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Entries in the book.
+    pub entries: BTreeMap<Utf8PathBuf, Checksum>,
+}
+
+impl Manifest {
+    /// Create an empty manifest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the checksum for an entry.
+    pub fn insert<P: Into<Utf8PathBuf>>(&mut self, path: P, checksum: Checksum) {
+        self.entries.insert(path.into(), checksum);
+    }
+
+    /// Number of entries in the manifest.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the manifest has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// An entry whose contents do not match the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corruption {
+    /// Path of the entry within the book.
+    pub path: Utf8PathBuf,
+
+    /// The checksum recorded in the manifest.
+    pub expected: Checksum,
+
+    /// The checksum of the entry in storage. When only metadata was checked,
+    /// the `sha256` field is empty.
+    pub actual: Checksum,
+}
+
+/// The result of verifying a book against its manifest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verification {
+    /// Entries which matched the manifest.
+    pub verified: Vec<Utf8PathBuf>,
+
+    /// Entries in the manifest which are missing from storage.
+    pub missing: Vec<Utf8PathBuf>,
+
+    /// Entries whose size or checksum did not match the manifest.
+    pub corrupted: Vec<Corruption>,
+}
+
+impl Verification {
+    /// Check if every entry in the manifest was present and intact.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.corrupted.is_empty()
+    }
+}
+
+/// Hashes bytes as they are read from an inner reader.
+#[derive(Debug)]
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    size: u64,
+}
+
+impl<R> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            size: 0,
+        }
+    }
+
+    fn checksum(self) -> Checksum {
+        Checksum {
+            size: self.size,
+            sha256: hex::encode(self.hasher.finalize()),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let start = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &poll {
+            let data = &buf.filled()[start..];
+            self.hasher.update(data);
+            self.size += data.len() as u64;
+        }
+        poll
+    }
+}
+
+/// Hashes and discards all bytes written to it.
+#[derive(Debug)]
+pub(crate) struct HashingSink(HashingReader<()>);
+
+impl HashingSink {
+    pub(crate) fn new() -> Self {
+        Self(HashingReader::new(()))
+    }
+
+    pub(crate) fn checksum(self) -> Checksum {
+        self.0.checksum()
+    }
+}
+
+impl AsyncWrite for HashingSink {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.0.hasher.update(buf);
+        self.0.size += buf.len() as u64;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Entry {
+    /// Upload the artifact from a reader, returning its size and checksum so that
+    /// it can be recorded in a [`Manifest`].
+    pub async fn upload_with_checksum<'s, R>(&'s self, source: &mut R) -> Result<Checksum, Error>
+    where
+        R: io::AsyncBufRead + Unpin + Send + Sync + 's,
+    {
+        let mut reader = io::BufReader::new(HashingReader::new(source));
+        self.upload(&mut reader).await?;
+        Ok(reader.into_inner().checksum())
+    }
+
+    /// Download the artifact, computing its size and checksum without keeping the contents.
+    pub async fn checksum(&self) -> Result<Checksum, Error> {
+        let mut sink = HashingSink::new();
+        self.download(&mut sink).await?;
+        Ok(sink.checksum())
+    }
+}
+
+impl Book {
+    /// Path (within the bucket) of the manifest for this book.
+    pub fn manifest_path(&self) -> Utf8PathBuf {
+        let mut path = self.volume.path().to_owned();
+        path.push(format!("{}.manifest.json", self.epoch.to_path()));
+        path
+    }
+
+    /// Read the manifest for this book, if one was written.
+    pub async fn manifest(&self) -> Result<Option<Manifest>, Error> {
+        let path = self.manifest_path();
+        let storage = self.volume.storage();
+        match storage.metadata(self.volume.bucket(), &path).await {
+            Ok(_) => {}
+            Err(err) if err.is_not_found() => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut buf = Vec::new();
+        storage
+            .download(self.volume.bucket(), &path, &mut buf)
+            .await?;
+        Ok(Some(serde_json::from_slice(&buf)?))
+    }
+
+    /// Write the manifest for this book, replacing any existing manifest.
+    pub async fn write_manifest(&self, manifest: &Manifest) -> Result<(), Error> {
+        let data = serde_json::to_vec_pretty(manifest)?;
+        self.volume
+            .storage()
+            .upload(
+                self.volume.bucket(),
+                &self.manifest_path(),
+                &mut data.as_slice(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Verify every entry in the manifest by downloading it and comparing checksums.
+    ///
+    /// Entries are checked concurrently, up to the bookshelf's upload concurrency.
+    pub async fn verify(&self) -> Result<Verification, Error> {
+        self.verify_with(true).await
+    }
+
+    /// Verify every entry in the manifest using only storage metadata (existence and size),
+    /// which avoids downloading the contents.
+    pub async fn verify_metadata(&self) -> Result<Verification, Error> {
+        self.verify_with(false).await
+    }
+
+    async fn verify_with(&self, download: bool) -> Result<Verification, Error> {
+        let manifest = self
+            .manifest()
+            .await?
+            .ok_or_else(|| Error::MissingManifest(self.manifest_path()))?;
+
+        let concurrency = self.volume.inner.config.upload_concurrency;
+        let checks = futures::stream::iter(manifest.entries)
+            .map(|(path, expected)| async move {
+                let entry = self.entry(&path);
+                let metadata = match self
+                    .volume
+                    .storage()
+                    .metadata(self.volume.bucket(), entry.path())
+                    .await
+                {
+                    Ok(metadata) => metadata,
+                    Err(err) if err.is_not_found() => return Ok((path, expected, None)),
+                    Err(err) => return Err(Error::from(err)),
+                };
+
+                let actual = if download && metadata.size == expected.size {
+                    entry.checksum().await?
+                } else {
+                    Checksum {
+                        size: metadata.size,
+                        sha256: String::new(),
+                    }
+                };
+
+                Ok((path, expected, Some(actual)))
+            })
+            .buffer_unordered(concurrency);
+
+        let mut results: Vec<_> = checks.try_collect().await?;
+        results.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        let mut report = Verification::default();
+        for (path, expected, actual) in results {
+            match actual {
+                None => {
+                    tracing::warn!(%path, "Entry missing from storage");
+                    report.missing.push(path);
+                }
+                Some(actual)
+                    if actual.size != expected.size
+                        || (download && actual.sha256 != expected.sha256) =>
+                {
+                    tracing::warn!(%path, "Entry does not match manifest");
+                    report.corrupted.push(Corruption {
+                        path,
+                        expected,
+                        actual,
+                    });
+                }
+                Some(_) => report.verified.push(path),
+            }
+        }
+
+        Ok(report)
+    }
+
+    pub(crate) async fn delete_manifest(&self) -> Result<(), Error> {
+        let path = self.manifest_path();
+        let storage = self.volume.storage();
+        match storage.metadata(self.volume.bucket(), &path).await {
+            Ok(_) => storage.delete(self.volume.bucket(), &path).await?,
+            Err(err) if err.is_not_found() => {}
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+}
+
+/// Compute the checksum of a local file.
+pub async fn checksum_file(path: &Utf8Path) -> Result<Checksum, io::Error> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut reader = HashingReader::new(file);
+    io::copy(&mut reader, &mut io::sink()).await?;
+    Ok(reader.checksum())
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::{MemoryStorage, Storage};
+
+    use crate::{Bookshelf, Epoch};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn manifest_roundtrip_and_verify() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage.clone(), "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        let epoch: Epoch = "20200101".parse().unwrap();
+        let book = volume.book(epoch);
+
+        assert_eq!(book.manifest().await.unwrap(), None);
+        assert!(matches!(
+            book.verify().await,
+            Err(Error::MissingManifest(_))
+        ));
+
+        let mut manifest = Manifest::new();
+        for (name, contents) in [("a.txt", "hello"), ("b.txt", "world"), ("c.txt", "!")] {
+            let checksum = book
+                .entry(name)
+                .upload_with_checksum(&mut contents.as_bytes())
+                .await
+                .unwrap();
+            manifest.insert(name, checksum);
+        }
+        book.write_manifest(&manifest).await.unwrap();
+
+        assert_eq!(book.manifest().await.unwrap(), Some(manifest.clone()));
+        assert_eq!(
+            manifest.entries[Utf8Path::new("a.txt")].sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+
+        let report = book.verify().await.unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.verified.len(), 3);
+
+        // Same size, different contents: only caught by a full verification.
+        book.entry("a.txt")
+            .upload(&mut "HELLO".as_bytes())
+            .await
+            .unwrap();
+        book.entry("b.txt").delete().await.unwrap();
+        book.entry("c.txt")
+            .upload(&mut "!!".as_bytes())
+            .await
+            .unwrap();
+
+        let report = book.verify_metadata().await.unwrap();
+        assert_eq!(report.missing, vec![Utf8PathBuf::from("b.txt")]);
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].path, "c.txt");
+
+        let report = book.verify().await.unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.missing, vec![Utf8PathBuf::from("b.txt")]);
+        let corrupted: Vec<_> = report.corrupted.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(corrupted, vec!["a.txt", "c.txt"]);
+
+        // The manifest is not part of the book's entries.
+        let shelf = Bookshelf::new(storage, "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        assert_eq!(volume.book(epoch).list().len(), 2);
+    }
+}
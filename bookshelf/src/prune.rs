@@ -0,0 +1,317 @@
+//! Bulk deletion of expired books.
+//!
+//! [`Book::delete`] removes every entry in a book at once, which is fine for a
+//! single book but can overwhelm a storage backend when retention expires
+//! thousands of epochs. A [`Pruner`] bounds the number of deletes in flight
+//! across all books, optionally rate limits each storage backend, and reports
+//! progress as it goes.
+//!
+//! Deletes that fail are collected in the [`PruneReport`] rather than aborting
+//! the run, and can be retried with [`Pruner::resume`]. A book's manifest is
+//! only removed once all of its entries are gone.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use camino::Utf8PathBuf;
+use tokio::sync::{watch, Semaphore};
+use tokio::time::Instant;
+
+use crate::{Book, Epoch, Error, Volume};
+
+/// Default number of deletes in flight at once.
+const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Progress of a [`Pruner`], accumulated across all of its runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PruneProgress {
+    /// Books queued for pruning.
+    pub books: usize,
+
+    /// Books whose entries and manifest have all been deleted.
+    pub pruned: usize,
+
+    /// Entries queued for deletion.
+    pub entries: usize,
+
+    /// Entries deleted.
+    pub deleted: usize,
+
+    /// Entries which failed to delete.
+    pub failed: usize,
+}
+
+/// The outcome of pruning a set of books.
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Books which were completely deleted.
+    pub pruned: Vec<Epoch>,
+
+    /// Entries which could not be deleted, by book. A book with no remaining
+    /// entries here still has its manifest.
+    pub remaining: BTreeMap<Epoch, Vec<Utf8PathBuf>>,
+
+    /// Errors encountered while deleting.
+    pub errors: Vec<Error>,
+}
+
+impl PruneReport {
+    /// Check if every book was completely deleted.
+    pub fn is_complete(&self) -> bool {
+        self.remaining.is_empty()
+    }
+}
+
+/// Spaces out requests to a single storage backend.
+#[derive(Debug)]
+struct RateLimit {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimit {
+    fn per_second(requests: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests.max(1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap();
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
+
+/// Deletes books with bounded concurrency, per-backend rate limits and progress reporting.
+#[derive(Debug)]
+pub struct Pruner {
+    permits: Arc<Semaphore>,
+    limits: HashMap<&'static str, RateLimit>,
+    progress: watch::Sender<PruneProgress>,
+}
+
+impl Default for Pruner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pruner {
+    /// Create a pruner with the default concurrency and no rate limits.
+    pub fn new() -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(DEFAULT_CONCURRENCY)),
+            limits: HashMap::new(),
+            progress: watch::Sender::new(PruneProgress::default()),
+        }
+    }
+
+    /// Set the maximum number of deletes in flight at once, across all books.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.permits = Arc::new(Semaphore::new(concurrency.max(1)));
+        self
+    }
+
+    /// Limit deletes to the storage backend with this driver name (e.g. `b2`)
+    /// to `requests` per second.
+    pub fn with_rate_limit(mut self, backend: &'static str, requests: u32) -> Self {
+        self.limits.insert(backend, RateLimit::per_second(requests));
+        self
+    }
+
+    /// Watch the progress of this pruner.
+    pub fn subscribe(&self) -> watch::Receiver<PruneProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Delete the books for these epochs from the volume.
+    pub async fn prune<I>(&self, volume: &Volume, epochs: I) -> PruneReport
+    where
+        I: IntoIterator<Item = Epoch>,
+    {
+        let books = epochs
+            .into_iter()
+            .map(|epoch| (epoch, volume.book(epoch).list()))
+            .collect();
+        self.prune_entries(volume, books).await
+    }
+
+    /// Retry the entries which could not be deleted in a previous run.
+    pub async fn resume(&self, volume: &Volume, report: PruneReport) -> PruneReport {
+        self.prune_entries(volume, report.remaining).await
+    }
+
+    #[tracing::instrument(skip_all, fields(volume = %volume.name(), books = books.len()))]
+    async fn prune_entries(
+        &self,
+        volume: &Volume,
+        books: BTreeMap<Epoch, Vec<Utf8PathBuf>>,
+    ) -> PruneReport {
+        let entries = books.values().map(Vec::len).sum::<usize>();
+        self.progress.send_modify(|progress| {
+            progress.books += books.len();
+            progress.entries += entries;
+        });
+
+        let results = futures::future::join_all(
+            books
+                .into_iter()
+                .map(|(epoch, paths)| self.prune_book(volume.book(epoch), paths)),
+        )
+        .await;
+
+        let mut report = PruneReport::default();
+        for (epoch, remaining, errors) in results {
+            if remaining.is_empty() && errors.is_empty() {
+                report.pruned.push(epoch);
+            } else {
+                report.remaining.insert(epoch, remaining);
+            }
+            report.errors.extend(errors);
+        }
+
+        tracing::info!(
+            pruned = report.pruned.len(),
+            remaining = report.remaining.len(),
+            "Pruned {entries} entries"
+        );
+        report
+    }
+
+    async fn prune_book(
+        &self,
+        book: Book,
+        paths: Vec<Utf8PathBuf>,
+    ) -> (Epoch, Vec<Utf8PathBuf>, Vec<Error>) {
+        let limit = self.limits.get(book.volume.storage().name());
+
+        let deletes = paths.into_iter().map(|path| {
+            let book = &book;
+            async move {
+                let _permit = self.permits.acquire().await.expect("semaphore is open");
+                if let Some(limit) = limit {
+                    limit.wait().await;
+                }
+
+                let result = book.entry(&path).delete().await;
+                self.progress.send_modify(|progress| match result {
+                    Ok(()) => progress.deleted += 1,
+                    Err(_) => progress.failed += 1,
+                });
+                (path, result)
+            }
+        });
+
+        let mut remaining = Vec::new();
+        let mut errors = Vec::new();
+        for (path, result) in futures::future::join_all(deletes).await {
+            if let Err(error) = result {
+                tracing::warn!(epoch = %book.epoch, %path, "Failed to delete entry: {error}");
+                remaining.push(path);
+                errors.push(error);
+            }
+        }
+
+        if remaining.is_empty() {
+            match book.delete_manifest().await {
+                Ok(()) => self.progress.send_modify(|progress| progress.pruned += 1),
+                Err(error) => {
+                    tracing::warn!(epoch = %book.epoch, "Failed to delete manifest: {error}");
+                    errors.push(error);
+                }
+            }
+        }
+
+        (book.epoch, remaining, errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::{Storage, TempDriver};
+
+    use crate::Bookshelf;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn prune_and_resume() {
+        let storage = Storage::new(TempDriver::new().unwrap());
+        let shelf = Bookshelf::new(storage.clone(), "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+
+        let epochs: Vec<Epoch> = ["20200101", "20200102", "20200103"]
+            .iter()
+            .map(|epoch| epoch.parse().unwrap())
+            .collect();
+        for epoch in &epochs {
+            let book = volume.book(*epoch);
+            for name in ["a.txt", "b.txt"] {
+                book.entry(name)
+                    .upload(&mut "data".as_bytes())
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let shelf = Bookshelf::new(storage.clone(), "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        assert_eq!(volume.list().len(), 3);
+
+        // Deleting an entry which is already gone fails with the local driver.
+        volume
+            .book(epochs[1])
+            .entry("b.txt")
+            .delete()
+            .await
+            .unwrap();
+
+        let pruner = Pruner::new()
+            .with_concurrency(2)
+            .with_rate_limit("temp", 100);
+        let progress = pruner.subscribe();
+
+        let start = Instant::now();
+        let report = pruner.prune(&volume, epochs.clone()).await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        assert!(!report.is_complete());
+        assert_eq!(report.pruned, vec![epochs[0], epochs[2]]);
+        assert_eq!(
+            report.remaining,
+            BTreeMap::from([(epochs[1], vec![Utf8PathBuf::from("b.txt")])])
+        );
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            *progress.borrow(),
+            PruneProgress {
+                books: 3,
+                pruned: 2,
+                entries: 6,
+                deleted: 5,
+                failed: 1,
+            }
+        );
+
+        volume
+            .book(epochs[1])
+            .entry("b.txt")
+            .upload(&mut "data".as_bytes())
+            .await
+            .unwrap();
+        let report = pruner.resume(&volume, report).await;
+        assert!(report.is_complete());
+        assert_eq!(report.pruned, vec![epochs[1]]);
+
+        let shelf = Bookshelf::new(storage, "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        assert!(volume.list().is_empty());
+    }
+}
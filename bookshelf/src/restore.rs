@@ -0,0 +1,171 @@
+//! Trial restores, to check that a book can actually be recovered from storage.
+//!
+//! Entries are copied into a temporary directory and then read back and checked
+//! against the book's manifest. The temporary directory is removed when the check
+//! finishes, so nothing is restored to its real location.
+
+use camino::Utf8Path;
+use storage::{Storage, StorageError, TempDriver};
+use tokio::io;
+
+use crate::manifest::{Corruption, HashingSink};
+use crate::{Book, Checksum, Entry, Error, Verification};
+
+/// Bucket used for restored entries in the scratch storage.
+const SCRATCH_BUCKET: &str = "restore";
+
+/// Size of the in-memory pipe between the download and the scratch upload.
+const PIPE_SIZE: usize = 64 * 1024;
+
+impl Book {
+    /// Restore every entry in the manifest into scratch space, and check that the
+    /// restored copies match the manifest.
+    ///
+    /// Books whose manifest records more than `limit` bytes are rejected before
+    /// anything is downloaded. Entries whose size in storage doesn't match the
+    /// manifest are reported as corrupted without being downloaded.
+    #[tracing::instrument(skip(self), fields(volume = %self.volume.name(), epoch = %self.epoch))]
+    pub async fn verify_restorable(&self, limit: u64) -> Result<Verification, Error> {
+        let manifest = self
+            .manifest()
+            .await?
+            .ok_or_else(|| Error::MissingManifest(self.manifest_path()))?;
+
+        let size = manifest
+            .entries
+            .values()
+            .map(|checksum| checksum.size)
+            .sum();
+        if size > limit {
+            return Err(Error::RestoreLimit { size, limit });
+        }
+
+        let scratch = Storage::from(TempDriver::new().map_err(StorageError::with("Temp"))?);
+
+        let mut report = Verification::default();
+        for (path, expected) in manifest.entries {
+            let entry = self.entry(&path);
+            let Ok(metadata) = self
+                .volume
+                .storage()
+                .metadata(self.volume.bucket(), entry.path())
+                .await
+            else {
+                tracing::warn!(%path, "Entry missing from storage");
+                report.missing.push(path);
+                continue;
+            };
+
+            let actual = if metadata.size == expected.size {
+                restore(&entry, &scratch, &path).await?
+            } else {
+                Checksum {
+                    size: metadata.size,
+                    sha256: String::new(),
+                }
+            };
+
+            if actual == expected {
+                report.verified.push(path);
+            } else {
+                tracing::warn!(%path, "Restored entry does not match manifest");
+                report.corrupted.push(Corruption {
+                    path,
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        tracing::info!(
+            verified = report.verified.len(),
+            missing = report.missing.len(),
+            corrupted = report.corrupted.len(),
+            "Restored {size} bytes to scratch space"
+        );
+        Ok(report)
+    }
+}
+
+/// Copy an entry into scratch storage, then read it back to compute its checksum.
+async fn restore(entry: &Entry, scratch: &Storage, path: &Utf8Path) -> Result<Checksum, Error> {
+    let (writer, reader) = io::duplex(PIPE_SIZE);
+
+    let download = async move {
+        let mut writer = writer;
+        entry.download(&mut writer).await?;
+
+        // Closing the pipe ends the upload.
+        drop(writer);
+        Ok::<_, Error>(())
+    };
+
+    let upload = async {
+        let mut reader = io::BufReader::new(reader);
+        scratch
+            .upload(SCRATCH_BUCKET, path, &mut reader)
+            .await
+            .map_err(Error::from)
+    };
+
+    futures::future::try_join(download, upload).await?;
+
+    let mut sink = HashingSink::new();
+    scratch.download(SCRATCH_BUCKET, path, &mut sink).await?;
+    Ok(sink.checksum())
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::MemoryStorage;
+
+    use crate::{Bookshelf, Epoch, Manifest};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn verify_restorable() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage, "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        let epoch: Epoch = "20200101".parse().unwrap();
+        let book = volume.book(epoch);
+
+        assert!(matches!(
+            book.verify_restorable(1024).await,
+            Err(Error::MissingManifest(_))
+        ));
+
+        let mut manifest = Manifest::new();
+        for (name, contents) in [("a.txt", "hello"), ("nested/b.txt", "world")] {
+            let checksum = book
+                .entry(name)
+                .upload_with_checksum(&mut contents.as_bytes())
+                .await
+                .unwrap();
+            manifest.insert(name, checksum);
+        }
+        book.write_manifest(&manifest).await.unwrap();
+
+        let report = book.verify_restorable(1024).await.unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.verified, vec!["a.txt", "nested/b.txt"]);
+
+        assert!(matches!(
+            book.verify_restorable(8).await,
+            Err(Error::RestoreLimit { size: 10, limit: 8 })
+        ));
+
+        book.entry("a.txt")
+            .upload(&mut "HELLO".as_bytes())
+            .await
+            .unwrap();
+        book.entry("nested/b.txt").delete().await.unwrap();
+
+        let report = book.verify_restorable(1024).await.unwrap();
+        assert_eq!(report.missing, vec!["nested/b.txt"]);
+        assert_eq!(report.corrupted.len(), 1);
+        assert_eq!(report.corrupted[0].path, "a.txt");
+        assert_eq!(report.corrupted[0].actual.size, 5);
+    }
+}
@@ -0,0 +1,320 @@
+//! Bookshelves which span several storage tiers.
+//!
+//! A [`TieredBookshelf`] combines bookshelves in different buckets, prefixes or
+//! storage backends, e.g. recent backups under `hot/` on local disk and older
+//! backups under `archive/` on B2. Volumes are merged across the tiers, each
+//! epoch is attributed to the tier(s) it was found in, and new books are written
+//! to the tier chosen by the [`WritePolicy`].
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::{Book, Bookshelf, Epoch, EpochSelector, Error, Volume};
+
+/// Which tier new books are written to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WritePolicy {
+    /// Write to the first tier added to the bookshelf.
+    #[default]
+    First,
+
+    /// Write to the last tier added to the bookshelf.
+    Last,
+
+    /// Write to the tier with this name.
+    Tier(String),
+}
+
+/// A set of bookshelves, searched in order, which act as one.
+#[derive(Debug, Clone)]
+pub struct TieredBookshelf {
+    tiers: Vec<(String, Bookshelf)>,
+    policy: WritePolicy,
+}
+
+impl TieredBookshelf {
+    /// Create a new tiered bookshelf with a single tier.
+    pub fn new<S: Into<String>>(name: S, shelf: Bookshelf) -> Self {
+        Self {
+            tiers: vec![(name.into(), shelf)],
+            policy: WritePolicy::default(),
+        }
+    }
+
+    /// Add another tier, which is searched after the existing tiers.
+    pub fn with_tier<S: Into<String>>(mut self, name: S, shelf: Bookshelf) -> Self {
+        self.tiers.push((name.into(), shelf));
+        self
+    }
+
+    /// Set the policy for which tier new books are written to.
+    pub fn with_policy(mut self, policy: WritePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get the bookshelf for a tier, by name.
+    pub fn tier(&self, name: &str) -> Option<&Bookshelf> {
+        self.tiers
+            .iter()
+            .find(|(tier, _)| tier == name)
+            .map(|(_, shelf)| shelf)
+    }
+
+    /// Iterate over the tiers, in search order.
+    pub fn tiers(&self) -> impl Iterator<Item = (&str, &Bookshelf)> {
+        self.tiers
+            .iter()
+            .map(|(name, shelf)| (name.as_str(), shelf))
+    }
+
+    /// The index of the tier which new books are written to.
+    fn target(&self) -> Result<usize, Error> {
+        match &self.policy {
+            WritePolicy::First => Ok(0),
+            WritePolicy::Last => Ok(self.tiers.len() - 1),
+            WritePolicy::Tier(name) => self
+                .tiers
+                .iter()
+                .position(|(tier, _)| tier == name)
+                .ok_or_else(|| Error::UnknownTier(name.clone())),
+        }
+    }
+
+    /// List all volumes in any tier of the bookshelf.
+    pub async fn list(&self) -> Result<Vec<TieredVolume>, Error> {
+        let target = self.target()?;
+
+        let mut listed = Vec::with_capacity(self.tiers.len());
+        for (_, shelf) in &self.tiers {
+            listed.push(shelf.list().await?);
+        }
+
+        let names: BTreeSet<Utf8PathBuf> = listed
+            .iter()
+            .flatten()
+            .map(|volume| volume.name().to_owned())
+            .collect();
+
+        Ok(names
+            .into_iter()
+            .map(|name| {
+                let volumes = self
+                    .tiers
+                    .iter()
+                    .zip(&listed)
+                    .map(|((tier, shelf), volumes)| {
+                        let volume = volumes
+                            .iter()
+                            .find(|volume| volume.name() == name.as_path())
+                            .cloned()
+                            .unwrap_or_else(|| shelf.empty_volume(name.clone()));
+                        (tier.clone(), volume)
+                    })
+                    .collect();
+                TieredVolume::new(name, volumes, target)
+            })
+            .collect())
+    }
+
+    /// Get a volume by name, merged across all tiers.
+    pub async fn volume(&self, name: &str) -> Result<TieredVolume, Error> {
+        let target = self.target()?;
+
+        let mut volumes = Vec::with_capacity(self.tiers.len());
+        for (tier, shelf) in &self.tiers {
+            volumes.push((tier.clone(), shelf.volume(name).await?));
+        }
+
+        Ok(TieredVolume::new(name.into(), volumes, target))
+    }
+}
+
+/// A volume merged across the tiers of a [`TieredBookshelf`].
+#[derive(Debug, Clone)]
+pub struct TieredVolume {
+    name: Utf8PathBuf,
+    volumes: Vec<(String, Volume)>,
+    epochs: BTreeMap<Epoch, Vec<usize>>,
+    target: usize,
+}
+
+impl TieredVolume {
+    fn new(name: Utf8PathBuf, volumes: Vec<(String, Volume)>, target: usize) -> Self {
+        let mut epochs: BTreeMap<Epoch, Vec<usize>> = BTreeMap::new();
+        for (index, (_, volume)) in volumes.iter().enumerate() {
+            for epoch in volume.list() {
+                epochs.entry(epoch).or_default().push(index);
+            }
+        }
+
+        Self {
+            name,
+            volumes,
+            epochs,
+            target,
+        }
+    }
+
+    /// Get the name of the volume.
+    pub fn name(&self) -> &Utf8Path {
+        &self.name
+    }
+
+    /// List all epochs in any tier of the volume.
+    pub fn list(&self) -> BTreeSet<Epoch> {
+        self.epochs.keys().cloned().collect()
+    }
+
+    /// Check if an epoch exists in any tier of the volume.
+    pub fn exists(&self, epoch: Epoch) -> bool {
+        self.epochs.contains_key(&epoch)
+    }
+
+    /// The name of the first tier which contains an epoch.
+    pub fn source(&self, epoch: Epoch) -> Option<&str> {
+        self.sources(epoch).into_iter().next()
+    }
+
+    /// The names of all tiers which contain an epoch, in search order.
+    pub fn sources(&self, epoch: Epoch) -> Vec<&str> {
+        self.epochs
+            .get(&epoch)
+            .map(|tiers| {
+                tiers
+                    .iter()
+                    .map(|&index| self.volumes[index].0.as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the volume in a single tier, by name.
+    pub fn tier(&self, name: &str) -> Option<&Volume> {
+        self.volumes
+            .iter()
+            .find(|(tier, _)| tier == name)
+            .map(|(_, volume)| volume)
+    }
+
+    /// The name of the tier which new books are written to.
+    pub fn target(&self) -> &str {
+        &self.volumes[self.target].0
+    }
+
+    /// Get a book by epoch, from the first tier which contains it.
+    pub fn get<E: Into<EpochSelector>>(&self, epoch: E) -> Option<Book> {
+        let selector = epoch.into();
+        let epoch = selector.find(&self.epochs);
+        tracing::trace!("Selected epoch {epoch:?} as {selector}");
+        epoch.map(|epoch| self.book(epoch))
+    }
+
+    /// Get a book by epoch, from the first tier which contains it, or create
+    /// a new, empty book in the target tier.
+    pub fn book(&self, epoch: Epoch) -> Book {
+        let index = self
+            .epochs
+            .get(&epoch)
+            .and_then(|tiers| tiers.first().copied())
+            .unwrap_or(self.target);
+        self.volumes[index].1.book(epoch)
+    }
+
+    /// Get the book for today.
+    pub fn today(&self) -> Book {
+        self.book(Epoch::today())
+    }
+
+    /// Get the book with the earliest date in any tier.
+    pub fn earliest(&self) -> Option<Book> {
+        self.get(EpochSelector::Earliest)
+    }
+
+    /// Get the book with the latest date in any tier.
+    pub fn latest(&self) -> Option<Book> {
+        self.get(EpochSelector::Latest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use storage::{MemoryStorage, Storage};
+
+    use super::*;
+
+    fn epoch(day: u32) -> Epoch {
+        Epoch::from(NaiveDate::from_ymd_opt(2020, 1, day).unwrap())
+    }
+
+    async fn shelf(prefix: &str, paths: &[&str]) -> Bookshelf {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        for path in paths {
+            let mut reader = std::io::Cursor::new("contents");
+            storage
+                .upload("bucket", &Utf8Path::new(prefix).join(path), &mut reader)
+                .await
+                .unwrap();
+        }
+        Bookshelf::new(storage, "bucket".into(), Some(prefix.into()))
+    }
+
+    #[tokio::test]
+    async fn merged_volumes() {
+        let hot = shelf("hot", &["db/20200103/dump", "db/20200102/dump"]).await;
+        let archive = shelf("archive", &["db/20200101/dump", "db/20200102/dump"]).await;
+        let shelf = TieredBookshelf::new("hot", hot).with_tier("archive", archive);
+
+        let volumes = shelf.list().await.unwrap();
+        assert_eq!(volumes.len(), 1);
+
+        let volume = shelf.volume("db").await.unwrap();
+        assert_eq!(
+            volume.list(),
+            [epoch(1), epoch(2), epoch(3)].into_iter().collect()
+        );
+        assert_eq!(volume.source(epoch(1)), Some("archive"));
+        assert_eq!(volume.sources(epoch(2)), vec!["hot", "archive"]);
+        assert_eq!(volume.source(epoch(4)), None);
+
+        let book = volume.earliest().unwrap();
+        assert_eq!(
+            book.entry("dump").path(),
+            Utf8Path::new("archive/db/20200101/dump")
+        );
+        assert_eq!(volume.latest().unwrap().epoch(), epoch(3));
+
+        assert_eq!(volume.target(), "hot");
+        assert_eq!(
+            volume.book(epoch(4)).entry("dump").path(),
+            Utf8Path::new("hot/db/20200104/dump")
+        );
+    }
+
+    #[tokio::test]
+    async fn write_policy() {
+        let hot = shelf("hot", &[]).await;
+        let archive = shelf("archive", &[]).await;
+        let shelf = TieredBookshelf::new("hot", hot).with_tier("archive", archive);
+
+        let volume = shelf
+            .clone()
+            .with_policy(WritePolicy::Last)
+            .volume("db")
+            .await
+            .unwrap();
+        assert_eq!(volume.target(), "archive");
+        assert_eq!(
+            volume.book(epoch(1)).entry("dump").path(),
+            Utf8Path::new("archive/db/20200101/dump")
+        );
+
+        let result = shelf
+            .with_policy(WritePolicy::Tier("cold".into()))
+            .volume("db")
+            .await;
+        assert!(matches!(result, Err(Error::UnknownTier(name)) if name == "cold"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Uploading many entries into a book at once.
+//!
+//! Entries are uploaded concurrently, up to the bookshelf's upload concurrency
+//! (see [`Bookshelf::with_upload_concurrency`](crate::Bookshelf::with_upload_concurrency)).
+//! A failed upload doesn't stop the others, so each entry has its own result.
+//!
+//! The size and checksum of each uploaded entry is recorded in the book's
+//! [manifest](crate::manifest), so the book can be verified later.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use futures::StreamExt as _;
+use tokio::fs;
+
+use crate::{Book, Checksum, Error, Manifest};
+
+/// Default number of entry uploads in flight at once.
+pub(crate) const DEFAULT_UPLOAD_CONCURRENCY: usize = 8;
+
+/// The result of uploading one local file as an entry.
+#[derive(Debug)]
+pub struct EntryUpload {
+    /// Path of the entry within the book.
+    pub path: Utf8PathBuf,
+
+    /// The local file which was uploaded.
+    pub local: Utf8PathBuf,
+
+    /// Whether the upload succeeded, with the size and checksum of the entry if it did.
+    pub result: Result<Checksum, Error>,
+}
+
+impl EntryUpload {
+    /// Whether the entry was uploaded.
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl Book {
+    /// Upload local files as entries in this book, concurrently.
+    ///
+    /// Each item is the path of the entry within the book, and the local file to
+    /// upload. Results are returned in the same order as the items.
+    ///
+    /// Entries which were uploaded are added to the book's manifest, keeping any
+    /// entries already in it. An error is returned only if the manifest can't be
+    /// updated, so concurrent calls for the same book should be avoided.
+    #[tracing::instrument(skip_all, fields(volume = %self.volume.name(), epoch = %self.epoch))]
+    pub async fn upload_entries<I, P, L>(&self, entries: I) -> Result<Vec<EntryUpload>, Error>
+    where
+        I: IntoIterator<Item = (P, L)>,
+        P: AsRef<Utf8Path>,
+        L: AsRef<Utf8Path>,
+    {
+        let concurrency = self.volume.inner.config.upload_concurrency;
+        let uploads: Vec<EntryUpload> = futures::stream::iter(entries)
+            .map(|(path, local)| {
+                let path = path.as_ref().to_owned();
+                let local = local.as_ref().to_owned();
+                async move {
+                    let result = self.upload_entry(&path, &local).await;
+                    if let Err(error) = &result {
+                        tracing::warn!(%path, %local, "Failed to upload entry: {error}");
+                    }
+                    EntryUpload {
+                        path,
+                        local,
+                        result,
+                    }
+                }
+            })
+            .buffered(concurrency)
+            .collect()
+            .await;
+
+        let failed = uploads.iter().filter(|upload| !upload.is_ok()).count();
+        tracing::debug!(failed, "Uploaded {} entries", uploads.len());
+
+        if failed < uploads.len() {
+            let mut manifest = self.manifest().await?.unwrap_or_else(Manifest::new);
+            for upload in &uploads {
+                if let Ok(checksum) = &upload.result {
+                    manifest.insert(upload.path.clone(), checksum.clone());
+                }
+            }
+            self.write_manifest(&manifest).await?;
+        }
+
+        Ok(uploads)
+    }
+
+    /// Upload a local file as an entry, returning the checksum of the bytes uploaded.
+    async fn upload_entry(&self, path: &Utf8Path, local: &Utf8Path) -> Result<Checksum, Error> {
+        let file = fs::File::open(local)
+            .await
+            .map_err(|source| Error::ReadFile {
+                path: local.to_owned(),
+                source,
+            })?;
+        self.entry(path)
+            .upload_with_checksum(&mut tokio::io::BufReader::new(file))
+            .await
+    }
+
+    /// Upload every file under a local directory as entries in this book, concurrently.
+    ///
+    /// Entries are named by their path relative to the directory, and results are
+    /// returned in path order. An error is returned if the directory can't be read,
+    /// in which case nothing is uploaded, or if the manifest can't be updated.
+    pub async fn upload_dir(&self, local_dir: &Utf8Path) -> Result<Vec<EntryUpload>, Error> {
+        let mut files = walk(local_dir).await?;
+        files.sort();
+
+        let entries = files.into_iter().map(|local| {
+            let path = local
+                .strip_prefix(local_dir)
+                .expect("walked files are inside the directory")
+                .to_owned();
+            (path, local)
+        });
+        self.upload_entries(entries).await
+    }
+}
+
+/// Find every file under a directory, following symlinks.
+async fn walk(root: &Utf8Path) -> Result<Vec<Utf8PathBuf>, Error> {
+    let read_dir =
+        |path: Utf8PathBuf| move |source: std::io::Error| Error::ReadDir { path, source };
+
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_owned()];
+    while let Some(directory) = directories.pop() {
+        let mut entries = fs::read_dir(&directory)
+            .await
+            .map_err(read_dir(directory.clone()))?;
+
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(read_dir(directory.clone()))?
+        {
+            let path = Utf8PathBuf::from_path_buf(entry.path()).map_err(|path| Error::ReadDir {
+                path: directory.clone(),
+                source: std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("{} is not UTF-8", path.display()),
+                ),
+            })?;
+
+            let metadata = fs::metadata(&path).await.map_err(read_dir(path.clone()))?;
+            if metadata.is_dir() {
+                directories.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::{MemoryStorage, Storage};
+
+    use crate::{Bookshelf, Epoch};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn upload_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+        std::fs::create_dir(root.join("nested")).unwrap();
+        std::fs::write(root.join("a.txt"), "hello").unwrap();
+        std::fs::write(root.join("nested/b.txt"), "world").unwrap();
+
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage, "bucket".into(), None).with_upload_concurrency(1);
+        let epoch: Epoch = "20200101".parse().unwrap();
+        let book = shelf.volume("backups").await.unwrap().book(epoch);
+
+        let uploads = book.upload_dir(root).await.unwrap();
+        let paths: Vec<_> = uploads.iter().map(|upload| upload.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "nested/b.txt"]);
+        assert!(uploads.iter().all(EntryUpload::is_ok));
+
+        let mut contents = Vec::new();
+        book.entry("nested/b.txt")
+            .download(&mut contents)
+            .await
+            .unwrap();
+        assert_eq!(contents, b"world");
+
+        let manifest = book.manifest().await.unwrap().unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest.entries[Utf8Path::new("nested/b.txt")].size, 5);
+        assert!(book.verify().await.unwrap().is_ok());
+
+        assert!(matches!(
+            book.upload_dir(&root.join("missing")).await,
+            Err(Error::ReadDir { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn upload_entries_reports_each_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap();
+        std::fs::write(root.join("a.txt"), "hello").unwrap();
+
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage, "bucket".into(), None);
+        let epoch: Epoch = "20200101".parse().unwrap();
+        let book = shelf.volume("backups").await.unwrap().book(epoch);
+
+        let uploads = book
+            .upload_entries([
+                ("missing.txt", root.join("missing.txt")),
+                ("a.txt", root.join("a.txt")),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(uploads.len(), 2);
+        assert_eq!(uploads[0].path, "missing.txt");
+        assert!(matches!(
+            uploads[0].result,
+            Err(Error::ReadFile { ref path, .. }) if path == &root.join("missing.txt")
+        ));
+        assert_eq!(uploads[1].path, "a.txt");
+        assert!(uploads[1].is_ok());
+        assert!(book.entry("a.txt").exists_live().await.unwrap());
+
+        let manifest = book.manifest().await.unwrap().unwrap();
+        assert_eq!(manifest.entries.keys().collect::<Vec<_>>(), vec!["a.txt"]);
+    }
+}
@@ -0,0 +1,287 @@
+//! Subcommands for inspecting and pruning the backups on a bookshelf.
+
+use bookshelf::expiration::ExpirationPolicy;
+use bookshelf::{Bookshelf, Epoch, EpochSelector, Pruner, Volume};
+use camino::Utf8PathBuf;
+use clap::{Args, Subcommand};
+use eyre::{eyre, WrapErr as _};
+use storage::Storage;
+use tokio::io::{AsyncWriteExt as _, BufWriter};
+
+use crate::files::Output;
+
+/// Select a bookshelf from storage.
+#[derive(Debug, Args)]
+pub(crate) struct BookshelfArgs {
+    /// Bucket containing the bookshelf.
+    bucket: String,
+
+    /// Prefix of the bookshelf within the bucket.
+    #[arg(long)]
+    prefix: Option<Utf8PathBuf>,
+
+    #[command(subcommand)]
+    command: BookshelfCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum BookshelfCommand {
+    /// List the volumes on the bookshelf.
+    Volumes,
+
+    /// List the epochs in a volume.
+    Epochs {
+        /// Name of the volume.
+        volume: String,
+    },
+
+    /// List the entries in a book.
+    Show {
+        /// Name of the volume.
+        volume: String,
+
+        /// Epoch of the book, as `YYYYMMDD`, `latest`, `earliest` or a count back from the latest.
+        epoch: EpochSelector,
+    },
+
+    /// Download an entry from a book.
+    Get {
+        /// Name of the volume.
+        volume: String,
+
+        /// Epoch of the book, as `YYYYMMDD`, `latest`, `earliest` or a count back from the latest.
+        epoch: EpochSelector,
+
+        /// Path of the entry within the book.
+        entry: Utf8PathBuf,
+
+        /// Local destination, defaults to the entry's file name in the current directory.
+        local: Option<Utf8PathBuf>,
+    },
+
+    /// Delete the books which have expired under a retention policy.
+    Expire {
+        /// Name of the volume.
+        volume: String,
+
+        #[command(flatten)]
+        policy: PolicyArgs,
+
+        /// Date to measure retention from, as `YYYYMMDD`. Defaults to today.
+        #[arg(long)]
+        origin: Option<Epoch>,
+
+        /// List the expired books without deleting them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Retention policy options, defaulting to [`ExpirationPolicy::default`].
+#[derive(Debug, Args)]
+struct PolicyArgs {
+    /// Number of days to retain daily backups.
+    #[arg(long, default_value_t = ExpirationPolicy::default().days)]
+    days: u32,
+
+    /// Number of weeks to retain weekly backups.
+    #[arg(long, default_value_t = ExpirationPolicy::default().weeks)]
+    weeks: u32,
+
+    /// Number of months to retain monthly backups.
+    #[arg(long, default_value_t = ExpirationPolicy::default().months)]
+    months: u32,
+
+    /// Number of years to retain yearly backups.
+    #[arg(long, default_value_t = ExpirationPolicy::default().years)]
+    years: u32,
+}
+
+impl From<PolicyArgs> for ExpirationPolicy {
+    fn from(args: PolicyArgs) -> Self {
+        ExpirationPolicy {
+            days: args.days,
+            weeks: args.weeks,
+            months: args.months,
+            years: args.years,
+        }
+    }
+}
+
+async fn writeln(output: &mut Output<'_>, line: impl std::fmt::Display) -> eyre::Result<()> {
+    output.write_all(format!("{line}\n").as_bytes()).await?;
+    Ok(())
+}
+
+async fn volume(shelf: &Bookshelf, name: &str) -> eyre::Result<Volume> {
+    let volume = shelf.volume(name).await?;
+    if volume.list().is_empty() {
+        return Err(eyre!("Volume {name} has no books"));
+    }
+    Ok(volume)
+}
+
+impl BookshelfArgs {
+    /// Run the command, writing any results to `output`.
+    pub(crate) async fn run(self, storage: &Storage, output: &mut Output<'_>) -> eyre::Result<()> {
+        let shelf = Bookshelf::new(storage.clone(), self.bucket, self.prefix);
+
+        match self.command {
+            BookshelfCommand::Volumes => {
+                for volume in shelf.list().await? {
+                    writeln(output, volume.name()).await?;
+                }
+            }
+            BookshelfCommand::Epochs { volume: name } => {
+                let volume = volume(&shelf, &name).await?;
+                for epoch in volume.list() {
+                    writeln(output, epoch.to_path()).await?;
+                }
+            }
+            BookshelfCommand::Show {
+                volume: name,
+                epoch,
+            } => {
+                let volume = volume(&shelf, &name).await?;
+                let book = volume
+                    .get(epoch)
+                    .ok_or_else(|| eyre!("No book for {epoch} in {name}"))?;
+                let mut entries = book.list();
+                entries.sort();
+                for entry in entries {
+                    writeln(output, entry).await?;
+                }
+            }
+            BookshelfCommand::Get {
+                volume: name,
+                epoch,
+                entry,
+                local,
+            } => {
+                let volume = volume(&shelf, &name).await?;
+                let book = volume
+                    .get(epoch)
+                    .ok_or_else(|| eyre!("No book for {epoch} in {name}"))?;
+                let local = match local {
+                    Some(local) => local,
+                    None => entry
+                        .file_name()
+                        .map(Utf8PathBuf::from)
+                        .ok_or_else(|| eyre!("{entry} does not name a file"))?,
+                };
+
+                let file = tokio::fs::File::create(&local)
+                    .await
+                    .wrap_err_with(|| format!("create {local}"))?;
+                let mut file = BufWriter::new(file);
+                book.entry(&entry)
+                    .download(&mut file)
+                    .await
+                    .wrap_err_with(|| format!("download {entry}"))?;
+                file.shutdown().await?;
+                tracing::info!("Downloaded {entry} to {local}");
+            }
+            BookshelfCommand::Expire {
+                volume: name,
+                policy,
+                origin,
+                dry_run,
+            } => {
+                let volume = volume(&shelf, &name).await?;
+                let policy = ExpirationPolicy::from(policy);
+                let origin = origin.unwrap_or_else(Epoch::today);
+                let expired = policy.expired(origin, volume.list().into_iter());
+
+                for epoch in &expired {
+                    writeln(output, format_args!("expired {}", epoch.to_path())).await?;
+                }
+                if dry_run || expired.is_empty() {
+                    return Ok(());
+                }
+
+                let report = Pruner::new().prune(&volume, expired).await;
+                for epoch in &report.pruned {
+                    writeln(output, format_args!("deleted {}", epoch.to_path())).await?;
+                }
+                for error in &report.errors {
+                    tracing::error!("{error}");
+                }
+                if !report.is_complete() {
+                    return Err(eyre!(
+                        "{} books in {name} could not be completely deleted",
+                        report.remaining.len()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::MemoryStorage;
+
+    use super::*;
+
+    async fn run(storage: &Storage, args: &[&str]) -> String {
+        use clap::Parser as _;
+
+        let args =
+            crate::Args::try_parse_from(["emporium", "bookshelf", "bucket"].iter().chain(args))
+                .unwrap();
+        let crate::Command::Bookshelf(command) = args.command else {
+            panic!("expected a bookshelf command");
+        };
+
+        let mut output = Vec::new();
+        command.run(storage, &mut output).await.unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[tokio::test]
+    async fn bookshelf_commands() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        let shelf = Bookshelf::new(storage.clone(), "bucket".into(), None);
+        let volume = shelf.volume("backups").await.unwrap();
+        for epoch in ["20180101", "20190101", "20200103"] {
+            let book = volume.book(epoch.parse().unwrap());
+            for name in ["a.txt", "nested/b.txt"] {
+                book.entry(name)
+                    .upload(&mut "data".as_bytes())
+                    .await
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(run(&storage, &["volumes"]).await, "backups\n");
+        assert_eq!(
+            run(&storage, &["epochs", "backups"]).await,
+            "20180101\n20190101\n20200103\n"
+        );
+        assert_eq!(
+            run(&storage, &["show", "backups", "latest"]).await,
+            "a.txt\nnested/b.txt\n"
+        );
+
+        let args = [
+            "expire", "backups", "--days", "1", "--weeks", "0", "--months", "0", "--years", "0",
+            "--origin", "20200103",
+        ];
+        let dry_run = [&args[..], &["--dry-run"]].concat();
+        assert_eq!(
+            run(&storage, &dry_run).await,
+            "expired 20180101\nexpired 20190101\n"
+        );
+        assert_eq!(
+            run(&storage, &["epochs", "backups"]).await,
+            "20180101\n20190101\n20200103\n"
+        );
+
+        assert_eq!(
+            run(&storage, &args).await,
+            "expired 20180101\nexpired 20190101\ndeleted 20180101\ndeleted 20190101\n"
+        );
+        assert_eq!(run(&storage, &["epochs", "backups"]).await, "20200103\n");
+    }
+}
@@ -0,0 +1,160 @@
+//! Subcommands for inspecting and modifying files, addressed by URI
+//! (e.g. `b2://bucket/path/to/file`).
+
+use camino::Utf8PathBuf;
+use clap::Subcommand;
+use eyre::{eyre, WrapErr as _};
+use http::Uri;
+use storage::Storage;
+use tokio::io::{AsyncWrite, AsyncWriteExt as _};
+
+/// Output stream for command results.
+pub(crate) type Output<'o> = dyn AsyncWrite + Unpin + Send + Sync + 'o;
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum FileCommand {
+    /// List the files in a bucket, optionally under a prefix.
+    Ls {
+        /// Bucket and prefix to list, e.g. `b2://bucket/prefix`.
+        uri: Uri,
+    },
+
+    /// Show the size and creation time of a file.
+    Stat {
+        /// File to inspect.
+        uri: Uri,
+    },
+
+    /// Print the contents of a file.
+    Cat {
+        /// File to print.
+        uri: Uri,
+    },
+
+    /// Download a file to a local path.
+    Get {
+        /// File to download.
+        uri: Uri,
+
+        /// Local destination, defaults to the file name in the current directory.
+        local: Option<Utf8PathBuf>,
+    },
+
+    /// Upload a local file.
+    Put {
+        /// Local file to upload.
+        local: Utf8PathBuf,
+
+        /// Destination in storage.
+        uri: Uri,
+    },
+
+    /// Delete a file.
+    Rm {
+        /// File to delete.
+        uri: Uri,
+    },
+}
+
+impl FileCommand {
+    /// Run the command, writing any results to `output`.
+    pub(crate) async fn run(self, storage: &Storage, output: &mut Output<'_>) -> eyre::Result<()> {
+        let driver = storage.uri();
+        match self {
+            FileCommand::Ls { uri } => {
+                let mut files = driver.list(&uri).await.wrap_err("list files")?;
+                files.sort();
+                for file in files {
+                    output.write_all(format!("{file}\n").as_bytes()).await?;
+                }
+            }
+            FileCommand::Stat { uri } => {
+                let metadata = driver.metadata(&uri).await.wrap_err("get metadata")?;
+                let stat = format!(
+                    "size: {}\ncreated: {}\n",
+                    metadata.size,
+                    metadata.created.to_rfc3339()
+                );
+                output.write_all(stat.as_bytes()).await?;
+            }
+            FileCommand::Cat { uri } => {
+                driver.download(&uri, output).await.wrap_err("download")?;
+            }
+            FileCommand::Get { uri, local } => {
+                let local = match local {
+                    Some(local) => local,
+                    None => Utf8PathBuf::from(uri.path())
+                        .file_name()
+                        .map(Utf8PathBuf::from)
+                        .ok_or_else(|| eyre!("{uri} does not name a file"))?,
+                };
+                driver
+                    .download_file(&uri, &local)
+                    .await
+                    .wrap_err_with(|| format!("download to {local}"))?;
+                tracing::info!("Downloaded {uri} to {local}");
+            }
+            FileCommand::Put { local, uri } => {
+                driver
+                    .upload_file(&uri, &local)
+                    .await
+                    .wrap_err_with(|| format!("upload from {local}"))?;
+                tracing::info!("Uploaded {local} to {uri}");
+            }
+            FileCommand::Rm { uri } => {
+                driver.delete(&uri).await.wrap_err("delete")?;
+                tracing::info!("Deleted {uri}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::Utf8Path;
+    use storage::MemoryStorage;
+
+    use super::*;
+
+    async fn run(storage: &Storage, command: FileCommand) -> String {
+        let mut output = Vec::new();
+        command.run(storage, &mut output).await.unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[tokio::test]
+    async fn file_commands() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        for name in ["b.txt", "a.txt", "other/c.txt"] {
+            storage
+                .upload("bucket", Utf8Path::new(name), &mut "hello".as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let uri = |path: &str| format!("memory://bucket/{path}").parse::<Uri>().unwrap();
+
+        let listing = run(&storage, FileCommand::Ls { uri: uri("") }).await;
+        assert_eq!(listing, "a.txt\nb.txt\nother/c.txt\n");
+
+        let listing = run(&storage, FileCommand::Ls { uri: uri("other") }).await;
+        assert_eq!(listing, "other/c.txt\n");
+
+        let stat = run(&storage, FileCommand::Stat { uri: uri("a.txt") }).await;
+        assert!(stat.starts_with("size: 5\n"));
+
+        let contents = run(&storage, FileCommand::Cat { uri: uri("a.txt") }).await;
+        assert_eq!(contents, "hello");
+
+        run(&storage, FileCommand::Rm { uri: uri("a.txt") }).await;
+        let listing = run(&storage, FileCommand::Ls { uri: uri("") }).await;
+        assert_eq!(listing, "b.txt\nother/c.txt\n");
+
+        let mut output = Vec::new();
+        let wrong_scheme = FileCommand::Cat {
+            uri: "b2://bucket/b.txt".parse().unwrap(),
+        };
+        assert!(wrong_scheme.run(&storage, &mut output).await.is_err());
+    }
+}
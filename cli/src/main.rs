@@ -0,0 +1,124 @@
+//! Command line tools for working with emporium storage backends.
+
+use std::process::ExitCode;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Parser, Subcommand};
+use emporium_report::{Category, Diagnostic};
+use eyre::WrapErr as _;
+use serde::Deserialize;
+use storage::StorageConfig;
+use tokio::io::AsyncWriteExt as _;
+use tracing_subscriber::EnvFilter;
+
+mod bookshelf;
+mod files;
+
+/// Inspect and modify files and bookshelves in any configured storage backend.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Configuration file (TOML) with a `storage` table. Without one, B2 is used
+    /// with credentials from the environment.
+    #[arg(long, short, env = "EMPORIUM_CONFIG", global = true)]
+    config: Option<Utf8PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    #[command(flatten)]
+    Files(files::FileCommand),
+
+    /// Inspect and prune the backups on a bookshelf.
+    Bookshelf(bookshelf::BookshelfArgs),
+}
+
+/// The configuration file for the CLI.
+#[derive(Debug, Deserialize)]
+struct Config {
+    storage: StorageConfig,
+}
+
+impl Config {
+    async fn load(path: Option<&Utf8Path>) -> eyre::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Config {
+                storage: StorageConfig::B2Env,
+            });
+        };
+
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .wrap_err_with(|| format!("read config from {path}"))?;
+        toml::from_str(&contents).wrap_err_with(|| format!("parse config from {path}"))
+    }
+}
+
+async fn run(args: Args) -> Result<(), Diagnostic> {
+    let config = Config::load(args.config.as_deref())
+        .await
+        .map_err(|error| {
+            Diagnostic::new(Category::Config, "Invalid configuration")
+                .with_hint("check the file passed with --config or EMPORIUM_CONFIG")
+                .with_causes(error.as_ref())
+        })?;
+    let storage = config
+        .storage
+        .build()
+        .await
+        .wrap_err("connect to storage")?;
+
+    let mut stdout = tokio::io::stdout();
+    match args.command {
+        Command::Files(command) => command.run(&storage, &mut stdout).await?,
+        Command::Bookshelf(command) => command.run(&storage, &mut stdout).await?,
+    }
+    stdout.flush().await.wrap_err("write output")?;
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(diagnostic) => {
+            eprintln!("error: {diagnostic}");
+            diagnostic.exit_code()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [storage.memory]
+            bucket = "backups"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(config.storage, StorageConfig::Memory { bucket } if bucket == "backups"));
+
+        let config: Config = toml::from_str(r#"storage = "b2-env""#).unwrap();
+        assert!(matches!(config.storage, StorageConfig::B2Env));
+    }
+
+    #[test]
+    fn parse_args() {
+        use clap::CommandFactory as _;
+        Args::command().debug_assert();
+    }
+}
@@ -7,28 +7,50 @@ use std::{
     future::Future,
     ops::Deref,
     pin::Pin,
-    sync::{Arc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
+    },
     time::{Duration, Instant},
 };
 
 use futures::FutureExt;
 use parking_lot::Mutex;
-use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    task::AbortHandle,
+};
+use tracing::{field, Instrument as _};
+
+mod stats;
+
+pub use self::stats::{CacheEvent, CacheStats};
+use self::stats::{Callback, Counters};
 
 #[derive(Debug)]
 struct RequestInner<T> {
-    inflight: Option<Weak<broadcast::Sender<T>>>,
+    inflight: Option<Inflight<T>>,
+}
+
+/// The spawned request, held weakly so that it is only kept alive by its handles.
+#[derive(Debug)]
+struct Inflight<T> {
+    sender: Weak<broadcast::Sender<T>>,
+    abort: Weak<AbortOnDrop>,
 }
 
 impl<T> RequestInner<T>
 where
     T: Clone + Send + Sync + 'static,
 {
-    fn get_reciever(&self) -> Option<broadcast::Receiver<T>> {
-        self.inflight
-            .as_ref()
-            .and_then(Weak::upgrade)
-            .map(|tx| tx.subscribe())
+    fn join(&self) -> Option<Handle<T>> {
+        let inflight = self.inflight.as_ref()?;
+
+        // Once the last handle is dropped the request is aborted, even if
+        // the task hasn't stopped yet, so it can't be joined.
+        let abort = inflight.abort.upgrade()?;
+        let tx = inflight.sender.upgrade()?;
+        Some(Handle::new(tx.subscribe(), abort))
     }
 }
 
@@ -41,9 +63,25 @@ where
     }
 }
 
+/// Aborts the spawned request when dropped, i.e. when no handle is waiting for it.
+#[derive(Debug)]
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        if !self.0.is_finished() {
+            tracing::trace!("Aborting request, no callers are waiting");
+        }
+        self.0.abort();
+    }
+}
+
 /// A handle to a request which is inflight.
+///
+/// The request is aborted when every handle to it has been dropped.
 pub struct Handle<T> {
     fut: BoxFut<'static, Result<T, RecvError>>,
+    _abort: Arc<AbortOnDrop>,
 }
 
 impl<T> fmt::Debug for Handle<T> {
@@ -67,9 +105,10 @@ impl<T> Handle<T>
 where
     T: Clone + Send + Sync + 'static,
 {
-    fn new(mut reciever: broadcast::Receiver<T>) -> Self {
+    fn new(mut reciever: broadcast::Receiver<T>, abort: Arc<AbortOnDrop>) -> Self {
         Self {
             fut: Box::pin(async move { reciever.recv().await }),
+            _abort: abort,
         }
     }
 }
@@ -79,9 +118,20 @@ pub type BoxFut<'f, O> = Pin<Box<dyn Future<Output = O> + Send + 'f>>;
 
 /// A coalesced request, which will ensure that only one of
 /// these requests can go through to the endpoint.
-#[derive(Debug)]
 pub struct Request<T> {
     inner: Arc<Mutex<RequestInner<T>>>,
+    counters: Arc<Counters>,
+    callback: Option<Callback<T>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Request<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Request")
+            .field("inner", &self.inner)
+            .field("counters", &self.counters)
+            .field("callback", &self.callback.is_some())
+            .finish()
+    }
 }
 
 impl<T> Clone for Request<T>
@@ -91,6 +141,8 @@ where
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            counters: Arc::clone(&self.counters),
+            callback: self.callback.clone(),
         }
     }
 }
@@ -102,6 +154,8 @@ where
     fn default() -> Self {
         Self {
             inner: Default::default(),
+            counters: Default::default(),
+            callback: None,
         }
     }
 }
@@ -110,8 +164,33 @@ impl<T> Request<T>
 where
     T: Clone + Send + Sync + 'static,
 {
+    /// Call a function with each value returned by the request, before it is
+    /// sent to the waiting callers.
+    pub fn with_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(CacheEvent<'_, T>) + Send + Sync + 'static,
+    {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// How many calls started a new request, and how many joined one already in flight.
+    pub fn stats(&self) -> CacheStats {
+        self.counters.snapshot()
+    }
+
+    /// Get a handle to the request if it is inflight, without starting it.
+    pub fn join(&self) -> Option<Handle<T>> {
+        let handle = self.inner.lock().join()?;
+        self.counters.join();
+        Some(handle)
+    }
+
     /// Get a handle to the one-and-only inflight request for
     /// this request manager.
+    ///
+    /// The request runs on a spawned task, which is aborted if every handle
+    /// is dropped before it completes. The next call then starts it again.
     pub fn handle<F>(&self, f: F) -> Handle<T>
     where
         F: FnOnce() -> BoxFut<'static, T>,
@@ -119,38 +198,56 @@ where
         // We must take the lock at this point to prevent another thread
         // from starting this request simultaneously.
         let mut inner = self.inner.lock();
-        let rx = {
-            if let Some(rx) = inner.get_reciever() {
+        let (rx, abort) = {
+            if let Some(handle) = inner.join() {
                 tracing::trace!("Found inflight request");
-                return Handle::new(rx);
+                self.counters.join();
+                return handle;
             }
+            self.counters.miss();
 
             let (tx, rx) = broadcast::channel::<T>(1);
 
             let tx = Arc::new(tx);
-            inner.inflight = Some(Arc::downgrade(&tx));
+            let sender = Arc::downgrade(&tx);
 
             let fut = (f)();
 
-            {
+            let task = {
                 let inner = Arc::clone(&self.inner);
+                let counters = Arc::clone(&self.counters);
+                let callback = self.callback.clone();
                 tracing::trace!("Launching new request");
-                tokio::spawn(async move {
-                    let res = fut.await;
-                    {
-                        // We'd like to hold the lock while we are sending responses, so that
-                        // we don't have a race condition which cuases some subscriber to not
-                        // recieve a response (b/c e.g. they subscribe right after we send)
-                        let mut inner = inner.lock();
-                        inner.inflight = None;
-
-                        let _ = tx.send(res);
+                let span = tracing::debug_span!("echocache::request", waiters = field::Empty);
+                tokio::spawn(
+                    async move {
+                        let res = fut.await;
+                        counters.refreshed(callback.as_ref(), &res);
+                        {
+                            // We'd like to hold the lock while we are sending responses, so that
+                            // we don't have a race condition which cuases some subscriber to not
+                            // recieve a response (b/c e.g. they subscribe right after we send)
+                            let mut inner = inner.lock();
+                            inner.inflight = None;
+
+                            let waiters = tx.receiver_count();
+                            tracing::Span::current().record("waiters", waiters);
+                            tracing::trace!(waiters, "Request complete");
+                            let _ = tx.send(res);
+                        }
                     }
-                });
+                    .instrument(span),
+                )
             };
-            rx
+
+            let abort = Arc::new(AbortOnDrop(task.abort_handle()));
+            inner.inflight = Some(Inflight {
+                sender,
+                abort: Arc::downgrade(&abort),
+            });
+            (rx, abort)
         };
-        Handle::new(rx)
+        Handle::new(rx, abort)
     }
 
     /// Get the value of the request, or start the request if it is not already inflight.
@@ -182,18 +279,33 @@ impl<T> InnerCache<T> {
 
 /// A type for caching a value which is fetched via
 /// an async function on the tokio runtime.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Cached<T> {
     inner: Arc<Mutex<InnerCache<T>>>,
     expiration: Option<Duration>,
+    counters: Arc<Counters>,
+    callback: Option<Callback<T>>,
+
+    /// Incremented, while holding the lock on `inner`, whenever the cache is
+    /// invalidated, so that requests started before then don't store their values.
+    generation: Arc<AtomicU64>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Cached<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cached")
+            .field("inner", &self.inner)
+            .field("expiration", &self.expiration)
+            .field("counters", &self.counters)
+            .field("callback", &self.callback.is_some())
+            .field("generation", &self.generation)
+            .finish()
+    }
 }
 
 impl<T> Default for Cached<T> {
     fn default() -> Self {
-        Self {
-            inner: Default::default(),
-            expiration: None,
-        }
+        Self::new(None)
     }
 }
 
@@ -204,6 +316,9 @@ impl<T> Cached<T> {
         Self {
             inner: Default::default(),
             expiration,
+            counters: Default::default(),
+            callback: None,
+            generation: Default::default(),
         }
     }
 
@@ -213,13 +328,74 @@ impl<T> Cached<T> {
         Self {
             inner: Arc::new(Mutex::new(InnerCache::new_with_value(value, expiration))),
             expiration,
+            counters: Default::default(),
+            callback: None,
+            generation: Default::default(),
         }
     }
 
+    /// Call a function when a value is refreshed or evicted, e.g. to log failed refreshes.
+    ///
+    /// The callback is run without holding the cache lock, so it may use the cache.
+    #[must_use]
+    pub fn with_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(CacheEvent<'_, T>) + Send + Sync + 'static,
+    {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// How often this cache (and its clones) answered requests from the cached value.
+    pub fn stats(&self) -> CacheStats {
+        self.counters.snapshot()
+    }
+
+    /// The number of times this cache (and its clones) has been invalidated.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
     /// Clear the cache, removing the value.
+    ///
+    /// A request which is already in flight will still answer its callers,
+    /// but won't store its value, so the next call to [`Cached::get`] starts
+    /// a fresh request.
     pub fn clear(&self) {
-        let mut inner = self.inner.lock();
-        *inner = InnerCache::Empty;
+        let previous = {
+            let mut inner = self.inner.lock();
+            self.generation.fetch_add(1, Ordering::Relaxed);
+            std::mem::take(&mut *inner)
+        };
+        if let InnerCache::Cached { value, .. } = previous {
+            self.counters.evicted(self.callback.as_ref(), &value);
+        }
+    }
+
+    /// Clear the cache if the cached value matches a predicate, e.g. when an
+    /// external signal shows that the value is stale. Returns whether the value
+    /// was removed.
+    ///
+    /// Expired values are passed to the predicate too. A request in flight is
+    /// left alone, since there is no value to check yet.
+    pub fn invalidate_if<F>(&self, predicate: F) -> bool
+    where
+        F: FnOnce(&T) -> bool,
+    {
+        let previous = {
+            let mut inner = self.inner.lock();
+            match inner.deref() {
+                InnerCache::Cached { value, .. } if predicate(value) => {
+                    self.generation.fetch_add(1, Ordering::Relaxed);
+                    std::mem::take(&mut *inner)
+                }
+                _ => return false,
+            }
+        };
+        if let InnerCache::Cached { value, .. } = previous {
+            self.counters.evicted(self.callback.as_ref(), &value);
+        }
+        true
     }
 
     /// Apply a function to the cached value, if it exists, and the cache is not expired. Return the result.
@@ -244,41 +420,211 @@ where
     T: Clone + Send + Sync + 'static,
 {
     /// Call a future to get a value, and cache it.
+    ///
+    /// Each call is traced in an `echocache::get` span, whose `cache` field records
+    /// whether the call was a `hit`, a `miss`, `stale`, or joined an inflight request.
+    ///
+    /// If every caller waiting for a request is dropped, the request is aborted and
+    /// nothing is cached, so the next call starts it again.
     pub async fn get<F>(&self, f: F) -> T
     where
         F: FnOnce() -> BoxFut<'static, T>,
     {
-        let handle = {
+        let span = tracing::trace_span!("echocache::get", cache = field::Empty);
+        let (handle, expired) = {
+            let _entered = span.enter();
             let mut inner = self.inner.lock();
-            match inner.deref() {
+            let joined = match inner.deref() {
                 InnerCache::Cached { value, expires }
                     if expires.map(|e| e >= Instant::now()).unwrap_or(true) =>
                 {
-                    return value.clone()
+                    span.record("cache", "hit");
+                    self.counters.hit();
+                    return value.clone();
+                }
+                // A request whose callers were all dropped was aborted, and is started again.
+                InnerCache::Inflight(request) => request.join(),
+                _ => None,
+            };
+
+            match joined {
+                Some(handle) => {
+                    span.record("cache", "join");
+                    self.counters.join();
+                    (handle, None)
                 }
-                InnerCache::Inflight(request) => request.handle(f),
-                _ => {
+                None => {
                     // We need to actually run the request.
+                    let status = match inner.deref() {
+                        InnerCache::Cached { .. } => "stale",
+                        _ => "miss",
+                    };
+                    span.record("cache", status);
+                    self.counters.miss();
                     let req = Request::default();
                     let handle = req.handle(|| {
                         let inner = Arc::clone(&self.inner);
                         let expiration = self.expiration;
+                        let counters = Arc::clone(&self.counters);
+                        let callback = self.callback.clone();
+                        let generation = Arc::clone(&self.generation);
+                        let started = generation.load(Ordering::Relaxed);
                         let fut = f();
                         Box::pin(async move {
                             let value = fut.await;
                             {
                                 let mut inner = inner.lock();
-                                *inner = InnerCache::new_with_value(value.clone(), expiration)
+                                if generation.load(Ordering::Relaxed) == started {
+                                    *inner = InnerCache::new_with_value(value.clone(), expiration)
+                                } else {
+                                    tracing::trace!("Cache invalidated during request");
+                                }
                             }
+                            counters.refreshed(callback.as_ref(), &value);
                             value
                         })
                     });
 
-                    *inner = InnerCache::Inflight(req);
-                    handle
+                    match std::mem::replace(&mut *inner, InnerCache::Inflight(req)) {
+                        InnerCache::Cached { value, .. } => (handle, Some(value)),
+                        _ => (handle, None),
+                    }
                 }
             }
         };
-        handle.await.unwrap()
+
+        if let Some(value) = expired {
+            self.counters.evicted(self.callback.as_ref(), &value);
+        }
+        handle.instrument(span).await.unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cache_stats_and_events() {
+        let refreshed = Arc::new(AtomicUsize::new(0));
+        let evicted = Arc::new(AtomicUsize::new(0));
+
+        let cache = Cached::<u32>::new(None).with_callback({
+            let refreshed = Arc::clone(&refreshed);
+            let evicted = Arc::clone(&evicted);
+            move |event| match event {
+                CacheEvent::Refreshed(value) => {
+                    refreshed.fetch_add(*value as usize, Ordering::SeqCst);
+                }
+                CacheEvent::Evicted(_) => {
+                    evicted.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+
+        assert_eq!(cache.get(|| Box::pin(async { 2 })).await, 2);
+        assert_eq!(cache.get(|| Box::pin(async { 3 })).await, 2);
+        cache.clear();
+        assert_eq!(cache.get(|| Box::pin(async { 5 })).await, 5);
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                inflight: 0,
+                refreshes: 2,
+                evictions: 1,
+            }
+        );
+        assert_eq!(refreshed.load(Ordering::SeqCst), 7);
+        assert_eq!(evicted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn invalidate_if() {
+        let cache = Cached::<u32>::new(None);
+        assert_eq!(cache.get(|| Box::pin(async { 2 })).await, 2);
+
+        assert!(!cache.invalidate_if(|value| *value == 3));
+        assert_eq!(cache.generation(), 0);
+        assert_eq!(cache.get(|| Box::pin(async { 3 })).await, 2);
+
+        assert!(cache.invalidate_if(|value| *value == 2));
+        assert_eq!(cache.generation(), 1);
+        assert_eq!(cache.get(|| Box::pin(async { 3 })).await, 3);
+    }
+
+    #[tokio::test]
+    async fn clear_during_request() {
+        let cache = Cached::<u32>::new(None);
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let stale = tokio::spawn({
+            let cache = cache.clone();
+            async move {
+                cache
+                    .get(|| {
+                        Box::pin(async move {
+                            rx.await.unwrap();
+                            1
+                        })
+                    })
+                    .await
+            }
+        });
+        while cache.stats().misses == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        cache.clear();
+        tx.send(()).unwrap();
+
+        // The request in flight still answers its caller, but isn't cached.
+        assert_eq!(stale.await.unwrap(), 1);
+        assert_eq!(cache.map_cached(|value| *value), None);
+        assert_eq!(cache.get(|| Box::pin(async { 2 })).await, 2);
+    }
+
+    #[tokio::test]
+    async fn dropped_request_is_aborted() {
+        let request = Request::<u32>::default();
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let handle = request.handle(|| {
+            Box::pin(async move {
+                let _tx = tx;
+                std::future::pending().await
+            })
+        });
+        assert!(request.join().is_some());
+
+        // Dropping the future drops the sender, closing the channel.
+        drop(handle);
+        assert!(rx.await.is_err());
+
+        assert!(request.join().is_none());
+        assert_eq!(request.get(|| Box::pin(async { 2 })).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn dropped_get_is_restarted() {
+        let cache = Cached::<u32>::new(None);
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+
+        let get = cache.get(|| {
+            Box::pin(async move {
+                let _tx = tx;
+                std::future::pending().await
+            })
+        });
+        assert!(get.now_or_never().is_none());
+        assert!(rx.await.is_err());
+
+        assert_eq!(cache.get(|| Box::pin(async { 2 })).await, 2);
+        assert_eq!(cache.stats().misses, 2);
+        assert_eq!(cache.stats().inflight, 0);
     }
 }
@@ -0,0 +1,97 @@
+//! Counters and callbacks which report how a cache is being used.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A snapshot of how often a cache was able to answer requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Requests answered with a cached value.
+    pub hits: usize,
+
+    /// Requests which had to start a new fetch.
+    pub misses: usize,
+
+    /// Requests which waited on a fetch which was already in flight.
+    pub inflight: usize,
+
+    /// Fetches which completed with a new value.
+    pub refreshes: usize,
+
+    /// Values which were removed from the cache, because they were cleared or expired.
+    pub evictions: usize,
+}
+
+impl CacheStats {
+    /// The fraction of requests which did not need to start a new fetch.
+    pub fn hit_ratio(&self) -> f64 {
+        let requests = self.hits + self.misses + self.inflight;
+        if requests == 0 {
+            return 0.0;
+        }
+        (self.hits + self.inflight) as f64 / requests as f64
+    }
+}
+
+/// A change to the value held by a cache, passed to the cache callback.
+#[derive(Debug)]
+pub enum CacheEvent<'v, T> {
+    /// A fetch completed, and this value is now cached.
+    Refreshed(&'v T),
+
+    /// This value was removed from the cache.
+    Evicted(&'v T),
+}
+
+/// A callback run when the value held by a cache changes.
+pub(crate) type Callback<T> = Arc<dyn Fn(CacheEvent<'_, T>) + Send + Sync>;
+
+/// Shared counters, reported as [`CacheStats`].
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    inflight: AtomicUsize,
+    refreshes: AtomicUsize,
+    evictions: AtomicUsize,
+}
+
+impl Counters {
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            inflight: self.inflight.load(Ordering::Relaxed),
+            refreshes: self.refreshes.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn join(&self) {
+        self.inflight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a new value, and tell the callback about it.
+    pub(crate) fn refreshed<T>(&self, callback: Option<&Callback<T>>, value: &T) {
+        self.refreshes.fetch_add(1, Ordering::Relaxed);
+        if let Some(callback) = callback {
+            (callback)(CacheEvent::Refreshed(value));
+        }
+    }
+
+    /// Record a removed value, and tell the callback about it.
+    pub(crate) fn evicted<T>(&self, callback: Option<&Callback<T>>, value: &T) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+        if let Some(callback) = callback {
+            (callback)(CacheEvent::Evicted(value));
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! B2 buckets.
+
+use b2_client::B2Client;
+
+use crate::{Attributes, InventoryError, ResourceKind, Snapshot};
+
+pub(crate) async fn collect(
+    client: &B2Client,
+    buckets: &[String],
+    snapshot: &mut Snapshot,
+) -> Result<(), InventoryError> {
+    for name in buckets {
+        let bucket = client
+            .get_bucket(name)
+            .await
+            .map_err(InventoryError::collect(ResourceKind::B2Bucket))?;
+
+        let mut attributes = Attributes::new();
+        attributes.insert("id".into(), bucket.id().to_string());
+        attributes.insert("type".into(), format!("{:?}", bucket.kind()));
+        if let Some(revision) = bucket.revision() {
+            attributes.insert("revision".into(), revision.to_string());
+        }
+        for (key, value) in bucket.info() {
+            attributes.insert(format!("info.{key}"), value.clone());
+        }
+        snapshot.insert(ResourceKind::B2Bucket, name.clone(), attributes);
+    }
+
+    Ok(())
+}
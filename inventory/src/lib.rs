@@ -0,0 +1,425 @@
+//! Point-in-time inventory of resources across the emporium services.
+//!
+//! An [`Inventory`] is configured with a client for each service to include, and
+//! [`Inventory::collect`] gathers the resources each one manages into a
+//! [`Snapshot`]. Snapshots are plain serializable documents, which can be saved
+//! to and loaded from [`Storage`], so that each run can compare what it sees
+//! against the previous run with [`Snapshot::diff`] to detect drift.
+//!
+//! Each service is only available when the feature of the same name is enabled.
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+use camino::Utf8Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use storage::{Storage, StorageError};
+
+#[cfg(feature = "b2")]
+mod b2;
+#[cfg(feature = "linode")]
+mod linode;
+#[cfg(feature = "octocat")]
+mod octocat;
+#[cfg(feature = "tailscale")]
+mod tailscale;
+
+/// Attributes recorded for a resource, which are compared between snapshots.
+pub type Attributes = BTreeMap<String, String>;
+
+/// The kind of a resource in a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    /// A Linode compute instance.
+    LinodeInstance,
+
+    /// A DNS domain managed by Linode.
+    LinodeDomain,
+
+    /// A B2 bucket.
+    B2Bucket,
+
+    /// An installation of the Github App.
+    GithubInstallation,
+
+    /// A device on the tailnet.
+    TailscaleDevice,
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceKind::LinodeInstance => f.write_str("linode instance"),
+            ResourceKind::LinodeDomain => f.write_str("linode domain"),
+            ResourceKind::B2Bucket => f.write_str("b2 bucket"),
+            ResourceKind::GithubInstallation => f.write_str("github installation"),
+            ResourceKind::TailscaleDevice => f.write_str("tailscale device"),
+        }
+    }
+}
+
+/// Errors from collecting, saving or loading a snapshot.
+#[derive(Debug, thiserror::Error)]
+pub enum InventoryError {
+    /// A service could not list its resources.
+    #[error("Collecting {kind} resources")]
+    Collect {
+        /// The kind of resource being collected.
+        kind: ResourceKind,
+
+        /// The error from the service.
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    /// The snapshot could not be read from or written to storage.
+    #[error("Storage: {0}")]
+    Storage(#[from] StorageError),
+
+    /// The snapshot could not be encoded or decoded.
+    #[error("Snapshot encoding: {0}")]
+    Encoding(#[from] serde_json::Error),
+}
+
+impl InventoryError {
+    /// Wrap errors from a service while collecting one kind of resource.
+    #[cfg(any(
+        feature = "b2",
+        feature = "linode",
+        feature = "octocat",
+        feature = "tailscale"
+    ))]
+    pub(crate) fn collect<E>(kind: ResourceKind) -> impl FnOnce(E) -> Self
+    where
+        E: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        move |error| InventoryError::Collect {
+            kind,
+            source: error.into(),
+        }
+    }
+}
+
+/// Resources across every configured service, at a point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// When the snapshot was taken.
+    pub taken_at: DateTime<Utc>,
+
+    #[serde(default)]
+    resources: BTreeMap<ResourceKind, BTreeMap<String, Attributes>>,
+}
+
+impl Default for Snapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Snapshot {
+    /// Create an empty snapshot, taken now.
+    pub fn new() -> Self {
+        Self {
+            taken_at: Utc::now(),
+            resources: BTreeMap::new(),
+        }
+    }
+
+    /// Record a resource, replacing any previous resource with the same kind and ID.
+    pub fn insert(&mut self, kind: ResourceKind, id: impl Into<String>, attributes: Attributes) {
+        self.resources
+            .entry(kind)
+            .or_default()
+            .insert(id.into(), attributes);
+    }
+
+    /// Get the attributes of a resource.
+    pub fn get(&self, kind: ResourceKind, id: &str) -> Option<&Attributes> {
+        self.resources.get(&kind)?.get(id)
+    }
+
+    /// Iterate over the resources of one kind, by ID.
+    pub fn resources(&self, kind: ResourceKind) -> impl Iterator<Item = (&str, &Attributes)> {
+        self.resources
+            .get(&kind)
+            .into_iter()
+            .flatten()
+            .map(|(id, attributes)| (id.as_str(), attributes))
+    }
+
+    /// Total number of resources in the snapshot.
+    pub fn len(&self) -> usize {
+        self.resources.values().map(BTreeMap::len).sum()
+    }
+
+    /// Check if the snapshot has no resources.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Changes between a previous snapshot and this one, ordered by kind and ID.
+    pub fn diff(&self, previous: &Snapshot) -> Vec<Change> {
+        let empty = BTreeMap::new();
+        let mut changes = Vec::new();
+
+        let kinds: std::collections::BTreeSet<_> = self
+            .resources
+            .keys()
+            .chain(previous.resources.keys())
+            .copied()
+            .collect();
+
+        for kind in kinds {
+            let before = previous.resources.get(&kind).unwrap_or(&empty);
+            let after = self.resources.get(&kind).unwrap_or(&empty);
+
+            let ids: std::collections::BTreeSet<_> = before.keys().chain(after.keys()).collect();
+            for id in ids {
+                let action = match (before.get(id), after.get(id)) {
+                    (None, Some(_)) => Action::Added,
+                    (Some(_), None) => Action::Removed,
+                    (Some(before), Some(after)) if before != after => Action::Modified {
+                        before: before.clone(),
+                        after: after.clone(),
+                    },
+                    _ => continue,
+                };
+
+                changes.push(Change {
+                    kind,
+                    id: id.clone(),
+                    action,
+                });
+            }
+        }
+
+        changes
+    }
+
+    /// Save the snapshot as JSON to storage.
+    #[tracing::instrument(skip(self, storage))]
+    pub async fn save(
+        &self,
+        storage: &Storage,
+        bucket: &str,
+        path: &Utf8Path,
+    ) -> Result<(), InventoryError> {
+        let data = serde_json::to_vec_pretty(self)?;
+        storage.upload(bucket, path, &mut data.as_slice()).await?;
+        tracing::debug!("Saved {} resources to {bucket}/{path}", self.len());
+        Ok(())
+    }
+
+    /// Load a snapshot saved with [`Snapshot::save`].
+    #[tracing::instrument(skip(storage))]
+    pub async fn load(
+        storage: &Storage,
+        bucket: &str,
+        path: &Utf8Path,
+    ) -> Result<Self, InventoryError> {
+        let mut data = Vec::new();
+        storage.download(bucket, path, &mut data).await?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+}
+
+/// A difference between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// The kind of resource which changed.
+    pub kind: ResourceKind,
+
+    /// The ID of the resource which changed.
+    pub id: String,
+
+    /// What happened to the resource.
+    pub action: Action,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.action {
+            Action::Added => write!(f, "+ {} {}", self.kind, self.id),
+            Action::Removed => write!(f, "- {} {}", self.kind, self.id),
+            Action::Modified { .. } => write!(f, "~ {} {}", self.kind, self.id),
+        }
+    }
+}
+
+/// What happened to a resource between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// The resource is new.
+    Added,
+
+    /// The resource no longer exists.
+    Removed,
+
+    /// The attributes of the resource changed.
+    Modified {
+        /// Attributes in the previous snapshot.
+        before: Attributes,
+
+        /// Attributes in the current snapshot.
+        after: Attributes,
+    },
+}
+
+/// Collects snapshots from a set of configured services.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    #[cfg(feature = "linode")]
+    linode: Option<::linode::LinodeClient>,
+
+    #[cfg(feature = "b2")]
+    b2: Option<(b2_client::B2Client, Vec<String>)>,
+
+    #[cfg(feature = "octocat")]
+    github: Option<::octocat::GithubApp>,
+
+    #[cfg(feature = "tailscale")]
+    tailscale: Option<::tailscale::TailscaleClient>,
+}
+
+impl Inventory {
+    /// Create an inventory with no services, which collects empty snapshots.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include Linode instances and domains.
+    #[cfg(feature = "linode")]
+    pub fn with_linode(mut self, client: ::linode::LinodeClient) -> Self {
+        self.linode = Some(client);
+        self
+    }
+
+    /// Include these B2 buckets. B2 keys are often restricted to a single bucket,
+    /// so buckets are looked up by name rather than listed.
+    #[cfg(feature = "b2")]
+    pub fn with_b2<I, S>(mut self, client: b2_client::B2Client, buckets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.b2 = Some((client, buckets.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Include installations of a Github App.
+    #[cfg(feature = "octocat")]
+    pub fn with_github(mut self, app: ::octocat::GithubApp) -> Self {
+        self.github = Some(app);
+        self
+    }
+
+    /// Include devices on a tailnet.
+    #[cfg(feature = "tailscale")]
+    pub fn with_tailscale(mut self, client: ::tailscale::TailscaleClient) -> Self {
+        self.tailscale = Some(client);
+        self
+    }
+
+    /// Collect a snapshot from every configured service.
+    #[tracing::instrument(skip(self))]
+    pub async fn collect(&self) -> Result<Snapshot, InventoryError> {
+        #[allow(unused_mut)]
+        let mut snapshot = Snapshot::new();
+
+        #[cfg(feature = "linode")]
+        if let Some(client) = &self.linode {
+            linode::collect(client, &mut snapshot).await?;
+        }
+
+        #[cfg(feature = "b2")]
+        if let Some((client, buckets)) = &self.b2 {
+            b2::collect(client, buckets, &mut snapshot).await?;
+        }
+
+        #[cfg(feature = "octocat")]
+        if let Some(app) = &self.github {
+            octocat::collect(app, &mut snapshot).await?;
+        }
+
+        #[cfg(feature = "tailscale")]
+        if let Some(client) = &self.tailscale {
+            tailscale::collect(client, &mut snapshot).await?;
+        }
+
+        tracing::debug!("Collected {} resources", snapshot.len());
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attributes(pairs: &[(&str, &str)]) -> Attributes {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_snapshots() {
+        let mut previous = Snapshot::new();
+        previous.insert(
+            ResourceKind::LinodeInstance,
+            "1",
+            attributes(&[("label", "web"), ("status", "running")]),
+        );
+        previous.insert(ResourceKind::LinodeDomain, "10", attributes(&[]));
+        previous.insert(ResourceKind::B2Bucket, "backups", attributes(&[]));
+
+        let mut current = Snapshot::new();
+        current.insert(
+            ResourceKind::LinodeInstance,
+            "1",
+            attributes(&[("label", "web"), ("status", "offline")]),
+        );
+        current.insert(ResourceKind::LinodeDomain, "10", attributes(&[]));
+        current.insert(ResourceKind::TailscaleDevice, "abc", attributes(&[]));
+
+        let changes = current.diff(&previous);
+        let summary: Vec<_> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            summary,
+            vec![
+                "~ linode instance 1",
+                "- b2 bucket backups",
+                "+ tailscale device abc"
+            ]
+        );
+
+        let Action::Modified { before, after } = &changes[0].action else {
+            panic!("expected a modified instance");
+        };
+        assert_eq!(before["status"], "running");
+        assert_eq!(after["status"], "offline");
+
+        assert!(current.diff(&current).is_empty());
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let mut snapshot = Snapshot::new();
+        snapshot.insert(
+            ResourceKind::GithubInstallation,
+            "42",
+            attributes(&[("account", "octocat")]),
+        );
+
+        let encoded = serde_json::to_string(&snapshot).unwrap();
+        let decoded: Snapshot = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded, snapshot);
+        assert_eq!(
+            decoded.get(ResourceKind::GithubInstallation, "42").unwrap()["account"],
+            "octocat"
+        );
+    }
+}
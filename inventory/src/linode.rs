@@ -0,0 +1,52 @@
+//! Linode instances and domains.
+
+use futures::TryStreamExt as _;
+use linode::LinodeClient;
+
+use crate::{Attributes, InventoryError, ResourceKind, Snapshot};
+
+pub(crate) async fn collect(
+    client: &LinodeClient,
+    snapshot: &mut Snapshot,
+) -> Result<(), InventoryError> {
+    let instances: Vec<_> = client
+        .list_lindoe_instances()
+        .await
+        .try_collect()
+        .await
+        .map_err(InventoryError::collect(ResourceKind::LinodeInstance))?;
+
+    for instance in instances {
+        let mut attributes = Attributes::new();
+        attributes.insert("label".into(), instance.label().into());
+        attributes.insert("status".into(), format!("{:?}", instance.status()));
+        attributes.insert("image".into(), instance.image().into());
+        attributes.insert("ipv4".into(), instance.ipv4().to_string());
+        if let Some(ipv6) = instance.ipv6() {
+            attributes.insert("ipv6".into(), ipv6.to_string());
+        }
+        snapshot.insert(
+            ResourceKind::LinodeInstance,
+            instance.id().to_string(),
+            attributes,
+        );
+    }
+
+    let domains: Vec<_> = client
+        .list_linode_domains()
+        .try_collect()
+        .await
+        .map_err(InventoryError::collect(ResourceKind::LinodeDomain))?;
+
+    for domain in domains {
+        let mut attributes = Attributes::new();
+        attributes.insert("domain".into(), domain.domain().into());
+        snapshot.insert(
+            ResourceKind::LinodeDomain,
+            domain.id().to_string(),
+            attributes,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! Github App installations.
+
+use futures::TryStreamExt as _;
+use octocat::GithubApp;
+
+use crate::{Attributes, InventoryError, ResourceKind, Snapshot};
+
+pub(crate) async fn collect(
+    app: &GithubApp,
+    snapshot: &mut Snapshot,
+) -> Result<(), InventoryError> {
+    let installations: Vec<_> = app
+        .installations()
+        .try_collect()
+        .await
+        .map_err(InventoryError::collect(ResourceKind::GithubInstallation))?;
+
+    for installation in installations {
+        let mut attributes = Attributes::new();
+        attributes.insert("account".into(), installation.account.login.clone());
+        for (permission, level) in &installation.permissions {
+            attributes.insert(format!("permission.{permission}"), format!("{level:?}"));
+        }
+        snapshot.insert(
+            ResourceKind::GithubInstallation,
+            installation.id.to_string(),
+            attributes,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,32 @@
+//! Devices on a tailnet.
+
+use tailscale::TailscaleClient;
+
+use crate::{Attributes, InventoryError, ResourceKind, Snapshot};
+
+pub(crate) async fn collect(
+    client: &TailscaleClient,
+    snapshot: &mut Snapshot,
+) -> Result<(), InventoryError> {
+    let devices = client
+        .devices()
+        .await
+        .map_err(InventoryError::collect(ResourceKind::TailscaleDevice))?;
+
+    for device in devices {
+        let mut attributes = Attributes::new();
+        attributes.insert("name".into(), device.name.clone());
+        let addresses: Vec<_> = device.addresses.iter().map(ToString::to_string).collect();
+        attributes.insert("addresses".into(), addresses.join(","));
+
+        // Older API responses don't include the ID, so fall back to the name.
+        let id = if device.id.is_empty() {
+            device.name
+        } else {
+            device.id
+        };
+        snapshot.insert(ResourceKind::TailscaleDevice, id, attributes);
+    }
+
+    Ok(())
+}
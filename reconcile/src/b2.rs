@@ -0,0 +1,169 @@
+//! B2 bucket settings.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use b2_client::{B2Client, B2RequestError, Bucket, BucketType, BucketUpdate, CorsRule};
+
+use crate::{Plan, Reconcile};
+
+/// Settings a bucket should have. Settings which aren't set are left alone.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BucketSettings {
+    bucket_type: Option<BucketType>,
+    info: Option<HashMap<String, String>>,
+    cors_rules: Option<Vec<CorsRule>>,
+}
+
+impl BucketSettings {
+    /// Settings which leave the bucket alone.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The bucket should have this access level.
+    pub fn bucket_type(mut self, bucket_type: BucketType) -> Self {
+        self.bucket_type = Some(bucket_type);
+        self
+    }
+
+    /// The bucket should have exactly these info key-value pairs.
+    pub fn info(mut self, info: HashMap<String, String>) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// The bucket should have exactly these CORS rules, in this order.
+    pub fn cors_rules(mut self, rules: Vec<CorsRule>) -> Self {
+        self.cors_rules = Some(rules);
+        self
+    }
+}
+
+/// A change to a bucket setting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BucketChange {
+    /// Change the access level.
+    Type(BucketType),
+
+    /// Replace the info key-value pairs.
+    Info(HashMap<String, String>),
+
+    /// Replace the CORS rules.
+    CorsRules(Vec<CorsRule>),
+}
+
+impl fmt::Display for BucketChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BucketChange::Type(bucket_type) => write!(f, "~ type = {bucket_type:?}"),
+            BucketChange::Info(info) => write!(f, "~ info = {} keys", info.len()),
+            BucketChange::CorsRules(rules) => write!(f, "~ cors rules = {} rules", rules.len()),
+        }
+    }
+}
+
+/// The settings of a single B2 bucket.
+#[derive(Debug, Clone)]
+pub struct BucketResource {
+    client: B2Client,
+    bucket: String,
+}
+
+impl BucketResource {
+    /// Manage the settings of a bucket, by name.
+    pub fn new(client: B2Client, bucket: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Reconcile for BucketResource {
+    type Desired = BucketSettings;
+    type Current = Bucket;
+    type Change = BucketChange;
+    type Error = Arc<B2RequestError>;
+
+    async fn current(&self) -> Result<Bucket, Self::Error> {
+        self.client.get_bucket(&self.bucket).await
+    }
+
+    fn plan(&self, desired: &BucketSettings, current: &Bucket) -> Plan<BucketChange> {
+        plan(
+            desired,
+            current.kind(),
+            current.info(),
+            current.cors_rules(),
+        )
+    }
+
+    async fn apply(&self, change: &BucketChange) -> Result<(), Self::Error> {
+        let update = match change {
+            BucketChange::Type(bucket_type) => BucketUpdate::new().bucket_type(*bucket_type),
+            BucketChange::Info(info) => BucketUpdate::new().bucket_info(info.clone()),
+            BucketChange::CorsRules(rules) => BucketUpdate::new().cors_rules(rules.clone()),
+        };
+
+        self.client
+            .update_bucket(&self.bucket, update)
+            .await
+            .map_err(Arc::new)?;
+        Ok(())
+    }
+}
+
+fn plan(
+    desired: &BucketSettings,
+    bucket_type: &BucketType,
+    info: &HashMap<String, String>,
+    cors_rules: &[CorsRule],
+) -> Plan<BucketChange> {
+    let mut plan = Plan::new();
+
+    if let Some(wanted) = desired.bucket_type.filter(|wanted| wanted != bucket_type) {
+        plan.push(BucketChange::Type(wanted));
+    }
+
+    if let Some(wanted) = desired.info.as_ref().filter(|wanted| *wanted != info) {
+        plan.push(BucketChange::Info(wanted.clone()));
+    }
+
+    if let Some(wanted) = desired
+        .cors_rules
+        .as_ref()
+        .filter(|wanted| wanted.as_slice() != cors_rules)
+    {
+        plan.push(BucketChange::CorsRules(wanted.clone()));
+    }
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_bucket_settings() {
+        let info: HashMap<_, _> = [("owner".to_owned(), "ops".to_owned())].into();
+
+        let desired = BucketSettings::new()
+            .bucket_type(BucketType::AllPrivate)
+            .info(info.clone())
+            .cors_rules(Vec::new());
+        assert!(plan(&desired, &BucketType::AllPrivate, &info, &[]).is_empty());
+
+        let plan = plan(&desired, &BucketType::AllPublic, &HashMap::new(), &[]);
+        assert_eq!(
+            plan.into_iter().collect::<Vec<_>>(),
+            vec![
+                BucketChange::Type(BucketType::AllPrivate),
+                BucketChange::Info(info)
+            ]
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! Linode DNS records.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use futures::TryStreamExt as _;
+use linode::{Domain, LinodeClient, LinodeError, Record, RecordType, SubDomain};
+
+use crate::{Plan, Reconcile};
+
+/// A DNS record which should exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesiredRecord {
+    /// The record type.
+    pub kind: RecordType,
+
+    /// The name of the record within the domain.
+    pub name: SubDomain,
+
+    /// The record target, e.g. an IP address.
+    pub target: String,
+}
+
+impl DesiredRecord {
+    /// A record which should exist.
+    pub fn new(kind: RecordType, name: impl Into<SubDomain>, target: impl Into<String>) -> Self {
+        Self {
+            kind,
+            name: name.into(),
+            target: target.into(),
+        }
+    }
+}
+
+/// Records in a domain, as fetched from Linode.
+#[derive(Debug, Clone)]
+pub struct DnsState {
+    /// The domain.
+    pub domain: Domain,
+
+    /// Every record in the domain.
+    pub records: Vec<Record>,
+}
+
+/// A change to a DNS record.
+#[derive(Debug, Clone)]
+pub enum DnsChange {
+    /// Create a new record.
+    Create {
+        /// The domain to create the record in.
+        domain: Domain,
+
+        /// The record to create.
+        record: DesiredRecord,
+    },
+
+    /// Point an existing record at a new target.
+    Update {
+        /// The record to update.
+        record: Record,
+
+        /// The new target.
+        target: String,
+    },
+
+    /// Delete a record.
+    Delete {
+        /// The record to delete.
+        record: Record,
+    },
+}
+
+impl fmt::Display for DnsChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsChange::Create { record, .. } => {
+                write!(f, "+ {} {} {}", record.kind, record.name, record.target)
+            }
+            DnsChange::Update { record, target } => write!(
+                f,
+                "~ {} {} {} -> {target}",
+                record.r#type(),
+                record.subdomain(),
+                record.target()
+            ),
+            DnsChange::Delete { record } => write!(
+                f,
+                "- {} {} {}",
+                record.r#type(),
+                record.subdomain(),
+                record.target()
+            ),
+        }
+    }
+}
+
+/// The records in a single Linode domain.
+///
+/// Only the names and types which appear in the desired records are managed,
+/// unless [`DnsRecords::with_prune`] is set, in which case every other record in
+/// the domain is deleted.
+#[derive(Debug, Clone)]
+pub struct DnsRecords {
+    client: LinodeClient,
+    domain: String,
+    ttl: Option<Duration>,
+    prune: bool,
+}
+
+impl DnsRecords {
+    /// Manage records in a domain, e.g. `example.com`.
+    pub fn new(client: LinodeClient, domain: impl Into<String>) -> Self {
+        Self {
+            client,
+            domain: domain.into(),
+            ttl: None,
+            prune: false,
+        }
+    }
+
+    /// Set this TTL when updating records.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Delete records whose name and type don't appear in the desired records.
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Reconcile for DnsRecords {
+    type Desired = Vec<DesiredRecord>;
+    type Current = DnsState;
+    type Change = DnsChange;
+    type Error = LinodeError;
+
+    async fn current(&self) -> Result<DnsState, LinodeError> {
+        let domain = self
+            .client
+            .get_linode_domain(&self.domain)
+            .await?
+            .ok_or_else(|| LinodeError::NotFound {
+                kind: "domain",
+                value: self.domain.clone(),
+            })?;
+
+        let records = self
+            .client
+            .list_linode_domain_records(&domain)
+            .try_collect()
+            .await?;
+
+        Ok(DnsState { domain, records })
+    }
+
+    fn plan(&self, desired: &Vec<DesiredRecord>, current: &DnsState) -> Plan<DnsChange> {
+        let wanted: Vec<_> = desired
+            .iter()
+            .map(|record| (record.kind, record.name.to_string(), record.target.as_str()))
+            .collect();
+        let existing: Vec<_> = current
+            .records
+            .iter()
+            .map(|record| {
+                (
+                    *record.r#type(),
+                    record.subdomain().to_string(),
+                    record.target(),
+                )
+            })
+            .collect();
+
+        diff(&wanted, &existing, self.prune)
+            .into_iter()
+            .map(|op| match op {
+                Op::Create(idx) => DnsChange::Create {
+                    domain: current.domain.clone(),
+                    record: desired[idx].clone(),
+                },
+                Op::Update(idx, target) => DnsChange::Update {
+                    record: current.records[idx].clone(),
+                    target: desired[target].target.clone(),
+                },
+                Op::Delete(idx) => DnsChange::Delete {
+                    record: current.records[idx].clone(),
+                },
+            })
+            .collect()
+    }
+
+    async fn apply(&self, change: &DnsChange) -> Result<(), LinodeError> {
+        match change {
+            DnsChange::Create { domain, record } => {
+                self.client
+                    .create_linode_domain_record(domain, &record.kind, &record.name, &record.target)
+                    .await?;
+            }
+            DnsChange::Update { record, target } => match self.ttl {
+                Some(ttl) => {
+                    self.client
+                        .set_linode_domain_record_with_ttl(
+                            &record.id(),
+                            record.r#type(),
+                            &record.subdomain(),
+                            target,
+                            ttl,
+                        )
+                        .await?
+                }
+                None => {
+                    self.client
+                        .set_linode_domain_record(
+                            &record.id(),
+                            record.r#type(),
+                            &record.subdomain(),
+                            target,
+                        )
+                        .await?
+                }
+            },
+            DnsChange::Delete { record } => {
+                self.client
+                    .delete_linode_domain_record(&record.id())
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A change, by index into the desired and existing records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// Create the desired record.
+    Create(usize),
+
+    /// Point the existing record at the target of the desired record.
+    Update(usize, usize),
+
+    /// Delete the existing record.
+    Delete(usize),
+}
+
+type Key<'r> = (RecordType, String, &'r str);
+
+/// Match desired records to existing ones with the same name and type.
+///
+/// Records which already have a desired target are left alone, and existing
+/// records with other targets are updated in preference to creating new ones.
+fn diff(desired: &[Key<'_>], existing: &[Key<'_>], prune: bool) -> Vec<Op> {
+    let mut groups: BTreeMap<(RecordType, &str), (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+    for (idx, (kind, name, target)) in desired.iter().enumerate() {
+        let (wanted, _) = groups.entry((*kind, name.as_str())).or_default();
+        if !wanted.iter().any(|&other| desired[other].2 == *target) {
+            wanted.push(idx);
+        }
+    }
+
+    let mut unmanaged = Vec::new();
+    for (idx, (kind, name, _)) in existing.iter().enumerate() {
+        match groups.get_mut(&(*kind, name.as_str())) {
+            Some((_, found)) => found.push(idx),
+            None => unmanaged.push(idx),
+        }
+    }
+
+    let mut ops = Vec::new();
+    for (wanted, found) in groups.values() {
+        let missing: Vec<_> = wanted
+            .iter()
+            .filter(|&&idx| {
+                !found
+                    .iter()
+                    .any(|&other| existing[other].2 == desired[idx].2)
+            })
+            .copied()
+            .collect();
+        let stale: Vec<_> = found
+            .iter()
+            .filter(|&&idx| {
+                !wanted
+                    .iter()
+                    .any(|&other| desired[other].2 == existing[idx].2)
+            })
+            .copied()
+            .collect();
+
+        let mut missing = missing.into_iter();
+        for idx in stale {
+            match missing.next() {
+                Some(target) => ops.push(Op::Update(idx, target)),
+                None => ops.push(Op::Delete(idx)),
+            }
+        }
+        ops.extend(missing.map(Op::Create));
+    }
+
+    if prune {
+        ops.extend(unmanaged.into_iter().map(Op::Delete));
+    }
+
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(kind: RecordType, name: &str, target: &'static str) -> Key<'static> {
+        (kind, name.to_owned(), target)
+    }
+
+    #[test]
+    fn diff_records() {
+        let desired = [
+            key(RecordType::A, "www", "192.0.2.1"),
+            key(RecordType::A, "www", "192.0.2.2"),
+            key(RecordType::AAAA, "www", "2001:db8::1"),
+            key(RecordType::TXT, "@", "v=spf1 -all"),
+        ];
+        let existing = [
+            key(RecordType::A, "www", "192.0.2.1"),
+            key(RecordType::A, "www", "192.0.2.9"),
+            key(RecordType::AAAA, "www", "2001:db8::1"),
+            key(RecordType::AAAA, "www", "2001:db8::9"),
+            key(RecordType::CNAME, "blog", "example.net"),
+        ];
+
+        assert_eq!(
+            diff(&desired, &existing, false),
+            vec![Op::Update(1, 1), Op::Delete(3), Op::Create(3)]
+        );
+        assert_eq!(
+            diff(&desired, &existing, true),
+            vec![
+                Op::Update(1, 1),
+                Op::Delete(3),
+                Op::Create(3),
+                Op::Delete(4)
+            ]
+        );
+        assert!(diff(&existing, &existing, true).is_empty());
+    }
+}
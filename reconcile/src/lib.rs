@@ -0,0 +1,158 @@
+//! Declarative reconciliation of service resources with a desired state.
+//!
+//! A [`Reconcile`] implementation knows how to fetch the current state of a
+//! resource, compute a [`Plan`] of changes which would bring it to a desired
+//! state, and apply each change. [`Reconcile::reconcile`] runs those steps in
+//! order, and in [`Mode::DryRun`] stops after planning, so the same desired
+//! state can be previewed and then applied.
+//!
+//! Implementations for each service are only available when the feature of the
+//! same name is enabled.
+
+use std::error::Error as StdError;
+use std::fmt;
+
+#[cfg(feature = "b2")]
+pub mod b2;
+#[cfg(feature = "linode")]
+pub mod dns;
+
+/// Whether [`Reconcile::reconcile`] applies the changes it plans.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Plan changes without applying them.
+    DryRun,
+
+    /// Plan changes and apply them.
+    #[default]
+    Apply,
+}
+
+/// An ordered list of changes, which will bring a resource to its desired state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan<C> {
+    changes: Vec<C>,
+}
+
+impl<C> Default for Plan<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Plan<C> {
+    /// Create a plan with no changes.
+    pub fn new() -> Self {
+        Self {
+            changes: Vec::new(),
+        }
+    }
+
+    /// Add a change to the end of the plan.
+    pub fn push(&mut self, change: C) {
+        self.changes.push(change);
+    }
+
+    /// Number of changes in the plan.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Check if the resource is already in its desired state.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Iterate over the changes, in the order they will be applied.
+    pub fn iter(&self) -> std::slice::Iter<'_, C> {
+        self.changes.iter()
+    }
+}
+
+impl<C> FromIterator<C> for Plan<C> {
+    fn from_iter<T: IntoIterator<Item = C>>(iter: T) -> Self {
+        Self {
+            changes: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<C> IntoIterator for Plan<C> {
+    type Item = C;
+    type IntoIter = std::vec::IntoIter<C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.into_iter()
+    }
+}
+
+impl<'p, C> IntoIterator for &'p Plan<C> {
+    type Item = &'p C;
+    type IntoIter = std::slice::Iter<'p, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.changes.iter()
+    }
+}
+
+impl<C: fmt::Display> fmt::Display for Plan<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("No changes");
+        }
+
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A resource which can be brought to a desired state.
+#[async_trait::async_trait]
+pub trait Reconcile: Send + Sync {
+    /// The state the resource should be in.
+    type Desired: Send + Sync;
+
+    /// The state the resource is in, as fetched from the service.
+    type Current: Send + Sync;
+
+    /// A single change to the resource.
+    type Change: fmt::Display + Send + Sync;
+
+    /// Errors from fetching or changing the resource.
+    type Error: StdError + Send + Sync + 'static;
+
+    /// Fetch the current state of the resource.
+    async fn current(&self) -> Result<Self::Current, Self::Error>;
+
+    /// Compute the changes which bring the current state to the desired state.
+    fn plan(&self, desired: &Self::Desired, current: &Self::Current) -> Plan<Self::Change>;
+
+    /// Apply a single change.
+    async fn apply(&self, change: &Self::Change) -> Result<(), Self::Error>;
+
+    /// Fetch the current state, plan changes, and apply them unless this is a dry run.
+    ///
+    /// Returns the plan, so that callers can report what was (or would be) changed.
+    async fn reconcile(
+        &self,
+        desired: &Self::Desired,
+        mode: Mode,
+    ) -> Result<Plan<Self::Change>, Self::Error> {
+        let current = self.current().await?;
+        let plan = self.plan(desired, &current);
+
+        for change in &plan {
+            match mode {
+                Mode::DryRun => tracing::info!(%change, "Would apply change"),
+                Mode::Apply => {
+                    tracing::info!(%change, "Applying change");
+                    self.apply(change).await?;
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+}
@@ -0,0 +1,94 @@
+//! Diagnostics for Backblaze B2 errors.
+
+use std::error::Error as StdError;
+
+use b2_client::{B2Error, B2RequestError};
+use http::StatusCode;
+
+use crate::{http_status, no_response, Category, Diagnostic};
+
+pub(crate) fn classify(error: &(dyn StdError + 'static)) -> Option<Diagnostic> {
+    if let Some(error) = error.downcast_ref::<B2RequestError>() {
+        return request(error);
+    }
+    error.downcast_ref::<B2Error>().map(response)
+}
+
+fn request(error: &B2RequestError) -> Option<Diagnostic> {
+    match error {
+        B2RequestError::B2(error) => Some(response(error)),
+        B2RequestError::NoCredentials(bucket) => Some(
+            Diagnostic::new(
+                Category::Config,
+                format!("No B2 credentials for bucket {bucket}"),
+            )
+            .with_hint("add the bucket to one of the accounts in the B2 configuration"),
+        ),
+        B2RequestError::BucketNotFound(bucket) => Some(
+            Diagnostic::new(Category::NotFound, format!("B2 bucket {bucket} not found"))
+                .with_hint("check the bucket name, and that the B2 key is allowed to access it"),
+        ),
+        B2RequestError::Serde(_, _) => Some(Diagnostic::new(
+            Category::Data,
+            "B2 sent a response which could not be decoded",
+        )),
+        B2RequestError::Body(_) => Some(no_response("B2")),
+        B2RequestError::RetriesExhausted => Some(
+            Diagnostic::new(Category::Unavailable, "B2 requests kept failing")
+                .with_hint("try again later"),
+        ),
+        B2RequestError::Io(_) | B2RequestError::Client(_) => None,
+    }
+}
+
+fn response(error: &B2Error) -> Diagnostic {
+    let diagnostic = http_status("B2", error.status_code());
+    match error.status_code() {
+        StatusCode::UNAUTHORIZED => diagnostic
+            .with_hint("check B2_KEY_ID and B2_KEY, or the key in the storage configuration"),
+        StatusCode::FORBIDDEN => diagnostic
+            .with_hint("check that the B2 key has the capabilities and bucket access this needs"),
+        _ => diagnostic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use storage_driver::StorageError;
+
+    use super::*;
+
+    #[test]
+    fn invalid_credentials() {
+        let error: B2Error = serde_json::from_str(
+            r#"{"status": 401, "code": "bad_auth_token", "message": "Invalid authorization token"}"#,
+        )
+        .unwrap();
+        let report =
+            eyre::Report::new(B2RequestError::B2(error)).wrap_err("authenticating b2 client");
+        let report = eyre::Report::new(StorageError::new("B2", report)).wrap_err("listing files");
+
+        let diagnostic = Diagnostic::from_report(&report);
+        assert_eq!(diagnostic.category(), Category::Credentials);
+        assert_eq!(diagnostic.summary(), "B2 credentials invalid");
+        assert!(diagnostic.hint().unwrap().contains("B2_KEY_ID"));
+        assert_eq!(
+            diagnostic.causes(),
+            [
+                "listing files",
+                "Storage error from B2",
+                "authenticating b2 client",
+                "401 Unauthorized: Invalid authorization token (bad_auth_token)"
+            ]
+        );
+        assert_eq!(diagnostic.category().code(), 77);
+    }
+
+    #[test]
+    fn missing_credentials() {
+        let report = eyre::Report::new(B2RequestError::NoCredentials("backups".into()));
+        let diagnostic = Diagnostic::from_report(&report);
+        assert_eq!(diagnostic.category(), Category::Config);
+        assert_eq!(diagnostic.summary(), "No B2 credentials for bucket backups");
+    }
+}
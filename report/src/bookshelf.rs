@@ -0,0 +1,29 @@
+//! Diagnostics for bookshelf errors.
+
+use std::error::Error as StdError;
+
+use bookshelf::Error;
+
+use crate::{Category, Diagnostic};
+
+pub(crate) fn classify(error: &(dyn StdError + 'static)) -> Option<Diagnostic> {
+    match error.downcast_ref::<Error>()? {
+        Error::NotFound(volume) => Some(
+            Diagnostic::new(Category::NotFound, format!("Volume {volume} not found"))
+                .with_hint("check the volume name and the bookshelf prefix"),
+        ),
+        Error::MissingManifest(path) => Some(
+            Diagnostic::new(Category::NotFound, format!("Manifest {path} not found"))
+                .with_hint("only books uploaded with a manifest can be verified"),
+        ),
+        Error::Manifest(_) => Some(Diagnostic::new(
+            Category::Data,
+            "Manifest could not be read or written",
+        )),
+        Error::RestoreLimit { size, .. } => Some(
+            Diagnostic::new(Category::Config, "Book is too large to restore")
+                .with_hint(format!("raise the restore limit to at least {size} bytes")),
+        ),
+        Error::Storage(_) => None,
+    }
+}
@@ -0,0 +1,317 @@
+//! User-facing diagnostics for errors from the emporium crates.
+//!
+//! Binaries built on these crates usually end up holding an [`eyre::Report`]
+//! whose chain has errors from several layers: a bookshelf error, wrapping a
+//! storage error, wrapping a B2 API error. [`Diagnostic::from_report`] walks
+//! that chain, and the first error it recognizes decides the [`Category`] (and so
+//! the exit code), a short summary and a hint for fixing the problem. The rest
+//! of the chain is kept as the causes.
+//!
+//! Errors from each service crate are only recognized when the feature of the
+//! same name is enabled.
+
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::fmt;
+use std::process::ExitCode;
+
+use http::StatusCode;
+
+#[cfg(feature = "b2")]
+mod b2;
+#[cfg(feature = "bookshelf")]
+mod bookshelf;
+#[cfg(feature = "octocat")]
+mod octocat;
+
+/// The kind of problem behind an error, which decides the exit code.
+///
+/// Exit codes follow the conventions from BSD's `sysexits.h`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// The configuration is missing or invalid.
+    Config,
+
+    /// Credentials are missing, invalid or expired.
+    Credentials,
+
+    /// The credentials are valid, but don't allow this operation.
+    Permission,
+
+    /// The requested resource does not exist.
+    NotFound,
+
+    /// A service asked clients to slow down.
+    RateLimited,
+
+    /// A service could not be reached, or failed to handle the request.
+    Unavailable,
+
+    /// Data from storage or a service could not be decoded.
+    Data,
+
+    /// Reading or writing local files failed.
+    Io,
+
+    /// Anything else, which is probably a bug.
+    Internal,
+}
+
+impl Category {
+    /// The process exit code for errors in this category.
+    pub fn code(self) -> u8 {
+        match self {
+            Category::Data => 65,
+            Category::NotFound => 66,
+            Category::Unavailable => 69,
+            Category::Internal => 70,
+            Category::Io => 74,
+            Category::RateLimited => 75,
+            Category::Credentials | Category::Permission => 77,
+            Category::Config => 78,
+        }
+    }
+}
+
+/// An error, described for the person running a binary.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    category: Category,
+    summary: Cow<'static, str>,
+    hint: Option<Cow<'static, str>>,
+    causes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic with a one-line summary of the problem.
+    pub fn new(category: Category, summary: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            category,
+            summary: summary.into(),
+            hint: None,
+            causes: Vec::new(),
+        }
+    }
+
+    /// Suggest how to fix the problem.
+    pub fn with_hint(mut self, hint: impl Into<Cow<'static, str>>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Record an error and its sources as the causes of this diagnostic.
+    pub fn with_causes(mut self, error: &(dyn StdError + 'static)) -> Self {
+        self.causes.extend(
+            chain(error)
+                .map(|error| error.to_string())
+                .filter(|cause| *cause != self.summary),
+        );
+        self
+    }
+
+    /// Diagnose an error, using the first error in its chain which is recognized.
+    ///
+    /// Errors which aren't recognized at all are [`Category::Internal`].
+    pub fn from_error(error: &(dyn StdError + 'static)) -> Self {
+        chain(error)
+            .find_map(classify)
+            .unwrap_or_else(|| Diagnostic::new(Category::Internal, error.to_string()))
+            .with_causes(error)
+    }
+
+    /// Diagnose the errors in a report.
+    pub fn from_report(report: &eyre::Report) -> Self {
+        let error: &(dyn StdError + 'static) = report.as_ref();
+        Self::from_error(error)
+    }
+
+    /// The kind of problem.
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    /// A one-line summary of the problem.
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+
+    /// A suggestion for how to fix the problem, if there is one.
+    pub fn hint(&self) -> Option<&str> {
+        self.hint.as_deref()
+    }
+
+    /// The messages from each error in the chain, outermost first.
+    pub fn causes(&self) -> &[String] {
+        &self.causes
+    }
+
+    /// The exit code for a process which stops because of this problem.
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(self.category.code())
+    }
+}
+
+impl From<eyre::Report> for Diagnostic {
+    fn from(report: eyre::Report) -> Self {
+        Diagnostic::from_report(&report)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.summary)?;
+        for cause in &self.causes {
+            write!(f, "\n  caused by: {cause}")?;
+        }
+        if let Some(hint) = &self.hint {
+            write!(f, "\n  hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+fn chain<'e>(
+    error: &'e (dyn StdError + 'static),
+) -> impl Iterator<Item = &'e (dyn StdError + 'static)> {
+    std::iter::successors(Some(error), |&error| error.source())
+}
+
+/// Diagnose a single error, or return `None` to defer to its source.
+fn classify(error: &(dyn StdError + 'static)) -> Option<Diagnostic> {
+    #[cfg(feature = "bookshelf")]
+    if let Some(diagnostic) = bookshelf::classify(error) {
+        return Some(diagnostic);
+    }
+
+    #[cfg(feature = "b2")]
+    if let Some(diagnostic) = b2::classify(error) {
+        return Some(diagnostic);
+    }
+
+    #[cfg(feature = "octocat")]
+    if let Some(diagnostic) = octocat::classify(error) {
+        return Some(diagnostic);
+    }
+
+    if let Some(error) = error.downcast_ref::<api_client::Error>() {
+        return api(error);
+    }
+
+    if let Some(error) = error.downcast_ref::<std::env::VarError>() {
+        let summary = match error {
+            std::env::VarError::NotPresent => "Missing environment variable",
+            std::env::VarError::NotUnicode(_) => "Environment variable is not valid unicode",
+        };
+        return Some(
+            Diagnostic::new(Category::Config, summary)
+                .with_hint("check that the credentials for the service are set in the environment"),
+        );
+    }
+
+    error.downcast_ref::<std::io::Error>().map(io)
+}
+
+fn api(error: &api_client::Error) -> Option<Diagnostic> {
+    match error {
+        api_client::Error::Request(_) | api_client::Error::ResponseBody(_) => {
+            Some(no_response("API"))
+        }
+        error => error.status().map(|status| http_status("API", status)),
+    }
+}
+
+fn io(error: &std::io::Error) -> Diagnostic {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => Diagnostic::new(Category::NotFound, "File not found"),
+        std::io::ErrorKind::PermissionDenied => {
+            Diagnostic::new(Category::Permission, "Permission denied")
+                .with_hint("check the permissions of the file and its directory")
+        }
+        _ => Diagnostic::new(Category::Io, "Reading or writing a file failed"),
+    }
+}
+
+/// Diagnose a failure to get any response from `service`.
+pub(crate) fn no_response(service: &str) -> Diagnostic {
+    Diagnostic::new(
+        Category::Unavailable,
+        format!("Could not get a response from {service}"),
+    )
+    .with_hint("check the network connection and try again")
+}
+
+/// Diagnose an error response from `service`.
+pub(crate) fn http_status(service: &str, status: StatusCode) -> Diagnostic {
+    match status {
+        StatusCode::UNAUTHORIZED => Diagnostic::new(
+            Category::Credentials,
+            format!("{service} credentials invalid"),
+        ),
+        StatusCode::FORBIDDEN => {
+            Diagnostic::new(Category::Permission, format!("{service} denied access"))
+        }
+        StatusCode::NOT_FOUND => {
+            Diagnostic::new(Category::NotFound, format!("{service} resource not found"))
+        }
+        StatusCode::TOO_MANY_REQUESTS => Diagnostic::new(
+            Category::RateLimited,
+            format!("{service} rate limit exceeded"),
+        )
+        .with_hint("wait a while before trying again"),
+        status if status.is_server_error() => Diagnostic::new(
+            Category::Unavailable,
+            format!("{service} is unavailable ({status})"),
+        )
+        .with_hint("try again later"),
+        status => Diagnostic::new(
+            Category::Internal,
+            format!("{service} rejected the request ({status})"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrecognized_errors() {
+        let report = eyre::eyre!("something broke").wrap_err("doing a thing");
+        let diagnostic = Diagnostic::from(report);
+        assert_eq!(diagnostic.category(), Category::Internal);
+        assert_eq!(diagnostic.summary(), "doing a thing");
+        assert_eq!(diagnostic.causes(), ["something broke"]);
+        assert_eq!(diagnostic.hint(), None);
+        assert_eq!(diagnostic.category().code(), 70);
+    }
+
+    #[test]
+    fn std_errors() {
+        let report = eyre::Report::new(std::env::VarError::NotPresent).wrap_err("reading key");
+        let diagnostic = Diagnostic::from_report(&report);
+        assert_eq!(diagnostic.category(), Category::Config);
+        assert_eq!(diagnostic.causes().len(), 2);
+
+        let error = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        let diagnostic = Diagnostic::from_error(&error);
+        assert_eq!(diagnostic.category(), Category::Permission);
+        assert_eq!(
+            diagnostic.to_string(),
+            "Permission denied\n  caused by: permission denied\n  hint: check the permissions of the file and its directory"
+        );
+    }
+
+    #[test]
+    fn status_categories() {
+        for (status, category) in [
+            (StatusCode::UNAUTHORIZED, Category::Credentials),
+            (StatusCode::FORBIDDEN, Category::Permission),
+            (StatusCode::NOT_FOUND, Category::NotFound),
+            (StatusCode::TOO_MANY_REQUESTS, Category::RateLimited),
+            (StatusCode::SERVICE_UNAVAILABLE, Category::Unavailable),
+            (StatusCode::BAD_REQUEST, Category::Internal),
+        ] {
+            assert_eq!(http_status("API", status).category(), category);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Diagnostics for Github errors.
+
+use std::error::Error as StdError;
+
+use http::StatusCode;
+use octocat::config::AppKeyError;
+use octocat::{Error, ResponseError};
+
+use crate::{http_status, no_response, Category, Diagnostic};
+
+pub(crate) fn classify(error: &(dyn StdError + 'static)) -> Option<Diagnostic> {
+    if let Some(error) = error.downcast_ref::<Error>() {
+        return github(error);
+    }
+
+    if let Some(error) = error.downcast_ref::<ResponseError>() {
+        return Some(response(error));
+    }
+
+    match error.downcast_ref::<AppKeyError>()? {
+        AppKeyError::Storage(_) => None,
+        _ => Some(
+            Diagnostic::new(Category::Config, "Github App key could not be loaded")
+                .with_hint("check the signing key in the Github App configuration"),
+        ),
+    }
+}
+
+fn github(error: &Error) -> Option<Diagnostic> {
+    match error {
+        Error::SecondaryRateLimit { retry_after } => {
+            let hint = match retry_after {
+                Some(delay) => format!("wait {}s before trying again", delay.as_secs()),
+                None => "wait at least a minute before trying again".into(),
+            };
+            Some(
+                Diagnostic::new(
+                    Category::RateLimited,
+                    "Github secondary rate limit exceeded",
+                )
+                .with_hint(hint),
+            )
+        }
+        Error::Response(error) => Some(response(error)),
+        Error::Request(_) | Error::Body(_) => Some(no_response("Github")),
+        Error::Signature(_) => Some(
+            Diagnostic::new(Category::Credentials, "Signing the Github App token failed")
+                .with_hint("check the Github App private key"),
+        ),
+        Error::Serde(_) => Some(Diagnostic::new(
+            Category::Data,
+            "Github sent a response which could not be decoded",
+        )),
+        Error::IO(_) | Error::Lfs(_) | Error::OsEncoding(_) | Error::Client(_) => None,
+    }
+}
+
+fn response(error: &ResponseError) -> Diagnostic {
+    let diagnostic = http_status("Github", error.status());
+    match error.status() {
+        StatusCode::UNAUTHORIZED => diagnostic.with_hint("check the Github App id and private key"),
+        StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => diagnostic
+            .with_hint("check that the Github App is installed with access to the repository"),
+        _ => diagnostic,
+    }
+}
@@ -0,0 +1,310 @@
+//! Cron expressions.
+//!
+//! Expressions use the standard five fields: minute, hour, day of month, month and
+//! day of week. Each field accepts `*`, single values, ranges (`1-5`), lists
+//! (`1,15`) and steps (`*/10`, `0-30/5`). Months and weekdays also accept
+//! three-letter names (`jan`, `mon`). As in most cron implementations, when both
+//! day fields are restricted a time matches if either of them does.
+//!
+//! All times are evaluated in UTC.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike as _, NaiveDate, NaiveTime, TimeZone as _, Timelike as _, Utc};
+use thiserror::Error;
+
+/// How far ahead to search for a matching time before giving up, e.g. for `0 0 31 2 *`.
+const SEARCH_LIMIT_DAYS: i64 = 366 * 5;
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// An error parsing a cron expression.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CronError {
+    /// The expression did not have five fields.
+    #[error("expected 5 fields in cron expression, found {0}")]
+    FieldCount(usize),
+
+    /// A field could not be parsed.
+    #[error("invalid {field} field: {value:?}")]
+    InvalidField {
+        /// The name of the field.
+        field: &'static str,
+
+        /// The text of the field.
+        value: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    names: &[],
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    names: &[],
+};
+const DAY: Field = Field {
+    name: "day of month",
+    min: 1,
+    max: 31,
+    names: &[],
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: &MONTHS,
+};
+// 7 is accepted as an alias for Sunday.
+const WEEKDAY: Field = Field {
+    name: "day of week",
+    min: 0,
+    max: 7,
+    names: &WEEKDAYS,
+};
+
+impl Field {
+    fn error(&self, value: &str) -> CronError {
+        CronError::InvalidField {
+            field: self.name,
+            value: value.to_owned(),
+        }
+    }
+
+    fn value(&self, text: &str) -> Option<u32> {
+        let value = match self
+            .names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(text))
+        {
+            Some(index) if self.min == 1 => index as u32 + 1,
+            Some(index) => index as u32,
+            None => text.parse().ok()?,
+        };
+        (self.min..=self.max).contains(&value).then_some(value)
+    }
+
+    /// Parse a field into a bitset of matching values.
+    fn parse(&self, text: &str) -> Result<u64, CronError> {
+        let mut bits = 0u64;
+        for item in text.split(',') {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step.parse().map_err(|_| self.error(text))?;
+                    if step == 0 {
+                        return Err(self.error(text));
+                    }
+                    (range, step)
+                }
+                None => (item, 1),
+            };
+
+            let (start, end) = if range == "*" {
+                (self.min, self.max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                let start = self.value(start).ok_or_else(|| self.error(text))?;
+                let end = self.value(end).ok_or_else(|| self.error(text))?;
+                if start > end {
+                    return Err(self.error(text));
+                }
+                (start, end)
+            } else {
+                let start = self.value(range).ok_or_else(|| self.error(text))?;
+                // `5/15` means "every 15, starting at 5".
+                let end = if step > 1 { self.max } else { start };
+                (start, end)
+            };
+
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(bits)
+    }
+}
+
+fn contains(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// A parsed cron expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parse a cron expression.
+    pub fn parse(expr: &str) -> Result<Self, CronError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CronError::FieldCount(fields.len()));
+        };
+
+        let mut weekdays = WEEKDAY.parse(weekday)?;
+        if contains(weekdays, 7) {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            source: fields.join(" "),
+            minutes: MINUTE.parse(minute)?,
+            hours: HOUR.parse(hour)?,
+            days: DAY.parse(day)?,
+            months: MONTH.parse(month)?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = contains(self.days, date.day());
+        let weekday = contains(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    /// The first matching time strictly after `after`, if there is one.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after
+            .naive_utc()
+            .with_second(0)?
+            .with_nanosecond(0)?
+            .checked_add_signed(chrono::Duration::minutes(1))?;
+        let limit = start.checked_add_signed(chrono::Duration::days(SEARCH_LIMIT_DAYS))?;
+
+        let mut time = start;
+        while time < limit {
+            let date = time.date();
+            if !contains(self.months, date.month()) {
+                let (year, month) = match date.month() {
+                    12 => (date.year() + 1, 1),
+                    month => (date.year(), month + 1),
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_time(NaiveTime::MIN);
+            } else if !self.matches_day(date) {
+                time = date.succ_opt()?.and_time(NaiveTime::MIN);
+            } else if !contains(self.hours, time.hour()) {
+                time = time.with_minute(0)? + chrono::Duration::hours(1);
+            } else if !contains(self.minutes, time.minute()) {
+                time += chrono::Duration::minutes(1);
+            } else {
+                return Some(Utc.from_utc_datetime(&time));
+            }
+        }
+
+        None
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn next(expr: &str, after: &str) -> Option<DateTime<Utc>> {
+        CronSchedule::parse(expr).unwrap().next_after(at(after))
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            CronSchedule::parse("* * *").unwrap_err(),
+            CronError::FieldCount(3)
+        );
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+        assert!(CronSchedule::parse("* * * foo *").is_err());
+    }
+
+    #[test]
+    fn next_times() {
+        assert_eq!(
+            next("*/15 * * * *", "2024-01-01T00:07:30Z"),
+            Some(at("2024-01-01T00:15:00Z"))
+        );
+        assert_eq!(
+            next("0 3 * * *", "2024-01-01T03:00:00Z"),
+            Some(at("2024-01-02T03:00:00Z"))
+        );
+        assert_eq!(
+            next("30 9 * * mon-fri", "2024-01-05T10:00:00Z"),
+            Some(at("2024-01-08T09:30:00Z"))
+        );
+        assert_eq!(
+            next("0 0 1 jan *", "2024-06-01T00:00:00Z"),
+            Some(at("2025-01-01T00:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2024-03-01T00:00:00Z"),
+            Some(at("2028-02-29T00:00:00Z"))
+        );
+        assert_eq!(next("0 0 31 2 *", "2024-01-01T00:00:00Z"), None);
+    }
+
+    #[test]
+    fn day_fields_are_either() {
+        // The 13th of the month, or any Friday.
+        assert_eq!(
+            next("0 0 13 * 5", "2024-01-01T00:00:00Z"),
+            Some(at("2024-01-05T00:00:00Z"))
+        );
+        assert_eq!(
+            next("0 0 13 * 5", "2024-01-12T00:00:00Z"),
+            Some(at("2024-01-13T00:00:00Z"))
+        );
+
+        // 7 is Sunday.
+        assert_eq!(
+            next("0 12 * * 7", "2024-01-01T00:00:00Z"),
+            Some(at("2024-01-07T12:00:00Z"))
+        );
+    }
+}
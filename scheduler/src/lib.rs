@@ -0,0 +1,450 @@
+//! A small scheduler for periodic async jobs.
+//!
+//! Jobs run on a [`Schedule`], either a cron expression or a fixed interval, with
+//! optional random jitter so that many instances don't all fire at once. A job
+//! never overlaps with itself: if the previous run is still active when the next
+//! one is due, that run is skipped.
+
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::Instrument as _;
+
+mod cron;
+
+pub use cron::{CronError, CronSchedule};
+
+/// Errors returned by jobs.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An error parsing a schedule.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ScheduleError {
+    /// The cron expression was invalid.
+    #[error(transparent)]
+    Cron(#[from] CronError),
+
+    /// The `@every` interval was invalid.
+    #[error("invalid interval: {0:?}")]
+    Interval(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Timing {
+    Cron(CronSchedule),
+    Interval(Duration),
+}
+
+/// When a job should run.
+///
+/// Schedules can be parsed from a cron expression, one of the shorthands
+/// `@hourly`, `@daily`, `@weekly`, `@monthly` or `@yearly`, or an interval
+/// written as `@every 30s` (with units `s`, `m`, `h` or `d`).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct Schedule {
+    timing: Timing,
+    jitter: Duration,
+}
+
+impl Schedule {
+    /// Run on a cron expression.
+    pub fn cron(expr: &str) -> Result<Self, CronError> {
+        Ok(Self {
+            timing: Timing::Cron(CronSchedule::parse(expr)?),
+            jitter: Duration::ZERO,
+        })
+    }
+
+    /// Run at a fixed interval.
+    pub fn every(period: Duration) -> Self {
+        Self {
+            timing: Timing::Interval(period),
+            jitter: Duration::ZERO,
+        }
+    }
+
+    /// Delay each run by a random amount, up to `jitter`.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The next time to run after `after`, including jitter.
+    ///
+    /// Returns `None` if the schedule will never run again.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next = match &self.timing {
+            Timing::Cron(cron) => cron.next_after(after)?,
+            Timing::Interval(period) => after + chrono::Duration::from_std(*period).ok()?,
+        };
+
+        if self.jitter.is_zero() {
+            return Some(next);
+        }
+
+        let jitter = Duration::from_millis(fastrand::u64(..=self.jitter.as_millis() as u64));
+        Some(next + chrono::Duration::from_std(jitter).ok()?)
+    }
+}
+
+fn parse_interval(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = text.split_at(split);
+    let value: u64 = value.parse().ok()?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return None,
+    };
+    Some(Duration::from_secs(value * seconds)).filter(|period| !period.is_zero())
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expr = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => match other.strip_prefix("@every") {
+                Some(interval) => {
+                    return parse_interval(interval)
+                        .map(Schedule::every)
+                        .ok_or_else(|| ScheduleError::Interval(interval.trim().to_owned()))
+                }
+                None => other,
+            },
+        };
+
+        Ok(Schedule::cron(expr)?)
+    }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = ScheduleError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.timing {
+            Timing::Cron(cron) => write!(f, "{cron}")?,
+            Timing::Interval(period) => write!(f, "@every {}s", period.as_secs())?,
+        }
+        if !self.jitter.is_zero() {
+            write!(f, " (jitter {:?})", self.jitter)?;
+        }
+        Ok(())
+    }
+}
+
+/// A job which can be run by the [`Scheduler`].
+#[async_trait::async_trait]
+pub trait Job: Send + Sync + 'static {
+    /// The name of the job, used in logs.
+    fn name(&self) -> &str;
+
+    /// Run the job once.
+    async fn run(&self) -> Result<(), BoxError>;
+}
+
+/// A [`Job`] which calls an async function.
+pub struct JobFn<F> {
+    name: String,
+    func: F,
+}
+
+impl<F> fmt::Debug for JobFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobFn").field("name", &self.name).finish()
+    }
+}
+
+/// Create a job from an async function.
+pub fn job_fn<F, Fut>(name: impl Into<String>, func: F) -> JobFn<F>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), BoxError>> + Send + 'static,
+{
+    JobFn {
+        name: name.into(),
+        func,
+    }
+}
+
+#[async_trait::async_trait]
+impl<F, Fut> Job for JobFn<F>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), BoxError>> + Send + 'static,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn run(&self) -> Result<(), BoxError> {
+        (self.func)().await
+    }
+}
+
+/// Clears the running flag when a run ends, even if the job panics.
+struct RunningGuard(Arc<AtomicBool>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[derive(Clone)]
+struct Runner {
+    job: Arc<dyn Job>,
+    running: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for Runner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Runner")
+            .field("job", &self.job.name())
+            .field("running", &self.running)
+            .finish()
+    }
+}
+
+impl Runner {
+    /// Start a run of the job, unless the previous run is still active.
+    fn trigger(&self) -> Option<JoinHandle<()>> {
+        let name = self.job.name().to_owned();
+        if self.running.swap(true, Ordering::AcqRel) {
+            tracing::warn!(job = %name, "Previous run is still active, skipping");
+            return None;
+        }
+
+        let guard = RunningGuard(self.running.clone());
+        let job = self.job.clone();
+        let span = tracing::info_span!("job", name = %name);
+        Some(tokio::spawn(
+            async move {
+                let _guard = guard;
+                let started = Instant::now();
+                tracing::debug!("Starting job");
+                match job.run().await {
+                    Ok(()) => tracing::info!(elapsed = ?started.elapsed(), "Job finished"),
+                    Err(error) => {
+                        tracing::error!(elapsed = ?started.elapsed(), "Job failed: {error}")
+                    }
+                }
+            }
+            .instrument(span),
+        ))
+    }
+
+    /// Run the job on its schedule until shutdown, then wait for any active run.
+    async fn drive(self, schedule: Schedule, mut shutdown: watch::Receiver<bool>) {
+        let mut active = None;
+        loop {
+            let now = Utc::now();
+            let Some(next) = schedule.next_after(now) else {
+                tracing::debug!(job = %self.job.name(), "Schedule has no more runs");
+                break;
+            };
+            tracing::trace!(job = %self.job.name(), %next, "Next run scheduled");
+
+            let delay = (next - now).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown.changed() => break,
+            }
+
+            if let Some(handle) = self.trigger() {
+                active = Some(handle);
+            }
+        }
+
+        if let Some(handle) = active {
+            let _ = handle.await;
+        }
+    }
+}
+
+/// Runs jobs on their schedules.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    entries: Vec<(Schedule, Runner)>,
+}
+
+impl Scheduler {
+    /// Create an empty scheduler.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a job to run on a schedule.
+    pub fn add<J: Job>(&mut self, schedule: Schedule, job: J) -> &mut Self {
+        tracing::debug!(job = %job.name(), %schedule, "Scheduled job");
+        self.entries.push((
+            schedule,
+            Runner {
+                job: Arc::new(job),
+                running: Arc::new(AtomicBool::new(false)),
+            },
+        ));
+        self
+    }
+
+    /// Number of scheduled jobs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if no jobs are scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Run all jobs until `shutdown` completes.
+    ///
+    /// No new runs start after shutdown, but runs which are already active are
+    /// allowed to finish before this returns.
+    pub async fn run_until<F>(self, shutdown: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let (tx, rx) = watch::channel(false);
+        let tasks: Vec<_> = self
+            .entries
+            .into_iter()
+            .map(|(schedule, runner)| tokio::spawn(runner.drive(schedule, rx.clone())))
+            .collect();
+
+        shutdown.await;
+        tracing::debug!("Shutting down scheduler");
+        let _ = tx.send(true);
+
+        for task in tasks {
+            if let Err(error) = task.await {
+                tracing::error!("Scheduler task failed: {error}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    #[test]
+    fn parse_schedules() {
+        let daily: Schedule = "@daily".parse().unwrap();
+        assert_eq!(daily, Schedule::cron("0 0 * * *").unwrap());
+
+        let every: Schedule = "@every 5m".parse().unwrap();
+        assert_eq!(every, Schedule::every(Duration::from_secs(300)));
+
+        assert!(matches!(
+            "@every 5x".parse::<Schedule>(),
+            Err(ScheduleError::Interval(_))
+        ));
+        assert!(matches!(
+            "* *".parse::<Schedule>(),
+            Err(ScheduleError::Cron(CronError::FieldCount(2)))
+        ));
+    }
+
+    #[test]
+    fn jitter_is_bounded() {
+        let now = Utc::now();
+        let schedule =
+            Schedule::every(Duration::from_secs(60)).with_jitter(Duration::from_secs(10));
+        for _ in 0..100 {
+            let delay = schedule.next_after(now).unwrap() - now;
+            assert!(delay >= chrono::Duration::seconds(60));
+            assert!(delay <= chrono::Duration::seconds(70));
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_do_not_overlap() {
+        let release = Arc::new(Notify::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let job = {
+            let release = release.clone();
+            let runs = runs.clone();
+            job_fn("blocking", move || {
+                let release = release.clone();
+                let runs = runs.clone();
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    release.notified().await;
+                    Ok(())
+                }
+            })
+        };
+
+        let runner = Runner {
+            job: Arc::new(job),
+            running: Arc::new(AtomicBool::new(false)),
+        };
+
+        let first = runner.trigger().expect("first run starts");
+        assert!(runner.trigger().is_none());
+
+        release.notify_one();
+        first.await.unwrap();
+
+        let second = runner.trigger().expect("runs again once finished");
+        release.notify_one();
+        second.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn run_until_shutdown() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut scheduler = Scheduler::new();
+        {
+            let runs = runs.clone();
+            scheduler.add(
+                Schedule::every(Duration::from_millis(10)),
+                job_fn("count", move || {
+                    let runs = runs.clone();
+                    async move {
+                        runs.fetch_add(1, Ordering::SeqCst);
+                        Ok(())
+                    }
+                }),
+            );
+        }
+
+        scheduler
+            .run_until(tokio::time::sleep(Duration::from_millis(100)))
+            .await;
+        let count = runs.load(Ordering::SeqCst);
+        assert!(count >= 2, "expected several runs, got {count}");
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), count);
+    }
+}
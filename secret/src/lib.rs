@@ -3,18 +3,27 @@
 use std::{borrow::Cow, env::VarError, fmt, ops::Deref};
 
 use http::{header::InvalidHeaderValue, HeaderValue};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use zeroize::Zeroize;
 
+pub mod provider;
+
+pub use provider::{ProviderError, SecretProvider};
+
 /// A Secret value.
 ///
-/// This wrapper just prevents the key from appearing in debug reprs.
+/// This wrapper just prevents the key from appearing in debug reprs, or
+/// when serialized, where it is written as `"****"`.
 ///
-/// Use [Secret::revealed] to get the underlying value.
-#[derive(Clone, Deserialize, Serialize)]
+/// Use [Secret::revealed] to get the underlying value, and
+/// [Secret::expose_serialized] to serialize it.
+#[derive(Clone, Deserialize)]
 #[serde(from = "String")]
 pub struct Secret(Cow<'static, str>);
 
+/// The value written in place of a secret in debug output and serialized data.
+const REDACTED: &str = "****";
+
 impl Secret {
     /// Create a new Secret from the value of an environment variable.
     pub fn from_env(var: &str) -> Result<Self, VarError> {
@@ -46,7 +55,15 @@ where
 
 impl fmt::Debug for Secret {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_tuple("Secret").field(&DirectDebug("****")).finish()
+        f.debug_tuple("Secret")
+            .field(&DirectDebug(REDACTED))
+            .finish()
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
     }
 }
 
@@ -70,6 +87,17 @@ impl Secret {
         Ok(header)
     }
 
+    /// Serialize the underlying value, rather than `"****"`.
+    ///
+    /// Use this with `#[serde(serialize_with = "Secret::expose_serialized")]` on
+    /// fields which must round-trip, e.g. credentials sent in a request body.
+    pub fn expose_serialized<S: Serializer>(
+        secret: &Secret,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(secret.revealed())
+    }
+
     /// Convert a string into a Secret.
     #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
@@ -114,4 +142,25 @@ mod test {
         // Check that we can still access the underlying key
         assert_eq!(apikey.revealed(), key);
     }
+
+    #[test]
+    fn secret_redacted_serialize() {
+        #[derive(Serialize, Deserialize)]
+        struct Config {
+            redacted: Secret,
+            #[serde(serialize_with = "Secret::expose_serialized")]
+            exposed: Secret,
+        }
+
+        let config = Config {
+            redacted: Secret::from("secret garden"),
+            exposed: Secret::from("open field"),
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(json, r#"{"redacted":"****","exposed":"open field"}"#);
+
+        let config: Config = serde_json::from_str(&json).unwrap();
+        assert_eq!(config.exposed.revealed(), "open field");
+    }
 }
@@ -0,0 +1,337 @@
+//! Sources of secret values, so configuration can refer to a secret by name
+//! rather than holding the value inline.
+//!
+//! Each [`SecretProvider`] returns `Ok(None)` for names it doesn't know, so a
+//! [`ChainProvider`] can try several sources in turn, e.g. the environment
+//! first, then 1Password.
+
+use std::{env::VarError, fmt, io, path::PathBuf};
+
+use crate::Secret;
+
+/// Errors that can occur when loading a secret from a provider.
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    /// No provider has a secret with this name.
+    #[error("Secret {0} not found")]
+    NotFound(String),
+
+    /// The name can't be used with this provider.
+    #[error("Invalid secret name {0:?}")]
+    InvalidName(String),
+
+    /// The environment variable is not valid unicode.
+    #[error("Environment variable ${0} is not valid unicode")]
+    Env(String),
+
+    /// The secret file could not be read.
+    #[error("Reading {path:?}: {source}")]
+    Io {
+        /// Path to the secret file.
+        path: PathBuf,
+
+        /// Error reading the file.
+        #[source]
+        source: io::Error,
+    },
+
+    /// The secret file is not valid utf-8.
+    #[error("Secret file {0:?} is not valid utf-8")]
+    Encoding(PathBuf),
+
+    /// The 1Password CLI could not read the secret.
+    #[error("op read {reference}: {message}")]
+    OnePassword {
+        /// The 1Password secret reference.
+        reference: String,
+
+        /// Error message from `op`.
+        message: String,
+    },
+}
+
+/// A source of secret values, looked up by name.
+#[async_trait::async_trait]
+pub trait SecretProvider: fmt::Debug + Send + Sync {
+    /// Get a secret by name, or `None` if this provider doesn't have it.
+    async fn get(&self, name: &str) -> Result<Option<Secret>, ProviderError>;
+
+    /// Get a secret by name, returning an error if the provider doesn't have it.
+    async fn require(&self, name: &str) -> Result<Secret, ProviderError> {
+        self.get(name)
+            .await?
+            .ok_or_else(|| ProviderError::NotFound(name.to_owned()))
+    }
+}
+
+/// Reads secrets from environment variables, optionally with a common prefix.
+#[derive(Debug, Clone, Default)]
+pub struct EnvProvider {
+    prefix: Option<String>,
+}
+
+impl EnvProvider {
+    /// Read secrets from environment variables with the same name.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read secrets from environment variables named `{prefix}{name}`.
+    pub fn with_prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretProvider for EnvProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>, ProviderError> {
+        let var = match &self.prefix {
+            Some(prefix) => format!("{prefix}{name}"),
+            None => name.to_owned(),
+        };
+
+        match Secret::from_env(&var) {
+            Ok(secret) => Ok(Some(secret)),
+            Err(VarError::NotPresent) => Ok(None),
+            Err(VarError::NotUnicode(_)) => Err(ProviderError::Env(var)),
+        }
+    }
+}
+
+/// Reads secrets from files in a directory, e.g. `/run/secrets`.
+///
+/// A single trailing newline is removed from the contents of each file.
+#[derive(Debug, Clone)]
+pub struct FileProvider {
+    root: PathBuf,
+}
+
+impl FileProvider {
+    /// Read secrets from files in a directory, where each file is named for its secret.
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretProvider for FileProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>, ProviderError> {
+        // Names must stay inside the root directory.
+        let relative = std::path::Path::new(name);
+        if name.is_empty()
+            || !relative
+                .components()
+                .all(|component| matches!(component, std::path::Component::Normal(_)))
+        {
+            return Err(ProviderError::InvalidName(name.to_owned()));
+        }
+
+        let path = self.root.join(relative);
+        let contents = match tokio::fs::read(&path).await {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(ProviderError::Io { path, source }),
+        };
+
+        let mut value = String::from_utf8(contents).map_err(|_| ProviderError::Encoding(path))?;
+        if value.ends_with('\n') {
+            value.pop();
+            if value.ends_with('\r') {
+                value.pop();
+            }
+        }
+        Ok(Some(value.into()))
+    }
+}
+
+/// Reads secrets from 1Password with `op read`.
+///
+/// Names may be full secret references, like `op://vault/item/field`, or, when
+/// a vault is set, `item/field` within that vault. The `op` CLI must already be
+/// signed in, or have a service account token in its environment.
+///
+/// Items and fields which don't exist are not found, as is every name within the
+/// vault when `op` isn't installed. Full secret references always need `op`.
+#[derive(Debug, Clone, Default)]
+pub struct OnePasswordProvider {
+    vault: Option<String>,
+}
+
+impl OnePasswordProvider {
+    /// Read secrets from full 1Password secret references.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read secrets named `item/field` from a vault.
+    pub fn with_vault<S: Into<String>>(mut self, vault: S) -> Self {
+        self.vault = Some(vault.into());
+        self
+    }
+
+    fn reference(&self, name: &str) -> Option<String> {
+        if name.starts_with("op://") {
+            return Some(name.to_owned());
+        }
+
+        self.vault
+            .as_ref()
+            .map(|vault| format!("op://{vault}/{name}"))
+    }
+}
+
+/// Check whether `op read` failed because the item or field doesn't exist.
+fn is_missing_item(stderr: &str) -> bool {
+    stderr.contains("isn't an item") || stderr.contains("isn't a field")
+}
+
+#[async_trait::async_trait]
+impl SecretProvider for OnePasswordProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>, ProviderError> {
+        let Some(reference) = self.reference(name) else {
+            return Ok(None);
+        };
+
+        let error = |message: String| ProviderError::OnePassword {
+            reference: reference.clone(),
+            message,
+        };
+
+        let output = match tokio::process::Command::new("op")
+            .args(["read", "--no-newline", &reference])
+            .output()
+            .await
+        {
+            Ok(output) => output,
+            // Without `op`, nothing in the vault can be found.
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !name.starts_with("op://") => {
+                return Ok(None)
+            }
+            Err(err) => return Err(error(err.to_string())),
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if is_missing_item(&stderr) {
+                return Ok(None);
+            }
+            return Err(error(stderr.trim().to_owned()));
+        }
+
+        let value =
+            String::from_utf8(output.stdout).map_err(|_| error("secret is not utf-8".into()))?;
+        Ok(Some(value.into()))
+    }
+}
+
+/// Tries several providers in order, returning the first secret found.
+///
+/// Errors from a provider are returned immediately, rather than trying the next.
+#[derive(Debug, Default)]
+pub struct ChainProvider {
+    providers: Vec<Box<dyn SecretProvider>>,
+}
+
+impl ChainProvider {
+    /// Create an empty chain, which finds no secrets.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a provider, which is tried after the existing providers.
+    pub fn with<P: SecretProvider + 'static>(mut self, provider: P) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl SecretProvider for ChainProvider {
+    async fn get(&self, name: &str) -> Result<Option<Secret>, ProviderError> {
+        for provider in &self.providers {
+            if let Some(secret) = provider.get(name).await? {
+                return Ok(Some(secret));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn chained_providers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("B2_KEY"), "from file\n").unwrap();
+        std::fs::write(dir.path().join("LINODE_TOKEN"), "from file\n").unwrap();
+        std::env::set_var("SECRET_PROVIDER_TEST_LINODE_TOKEN", "from env");
+
+        let provider = ChainProvider::new()
+            .with(EnvProvider::new().with_prefix("SECRET_PROVIDER_TEST_"))
+            .with(FileProvider::new(dir.path()))
+            .with(OnePasswordProvider::new());
+
+        let secret = provider.require("LINODE_TOKEN").await.unwrap();
+        assert_eq!(secret.revealed(), "from env");
+
+        let secret = provider.require("B2_KEY").await.unwrap();
+        assert_eq!(secret.revealed(), "from file");
+
+        assert!(matches!(
+            provider.require("GITHUB_KEY").await,
+            Err(ProviderError::NotFound(name)) if name == "GITHUB_KEY"
+        ));
+        assert!(matches!(
+            provider.get("../B2_KEY").await,
+            Err(ProviderError::InvalidName(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn chain_ending_with_vault() {
+        std::env::set_var("SECRET_PROVIDER_VAULT_TEST_TOKEN", "from env");
+
+        let provider = ChainProvider::new()
+            .with(EnvProvider::new().with_prefix("SECRET_PROVIDER_VAULT_TEST_"))
+            .with(OnePasswordProvider::new().with_vault("emporium-test-missing-vault"));
+
+        let secret = provider.require("TOKEN").await.unwrap();
+        assert_eq!(secret.revealed(), "from env");
+
+        assert!(matches!(
+            provider.require("missing-item/credential").await,
+            Err(ProviderError::NotFound(name)) if name == "missing-item/credential"
+        ));
+    }
+
+    #[test]
+    fn one_password_missing_item() {
+        assert!(is_missing_item(
+            r#"[ERROR] 2024/05/01 12:00:00 could not read secret 'op://infra/nope/password': error finding item: "nope" isn't an item in the "infra" vault. Specify the item with its UUID, name, or domain."#
+        ));
+        assert!(is_missing_item(
+            r#"[ERROR] 2024/05/01 12:00:00 could not read secret 'op://infra/b2/nope': error finding field: "nope" isn't a field in the "b2" item"#
+        ));
+        assert!(!is_missing_item(
+            "[ERROR] 2024/05/01 12:00:00 You are not currently signed in."
+        ));
+    }
+
+    #[test]
+    fn one_password_reference() {
+        let provider = OnePasswordProvider::new();
+        assert_eq!(
+            provider.reference("op://vault/item/field").as_deref(),
+            Some("op://vault/item/field")
+        );
+        assert_eq!(provider.reference("item/field"), None);
+
+        let provider = provider.with_vault("infra");
+        assert_eq!(
+            provider.reference("item/field").as_deref(),
+            Some("op://infra/item/field")
+        );
+    }
+}
@@ -1,12 +1,14 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::{fmt, ops::Deref};
 
 use api_client::Secret;
 use camino::Utf8PathBuf;
-use echocache::Cached;
+use echocache::{CacheEvent, Cached};
 use serde::{Deserialize, Serialize};
 
-use crate::{errors::B2ResponseExt, file::FileInfo, B2Client, B2RequestError};
+use crate::errors::{B2ErrorCode, B2ResponseExt};
+use crate::{file::FileInfo, B2Client, B2RequestError};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(from = "String", into = "String")]
@@ -51,27 +53,52 @@ impl AsRef<BucketID> for BucketID {
     }
 }
 
+/// A B2 bucket, as returned by the B2 API.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Bucket {
     bucket_name: String,
     bucket_id: BucketID,
     bucket_type: BucketType,
+    #[serde(default)]
+    bucket_info: HashMap<String, String>,
+    #[serde(default)]
+    cors_rules: Vec<CorsRule>,
+    #[serde(default)]
+    revision: Option<u64>,
 }
 
 impl Bucket {
+    /// Name of the bucket.
     #[allow(unused)]
     pub fn name(&self) -> &str {
         &self.bucket_name
     }
 
+    /// Unique ID of the bucket.
     pub fn id(&self) -> &BucketID {
         &self.bucket_id
     }
 
+    /// Access level of the bucket.
     pub fn kind(&self) -> &BucketType {
         &self.bucket_type
     }
+
+    /// User-defined key-value metadata attached to the bucket.
+    pub fn info(&self) -> &HashMap<String, String> {
+        &self.bucket_info
+    }
+
+    /// CORS rules applied to downloads from the bucket.
+    pub fn cors_rules(&self) -> &[CorsRule] {
+        &self.cors_rules
+    }
+
+    /// Revision of the bucket settings, incremented on every update.
+    pub fn revision(&self) -> Option<u64> {
+        self.revision
+    }
 }
 
 impl AsRef<BucketID> for Bucket {
@@ -110,17 +137,171 @@ impl From<()> for SelectBucket {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Access level of a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum BucketType {
+    /// Files require authorization to download.
     AllPrivate,
+
+    /// Files can be downloaded by anyone.
     AllPublic,
+
+    /// Bucket containing B2 snapshots.
     Snapshot,
 }
 
+/// A CORS rule for browser access to a bucket.
+///
+/// See the [B2 CORS documentation](https://www.backblaze.com/docs/cloud-storage-cross-origin-resource-sharing-rules).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CorsRule {
+    /// Unique name for the rule.
+    pub cors_rule_name: String,
+
+    /// Origins allowed to access the bucket, e.g. `https://example.com` or `*`.
+    pub allowed_origins: Vec<String>,
+
+    /// Operations allowed, e.g. `b2_download_file_by_name` or `s3_get`.
+    pub allowed_operations: Vec<String>,
+
+    /// Headers allowed in a preflight request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_headers: Vec<String>,
+
+    /// Headers exposed to the browser in responses.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub expose_headers: Vec<String>,
+
+    /// How long the browser may cache the preflight response, in seconds.
+    pub max_age_seconds: u32,
+}
+
+/// Object lock retention mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RetentionMode {
+    /// Retention can be shortened or removed by keys with the `bypassGovernance` capability.
+    Governance,
+
+    /// Retention can not be shortened or removed.
+    Compliance,
+}
+
+/// Unit for a retention period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RetentionUnit {
+    /// Retention period in days.
+    Days,
+
+    /// Retention period in years.
+    Years,
+}
+
+/// Length of a retention period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPeriod {
+    /// Number of units.
+    pub duration: u32,
+
+    /// Unit of the duration.
+    pub unit: RetentionUnit,
+}
+
+/// Default retention applied to new files in a bucket with object lock enabled.
+///
+/// A retention with no mode disables default retention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultRetention {
+    /// Retention mode, or `None` to disable default retention.
+    pub mode: Option<RetentionMode>,
+
+    /// Retention period, required when a mode is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<RetentionPeriod>,
+}
+
+impl DefaultRetention {
+    /// Disable default retention.
+    pub fn disabled() -> Self {
+        Self {
+            mode: None,
+            period: None,
+        }
+    }
+}
+
+/// Changes to apply to a bucket with `b2_update_bucket`.
+///
+/// Only the fields which are set are changed.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BucketUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket_type: Option<BucketType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket_info: Option<HashMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cors_rules: Option<Vec<CorsRule>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    default_retention: Option<DefaultRetention>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    if_revision_is: Option<u64>,
+}
+
+impl BucketUpdate {
+    /// Create an update which changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the bucket access level.
+    pub fn bucket_type(mut self, bucket_type: BucketType) -> Self {
+        self.bucket_type = Some(bucket_type);
+        self
+    }
+
+    /// Replace the bucket info key-value pairs.
+    pub fn bucket_info(mut self, info: HashMap<String, String>) -> Self {
+        self.bucket_info = Some(info);
+        self
+    }
+
+    /// Replace the CORS rules. An empty list removes all rules.
+    pub fn cors_rules(mut self, rules: Vec<CorsRule>) -> Self {
+        self.cors_rules = Some(rules);
+        self
+    }
+
+    /// Set the default retention for new files.
+    pub fn default_retention(mut self, retention: DefaultRetention) -> Self {
+        self.default_retention = Some(retention);
+        self
+    }
+
+    /// Only apply the update if the bucket is still at this revision.
+    pub fn if_revision_is(mut self, revision: u64) -> Self {
+        self.if_revision_is = Some(revision);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BucketUpdateBody<'u> {
+    #[serde(serialize_with = "Secret::expose_serialized")]
+    account_id: Secret,
+    bucket_id: BucketID,
+    #[serde(flatten)]
+    update: &'u BucketUpdate,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct BucketListBody {
+    #[serde(serialize_with = "Secret::expose_serialized")]
     account_id: Secret,
     #[serde(skip_serializing_if = "Option::is_none")]
     bucket_id: Option<BucketID>,
@@ -164,10 +345,16 @@ impl B2Client {
         let cache = if let Some(cache) = { self.buckets.get(name).map(|r| r.value().clone()) } {
             cache
         } else {
-            let cache = self
-                .buckets
-                .entry(name.into())
-                .or_insert(Cached::new(Some(std::time::Duration::from_secs(300))));
+            let cache = self.buckets.entry(name.into()).or_insert(
+                Cached::<Result<Bucket, Arc<B2RequestError>>>::new(Some(
+                    std::time::Duration::from_secs(300),
+                ))
+                .with_callback(|event| {
+                    if let CacheEvent::Refreshed(Err(error)) = event {
+                        tracing::warn!("Bucket lookup failed: {error}");
+                    }
+                }),
+            );
             cache.clone()
         };
 
@@ -190,6 +377,75 @@ impl B2Client {
             .await
     }
 
+    /// Update the settings of a bucket by name, returning the updated bucket.
+    #[tracing::instrument(skip(self, update))]
+    pub async fn update_bucket(
+        &self,
+        name: &str,
+        update: BucketUpdate,
+    ) -> Result<Bucket, B2RequestError> {
+        let bucket = auth!(self.b2_list_buckets(String::from(name), None))
+            .await?
+            .pop()
+            .ok_or_else(|| B2RequestError::BucketNotFound(name.to_owned()))?;
+        let updated = auth!(self.b2_update_bucket(bucket.id(), &update)).await?;
+        self.buckets.remove(name);
+        Ok(updated)
+    }
+
+    /// Replace the CORS rules on a bucket.
+    pub async fn set_cors_rules(
+        &self,
+        name: &str,
+        rules: Vec<CorsRule>,
+    ) -> Result<Bucket, B2RequestError> {
+        self.update_bucket(name, BucketUpdate::new().cors_rules(rules))
+            .await
+    }
+
+    /// Replace the bucket info key-value pairs on a bucket.
+    pub async fn set_bucket_info(
+        &self,
+        name: &str,
+        info: HashMap<String, String>,
+    ) -> Result<Bucket, B2RequestError> {
+        self.update_bucket(name, BucketUpdate::new().bucket_info(info))
+            .await
+    }
+
+    /// Set the default retention for new files in a bucket.
+    pub async fn set_default_retention(
+        &self,
+        name: &str,
+        retention: DefaultRetention,
+    ) -> Result<Bucket, B2RequestError> {
+        self.update_bucket(name, BucketUpdate::new().default_retention(retention))
+            .await
+    }
+
+    /// Update a bucket with the B2 API
+    #[tracing::instrument(skip_all, fields(bucket=%bucket))]
+    pub(crate) async fn b2_update_bucket(
+        &self,
+        bucket: &BucketID,
+        update: &BucketUpdate,
+    ) -> Result<Bucket, B2RequestError> {
+        let body = BucketUpdateBody {
+            account_id: self.authorization().account_id.clone(),
+            bucket_id: bucket.clone(),
+            update,
+        };
+
+        let request = self.authorization().post("b2_update_bucket", &body);
+
+        self.client
+            .execute(request)
+            .await
+            .map_err(B2RequestError::from)?
+            .deserialize()
+            .await
+    }
+
     /// List all buckets with the B2 API
     #[tracing::instrument(skip_all)]
     pub(crate) async fn b2_list_buckets<L: Into<SelectBucket>>(
@@ -222,7 +478,7 @@ impl B2Client {
             .client
             .execute(request)
             .await
-            .map_err(B2RequestError::Client)?
+            .map_err(B2RequestError::from)?
             .deserialize()
             .await?;
 
@@ -309,4 +565,96 @@ mod tests {
         let bucket = client.get_bucket("test").await.unwrap();
         assert_eq!(bucket.name(), "test");
     }
+
+    #[tokio::test]
+    async fn update_bucket_cors() {
+        let mut mock = api_client::mock::MockService::new();
+        mock.add(
+            "/b2api/v2/b2_list_buckets",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&json! {
+                {
+                    "buckets": [
+                        {
+                            "bucketId": "test",
+                            "bucketName": "test",
+                            "bucketType": "allPrivate"
+                        }
+                    ]
+                }
+            })
+            .unwrap(),
+        );
+        mock.add(
+            "/b2api/v2/b2_update_bucket",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&json! {
+                {
+                    "bucketId": "test",
+                    "bucketName": "test",
+                    "bucketType": "allPublic",
+                    "bucketInfo": {"owner": "ops"},
+                    "corsRules": [
+                        {
+                            "corsRuleName": "downloadFromAnyOrigin",
+                            "allowedOrigins": ["https"],
+                            "allowedOperations": ["b2_download_file_by_name"],
+                            "maxAgeSeconds": 3600
+                        }
+                    ],
+                    "revision": 3
+                }
+            })
+            .unwrap(),
+        );
+
+        let client = B2Client::from_client_and_authorization(
+            SharedService::new(mock),
+            B2Authorization::test(),
+            B2ApplicationKey::test(),
+        );
+
+        let rule = CorsRule {
+            cors_rule_name: "downloadFromAnyOrigin".into(),
+            allowed_origins: vec!["https".into()],
+            allowed_operations: vec!["b2_download_file_by_name".into()],
+            allowed_headers: Vec::new(),
+            expose_headers: Vec::new(),
+            max_age_seconds: 3600,
+        };
+
+        let bucket = client
+            .set_cors_rules("test", vec![rule.clone()])
+            .await
+            .unwrap();
+        assert_eq!(bucket.cors_rules(), &[rule]);
+        assert_eq!(bucket.info()["owner"], "ops");
+        assert_eq!(bucket.revision(), Some(3));
+    }
+
+    #[test]
+    fn serialize_bucket_update() {
+        let update = BucketUpdate::new()
+            .default_retention(DefaultRetention {
+                mode: Some(RetentionMode::Governance),
+                period: Some(RetentionPeriod {
+                    duration: 7,
+                    unit: RetentionUnit::Days,
+                }),
+            })
+            .if_revision_is(2);
+
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({
+                "defaultRetention": {
+                    "mode": "governance",
+                    "period": {"duration": 7, "unit": "days"}
+                },
+                "ifRevisionIs": 2
+            })
+        );
+    }
 }
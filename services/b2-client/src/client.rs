@@ -7,18 +7,22 @@ use dashmap::DashMap;
 use eyre::{eyre, Context};
 use futures::StreamExt;
 use hyperdriver::Body;
+use serde::Deserialize;
 use tokio::io;
 use tokio::io::AsyncWriteExt;
 
 use echocache::Cached;
-use storage_driver::{Driver, Metadata, Reader, StorageError, Writer};
+use storage_driver::{ByteRange, Driver, Metadata, Progress, Reader, StorageError, Writer};
 
 use crate::application::B2ApplicationKey;
 use crate::application::{AuthenticationError, B2Authorization};
-use crate::errors::B2ErrorCode;
 use crate::errors::B2RequestError;
+use crate::errors::{B2Error, B2ErrorCode};
+use crate::request_log::{RequestLog, RequestLogLayer};
 
 use super::B2_DEFAULT_CONCURRENCY;
+use super::B2_LARGE_FILE_SIZE;
+use super::B2_MINIMUM_PART_SIZE;
 use super::B2_STORAGE_NAME;
 use super::B2_STORAGE_SCHEME;
 use super::B2_UPLOAD_RETRIES;
@@ -26,10 +30,25 @@ use super::B2_UPLOAD_RETRIES;
 type BucketResult = Result<crate::bucket::Bucket, Arc<B2RequestError>>;
 type ArcLockMap<K, V> = Arc<DashMap<K, V>>;
 
-#[derive(Debug, Clone)]
-pub(crate) struct UploadSettings {
-    pub(crate) concurrency: usize,
-    pub(crate) retries: usize,
+/// Settings which control how files are uploaded to B2.
+///
+/// Large files are uploaded in parts, with up to `concurrency` parts in flight
+/// (and held in memory) at once.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UploadSettings {
+    /// Number of file parts to upload simultaneously.
+    pub concurrency: usize,
+
+    /// Number of times to retry an upload when B2 is unavailable.
+    pub retries: usize,
+
+    /// Size of each part of a large file upload, in bytes. Defaults to the part size
+    /// recommended by B2 when authorizing the account.
+    pub part_size: Option<usize>,
+
+    /// Files at least this large (in bytes) are uploaded in parts.
+    pub large_file_size: usize,
 }
 
 impl Default for UploadSettings {
@@ -37,6 +56,8 @@ impl Default for UploadSettings {
         UploadSettings {
             concurrency: B2_DEFAULT_CONCURRENCY,
             retries: B2_UPLOAD_RETRIES,
+            part_size: None,
+            large_file_size: B2_LARGE_FILE_SIZE,
         }
     }
 }
@@ -52,6 +73,9 @@ pub struct B2Client {
 
     /// Upload settings for this client.
     pub(crate) uploads: UploadSettings,
+
+    /// Debug log of requests made by this client.
+    log: RequestLog,
 }
 
 impl B2Client {
@@ -68,22 +92,84 @@ impl B2Client {
         authorization: B2Authorization,
         keys: B2ApplicationKey,
     ) -> Self {
+        let log = RequestLog::default();
         B2Client {
-            client: api_client::ApiClient::new_with_inner_service(
+            client: api_client::ApiClient::builder(
                 authorization
                     .api_url
                     .to_string()
                     .parse()
                     .expect("Invalid API URL"),
                 authorization,
-                client,
-            ),
+            )
+            .with_inner_service(client)
+            .layer(RequestLogLayer::new(log.clone()))
+            .build()
+            .with_error_decoder(api_client::JsonErrorDecoder::<B2Error>::new()),
             keys: Arc::new(keys),
             buckets: Default::default(),
             uploads: Default::default(),
+            log,
         }
     }
 
+    /// Set the settings used when uploading files.
+    pub fn with_upload_settings(mut self, uploads: UploadSettings) -> Self {
+        self.uploads = uploads;
+        self
+    }
+
+    /// Set the number of file parts to upload simultaneously.
+    pub fn with_upload_concurrency(mut self, concurrency: usize) -> Self {
+        self.uploads.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Set the size of each part of a large file upload, in bytes.
+    pub fn with_part_size(mut self, part_size: usize) -> Self {
+        self.uploads.part_size = Some(part_size);
+        self
+    }
+
+    /// Set the size at which files are uploaded in parts, in bytes.
+    pub fn with_large_file_size(mut self, large_file_size: usize) -> Self {
+        self.uploads.large_file_size = large_file_size;
+        self
+    }
+
+    /// The settings used when uploading files.
+    pub fn upload_settings(&self) -> &UploadSettings {
+        &self.uploads
+    }
+
+    /// Record B2 API requests and responses to `path` as JSON lines, for debugging.
+    ///
+    /// Authorization headers and tokens are redacted, and only the bodies of error
+    /// responses are recorded. Clones of this client share the log, and it can be
+    /// started and stopped while requests are in flight.
+    pub async fn start_request_log(&self, path: impl AsRef<std::path::Path>) -> io::Result<()> {
+        self.log.start(path.as_ref()).await
+    }
+
+    /// Stop recording requests.
+    pub fn stop_request_log(&self) {
+        self.log.stop()
+    }
+
+    /// The file requests are being recorded to, if the request log is started.
+    pub fn request_log(&self) -> Option<std::path::PathBuf> {
+        self.log.path()
+    }
+
+    /// Size of each part of a large file upload, using the part size recommended
+    /// by B2 unless one was configured.
+    pub(crate) fn part_size(&self) -> usize {
+        self.uploads
+            .part_size
+            .unwrap_or_else(|| self.authorization().recommended_part_size())
+            .max(B2_MINIMUM_PART_SIZE)
+    }
+
     pub(crate) fn authorization(&self) -> arc_swap::Guard<Arc<B2Authorization>> {
         self.client.auth()
     }
@@ -103,42 +189,21 @@ impl B2Client {
     }
 }
 
-macro_rules! auth {
-($driver:ident.$method:ident($($args:expr),+)) => {
-    async {
-        let mut result = $driver.$method($($args),+).await;
-        if let Err(err) = &result {
-            if let Some(err) = err.b2() {
-                if matches!(err.kind(), B2ErrorCode::ExpiredAuthToken) {
-                    if let Err(error) = $driver.refresh_authorization().await {
-                        tracing::error!("Encountered an error refreshing credentials: {error}");
-                    } else {
-                        tracing::debug!("Refreshed B2 Authorization credentials");
-                        result = $driver.$method($($args),+).await;
-                    }
-                }
-            }
-        }
-        result
-    }
-};
-}
-
 impl B2Client {
     async fn impl_download(
         &self,
         bucket: &str,
         remote: &Utf8Path,
+        range: Option<&ByteRange>,
         local: &mut Writer<'_>,
     ) -> Result<(), StorageError> {
-        let stream = auth!(self.b2_download_file_by_name(bucket, remote))
+        let stream = auth!(self.b2_download_file_by_name(bucket, remote, range))
             .await
             .context("open download stream")
             .map_err(StorageError::with(B2_STORAGE_NAME))?;
 
-        let mut src = tokio_util::io::StreamReader::new(
-            stream.map(|s| s.map_err(|err| io::Error::new(io::ErrorKind::Other, err))),
-        );
+        let mut src =
+            tokio_util::io::StreamReader::new(stream.map(|s| s.map_err(io::Error::other)));
         tokio::io::copy(&mut src, local)
             .await
             .context("copy file to upload stream")
@@ -178,6 +243,10 @@ impl Driver for B2Client {
             .with_context(|| format!("list files in {}:{remote:?}", bucket.name()))
             .map_err(StorageError::with(B2_STORAGE_NAME))?;
 
+        if infos.is_empty() {
+            return Err(StorageError::not_found(B2_STORAGE_NAME, remote));
+        }
+
         if infos.len() != 1 {
             return Err(eyre!("{} files found with name {remote}", infos.len()))
                 .map_err(StorageError::with(B2_STORAGE_NAME));
@@ -201,6 +270,44 @@ impl Driver for B2Client {
         Ok(())
     }
 
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        let bucket_id = auth!(self.get_bucket(bucket))
+            .await
+            .with_context(|| format!("get {bucket} id"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?
+            .id()
+            .clone();
+
+        self.delete_files(&bucket_id, remotes)
+            .await
+            .with_context(|| format!("delete {} files from b2://{bucket}", remotes.len()))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?;
+        Ok(())
+    }
+
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        let bucket_id = auth!(self.get_bucket(bucket))
+            .await
+            .with_context(|| format!("get {bucket} id"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?
+            .id()
+            .clone();
+
+        let source = auth!(self.b2_list_file_names(&bucket_id, Some(from.to_string()), None))
+            .await
+            .with_context(|| format!("list files in {bucket}:{from:?}"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?
+            .into_iter()
+            .find(|file| file.path() == from)
+            .ok_or_else(|| StorageError::not_found(B2_STORAGE_NAME, from))?;
+
+        auth!(self.copy_file(bucket_id.clone(), &source, to))
+            .await
+            .with_context(|| format!("copy b2://{bucket}:{from} to {to}"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?;
+        Ok(())
+    }
+
     async fn upload(
         &self,
         bucket: &str,
@@ -214,7 +321,28 @@ impl Driver for B2Client {
             .id()
             .clone();
 
-        auth!(self.upload_reader(bucket_id.clone(), local, remote, None))
+        auth!(self.upload_reader(bucket_id.clone(), local, remote, None, None))
+            .await
+            .with_context(|| format!("upload to b2://{bucket}:{remote}"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?;
+        Ok(())
+    }
+
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        let bucket_id = auth!(self.get_bucket(bucket))
+            .await
+            .with_context(|| format!("get {bucket} id"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?
+            .id()
+            .clone();
+
+        auth!(self.upload_reader(bucket_id.clone(), local, remote, None, Some(progress)))
             .await
             .with_context(|| format!("upload to b2://{bucket}:{remote}"))
             .map_err(StorageError::with(B2_STORAGE_NAME))?;
@@ -247,13 +375,32 @@ impl Driver for B2Client {
         remote: &Utf8Path,
         local: &mut Writer<'_>,
     ) -> Result<(), StorageError> {
-        self.impl_download(bucket, remote, local)
+        self.impl_download(bucket, remote, None, local)
             .await
             .with_context(|| format!("download from b2://{bucket}:{remote}"))
             .map_err(StorageError::with(B2_STORAGE_NAME))?;
         Ok(())
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        local: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        // An empty range can't be requested, and there is nothing to download.
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        self.impl_download(bucket, remote, Some(&range), local)
+            .await
+            .with_context(|| format!("download {range} from b2://{bucket}:{remote}"))
+            .map_err(StorageError::with(B2_STORAGE_NAME))?;
+        Ok(())
+    }
+
     async fn list(
         &self,
         bucket: &str,
@@ -276,3 +423,26 @@ impl Driver for B2Client {
         Ok(infos.into_iter().map(|f| f.path().to_string()).collect())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn upload_settings() {
+        let uploads: UploadSettings =
+            serde_json::from_str(r#"{"concurrency": 8, "part_size": 1024}"#).unwrap();
+        assert_eq!(uploads.concurrency, 8);
+        assert_eq!(uploads.retries, B2_UPLOAD_RETRIES);
+
+        let client = B2Client::test();
+        assert_eq!(client.part_size(), 1024 * 1024 * 100);
+
+        // Parts smaller than B2 allows are rounded up.
+        let client = client.with_upload_settings(uploads);
+        assert_eq!(client.part_size(), B2_MINIMUM_PART_SIZE);
+
+        let client = client.with_part_size(50 * 1024 * 1024);
+        assert_eq!(client.part_size(), 50 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,226 @@
+use std::ops::Range;
+
+use camino::Utf8Path;
+use futures::{StreamExt as _, TryStreamExt as _};
+use serde::{Deserialize, Serialize};
+
+use crate::bucket::BucketID;
+use crate::file::{BzMime, FileID, FileInfo};
+use crate::upload::UnfinishedLargeFile;
+use crate::{errors::B2ResponseExt, B2Client, B2RequestError};
+
+use super::B2_MAXIMUM_COPY_SIZE;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CopyFileBody<'f> {
+    source_file_id: &'f FileID,
+    file_name: &'f Utf8Path,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CopyPartBody<'f> {
+    source_file_id: &'f FileID,
+    large_file_id: &'f FileID,
+    part_number: usize,
+    range: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CopyPartResponse {
+    content_sha1: String,
+}
+
+impl B2Client {
+    #[tracing::instrument(skip_all, fields(source=%source, %name))]
+    async fn b2_copy_file(
+        &self,
+        source: &FileID,
+        name: &Utf8Path,
+    ) -> Result<FileInfo, B2RequestError> {
+        let body = CopyFileBody {
+            source_file_id: source,
+            file_name: name,
+        };
+
+        let req = self.authorization().post("b2_copy_file", &body);
+        let resp = self.client.execute(req).await?;
+
+        let info: FileInfo = resp.deserialize().await?;
+        tracing::debug!(file=?info.id(), "copied file");
+
+        Ok(info)
+    }
+
+    #[tracing::instrument(skip_all, fields(source=%source, file=%large_file.id(), %part))]
+    async fn b2_copy_part(
+        &self,
+        source: &FileID,
+        large_file: &FileInfo,
+        part: usize,
+        range: Range<u64>,
+    ) -> Result<[u8; 20], B2RequestError> {
+        let body = CopyPartBody {
+            source_file_id: source,
+            large_file_id: large_file.id(),
+            part_number: part,
+            range: format!("bytes={}-{}", range.start, range.end - 1),
+        };
+
+        let req = self.authorization().post("b2_copy_part", &body);
+        let resp = self.client.execute(req).await?;
+
+        let copied: CopyPartResponse = resp.deserialize().await?;
+        let mut sha = [0; 20];
+        hex::decode_to_slice(&copied.content_sha1, &mut sha)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        Ok(sha)
+    }
+
+    /// Copy a file to a new name in the same bucket, without downloading it.
+    ///
+    /// Files which are too large to copy in a single request are copied as a
+    /// large file, in parts of the configured part size.
+    #[tracing::instrument(skip_all, fields(%bucket, from=%source.path(), %to))]
+    pub(crate) async fn copy_file(
+        &self,
+        bucket: BucketID,
+        source: &FileInfo,
+        to: &Utf8Path,
+    ) -> Result<(), B2RequestError> {
+        if source.content_length() as u64 <= B2_MAXIMUM_COPY_SIZE {
+            self.b2_copy_file(source.id(), to).await?;
+            return Ok(());
+        }
+
+        tracing::debug!("File {} is a large file, copying in parts", source.path());
+        let content_type = match source.content_type() {
+            BzMime::Mime(mime) => Some(mime.clone()),
+            _ => None,
+        };
+        let info = self.b2_start_large_file(bucket, to, content_type).await?;
+        let guard = UnfinishedLargeFile::new(self.clone(), info.clone());
+
+        let parts = part_ranges(source.content_length() as u64, self.part_size() as u64);
+        let result: Result<Vec<[u8; 20]>, B2RequestError> =
+            futures::stream::iter(parts.into_iter().enumerate())
+                .map(|(idx, range)| self.b2_copy_part(source.id(), &info, idx + 1, range))
+                .buffered(self.uploads.concurrency)
+                .try_collect()
+                .await;
+
+        let result = match result {
+            Ok(shas) => self.b2_finish_large_file(&info, &shas).await,
+            Err(error) => Err(error),
+        };
+
+        if let Err(error) = &result {
+            tracing::error!(file=?info.id(), "Error during multi-part copy: {error}");
+            let _ = self.b2_cancel_large_file(&info).await;
+        }
+        guard.finished();
+        result
+    }
+}
+
+/// Split a file of `size` bytes into consecutive ranges of at most `part_size` bytes.
+fn part_ranges(size: u64, part_size: u64) -> Vec<Range<u64>> {
+    (0..size)
+        .step_by(part_size as usize)
+        .map(|start| start..(start + part_size).min(size))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperdriver::service::SharedService;
+    use serde_json::json;
+    use storage_driver::Driver as _;
+
+    use crate::application::B2Authorization;
+    use crate::B2ApplicationKey;
+
+    use super::*;
+
+    #[test]
+    fn split_parts() {
+        assert_eq!(part_ranges(10, 4), vec![0..4, 4..8, 8..10]);
+        assert_eq!(part_ranges(8, 4), vec![0..4, 4..8]);
+        assert!(part_ranges(0, 4).is_empty());
+    }
+
+    #[tokio::test]
+    async fn copy_small_file() {
+        let file = |name: &str, id: &str| {
+            json! {
+                {
+                    "accountId": "b2_account_id",
+                    "action": "upload",
+                    "bucketId": "test",
+                    "contentLength": 11,
+                    "contentType": "text/plain",
+                    "fileId": id,
+                    "fileName": name,
+                    "uploadTimestamp": 1700000000000u64
+                }
+            }
+        };
+
+        let mut mock = api_client::mock::MockService::new();
+        mock.add(
+            "/b2api/v2/b2_list_buckets",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&json! {
+                {
+                    "buckets": [
+                        {
+                            "bucketId": "test",
+                            "bucketName": "test",
+                            "bucketType": "allPrivate"
+                        }
+                    ]
+                }
+            })
+            .unwrap(),
+        );
+        mock.add(
+            "/b2api/v2/b2_list_file_names",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&json! {
+                {
+                    "files": [file("a.txt", "4_za"), file("a.txt.bak", "4_zb")],
+                    "nextFileName": null
+                }
+            })
+            .unwrap(),
+        );
+        mock.add(
+            "/b2api/v2/b2_copy_file",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&file("b.txt", "4_zc")).unwrap(),
+        );
+
+        let client = B2Client::from_client_and_authorization(
+            SharedService::new(mock),
+            B2Authorization::test(),
+            B2ApplicationKey::test(),
+        );
+
+        client
+            .copy("test", Utf8Path::new("a.txt"), Utf8Path::new("b.txt"))
+            .await
+            .unwrap();
+
+        let err = client
+            .copy("test", Utf8Path::new("missing.txt"), Utf8Path::new("b.txt"))
+            .await
+            .unwrap_err();
+        assert!(err.is_not_found());
+    }
+}
@@ -1,19 +1,119 @@
+use std::time::Duration;
+
 use api_client::uri::UriExtension as _;
-use camino::{Utf8Path, Utf8PathBuf};
+use api_client::Secret;
+use camino::Utf8Path;
+use chrono::{DateTime, Utc};
 use http_body_util::BodyExt as _;
 use hyperdriver::Body;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use storage_driver::ByteRange;
 
-use crate::{errors::B2ResponseExt, B2Client, B2RequestError};
+use crate::bucket::BucketID;
+use crate::errors::{B2ErrorCode, B2ResponseExt};
+use crate::{B2Client, B2RequestError};
 const B2_FILE_URL_BASE: &str = "file";
 
+/// Characters which are escaped in each segment of a file name in a download URL.
+const B2_FILE_NAME_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Query parameter which carries a download authorization token.
+const B2_AUTHORIZATION_QUERY: &str = "Authorization";
+
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+/// Build the "friendly" URL for a file, `{download_url}/file/{bucket}/{filename}`.
+fn file_url(download_url: http::Uri, bucket: &str, filename: &Utf8Path) -> http::Uri {
+    let mut path = format!("{B2_FILE_URL_BASE}/{bucket}");
+    for segment in filename.as_str().split('/').filter(|s| !s.is_empty()) {
+        path.push('/');
+        path.extend(utf8_percent_encode(segment, B2_FILE_NAME_ESCAPE));
+    }
+
+    download_url.join(path)
+}
+
+/// A time-limited token which grants download access to the files in a bucket
+/// whose names start with a prefix.
+///
+/// Use [`DownloadAuthorization::url`] to hand out links to files, which work
+/// without any other credentials until the authorization expires.
+#[derive(Debug, Clone)]
+pub struct DownloadAuthorization {
+    bucket: String,
+    prefix: String,
+    token: Secret,
+    download_url: http::Uri,
+    expires: DateTime<Utc>,
+}
+
+impl DownloadAuthorization {
+    /// The name of the bucket this authorization applies to.
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// The file name prefix this authorization applies to.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The authorization token, which can also be sent in an `Authorization` header.
+    pub fn token(&self) -> &Secret {
+        &self.token
+    }
+
+    /// When the authorization expires.
+    pub fn expires(&self) -> DateTime<Utc> {
+        self.expires
+    }
+
+    /// Check if the authorization has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires <= Utc::now()
+    }
+
+    /// A download URL for a file which carries this authorization, or `None`
+    /// if the file name does not start with the authorized prefix.
+    pub fn url(&self, filename: &Utf8Path) -> Option<http::Uri> {
+        if !filename.as_str().starts_with(&self.prefix) {
+            return None;
+        }
+
+        Some(
+            file_url(self.download_url.clone(), &self.bucket, filename)
+                .replace_query(B2_AUTHORIZATION_QUERY, self.token.revealed()),
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadAuthorizationBody<'a> {
+    bucket_id: &'a BucketID,
+    file_name_prefix: &'a str,
+    valid_duration_in_seconds: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DownloadAuthorizationResponse {
+    file_name_prefix: String,
+    authorization_token: Secret,
+}
+
 impl B2Client {
     #[tracing::instrument(skip(self), level = "trace")]
     pub(crate) async fn b2_download_file_by_name(
         &self,
         bucket: &str,
         filename: &Utf8Path,
+        range: Option<&ByteRange>,
     ) -> Result<impl futures::stream::Stream<Item = Result<bytes::Bytes, BoxError>>, B2RequestError>
     {
         let url = self.b2_download_file_by_name_url(bucket, filename);
@@ -25,12 +125,14 @@ impl B2Client {
             .revealed()
             .to_owned();
 
-        let request = http::Request::builder()
+        let mut request = http::Request::builder()
             .method(http::Method::GET)
             .uri(url)
-            .header(http::header::AUTHORIZATION, key.clone())
-            .body(Body::empty())
-            .unwrap();
+            .header(http::header::AUTHORIZATION, key.clone());
+        if let Some(range) = range {
+            request = request.header(http::header::RANGE, range.to_string());
+        }
+        let request = request.body(Body::empty()).unwrap();
 
         let resp = self.client.execute(request).await?.handle_errors().await?;
 
@@ -42,17 +144,80 @@ impl B2Client {
         bucket: &str,
         filename: &Utf8Path,
     ) -> http::Uri {
-        let mut path = Utf8PathBuf::from(B2_FILE_URL_BASE);
-        path.push(bucket);
-        path.extend(filename);
+        file_url(self.authorization().download_url.clone(), bucket, filename)
+    }
 
-        let url = self.authorization().download_url.clone();
-        url.join(path.as_str())
+    /// The public download URL for a file.
+    ///
+    /// This URL only works without credentials for files in public buckets. For
+    /// private buckets, use [`B2Client::get_download_authorization`].
+    pub fn download_url(&self, bucket: &str, filename: &Utf8Path) -> http::Uri {
+        self.b2_download_file_by_name_url(bucket, filename)
+    }
+
+    /// Authorize downloads of files in a bucket whose names start with `prefix`,
+    /// for `ttl`.
+    ///
+    /// B2 accepts durations from one second up to one week.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_download_authorization(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        ttl: Duration,
+    ) -> Result<DownloadAuthorization, B2RequestError> {
+        let bucket_id = auth!(self.b2_list_buckets(String::from(bucket), None))
+            .await?
+            .pop()
+            .ok_or_else(|| B2RequestError::BucketNotFound(bucket.to_owned()))?
+            .id()
+            .clone();
+
+        let expires = Utc::now() + chrono::Duration::seconds(ttl.as_secs() as i64);
+        let response = auth!(self.b2_get_download_authorization(&bucket_id, prefix, ttl)).await?;
+
+        Ok(DownloadAuthorization {
+            bucket: bucket.to_owned(),
+            prefix: response.file_name_prefix,
+            token: response.authorization_token,
+            download_url: self.authorization().download_url.clone(),
+            expires,
+        })
+    }
+
+    #[tracing::instrument(skip(self), level = "trace")]
+    async fn b2_get_download_authorization(
+        &self,
+        bucket: &BucketID,
+        prefix: &str,
+        ttl: Duration,
+    ) -> Result<DownloadAuthorizationResponse, B2RequestError> {
+        let body = DownloadAuthorizationBody {
+            bucket_id: bucket,
+            file_name_prefix: prefix,
+            valid_duration_in_seconds: ttl.as_secs(),
+        };
+
+        let request = self
+            .authorization()
+            .post("b2_get_download_authorization", &body);
+
+        self.client
+            .execute(request)
+            .await
+            .map_err(B2RequestError::from)?
+            .deserialize()
+            .await
     }
 }
 
 #[cfg(test)]
 mod test {
+    use hyperdriver::service::SharedService;
+    use serde_json::json;
+
+    use crate::application::B2Authorization;
+    use crate::B2ApplicationKey;
 
     use super::*;
 
@@ -65,4 +230,71 @@ mod test {
             "https://f999.backblazeb2.test/file/bucket/path/to/my/stuff.txt"
         );
     }
+
+    #[test]
+    fn download_url_escapes_file_names() {
+        let client = B2Client::test();
+        let url = client.download_url("bucket", "logs/2020 01/a+b.txt".into());
+        assert_eq!(
+            &url.to_string(),
+            "https://f999.backblazeb2.test/file/bucket/logs/2020%2001/a%2Bb.txt"
+        );
+    }
+
+    #[tokio::test]
+    async fn download_authorization() {
+        let mut mock = api_client::mock::MockService::new();
+        mock.add(
+            "/b2api/v2/b2_list_buckets",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&json! {
+                {
+                    "buckets": [
+                        {
+                            "bucketId": "test-id",
+                            "bucketName": "test",
+                            "bucketType": "allPrivate"
+                        }
+                    ]
+                }
+            })
+            .unwrap(),
+        );
+        mock.add(
+            "/b2api/v2/b2_get_download_authorization",
+            http::StatusCode::OK,
+            http::HeaderMap::new(),
+            serde_json::to_vec(&json! {
+                {
+                    "bucketId": "test-id",
+                    "fileNamePrefix": "reports/",
+                    "authorizationToken": "3_download_token"
+                }
+            })
+            .unwrap(),
+        );
+
+        let client = B2Client::from_client_and_authorization(
+            SharedService::new(mock),
+            B2Authorization::test(),
+            B2ApplicationKey::test(),
+        );
+
+        let authorization = client
+            .get_download_authorization("test", "reports/", Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(authorization.prefix(), "reports/");
+        assert!(!authorization.is_expired());
+
+        assert_eq!(
+            authorization
+                .url("reports/daily.csv".into())
+                .unwrap()
+                .to_string(),
+            "https://f999.backblazeb2.test/file/test/reports/daily.csv?Authorization=3_download_token"
+        );
+        assert!(authorization.url("secrets/key.pem".into()).is_none());
+    }
 }
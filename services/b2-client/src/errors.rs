@@ -36,6 +36,7 @@ impl B2Error {
 
 /// An error code returned by the B2 API.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum B2ErrorCode {
     /// The authorization token has expired, and should be refreshed.
     ExpiredAuthToken,
@@ -106,17 +107,34 @@ pub enum B2RequestError {
     #[error("no credentials for bucket {0}")]
     NoCredentials(String),
 
+    /// The bucket does not exist, or is not visible to these credentials.
+    #[error("bucket {0} not found")]
+    BucketNotFound(String),
+
     /// An error occurred while reading the response body.
     #[error("body: {0}")]
     Body(#[source] Box<dyn std::error::Error + Send + Sync>),
 
     /// An error occurred while making a request to the B2 API.
     #[error("client: {0}")]
-    Client(#[from] api_client::Error),
+    Client(#[source] api_client::Error),
 
     /// The request encountered too many errors during retries.
     #[error("Retries exhausted")]
     RetriesExhausted,
+
+    /// A background task panicked or was cancelled before it finished.
+    #[error("task: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+impl From<api_client::Error> for B2RequestError {
+    fn from(error: api_client::Error) -> Self {
+        match error.into_api_error() {
+            Ok(error) => B2RequestError::B2(error),
+            Err(error) => B2RequestError::Client(error),
+        }
+    }
 }
 
 impl From<AuthenticationError> for B2RequestError {
@@ -64,10 +64,17 @@ impl FileInfo {
         &self.file_name
     }
 
-    #[allow(unused)]
     pub fn id(&self) -> &FileID {
         &self.file_id
     }
+
+    pub fn content_length(&self) -> usize {
+        self.content_length
+    }
+
+    pub fn content_type(&self) -> &BzMime {
+        &self.content_type
+    }
 }
 
 impl From<FileInfo> for Metadata {
@@ -149,6 +156,58 @@ impl B2Client {
 
         Ok(())
     }
+
+    /// Delete several files from a bucket.
+    ///
+    /// Files are found with a single listing of the longest prefix the names
+    /// share, rather than a listing for each file.
+    #[tracing::instrument(skip(self, bucket, names), fields(bucket=%bucket.as_ref(), count=names.len()))]
+    pub async fn delete_files<B: AsRef<BucketID>>(
+        &self,
+        bucket: B,
+        names: &[&Utf8Path],
+    ) -> Result<(), B2RequestError> {
+        if names.is_empty() {
+            return Ok(());
+        }
+
+        let files = self
+            .b2_list_file_names(bucket, Some(common_prefix(names)), None)
+            .await?;
+
+        futures::future::try_join_all(
+            files
+                .iter()
+                .filter(|file| names.contains(&file.path()))
+                .map(|file| {
+                    Box::pin(async move {
+                        tracing::trace!(id = ?file.id(), "Deleting file");
+                        self.b2_delete_file_version(file.path(), file.id()).await
+                    })
+                }),
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// The longest string prefix shared by all of the names.
+fn common_prefix(names: &[&Utf8Path]) -> String {
+    let Some((first, rest)) = names.split_first() else {
+        return String::new();
+    };
+
+    let mut prefix = first.as_str();
+    for name in rest {
+        let shared = prefix
+            .char_indices()
+            .zip(name.as_str().chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(name.as_str().len()), |((idx, _), _)| idx);
+        prefix = &prefix[..shared];
+    }
+    prefix.to_owned()
 }
 
 mod mime {
@@ -240,3 +299,20 @@ mod mime {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_prefix() {
+        let names = [
+            Utf8Path::new("shelf/volume/2024-01-01/a.txt"),
+            Utf8Path::new("shelf/volume/2024-01-01/b.txt"),
+            Utf8Path::new("shelf/volume/2024-01-01/nested/c.txt"),
+        ];
+        assert_eq!(common_prefix(&names), "shelf/volume/2024-01-01/");
+        assert_eq!(common_prefix(&names[..1]), "shelf/volume/2024-01-01/a.txt");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
@@ -1,12 +1,37 @@
 //! Backblaze B2 Storage Driver
 
+/// Call a client method, refreshing the authorization and retrying once if the
+/// B2 API reports that the auth token has expired.
+macro_rules! auth {
+($driver:ident.$method:ident($($args:expr),+)) => {
+    async {
+        let mut result = $driver.$method($($args),+).await;
+        if let Err(err) = &result {
+            if let Some(err) = err.b2() {
+                if matches!(err.kind(), B2ErrorCode::ExpiredAuthToken) {
+                    if let Err(error) = $driver.refresh_authorization().await {
+                        tracing::error!("Encountered an error refreshing credentials: {error}");
+                    } else {
+                        tracing::debug!("Refreshed B2 Authorization credentials");
+                        result = $driver.$method($($args),+).await;
+                    }
+                }
+            }
+        }
+        result
+    }
+};
+}
+
 mod application;
 mod bucket;
 mod client;
+mod copy;
 mod download;
 mod errors;
 mod file;
 mod multi;
+mod request_log;
 mod upload;
 
 /// The name of the storage driver.
@@ -21,6 +46,16 @@ const B2_STORAGE_SCHEME: &str = "b2";
 /// but we can split up smaller files if we want, so we do that here.
 const B2_LARGE_FILE_SIZE: usize = 1024 * 1024 * 1024; // 1GB
 
+/// The minimum size of a part in a large file upload, other than the last part.
+///
+/// This is a limitation of the B2 API.
+const B2_MINIMUM_PART_SIZE: usize = 5 * 1024 * 1024; // 5MB
+
+/// The maximum file size which can be copied in a single request.
+///
+/// This is a limitation of the B2 API, larger files are copied in parts.
+const B2_MAXIMUM_COPY_SIZE: u64 = 5 * 1000 * 1000 * 1000; // 5GB
+
 /// Number of file parts to simultaneously upload.
 const B2_DEFAULT_CONCURRENCY: usize = 4;
 
@@ -34,6 +69,11 @@ const B2_DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(6
 const B2_DEFAULT_CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 
 pub use crate::application::B2ApplicationKey;
-pub use crate::client::B2Client;
+pub use crate::bucket::{
+    Bucket, BucketType, BucketUpdate, CorsRule, DefaultRetention, RetentionMode, RetentionPeriod,
+    RetentionUnit,
+};
+pub use crate::client::{B2Client, UploadSettings};
+pub use crate::download::DownloadAuthorization;
 pub use crate::errors::{B2Error, B2RequestError};
 pub use crate::multi::{B2MultiClient, B2MultiConfig};
@@ -14,12 +14,12 @@ use hyperdriver::Body;
 use serde::Deserialize;
 
 use storage_driver::StorageError;
-use storage_driver::{Driver, Metadata, Reader, Writer};
+use storage_driver::{ByteRange, Driver, Metadata, Progress, Reader, Writer};
 
 use crate::application::AuthenticationError;
 use crate::application::AuthenticationErrorKind;
 use crate::application::B2ApplicationKey;
-use crate::client::B2Client;
+use crate::client::{B2Client, UploadSettings};
 
 use super::B2_STORAGE_NAME;
 use super::B2_STORAGE_SCHEME;
@@ -37,6 +37,10 @@ pub struct B2MultiConfig {
     /// Map of bucket names to application keys.
     #[serde(flatten)]
     pub buckets: HashMap<Box<str>, B2ApplicationKey>,
+
+    /// Settings used when uploading files to any bucket.
+    #[serde(default)]
+    pub uploads: UploadSettings,
 }
 
 impl B2MultiConfig {
@@ -45,7 +49,7 @@ impl B2MultiConfig {
         if self.buckets.is_empty() {
             tracing::warn!("No buckets configured for B2 client");
         }
-        B2MultiClient::new(self.buckets)
+        B2MultiClient::new(self.buckets).with_upload_settings(self.uploads)
     }
 }
 
@@ -60,6 +64,7 @@ impl B2MultiConfig {
 pub struct B2MultiClient {
     client: hyperdriver::client::SharedClientService<Body, Body>,
     buckets: Arc<DashMap<Box<str>, B2BucketStatus>>,
+    uploads: UploadSettings,
 }
 
 impl B2MultiClient {
@@ -77,9 +82,16 @@ impl B2MultiClient {
                     .map(|(b, k)| (b, B2BucketStatus::Key(k)))
                     .collect(),
             ),
+            uploads: UploadSettings::default(),
         }
     }
 
+    /// Set the settings used when uploading files to any bucket.
+    pub fn with_upload_settings(mut self, uploads: UploadSettings) -> Self {
+        self.uploads = uploads;
+        self
+    }
+
     /// Get a client for a given bucket.
     async fn get_bucket_client(&self, bucket: &str) -> Result<B2Client, AuthenticationError> {
         let bucket: Box<str> = bucket.into();
@@ -91,7 +103,8 @@ impl B2MultiClient {
                         self.client.clone(),
                         key.fetch_authorization(&mut self.client.clone()).await?,
                         key.clone(),
-                    );
+                    )
+                    .with_upload_settings(self.uploads.clone());
 
                     *entry.get_mut() = B2BucketStatus::Authorized(client.clone());
                     Ok(client)
@@ -132,6 +145,15 @@ impl Driver for B2MultiClient {
         client.delete(bucket, remote).await
     }
 
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        let client = self
+            .get_bucket_client(bucket)
+            .await
+            .context("authorize bucket key")
+            .map_err(StorageError::with(self::B2_STORAGE_NAME))?;
+        client.delete_many(bucket, remotes).await
+    }
+
     async fn upload(
         &self,
         bucket: &str,
@@ -146,6 +168,23 @@ impl Driver for B2MultiClient {
         client.upload(bucket, remote, local).await
     }
 
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        let client = self
+            .get_bucket_client(bucket)
+            .await
+            .context("authorize bucket key")
+            .map_err(StorageError::with(self::B2_STORAGE_NAME))?;
+        client
+            .upload_with_progress(bucket, remote, local, progress)
+            .await
+    }
+
     async fn download(
         &self,
         bucket: &str,
@@ -160,6 +199,44 @@ impl Driver for B2MultiClient {
         client.download(bucket, remote, local).await
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        local: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let client = self
+            .get_bucket_client(bucket)
+            .await
+            .context("authorize bucket key")
+            .map_err(StorageError::with(self::B2_STORAGE_NAME))?;
+        client.download_range(bucket, remote, range, local).await
+    }
+
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        let client = self
+            .get_bucket_client(bucket)
+            .await
+            .context("authorize bucket key")
+            .map_err(StorageError::with(self::B2_STORAGE_NAME))?;
+        client.copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        let client = self
+            .get_bucket_client(bucket)
+            .await
+            .context("authorize bucket key")
+            .map_err(StorageError::with(self::B2_STORAGE_NAME))?;
+        client.rename(bucket, from, to).await
+    }
+
     async fn list(
         &self,
         bucket: &str,
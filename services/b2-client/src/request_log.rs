@@ -0,0 +1,358 @@
+//! Recording B2 API requests and responses, for debugging.
+//!
+//! Each request is written as a line of JSON, with its response status and
+//! headers. Bodies are only recorded for error responses, since successful
+//! responses can carry authorization tokens or file contents. Authorization
+//! headers, sensitive headers and authorization query parameters are redacted.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use arc_swap::ArcSwapOption;
+use chrono::{DateTime, Utc};
+use http::header::{self, HeaderMap};
+use http_body_util::BodyExt as _;
+use hyperdriver::Body;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt as _;
+use tracing::Instrument as _;
+
+const REDACTED: &str = "<redacted>";
+
+/// A JSON lines file which requests are recorded to.
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    file: tokio::sync::Mutex<tokio::fs::File>,
+}
+
+/// A request log which can be started and stopped at runtime, shared by clones
+/// of a client.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RequestLog {
+    file: Arc<ArcSwapOption<LogFile>>,
+}
+
+impl RequestLog {
+    /// Start recording requests to `path`, appending to it if it exists.
+    pub(crate) async fn start(&self, path: &Path) -> std::io::Result<()> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        self.file.store(Some(Arc::new(LogFile {
+            path: path.to_owned(),
+            file: tokio::sync::Mutex::new(file),
+        })));
+        Ok(())
+    }
+
+    /// Stop recording requests.
+    pub(crate) fn stop(&self) {
+        self.file.store(None);
+    }
+
+    /// The file requests are being recorded to.
+    pub(crate) fn path(&self) -> Option<PathBuf> {
+        self.file.load().as_ref().map(|log| log.path.clone())
+    }
+}
+
+impl LogFile {
+    async fn write(&self, entry: &Entry) {
+        let mut line = match serde_json::to_vec(entry) {
+            Ok(line) => line,
+            Err(error) => {
+                tracing::warn!("Failed to serialize request log entry: {error}");
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut file = self.file.lock().await;
+        if let Err(error) = file.write_all(&line).await {
+            tracing::warn!(path = %self.path.display(), "Failed to write request log: {error}");
+        }
+    }
+}
+
+/// A recorded request and its response.
+#[derive(Debug, Serialize)]
+struct Entry {
+    timestamp: DateTime<Utc>,
+    method: String,
+    uri: String,
+    request_headers: BTreeMap<String, String>,
+    elapsed_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<u16>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    response_headers: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_body: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Entry {
+    fn new(request: &http::Request<Body>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            method: request.method().to_string(),
+            uri: redact_uri(request.uri()),
+            request_headers: redact_headers(request.headers()),
+            elapsed_ms: 0,
+            status: None,
+            response_headers: BTreeMap::new(),
+            response_body: None,
+            error: None,
+        }
+    }
+}
+
+fn redact_headers(headers: &HeaderMap) -> BTreeMap<String, String> {
+    let mut redacted: BTreeMap<String, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let value = if value.is_sensitive() || name == header::AUTHORIZATION {
+            REDACTED.into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+
+        redacted
+            .entry(name.as_str().to_owned())
+            .and_modify(|values| {
+                values.push_str(", ");
+                values.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    redacted
+}
+
+/// Download URLs can carry an authorization token in the query.
+fn redact_uri(uri: &http::Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if name.eq_ignore_ascii_case("authorization") => {
+                format!("{name}={REDACTED}")
+            }
+            _ => pair.to_owned(),
+        })
+        .collect();
+
+    let path = uri.path();
+    match (uri.scheme_str(), uri.authority()) {
+        (Some(scheme), Some(authority)) => {
+            format!("{scheme}://{authority}{path}?{}", query.join("&"))
+        }
+        _ => format!("{path}?{}", query.join("&")),
+    }
+}
+
+/// A layer which records requests to a [`RequestLog`], while it is started.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestLogLayer {
+    log: RequestLog,
+}
+
+impl RequestLogLayer {
+    pub(crate) fn new(log: RequestLog) -> Self {
+        Self { log }
+    }
+}
+
+impl<S> tower::Layer<S> for RequestLogLayer {
+    type Service = RequestLogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLogService {
+            inner,
+            log: self.log.clone(),
+        }
+    }
+}
+
+/// Records requests to a [`RequestLog`], while it is started.
+#[derive(Debug, Clone)]
+pub(crate) struct RequestLogService<S> {
+    inner: S,
+    log: RequestLog,
+}
+
+impl<S> tower::Service<http::Request<Body>> for RequestLogService<S>
+where
+    S: tower::Service<
+            http::Request<Body>,
+            Response = http::Response<Body>,
+            Error = hyperdriver::client::Error,
+        > + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = hyperdriver::client::Error;
+    type Future = api_client::BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+        let Some(log) = self.log.file.load_full() else {
+            return Box::pin(self.inner.call(request));
+        };
+
+        let mut entry = Entry::new(&request);
+        let start = Instant::now();
+        let future = self.inner.call(request);
+
+        Box::pin(
+            async move {
+                let result = future.await;
+                entry.elapsed_ms = start.elapsed().as_millis() as u64;
+
+                let result = match result {
+                    Ok(response) => {
+                        entry.status = Some(response.status().as_u16());
+                        entry.response_headers = redact_headers(response.headers());
+                        if response.status().is_success() {
+                            Ok(response)
+                        } else {
+                            // Buffer the error body so it can be recorded and still decoded.
+                            let (parts, body) = response.into_parts();
+                            let body = match body.collect().await {
+                                Ok(body) => body.to_bytes(),
+                                Err(error) => {
+                                    entry.error = Some(format!("reading body: {error}"));
+                                    bytes::Bytes::new()
+                                }
+                            };
+                            entry.response_body = Some(String::from_utf8_lossy(&body).into_owned());
+                            Ok(http::Response::from_parts(parts, Body::from(body)))
+                        }
+                    }
+                    Err(error) => {
+                        entry.error = Some(error.to_string());
+                        Err(error)
+                    }
+                };
+
+                log.write(&entry).await;
+                result
+            }
+            .in_current_span(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::{Layer as _, ServiceExt as _};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn record_requests() {
+        let path =
+            std::env::temp_dir().join(format!("b2-request-log-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let log = RequestLog::default();
+        let service = RequestLogLayer::new(log.clone()).layer(tower::service_fn(
+            |request: http::Request<Body>| async move {
+                let status = if request.uri().path().ends_with("b2_list_buckets") {
+                    http::StatusCode::OK
+                } else {
+                    http::StatusCode::SERVICE_UNAVAILABLE
+                };
+                Ok::<_, hyperdriver::client::Error>(
+                    http::Response::builder()
+                        .status(status)
+                        .body(Body::from(r#"{"code": "service_unavailable"}"#))
+                        .unwrap(),
+                )
+            },
+        ));
+
+        let request = |uri: &str| {
+            http::Request::post(uri)
+                .header(header::AUTHORIZATION, "secret-token")
+                .header("X-Bz-Part-Number", 2)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // Nothing is recorded until the log is started.
+        service
+            .clone()
+            .oneshot(request("https://api.example.com/b2api/v3/b2_list_buckets"))
+            .await
+            .unwrap();
+        assert!(log.path().is_none());
+
+        log.start(&path).await.unwrap();
+        assert_eq!(log.path().as_deref(), Some(path.as_path()));
+
+        service
+            .clone()
+            .oneshot(request("https://api.example.com/b2api/v3/b2_list_buckets"))
+            .await
+            .unwrap();
+        let response = service
+            .clone()
+            .oneshot(request(
+                "https://f000.example.com/file/bucket/a.txt?Authorization=download-token",
+            ))
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], br#"{"code": "service_unavailable"}"#);
+
+        log.stop();
+        service
+            .oneshot(request("https://api.example.com/b2api/v3/b2_list_buckets"))
+            .await
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!contents.contains("secret-token"));
+        assert!(!contents.contains("download-token"));
+
+        let entries: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0]["status"], 200);
+        assert_eq!(entries[0]["request_headers"]["authorization"], REDACTED);
+        assert_eq!(entries[0]["request_headers"]["x-bz-part-number"], "2");
+        assert!(entries[0].get("response_body").is_none());
+
+        assert_eq!(entries[1]["status"], 503);
+        assert_eq!(
+            entries[1]["uri"],
+            "https://f000.example.com/file/bucket/a.txt?Authorization=<redacted>"
+        );
+        assert_eq!(
+            entries[1]["response_body"],
+            r#"{"code": "service_unavailable"}"#
+        );
+    }
+}
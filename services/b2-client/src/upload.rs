@@ -1,14 +1,12 @@
 use std::io;
-use std::ops::Deref;
 use std::sync::Arc;
 
 use bytes::Bytes;
 use camino::Utf8PathBuf;
-use futures::FutureExt;
 use http::StatusCode;
-use storage_driver::Reader;
-use tokio::io::AsyncReadExt;
-use tokio::task::JoinHandle;
+use storage_driver::{Progress, Reader};
+use tokio::io::{AsyncBufReadExt as _, AsyncReadExt};
+use tokio::task::JoinSet;
 
 use api_client::Secret;
 use camino::Utf8Path;
@@ -241,7 +239,7 @@ impl B2Client {
     }
 
     #[tracing::instrument(skip(self))]
-    async fn b2_start_large_file(
+    pub(crate) async fn b2_start_large_file(
         &self,
         bucket: BucketID,
         filename: &Utf8Path,
@@ -262,7 +260,7 @@ impl B2Client {
     }
 
     #[tracing::instrument(skip_all, fields(file=%info.id()))]
-    async fn b2_finish_large_file(
+    pub(crate) async fn b2_finish_large_file(
         &self,
         info: &FileInfo,
         shas: &[[u8; 20]],
@@ -282,7 +280,7 @@ impl B2Client {
     }
 
     #[tracing::instrument(skip_all, fields(file=%info.id()))]
-    async fn b2_cancel_large_file(&self, info: &FileInfo) -> Result<(), B2RequestError> {
+    pub(crate) async fn b2_cancel_large_file(&self, info: &FileInfo) -> Result<(), B2RequestError> {
         let body = CancelLargeFileBody {
             file_id: info.id().clone(),
         };
@@ -296,41 +294,24 @@ impl B2Client {
         Ok(())
     }
 
+    /// Spawn a task into `tasks` which uploads a single part, returning its
+    /// part number and digest.
     #[tracing::instrument("part", skip_all, fields(part=%part))]
-    async fn upload_part_inner(
+    async fn spawn_part(
         &self,
-        semaphore: Arc<tokio::sync::Semaphore>,
-        mut file: &mut Reader<'_>,
+        tasks: &mut JoinSet<Result<(usize, FileDigest), B2RequestError>>,
+        permit: tokio::sync::OwnedSemaphorePermit,
+        buffer: Vec<u8>,
         part: usize,
-        part_size: usize,
         info: &FileInfo,
-    ) -> Result<Option<JoinHandle<Result<FileDigest, B2RequestError>>>, B2RequestError> {
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
-
-        tracing::trace!("Gathering chunk");
-        let mut buffer = Vec::with_capacity(part_size);
-        let mut chunk = (&mut file).take(part_size as u64);
-
-        tokio::io::copy_buf(&mut chunk, &mut buffer).await?;
-
-        while buffer.len() < part_size {
-            if chunk.read_buf(&mut buffer).await? == 0 {
-                break;
-            }
-        }
-
-        if buffer.is_empty() {
-            tracing::trace!("Empty buffer, breaking");
-            return Ok(None);
-        }
-
+    ) -> Result<(), B2RequestError> {
         tracing::trace!("Preparing upload");
         let retries = self.uploads.retries;
         let file_id = info.id().clone();
         let mut uploader = self.b2_get_upload_part_url(file_id.clone()).await?;
         let client = self.clone();
         tracing::trace!("Spawning upload");
-        let handle = tokio::spawn(
+        tasks.spawn(
             async move {
                 tracing::trace!("digesting");
                 let buffer = bytes::Bytes::from(buffer);
@@ -339,8 +320,7 @@ impl B2Client {
                     move || digest(&buffer as &[u8])
                 })
                 .in_current_span()
-                .await
-                .expect("blocking thread")?;
+                .await??;
 
                 for attempt in 1..=retries {
                     tracing::trace!(%attempt, "uploading part");
@@ -350,7 +330,7 @@ impl B2Client {
                         .await
                     {
                         Ok(()) => {
-                            return Ok::<_, B2RequestError>(digest);
+                            return Ok((part, digest));
                         }
                         // Err(B2RequestError::Request(error)) if error.is_timeout() => {
                         //     uploader.increase_timeout();
@@ -371,45 +351,74 @@ impl B2Client {
             }
             .in_current_span(),
         );
-        Ok(Some(handle))
+        Ok(())
     }
 
+    /// Upload parts as they are read from the file, starting with `first`.
+    ///
+    /// Each part holds a permit while it is read and uploaded, so at most
+    /// `concurrency` parts are buffered in memory at once. If any part fails,
+    /// reading stops and the parts still uploading are aborted.
     async fn upload_multipart_inner(
         &self,
         file: &mut Reader<'_>,
         filename: &Utf8Path,
         part_size: usize,
         info: &FileInfo,
-        content_length: usize,
+        first: Vec<u8>,
+        progress: Option<&Progress>,
     ) -> Result<(), B2RequestError> {
-        tracing::debug!("File {filename} is larger than 1GB, using large file upload");
-
         let semaphore = Arc::new(tokio::sync::Semaphore::new(self.uploads.concurrency));
-        let parts = (content_length / part_size) + 1;
 
-        let mut handles = Vec::with_capacity(parts);
+        // Dropping the task set when returning early aborts any uploads in flight.
+        let mut tasks = JoinSet::new();
+        let mut digests = Vec::new();
+        let mut buffer = first;
+        let mut uploaded = 0;
+
+        let mut finished = |part: (usize, FileDigest), digests: &mut Vec<(usize, FileDigest)>| {
+            uploaded += part.1.content_length() as u64;
+            tracing::debug!(part = part.0, uploaded, "Uploaded part of {filename}");
+            if let Some(progress) = progress {
+                progress.report(uploaded);
+            }
+            digests.push(part);
+        };
 
-        for part in 1..=parts {
-            let handle = self
-                .upload_part_inner(semaphore.clone(), file, part, part_size, info)
-                .await?;
-            if let Some(handle) = handle {
-                handles.push(handle.map(|r| match r {
-                    Ok(Ok(sha)) => Ok(sha),
-                    Ok(Err(error)) => Err(error),
-                    Err(_) => panic!("upload task paniced"),
-                }));
+        for part in 1.. {
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("upload semaphore is never closed");
+
+            while let Some(result) = tasks.try_join_next() {
+                finished(result??, &mut digests);
+            }
+
+            if part > 1 {
+                tracing::trace!(%part, "Gathering chunk");
+                buffer = read_part(file, part_size).await?;
+            }
+
+            if buffer.is_empty() {
+                tracing::trace!("Empty buffer, breaking");
+                break;
             }
-        }
 
-        semaphore.close();
+            self.spawn_part(&mut tasks, permit, std::mem::take(&mut buffer), part, info)
+                .await?;
+        }
 
         tracing::trace!("Waiting for uploads to complete");
-        let digests = futures::future::try_join_all(handles).await?;
+        while let Some(result) = tasks.join_next().await {
+            finished(result??, &mut digests);
+        }
         let parts_uploaded = digests.len();
         tracing::debug!("Uploaded {filename} in {parts_uploaded} parts");
 
-        let shas: Vec<[u8; 20]> = digests.iter().map(|d| d.digest).collect();
+        digests.sort_by_key(|(part, _)| *part);
+        let shas: Vec<[u8; 20]> = digests.iter().map(|(_, d)| d.digest).collect();
 
         self.b2_finish_large_file(info, &shas).await?;
 
@@ -425,55 +434,74 @@ impl B2Client {
         content_length: usize,
         content_sha: &[u8],
     ) -> Result<(), B2RequestError> {
-        let part_size = self.authorization().recommended_part_size();
+        let part_size = self.part_size();
         let parts = (content_length / part_size) + 1;
 
-        if content_length >= crate::B2_LARGE_FILE_SIZE && parts > 1 {
-            self.upload_large_file(bucket, file, filename, content_type, content_length)
+        if content_length >= self.uploads.large_file_size && parts > 1 {
+            tracing::debug!("File {filename} is a large file, using large file upload");
+            self.upload_large_file(bucket, file, filename, content_type)
                 .await
         } else {
-            tracing::trace!("upload as single part");
-
-            let mut uploader = self.b2_get_upload_url(bucket.clone()).await?;
-
             let body: Bytes = {
                 let mut body = Vec::with_capacity(content_length);
                 file.read_to_end(&mut body).await?;
                 body.into()
             };
 
-            for attempt in 1..=self.uploads.retries {
-                tracing::trace!(%attempt, "uploading");
-
-                match uploader
-                    .b2_upload_file(
-                        body.clone().into(),
-                        filename,
-                        content_type.clone(),
-                        content_length,
-                        content_sha,
-                    )
-                    .await
+            self.upload_single(bucket, body, filename, content_type, content_sha)
+                .await
+        }
+    }
+
+    async fn upload_single(
+        &self,
+        bucket: BucketID,
+        body: Bytes,
+        filename: &Utf8Path,
+        content_type: Option<mime::Mime>,
+        content_sha: &[u8],
+    ) -> Result<(), B2RequestError> {
+        tracing::trace!("upload as single part");
+
+        let mut uploader = self.b2_get_upload_url(bucket.clone()).await?;
+
+        for attempt in 1..=self.uploads.retries {
+            tracing::trace!(%attempt, "uploading");
+
+            match uploader
+                .b2_upload_file(
+                    body.clone().into(),
+                    filename,
+                    content_type.clone(),
+                    body.len(),
+                    content_sha,
+                )
+                .await
+            {
+                Ok(()) => {
+                    return Ok(());
+                }
+                Err(B2RequestError::B2(error))
+                    if error.status_code() == StatusCode::SERVICE_UNAVAILABLE =>
                 {
-                    Ok(()) => {
-                        return Ok(());
-                    }
-                    Err(B2RequestError::B2(error))
-                        if error.status_code() == StatusCode::SERVICE_UNAVAILABLE =>
-                    {
-                        tracing::debug!("Re-trying upload, service was not available");
-                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                        uploader = self.b2_get_upload_url(bucket.clone()).await?;
-                    }
-                    Err(error) => {
-                        return Err(error);
-                    }
+                    tracing::debug!("Re-trying upload, service was not available");
+                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    uploader = self.b2_get_upload_url(bucket.clone()).await?;
+                }
+                Err(error) => {
+                    return Err(error);
                 }
             }
-            Err(B2RequestError::RetriesExhausted)
         }
+        Err(B2RequestError::RetriesExhausted)
     }
 
+    /// Upload from a reader without knowing its length up front.
+    ///
+    /// Objects which fit in a single part are uploaded in one request. Larger objects
+    /// are streamed as a large file, holding at most `concurrency` parts in memory.
+    ///
+    /// Progress is reported as each part finishes uploading.
     #[tracing::instrument(skip_all, fields(%bucket, remote=%filename.file_name().unwrap()))]
     pub(crate) async fn upload_reader(
         &self,
@@ -481,32 +509,41 @@ impl B2Client {
         reader: &mut Reader<'_>,
         filename: &Utf8Path,
         content_type: Option<mime::Mime>,
+        progress: Option<&Progress>,
     ) -> Result<(), B2RequestError> {
-        let buffer = {
-            let mut buffer = Vec::new();
-            reader.read_to_end(&mut buffer).await?;
-            bytes::Bytes::from(buffer)
-        };
+        let part_size = self.part_size();
+        let first = read_part(reader, part_size).await?;
+
+        if !reader.fill_buf().await?.is_empty() {
+            tracing::debug!("File {filename} is larger than one part, using large file upload");
+            return self
+                .upload_parts(
+                    bucket,
+                    reader,
+                    filename,
+                    content_type,
+                    part_size,
+                    first,
+                    progress,
+                )
+                .await;
+        }
 
+        let buffer = bytes::Bytes::from(first);
         let digest = tokio::task::spawn_blocking({
             let buffer = buffer.clone();
             move || digest(&buffer as &[u8])
         })
         .in_current_span()
-        .await
-        .expect("blocking thread")?;
-
-        let mut reader = tokio::io::BufReader::new(buffer.deref());
+        .await??;
 
-        self.upload_inner(
-            bucket,
-            &mut reader,
-            filename,
-            content_type,
-            digest.content_length(),
-            digest.digest(),
-        )
-        .await
+        let length = buffer.len() as u64;
+        self.upload_single(bucket, buffer, filename, content_type, digest.digest())
+            .await?;
+        if let Some(progress) = progress {
+            progress.report(length);
+        }
+        Ok(())
     }
 
     #[tracing::instrument(skip_all, fields(%bucket, local=%local.file_name().unwrap(), remote=%remote.file_name().unwrap()))]
@@ -520,16 +557,14 @@ impl B2Client {
         tracing::trace!("Computing SHA1 file digest");
         let filename = local.to_owned();
         let digest = tokio::task::spawn_blocking(move || {
-            let file = std::fs::File::open(filename).expect("open file");
-            let mut rdr = io::BufReader::new(file);
-            digest(&mut rdr).expect("digest")
+            let file = std::fs::File::open(filename)?;
+            digest(io::BufReader::new(file))
         })
         .in_current_span()
-        .await
-        .expect("blocking thread");
+        .await??;
 
         tracing::trace!("Preparing reader stream for send");
-        let mut file = tokio::io::BufReader::new(tokio::fs::File::open(local).await.unwrap());
+        let mut file = tokio::io::BufReader::new(tokio::fs::File::open(local).await?);
 
         tracing::trace!("uploading");
         self.upload_inner(
@@ -546,6 +581,9 @@ impl B2Client {
     }
 
     /// Upload a large file using the B2 API
+    ///
+    /// The file is streamed in parts of the recommended part size, and must contain
+    /// at least two parts.
     #[tracing::instrument(skip_all, fields(%bucket, remote=%filename.file_name().unwrap()))]
     pub async fn upload_large_file(
         &self,
@@ -553,7 +591,23 @@ impl B2Client {
         file: &mut Reader<'_>,
         filename: &Utf8Path,
         content_type: Option<mime::Mime>,
-        content_length: usize,
+    ) -> Result<(), B2RequestError> {
+        let part_size = self.part_size();
+        let first = read_part(file, part_size).await?;
+        self.upload_parts(bucket, file, filename, content_type, part_size, first, None)
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn upload_parts(
+        &self,
+        bucket: BucketID,
+        file: &mut Reader<'_>,
+        filename: &Utf8Path,
+        content_type: Option<mime::Mime>,
+        part_size: usize,
+        first: Vec<u8>,
+        progress: Option<&Progress>,
     ) -> Result<(), B2RequestError> {
         tracing::trace!("Multi-part upload");
 
@@ -562,28 +616,99 @@ impl B2Client {
             .await?;
 
         tracing::info!(file=?info.id(), "Multi-part upload");
+        let guard = UnfinishedLargeFile::new(self.clone(), info.clone());
 
         match self
-            .upload_multipart_inner(
-                file,
-                filename,
-                self.authorization().recommended_part_size(),
-                &info,
-                content_length,
-            )
+            .upload_multipart_inner(file, filename, part_size, &info, first, progress)
             .await
         {
             Ok(_) => {
                 tracing::info!(file=?info.id(), "Finished multi-part upload");
+                guard.finished();
                 Ok(())
             }
             Err(error) => {
                 tracing::error!(file=?info.id(), "Error during multi-part upload: {error}");
 
                 let _ = self.b2_cancel_large_file(&info).await;
+                guard.finished();
 
                 Err(error)
             }
         }
     }
 }
+
+/// Cancels a large file if its upload or copy is dropped part way through, so that
+/// the parts already uploaded don't linger in the bucket.
+///
+/// Dropping the upload aborts the parts in flight, but the cancellation is
+/// itself a request, so it is spawned onto the runtime.
+pub(crate) struct UnfinishedLargeFile {
+    client: B2Client,
+    info: Option<FileInfo>,
+}
+
+impl UnfinishedLargeFile {
+    pub(crate) fn new(client: B2Client, info: FileInfo) -> Self {
+        Self {
+            client,
+            info: Some(info),
+        }
+    }
+
+    /// The large file was finished or cancelled, so there is nothing to clean up.
+    pub(crate) fn finished(mut self) {
+        self.info = None;
+    }
+}
+
+impl Drop for UnfinishedLargeFile {
+    fn drop(&mut self) {
+        let Some(info) = self.info.take() else {
+            return;
+        };
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!(file=?info.id(), "Upload dropped outside of a runtime, large file was not cancelled");
+            return;
+        };
+
+        tracing::debug!(file=?info.id(), "Upload dropped, cancelling large file");
+        let client = self.client.clone();
+        runtime.spawn(
+            async move {
+                if let Err(error) = client.b2_cancel_large_file(&info).await {
+                    tracing::warn!(file=?info.id(), "Failed to cancel dropped large file: {error}");
+                }
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Read up to `part_size` bytes from the file, returning fewer only at the end of the file.
+async fn read_part(mut file: &mut Reader<'_>, part_size: usize) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(part_size);
+    (&mut file)
+        .take(part_size as u64)
+        .read_to_end(&mut buffer)
+        .await?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn read_parts() {
+        let data = b"abcdefghij".to_vec();
+        let mut reader = tokio::io::BufReader::new(&data[..]);
+
+        assert_eq!(read_part(&mut reader, 4).await.unwrap(), b"abcd");
+        assert_eq!(read_part(&mut reader, 4).await.unwrap(), b"efgh");
+        assert_eq!(read_part(&mut reader, 4).await.unwrap(), b"ij");
+        assert!(read_part(&mut reader, 4).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,315 @@
+//! The catalog of Linode instance types and regions, with pricing.
+//!
+//! [`Requirements::cheapest`] picks the least expensive type which satisfies
+//! CPU, memory and disk constraints in a region, for cost-aware scaling.
+
+use futures::TryStreamExt as _;
+use serde::Deserialize;
+
+use crate::{LinodeClient, LinodeError, Paginated, Result};
+
+/// Price of an instance type.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Price {
+    /// Cost per hour, in US dollars.
+    pub hourly: f64,
+
+    /// Cost per month, in US dollars.
+    pub monthly: f64,
+}
+
+/// Price of an instance type in a region which doesn't use the default price.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RegionPrice {
+    /// The region ID.
+    pub id: String,
+
+    /// Cost per hour, in US dollars.
+    pub hourly: f64,
+
+    /// Cost per month, in US dollars.
+    pub monthly: f64,
+}
+
+/// Class of an instance type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TypeClass {
+    /// Shared CPU "Nanode" instances.
+    Nanode,
+
+    /// Shared CPU instances.
+    Standard,
+
+    /// Dedicated CPU instances.
+    Dedicated,
+
+    /// High memory instances.
+    Highmem,
+
+    /// Premium CPU instances.
+    Premium,
+
+    /// GPU instances.
+    Gpu,
+
+    /// Any other class Linode reports.
+    #[serde(other)]
+    Other,
+}
+
+/// An instance type (plan) which Linodes can be created with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LinodeType {
+    /// The type ID, e.g. `g6-standard-2`.
+    pub id: String,
+
+    /// A human readable label, e.g. `Linode 4GB`.
+    pub label: String,
+
+    /// The class of the type.
+    pub class: TypeClass,
+
+    /// Number of virtual CPUs.
+    pub vcpus: u32,
+
+    /// Memory, in MB.
+    pub memory: u64,
+
+    /// Disk space, in MB.
+    pub disk: u64,
+
+    /// Number of GPUs.
+    #[serde(default)]
+    pub gpus: u32,
+
+    /// Monthly outbound transfer allowance, in GB.
+    #[serde(default)]
+    pub transfer: u64,
+
+    /// The default price.
+    pub price: Price,
+
+    /// Prices in regions which don't use the default price.
+    #[serde(default)]
+    pub region_prices: Vec<RegionPrice>,
+
+    /// The type which replaces this one, if it is being retired.
+    #[serde(default)]
+    pub successor: Option<String>,
+}
+
+impl LinodeType {
+    /// The price of this type in a region.
+    pub fn price_in(&self, region: &str) -> Price {
+        self.region_prices
+            .iter()
+            .find(|price| price.id == region)
+            .map(|price| Price {
+                hourly: price.hourly,
+                monthly: price.monthly,
+            })
+            .unwrap_or(self.price)
+    }
+}
+
+/// A region where Linode resources can be created.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Region {
+    /// The region ID, e.g. `us-east`.
+    pub id: String,
+
+    /// A human readable label.
+    #[serde(default)]
+    pub label: String,
+
+    /// Country code of the region.
+    pub country: String,
+
+    /// Services available in the region, e.g. `Linodes` or `Cloud Firewall`.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+
+    /// Whether the region is `ok` or in an `outage`.
+    pub status: String,
+}
+
+impl Region {
+    /// Check if a service is available in this region.
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(capability))
+    }
+}
+
+/// Minimum resources required of an instance type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Requirements {
+    vcpus: u32,
+    memory: u64,
+    disk: u64,
+    gpus: u32,
+    classes: Vec<TypeClass>,
+}
+
+impl Requirements {
+    /// Require nothing, which matches every type.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require at least this many virtual CPUs.
+    pub fn vcpus(mut self, vcpus: u32) -> Self {
+        self.vcpus = vcpus;
+        self
+    }
+
+    /// Require at least this much memory, in MB.
+    pub fn memory(mut self, memory: u64) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    /// Require at least this much disk space, in MB.
+    pub fn disk(mut self, disk: u64) -> Self {
+        self.disk = disk;
+        self
+    }
+
+    /// Require at least this many GPUs.
+    pub fn gpus(mut self, gpus: u32) -> Self {
+        self.gpus = gpus;
+        self
+    }
+
+    /// Only consider types of this class. May be called more than once to allow
+    /// several classes.
+    pub fn class(mut self, class: TypeClass) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    /// Check if a type satisfies these requirements.
+    pub fn matches(&self, linode_type: &LinodeType) -> bool {
+        linode_type.vcpus >= self.vcpus
+            && linode_type.memory >= self.memory
+            && linode_type.disk >= self.disk
+            && linode_type.gpus >= self.gpus
+            && (self.classes.is_empty() || self.classes.contains(&linode_type.class))
+    }
+
+    /// The cheapest type which satisfies these requirements in a region.
+    ///
+    /// Retired types (those with a successor) are skipped. Ties are broken by
+    /// preferring more memory.
+    pub fn cheapest<'t>(&self, types: &'t [LinodeType], region: &str) -> Option<&'t LinodeType> {
+        types
+            .iter()
+            .filter(|linode_type| linode_type.successor.is_none() && self.matches(linode_type))
+            .min_by(|a, b| {
+                a.price_in(region)
+                    .hourly
+                    .total_cmp(&b.price_in(region).hourly)
+                    .then(b.memory.cmp(&a.memory))
+            })
+    }
+}
+
+impl LinodeClient {
+    /// List all instance types.
+    #[tracing::instrument(skip(self))]
+    pub fn list_linode_types(&self) -> Paginated<LinodeType> {
+        self.get_paginated("linode/types")
+    }
+
+    /// List all regions.
+    #[tracing::instrument(skip(self))]
+    pub fn list_regions(&self) -> Paginated<Region> {
+        self.get_paginated("regions")
+    }
+
+    /// Find the cheapest instance type which satisfies `requirements` in a region.
+    #[tracing::instrument(skip(self))]
+    pub async fn recommend_linode_type(
+        &self,
+        region: &str,
+        requirements: &Requirements,
+    ) -> Result<Option<LinodeType>> {
+        let types: Vec<LinodeType> = self
+            .list_linode_types()
+            .try_collect()
+            .await
+            .map_err(|error| LinodeError::Request(api_client::Error::ResponseBody(error)))?;
+
+        let recommended = requirements.cheapest(&types, region).cloned();
+        if let Some(linode_type) = &recommended {
+            tracing::debug!(
+                "Recommended {} at ${}/hr in {region}",
+                linode_type.id,
+                linode_type.price_in(region).hourly
+            );
+        }
+        Ok(recommended)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types() -> Vec<LinodeType> {
+        serde_json::from_value(serde_json::json!([
+            {
+                "id": "g6-nanode-1", "label": "Nanode 1GB", "class": "nanode",
+                "vcpus": 1, "memory": 1024, "disk": 25600,
+                "price": {"hourly": 0.0075, "monthly": 5.0},
+                "region_prices": [{"id": "id-cgk", "hourly": 0.009, "monthly": 6.0}]
+            },
+            {
+                "id": "g6-standard-2", "label": "Linode 4GB", "class": "standard",
+                "vcpus": 2, "memory": 4096, "disk": 81920,
+                "price": {"hourly": 0.036, "monthly": 24.0}
+            },
+            {
+                "id": "g6-dedicated-2", "label": "Dedicated 4GB", "class": "dedicated",
+                "vcpus": 2, "memory": 4096, "disk": 81920,
+                "price": {"hourly": 0.054, "monthly": 36.0}
+            },
+            {
+                "id": "g6-standard-old", "label": "Linode 4GB (retired)", "class": "standard",
+                "vcpus": 2, "memory": 4096, "disk": 81920,
+                "price": {"hourly": 0.03, "monthly": 20.0},
+                "successor": "g6-standard-2"
+            }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn cheapest_type() {
+        let types = types();
+
+        let nanode = Requirements::new().cheapest(&types, "us-east").unwrap();
+        assert_eq!(nanode.id, "g6-nanode-1");
+        assert_eq!(nanode.price_in("id-cgk").monthly, 6.0);
+        assert_eq!(nanode.price_in("us-east").monthly, 5.0);
+
+        let requirements = Requirements::new().vcpus(2).memory(2048);
+        assert_eq!(
+            requirements.cheapest(&types, "us-east").unwrap().id,
+            "g6-standard-2"
+        );
+
+        let requirements = requirements.class(TypeClass::Dedicated);
+        assert_eq!(
+            requirements.cheapest(&types, "us-east").unwrap().id,
+            "g6-dedicated-2"
+        );
+
+        assert!(Requirements::new()
+            .memory(8192)
+            .cheapest(&types, "us-east")
+            .is_none());
+    }
+}
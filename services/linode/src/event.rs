@@ -0,0 +1,493 @@
+//! Account events and notifications.
+//!
+//! Linode records an event for each change to an account, e.g. creating an
+//! instance or attaching a volume. Long-running changes start as a `started`
+//! event which later becomes `finished` or `failed`, so
+//! [`LinodeClient::wait_for_event`] can be used to wait for them to finish.
+
+use std::fmt;
+use std::time::Duration;
+
+use api_client::PaginatedData;
+use serde::{Deserialize, Serialize};
+
+use crate::{Empty, LinodeClient, LinodeError, LinodeID, Paginated, Paginator, Result};
+
+/// How often to check for an event while waiting for it to finish.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The ID of a Linode event.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct EventID(LinodeID);
+
+impl fmt::Display for EventID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The action recorded by an event.
+///
+/// Linode records many more actions than are named here. Those are kept as
+/// [`EventAction::Other`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum EventAction {
+    /// An instance was created.
+    LinodeCreate,
+
+    /// An instance was booted.
+    LinodeBoot,
+
+    /// An instance was rebooted.
+    LinodeReboot,
+
+    /// An instance was shut down.
+    LinodeShutdown,
+
+    /// An instance was rebuilt from an image.
+    LinodeRebuild,
+
+    /// An instance was resized.
+    LinodeResize,
+
+    /// An instance was deleted.
+    LinodeDelete,
+
+    /// A disk was created.
+    DiskCreate,
+
+    /// A disk was resized.
+    DiskResize,
+
+    /// A volume was created.
+    VolumeCreate,
+
+    /// A volume was attached to an instance.
+    VolumeAttach,
+
+    /// A volume was detached from an instance.
+    VolumeDetach,
+
+    /// A volume was resized.
+    VolumeResize,
+
+    /// A volume was deleted.
+    VolumeDelete,
+
+    /// Any other action, by its name in the Linode API.
+    Other(String),
+}
+
+impl EventAction {
+    /// The name of the action in the Linode API, e.g. `linode_create`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventAction::LinodeCreate => "linode_create",
+            EventAction::LinodeBoot => "linode_boot",
+            EventAction::LinodeReboot => "linode_reboot",
+            EventAction::LinodeShutdown => "linode_shutdown",
+            EventAction::LinodeRebuild => "linode_rebuild",
+            EventAction::LinodeResize => "linode_resize",
+            EventAction::LinodeDelete => "linode_delete",
+            EventAction::DiskCreate => "disk_create",
+            EventAction::DiskResize => "disk_resize",
+            EventAction::VolumeCreate => "volume_create",
+            EventAction::VolumeAttach => "volume_attach",
+            EventAction::VolumeDetach => "volume_detach",
+            EventAction::VolumeResize => "volume_resize",
+            EventAction::VolumeDelete => "volume_delete",
+            EventAction::Other(action) => action,
+        }
+    }
+}
+
+impl fmt::Display for EventAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for EventAction {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "linode_create" => EventAction::LinodeCreate,
+            "linode_boot" => EventAction::LinodeBoot,
+            "linode_reboot" => EventAction::LinodeReboot,
+            "linode_shutdown" => EventAction::LinodeShutdown,
+            "linode_rebuild" => EventAction::LinodeRebuild,
+            "linode_resize" => EventAction::LinodeResize,
+            "linode_delete" => EventAction::LinodeDelete,
+            "disk_create" => EventAction::DiskCreate,
+            "disk_resize" => EventAction::DiskResize,
+            "volume_create" => EventAction::VolumeCreate,
+            "volume_attach" => EventAction::VolumeAttach,
+            "volume_detach" => EventAction::VolumeDetach,
+            "volume_resize" => EventAction::VolumeResize,
+            "volume_delete" => EventAction::VolumeDelete,
+            _ => EventAction::Other(value),
+        }
+    }
+}
+
+impl From<EventAction> for String {
+    fn from(value: EventAction) -> Self {
+        match value {
+            EventAction::Other(action) => action,
+            action => action.as_str().to_owned(),
+        }
+    }
+}
+
+/// The status of an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventStatus {
+    /// The action is scheduled to start.
+    Scheduled,
+
+    /// The action has started, and is not yet finished.
+    Started,
+
+    /// The action finished successfully.
+    Finished,
+
+    /// The action failed.
+    Failed,
+
+    /// The event is a notification, which has no progress.
+    Notification,
+}
+
+impl fmt::Display for EventStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            EventStatus::Scheduled => "scheduled",
+            EventStatus::Started => "started",
+            EventStatus::Finished => "finished",
+            EventStatus::Failed => "failed",
+            EventStatus::Notification => "notification",
+        };
+        f.write_str(status)
+    }
+}
+
+/// The resource an event or notification is about.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Entity {
+    /// The ID of the resource.
+    pub id: LinodeID,
+
+    /// The label of the resource.
+    #[serde(default)]
+    pub label: String,
+
+    /// The kind of resource, e.g. `linode` or `volume`.
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// The API URL of the resource.
+    #[serde(default)]
+    pub url: String,
+}
+
+/// An event on the account.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Event {
+    /// The ID of the event.
+    pub id: EventID,
+
+    /// The action which caused the event.
+    pub action: EventAction,
+
+    /// The status of the action.
+    pub status: EventStatus,
+
+    /// The resource the action was applied to, if any.
+    #[serde(default)]
+    pub entity: Option<Entity>,
+
+    /// A second resource involved in the action, e.g. the instance a volume was
+    /// attached to.
+    #[serde(default)]
+    pub secondary_entity: Option<Entity>,
+
+    /// How much of the action is complete, as a percentage.
+    #[serde(default)]
+    pub percent_complete: Option<u8>,
+
+    /// When the event was created, in UTC.
+    pub created: String,
+
+    /// The user who caused the event, if it wasn't caused by Linode.
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// Additional information about the event.
+    #[serde(default)]
+    pub message: Option<String>,
+
+    /// Whether the event has been marked as seen.
+    #[serde(default)]
+    pub seen: bool,
+}
+
+/// How urgent a notification is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationSeverity {
+    /// The notification is informational.
+    Minor,
+
+    /// The notification should be addressed.
+    Major,
+
+    /// The notification needs immediate attention.
+    Critical,
+}
+
+/// A notification on the account, e.g. about scheduled maintenance.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Notification {
+    /// The kind of notification, e.g. `maintenance` or `outage`.
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// A short description of the notification.
+    pub label: String,
+
+    /// The full notification message.
+    pub message: String,
+
+    /// How urgent the notification is.
+    pub severity: NotificationSeverity,
+
+    /// The resource the notification is about, if any.
+    #[serde(default)]
+    pub entity: Option<Entity>,
+
+    /// When the notified condition starts, in UTC.
+    #[serde(default)]
+    pub when: Option<String>,
+
+    /// When the notified condition ends, in UTC.
+    #[serde(default)]
+    pub until: Option<String>,
+}
+
+/// Filter for the most recent event for an entity and action.
+#[derive(Debug, Serialize)]
+struct EventFilter<'a> {
+    #[serde(rename = "entity.id")]
+    entity: LinodeID,
+    action: &'a EventAction,
+    #[serde(rename = "+order_by")]
+    order_by: &'static str,
+    #[serde(rename = "+order")]
+    order: &'static str,
+}
+
+impl LinodeClient {
+    /// List events on the account.
+    #[tracing::instrument(skip(self))]
+    pub fn list_events(&self) -> Paginated<Event> {
+        self.get_paginated("account/events")
+    }
+
+    /// Get an event by its ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_event(&self, id: EventID) -> Result<Event> {
+        self.get(&format!("account/events/{id}")).await
+    }
+
+    /// Mark an event, and every earlier event, as seen.
+    #[tracing::instrument(skip(self))]
+    pub async fn mark_event_seen(&self, id: EventID) -> Result<()> {
+        self.post::<_, Empty>(&format!("account/events/{id}/seen"), &serde_json::json!({}))
+            .await?;
+        tracing::debug!("Marked events up to {id} as seen");
+        Ok(())
+    }
+
+    /// List notifications on the account.
+    #[tracing::instrument(skip(self))]
+    pub fn list_notifications(&self) -> Paginated<Notification> {
+        self.get_paginated("account/notifications")
+    }
+
+    /// Get the most recent event for an action on an entity, e.g. `linode_create`
+    /// on a new instance.
+    #[tracing::instrument(skip(self))]
+    pub async fn latest_event(
+        &self,
+        entity: LinodeID,
+        action: &EventAction,
+    ) -> Result<Option<Event>> {
+        let filter = EventFilter {
+            entity,
+            action,
+            order_by: "created",
+            order: "desc",
+        };
+        let request = self
+            .inner
+            .get("account/events")
+            .header("X-Filter", serde_json::to_string(&filter)?);
+
+        // Only the first page is checked, so that a filter which isn't applied
+        // doesn't page through the account's whole history.
+        let page: PaginatedData<Event, Paginator> = self.execute_and_deserialize(request).await?;
+        Ok(page.data.into_iter().find(|event| {
+            event.action == *action && event.entity.as_ref().map(|e| e.id) == Some(entity)
+        }))
+    }
+
+    /// Wait until a new event for an action on an entity is finished, checking
+    /// it every few seconds.
+    ///
+    /// Linode records the event when it accepts the request, which may be after
+    /// an earlier event for the same action. Pass the ID of the latest event from
+    /// before the action was started, from [`LinodeClient::latest_event`], as
+    /// `after`, so that the earlier event isn't mistaken for the new one.
+    ///
+    /// Returns [`LinodeError::EventFailed`] if the action fails, or
+    /// [`LinodeError::Timeout`] if it doesn't finish within `timeout`.
+    #[tracing::instrument(skip(self))]
+    pub async fn wait_for_event(
+        &self,
+        entity: LinodeID,
+        action: EventAction,
+        after: Option<EventID>,
+        timeout: Duration,
+    ) -> Result<Event> {
+        let wait = async {
+            loop {
+                let event = self
+                    .latest_event(entity, &action)
+                    .await?
+                    .filter(|event| Some(event.id) != after);
+
+                match event {
+                    Some(event) if event.status == EventStatus::Finished => return Ok(event),
+                    Some(event) if event.status == EventStatus::Failed => {
+                        return Err(LinodeError::EventFailed {
+                            id: event.id,
+                            action: event.action,
+                            message: event.message.unwrap_or_default(),
+                        })
+                    }
+                    Some(event) => tracing::trace!(
+                        "Event {} is {} ({}% complete), waiting for it to finish",
+                        event.id,
+                        event.status,
+                        event.percent_complete.unwrap_or_default()
+                    ),
+                    None => tracing::trace!("No new {action} event for {entity} yet"),
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| LinodeError::Timeout {
+                kind: "event",
+                value: format!("{action} on {entity}"),
+                status: EventStatus::Finished.to_string(),
+            })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event() {
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "action": "volume_attach",
+            "created": "2018-01-01T00:01:01",
+            "duration": 300.56,
+            "entity": {
+                "id": 11111,
+                "label": "my-volume",
+                "type": "volume",
+                "url": "/v4/volumes/11111"
+            },
+            "id": 123,
+            "message": null,
+            "percent_complete": 40,
+            "rate": null,
+            "read": true,
+            "secondary_entity": {
+                "id": 67890,
+                "label": "web-1",
+                "type": "linode",
+                "url": "/v4/linode/instances/67890"
+            },
+            "seen": true,
+            "status": "started",
+            "time_remaining": null,
+            "username": "exampleUser"
+        }))
+        .unwrap();
+        assert_eq!(event.id.to_string(), "123");
+        assert_eq!(event.action, EventAction::VolumeAttach);
+        assert_eq!(event.status, EventStatus::Started);
+        assert_eq!(event.entity.unwrap().kind, "volume");
+        assert_eq!(event.secondary_entity.unwrap().id, LinodeID(67890));
+        assert_eq!(event.percent_complete, Some(40));
+
+        let event: Event = serde_json::from_value(serde_json::json!({
+            "action": "account_update",
+            "created": "2018-01-01T00:01:01",
+            "entity": null,
+            "id": 124,
+            "status": "notification",
+        }))
+        .unwrap();
+        assert_eq!(event.action, EventAction::Other("account_update".into()));
+        assert!(event.entity.is_none());
+    }
+
+    #[test]
+    fn event_filter() {
+        let filter = EventFilter {
+            entity: LinodeID(67890),
+            action: &EventAction::LinodeCreate,
+            order_by: "created",
+            order: "desc",
+        };
+        assert_eq!(
+            serde_json::to_value(&filter).unwrap(),
+            serde_json::json!({
+                "entity.id": 67890,
+                "action": "linode_create",
+                "+order_by": "created",
+                "+order": "desc",
+            })
+        );
+    }
+
+    #[test]
+    fn notification() {
+        let notification: Notification = serde_json::from_value(serde_json::json!({
+            "body": null,
+            "entity": {
+                "id": 3456,
+                "label": "Linode not booting.",
+                "type": "ticket",
+                "url": "/support/tickets/3456"
+            },
+            "label": "You have an important ticket open!",
+            "message": "You have an important ticket open!",
+            "severity": "major",
+            "type": "ticket_important",
+            "until": null,
+            "when": null
+        }))
+        .unwrap();
+        assert_eq!(notification.severity, NotificationSeverity::Major);
+        assert_eq!(notification.kind, "ticket_important");
+    }
+}
@@ -0,0 +1,631 @@
+//! DNS-based failover between a primary and a standby target.
+//!
+//! A [`FailoverMonitor`] periodically probes both targets, and points the A/AAAA
+//! records for a name at whichever target should be serving. Transitions are
+//! damped by [`Hysteresis`], so that a single failed probe does not flip DNS, and
+//! every transition is published as a [`FailoverEvent`].
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+use crate::{
+    Domain, Instance, InstanceStatus, LinodeClient, LinodeError, LinodeID, RecordType, Result,
+    SubDomain,
+};
+
+/// A health check for a failover target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Probe {
+    /// The Linode instance backing the target must be running.
+    Status,
+
+    /// A TCP connection to the port must succeed.
+    Tcp {
+        /// Port to connect to.
+        port: u16,
+    },
+
+    /// An HTTP GET request to the path must return a 2xx or 3xx status.
+    Http {
+        /// Port to connect to.
+        port: u16,
+
+        /// Path to request.
+        path: String,
+
+        /// Host header to send, defaults to the target address.
+        host: Option<String>,
+    },
+}
+
+impl Probe {
+    /// A TCP connection probe.
+    pub fn tcp(port: u16) -> Self {
+        Probe::Tcp { port }
+    }
+
+    /// An HTTP probe for a path.
+    pub fn http(port: u16, path: impl Into<String>) -> Self {
+        Probe::Http {
+            port,
+            path: path.into(),
+            host: None,
+        }
+    }
+}
+
+/// One side of a failover pair: the addresses to publish, and how to check them.
+#[derive(Debug, Clone)]
+pub struct Target {
+    label: String,
+    instance: Option<LinodeID>,
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+    probes: Vec<Probe>,
+}
+
+impl Target {
+    /// Create a target with no addresses or probes.
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            instance: None,
+            ipv4: None,
+            ipv6: None,
+            probes: Vec::new(),
+        }
+    }
+
+    /// Create a target for a Linode instance, using its public addresses
+    /// and checking its status.
+    pub fn from_instance(instance: &Instance) -> Self {
+        Self {
+            label: instance.label().into(),
+            instance: Some(instance.id()),
+            ipv4: Some(instance.ipv4()),
+            ipv6: instance.ipv6(),
+            probes: vec![Probe::Status],
+        }
+    }
+
+    /// Set the IPv4 address published in the A record.
+    pub fn with_ipv4(mut self, addr: Ipv4Addr) -> Self {
+        self.ipv4 = Some(addr);
+        self
+    }
+
+    /// Set the IPv6 address published in the AAAA record.
+    pub fn with_ipv6(mut self, addr: Ipv6Addr) -> Self {
+        self.ipv6 = Some(addr);
+        self
+    }
+
+    /// Add a health probe.
+    pub fn with_probe(mut self, probe: Probe) -> Self {
+        self.probes.push(probe);
+        self
+    }
+
+    /// The label of the target.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn addr(&self) -> Option<IpAddr> {
+        self.ipv4
+            .map(IpAddr::V4)
+            .or_else(|| self.ipv6.map(IpAddr::V6))
+    }
+
+    /// The address to publish for each record type, or `None` where the target
+    /// has no address of that family.
+    fn records(&self) -> [(RecordType, Option<IpAddr>); 2] {
+        [
+            (RecordType::A, self.ipv4.map(IpAddr::V4)),
+            (RecordType::AAAA, self.ipv6.map(IpAddr::V6)),
+        ]
+    }
+}
+
+/// Which side of the failover pair a target is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// The preferred target.
+    Primary,
+
+    /// The target used while the primary is unhealthy.
+    Standby,
+}
+
+impl Role {
+    fn other(self) -> Self {
+        match self {
+            Role::Primary => Role::Standby,
+            Role::Standby => Role::Primary,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Primary => f.write_str("primary"),
+            Role::Standby => f.write_str("standby"),
+        }
+    }
+}
+
+/// How many consecutive probe results are required before DNS is changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hysteresis {
+    /// Consecutive failed checks of the active target before failing over.
+    pub failures: u32,
+
+    /// Consecutive successful checks of the primary before failing back to it.
+    pub recoveries: u32,
+}
+
+impl Default for Hysteresis {
+    fn default() -> Self {
+        Self {
+            failures: 3,
+            recoveries: 5,
+        }
+    }
+}
+
+/// Configuration for a failover pair.
+#[derive(Debug, Clone)]
+pub struct FailoverConfig {
+    domain: String,
+    name: SubDomain,
+    primary: Target,
+    standby: Target,
+    ttl: Duration,
+    interval: Duration,
+    timeout: Duration,
+    hysteresis: Hysteresis,
+}
+
+impl FailoverConfig {
+    /// Create a failover configuration for `name` in `domain`.
+    pub fn new(
+        domain: impl Into<String>,
+        name: impl Into<SubDomain>,
+        primary: Target,
+        standby: Target,
+    ) -> Self {
+        Self {
+            domain: domain.into(),
+            name: name.into(),
+            primary,
+            standby,
+            ttl: Duration::from_secs(300),
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(5),
+            hysteresis: Hysteresis::default(),
+        }
+    }
+
+    /// Set the TTL for the failover records. Linode rounds this up to the nearest TTL it supports.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Set how often the targets are checked.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the timeout for each network probe.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the hysteresis thresholds.
+    pub fn with_hysteresis(mut self, hysteresis: Hysteresis) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    fn target(&self, role: Role) -> &Target {
+        match role {
+            Role::Primary => &self.primary,
+            Role::Standby => &self.standby,
+        }
+    }
+}
+
+/// The health of both targets at a single check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    /// Whether the primary passed all of its probes.
+    pub primary: bool,
+
+    /// Whether the standby passed all of its probes.
+    pub standby: bool,
+}
+
+impl Health {
+    fn of(&self, role: Role) -> bool {
+        match role {
+            Role::Primary => self.primary,
+            Role::Standby => self.standby,
+        }
+    }
+}
+
+/// A change of the active target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverEvent {
+    /// The target which was active before the transition.
+    pub from: Role,
+
+    /// The target which is now active.
+    pub to: Role,
+
+    /// The label of the newly active target.
+    pub label: String,
+
+    /// Target health at the check which caused the transition.
+    pub health: Health,
+}
+
+/// The hysteresis state machine which decides the active target.
+#[derive(Debug, Clone)]
+struct FailoverState {
+    active: Role,
+    hysteresis: Hysteresis,
+    failures: u32,
+    recoveries: u32,
+}
+
+impl FailoverState {
+    fn new(hysteresis: Hysteresis) -> Self {
+        Self {
+            active: Role::Primary,
+            hysteresis,
+            failures: 0,
+            recoveries: 0,
+        }
+    }
+
+    /// Record a check, returning the new active role if it changed.
+    fn observe(&mut self, health: Health) -> Option<Role> {
+        if health.of(self.active) {
+            self.failures = 0;
+        } else {
+            self.failures += 1;
+        }
+
+        if self.active == Role::Standby && health.primary {
+            self.recoveries += 1;
+        } else {
+            self.recoveries = 0;
+        }
+
+        let other = self.active.other();
+        let fail_over = self.failures >= self.hysteresis.failures && health.of(other);
+        let fail_back =
+            self.active == Role::Standby && self.recoveries >= self.hysteresis.recoveries;
+
+        if fail_over || fail_back {
+            self.active = other;
+            self.failures = 0;
+            self.recoveries = 0;
+            Some(other)
+        } else {
+            None
+        }
+    }
+}
+
+/// Watches a failover pair and keeps DNS pointed at the healthy target.
+#[derive(Debug)]
+pub struct FailoverMonitor {
+    client: LinodeClient,
+    config: FailoverConfig,
+    state: FailoverState,
+    events: broadcast::Sender<FailoverEvent>,
+}
+
+impl FailoverMonitor {
+    /// Create a monitor. The primary is assumed to be active until the first check.
+    pub fn new(client: LinodeClient, config: FailoverConfig) -> Self {
+        let (events, _) = broadcast::channel(16);
+        Self {
+            client,
+            state: FailoverState::new(config.hysteresis),
+            config,
+            events,
+        }
+    }
+
+    /// Subscribe to transition events.
+    pub fn subscribe(&self) -> broadcast::Receiver<FailoverEvent> {
+        self.events.subscribe()
+    }
+
+    /// The currently active target.
+    pub fn active(&self) -> Role {
+        self.state.active
+    }
+
+    /// Probe both targets once, and update DNS if the active target changes.
+    ///
+    /// The transition is only recorded once DNS is updated, so if that fails,
+    /// the next check tries it again.
+    #[tracing::instrument(skip(self), fields(name = %self.config.name, domain = %self.config.domain))]
+    pub async fn check(&mut self) -> Result<Option<FailoverEvent>> {
+        let (primary, standby) = futures::join!(
+            self.healthy(&self.config.primary),
+            self.healthy(&self.config.standby)
+        );
+        let health = Health { primary, standby };
+        tracing::trace!(?health, "Checked failover targets");
+
+        if !health.primary && !health.standby {
+            tracing::warn!("Both failover targets are unhealthy");
+        }
+
+        let from = self.state.active;
+        let mut state = self.state.clone();
+        let Some(to) = state.observe(health) else {
+            self.state = state;
+            return Ok(None);
+        };
+
+        let label = self.config.target(to).label.clone();
+        tracing::info!(%from, %to, %label, "Failing over");
+        self.publish(to).await?;
+        self.state = state;
+
+        let event = FailoverEvent {
+            from,
+            to,
+            label,
+            health,
+        };
+        let _ = self.events.send(event.clone());
+        Ok(Some(event))
+    }
+
+    /// Run the monitor until an API error occurs.
+    ///
+    /// DNS is first reconciled with the active target, then targets are checked every interval.
+    pub async fn run(mut self) -> Result<()> {
+        self.publish(self.state.active).await?;
+
+        let mut interval = tokio::time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.check().await?;
+        }
+    }
+
+    async fn domain(&self) -> Result<Domain> {
+        self.client
+            .get_linode_domain(&self.config.domain)
+            .await?
+            .ok_or_else(|| LinodeError::NotFound {
+                kind: "domain",
+                value: self.config.domain.clone(),
+            })
+    }
+
+    /// Point the records at a target, skipping records which are already correct.
+    ///
+    /// Records for an address family the target doesn't have are deleted, so they
+    /// don't keep pointing at the other target.
+    async fn publish(&self, role: Role) -> Result<()> {
+        let domain = self.domain().await?;
+        let name = &self.config.name;
+
+        for (kind, addr) in self.config.target(role).records() {
+            let record = self
+                .client
+                .get_linode_domain_record(&domain, &kind, name)
+                .await?;
+
+            match (record, addr) {
+                (Some(record), Some(addr)) if record.addr() == Some(addr) => {
+                    tracing::trace!(%kind, target = %addr, "Record already up to date");
+                }
+                (Some(record), Some(addr)) => {
+                    self.client
+                        .set_linode_domain_record_with_ttl(
+                            &record.id(),
+                            &kind,
+                            name,
+                            &addr.to_string(),
+                            self.config.ttl,
+                        )
+                        .await?;
+                }
+                (None, Some(addr)) => {
+                    self.client
+                        .create_linode_domain_record_with_ttl(
+                            &domain,
+                            &kind,
+                            name,
+                            &addr.to_string(),
+                            self.config.ttl,
+                        )
+                        .await?;
+                }
+                (Some(record), None) => {
+                    tracing::debug!(%kind, "Deleting record, target has no address for it");
+                    self.client
+                        .delete_linode_domain_record(&record.id())
+                        .await?;
+                }
+                (None, None) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn healthy(&self, target: &Target) -> bool {
+        for probe in &target.probes {
+            let result = self.probe(target, probe).await;
+            if let Err(error) = &result {
+                tracing::debug!(target = %target.label, ?probe, "Probe failed: {error}");
+            }
+            if !matches!(result, Ok(true)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    async fn probe(&self, target: &Target, probe: &Probe) -> std::io::Result<bool> {
+        match probe {
+            Probe::Status => {
+                let Some(id) = &target.instance else {
+                    return Ok(false);
+                };
+                match self.client.get_linode_instance(id).await {
+                    Ok(instance) => Ok(instance.status() == InstanceStatus::Running),
+                    Err(error) => Err(std::io::Error::other(error)),
+                }
+            }
+            Probe::Tcp { port } => {
+                self.connect(target, *port).await?;
+                Ok(true)
+            }
+            Probe::Http { port, path, host } => {
+                let mut stream = self.connect(target, *port).await?;
+                let host = match host {
+                    Some(host) => host.clone(),
+                    None => target
+                        .addr()
+                        .map(|addr| addr.to_string())
+                        .unwrap_or_default(),
+                };
+                let request =
+                    format!("GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+
+                let status = tokio::time::timeout(self.config.timeout, async {
+                    stream.write_all(request.as_bytes()).await?;
+                    let mut buf = [0u8; 64];
+                    let n = stream.read(&mut buf).await?;
+                    Ok::<_, std::io::Error>(parse_status(&buf[..n]))
+                })
+                .await??;
+
+                Ok(matches!(status, Some(200..=399)))
+            }
+        }
+    }
+
+    async fn connect(&self, target: &Target, port: u16) -> std::io::Result<TcpStream> {
+        let addr = target.addr().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::AddrNotAvailable,
+                "target has no address",
+            )
+        })?;
+        let stream = tokio::time::timeout(
+            self.config.timeout,
+            TcpStream::connect(SocketAddr::new(addr, port)),
+        )
+        .await??;
+        Ok(stream)
+    }
+}
+
+/// Parse the status code from the start of an HTTP/1 response.
+fn parse_status(head: &[u8]) -> Option<u16> {
+    let line = std::str::from_utf8(head).ok()?.lines().next()?;
+    let mut parts = line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: Health = Health {
+        primary: true,
+        standby: true,
+    };
+
+    const PRIMARY_DOWN: Health = Health {
+        primary: false,
+        standby: true,
+    };
+
+    const BOTH_DOWN: Health = Health {
+        primary: false,
+        standby: false,
+    };
+
+    #[test]
+    fn target_records() {
+        let target = Target::new("primary").with_ipv4(Ipv4Addr::new(192, 0, 2, 1));
+        assert_eq!(
+            target.records(),
+            [
+                (RecordType::A, Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))),
+                (RecordType::AAAA, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut state = FailoverState::new(Hysteresis {
+            failures: 2,
+            recoveries: 3,
+        });
+
+        // A single failure is not enough to fail over.
+        assert_eq!(state.observe(PRIMARY_DOWN), None);
+        assert_eq!(state.observe(UP), None);
+        assert_eq!(state.observe(PRIMARY_DOWN), None);
+        assert_eq!(state.observe(PRIMARY_DOWN), Some(Role::Standby));
+
+        // Failing back needs consecutive recoveries.
+        assert_eq!(state.observe(UP), None);
+        assert_eq!(state.observe(PRIMARY_DOWN), None);
+        assert_eq!(state.observe(UP), None);
+        assert_eq!(state.observe(UP), None);
+        assert_eq!(state.observe(UP), Some(Role::Primary));
+    }
+
+    #[test]
+    fn no_failover_to_unhealthy_standby() {
+        let mut state = FailoverState::new(Hysteresis {
+            failures: 1,
+            recoveries: 1,
+        });
+
+        assert_eq!(state.observe(BOTH_DOWN), None);
+        assert_eq!(state.observe(BOTH_DOWN), None);
+        assert_eq!(state.active, Role::Primary);
+
+        // Once the standby is healthy, the accumulated failures apply immediately.
+        assert_eq!(state.observe(PRIMARY_DOWN), Some(Role::Standby));
+    }
+
+    #[test]
+    fn parse_http_status() {
+        assert_eq!(parse_status(b"HTTP/1.1 204 No Content\r\n"), Some(204));
+        assert_eq!(parse_status(b"HTTP/1.0 503 Unavailable"), Some(503));
+        assert_eq!(parse_status(b"SSH-2.0-OpenSSH"), None);
+        assert_eq!(parse_status(b""), None);
+    }
+}
@@ -0,0 +1,411 @@
+//! Linode Cloud Firewalls, their rules, and the devices they protect.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Empty, LinodeClient, LinodeID, Paginated, Result};
+
+/// Whether traffic matching a rule (or no rule) is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FirewallAction {
+    /// Allow the traffic.
+    Accept,
+
+    /// Drop the traffic.
+    Drop,
+}
+
+/// Network protocol matched by a firewall rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FirewallProtocol {
+    /// TCP traffic.
+    Tcp,
+
+    /// UDP traffic.
+    Udp,
+
+    /// ICMP traffic.
+    Icmp,
+
+    /// IP-in-IP encapsulated traffic.
+    Ipencap,
+}
+
+/// Addresses matched by a firewall rule, in CIDR notation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallAddresses {
+    /// IPv4 addresses or networks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ipv4: Vec<String>,
+
+    /// IPv6 addresses or networks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ipv6: Vec<String>,
+}
+
+impl FirewallAddresses {
+    /// Match every IPv4 and IPv6 address.
+    pub fn any() -> Self {
+        Self {
+            ipv4: vec!["0.0.0.0/0".into()],
+            ipv6: vec!["::/0".into()],
+        }
+    }
+}
+
+/// A single inbound or outbound firewall rule.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallRule {
+    /// What to do with matching traffic.
+    pub action: FirewallAction,
+
+    /// Protocol to match.
+    pub protocol: FirewallProtocol,
+
+    /// Ports to match, e.g. `22,80,8000-8080`. Omitted to match every port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ports: Option<String>,
+
+    /// Addresses to match.
+    pub addresses: FirewallAddresses,
+
+    /// A label for the rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// A description of the rule.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl FirewallRule {
+    /// Accept TCP traffic on these ports from any address.
+    pub fn accept_tcp(ports: impl Into<String>) -> Self {
+        Self {
+            action: FirewallAction::Accept,
+            protocol: FirewallProtocol::Tcp,
+            ports: Some(ports.into()),
+            addresses: FirewallAddresses::any(),
+            label: None,
+            description: None,
+        }
+    }
+
+    /// Set the label for this rule.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+/// The complete set of rules for a firewall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirewallRules {
+    /// Rules for traffic arriving at a device.
+    #[serde(default)]
+    pub inbound: Vec<FirewallRule>,
+
+    /// Action for inbound traffic which matches no rule.
+    pub inbound_policy: FirewallAction,
+
+    /// Rules for traffic leaving a device.
+    #[serde(default)]
+    pub outbound: Vec<FirewallRule>,
+
+    /// Action for outbound traffic which matches no rule.
+    pub outbound_policy: FirewallAction,
+}
+
+impl Default for FirewallRules {
+    /// Drop unmatched inbound traffic, and accept all outbound traffic.
+    fn default() -> Self {
+        Self {
+            inbound: Vec::new(),
+            inbound_policy: FirewallAction::Drop,
+            outbound: Vec::new(),
+            outbound_policy: FirewallAction::Accept,
+        }
+    }
+}
+
+/// Whether a firewall is being enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FirewallStatus {
+    /// Rules are enforced.
+    Enabled,
+
+    /// Rules are not enforced.
+    Disabled,
+
+    /// The firewall has been deleted.
+    Deleted,
+}
+
+/// A Linode Cloud Firewall.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Firewall {
+    /// The ID of the firewall.
+    pub id: LinodeID,
+
+    /// A label for the firewall.
+    pub label: String,
+
+    /// Whether the firewall is being enforced.
+    pub status: FirewallStatus,
+
+    /// The firewall rules.
+    pub rules: FirewallRules,
+
+    /// Tags applied to the firewall.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Kind of device a firewall can be attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirewallDeviceType {
+    /// A Linode instance.
+    Linode,
+
+    /// A NodeBalancer.
+    NodeBalancer,
+}
+
+/// The entity protected by a firewall device attachment.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FirewallEntity {
+    /// The ID of the Linode or NodeBalancer.
+    pub id: LinodeID,
+
+    /// The kind of entity.
+    #[serde(rename = "type")]
+    pub kind: FirewallDeviceType,
+
+    /// The label of the entity.
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// A device attached to a firewall.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FirewallDevice {
+    /// The ID of the attachment, used to detach the device.
+    pub id: LinodeID,
+
+    /// The attached entity.
+    pub entity: FirewallEntity,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct CreateFirewallDevices {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    linodes: Vec<LinodeID>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nodebalancers: Vec<LinodeID>,
+}
+
+/// Request to create a firewall.
+#[derive(Debug, Serialize)]
+pub struct CreateFirewall {
+    label: String,
+    rules: FirewallRules,
+    devices: CreateFirewallDevices,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl CreateFirewall {
+    /// Create a firewall with these rules.
+    pub fn new(label: impl Into<String>, rules: FirewallRules) -> Self {
+        Self {
+            label: label.into(),
+            rules,
+            devices: Default::default(),
+            tags: Vec::new(),
+        }
+    }
+
+    /// Attach the firewall to a Linode instance when it is created.
+    pub fn linode(mut self, id: LinodeID) -> Self {
+        self.devices.linodes.push(id);
+        self
+    }
+
+    /// Attach the firewall to a NodeBalancer when it is created.
+    pub fn nodebalancer(mut self, id: LinodeID) -> Self {
+        self.devices.nodebalancers.push(id);
+        self
+    }
+
+    /// Add a tag to the firewall.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+}
+
+/// Changes to a firewall's label, status or tags.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateFirewall {
+    /// A new label for the firewall.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Enable or disable the firewall.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<FirewallStatus>,
+
+    /// Replace the tags on the firewall.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize)]
+struct AttachFirewallDevice {
+    id: LinodeID,
+
+    #[serde(rename = "type")]
+    kind: FirewallDeviceType,
+}
+
+impl LinodeClient {
+    /// List all firewalls.
+    #[tracing::instrument(skip(self))]
+    pub fn list_firewalls(&self) -> Paginated<Firewall> {
+        self.get_paginated("networking/firewalls")
+    }
+
+    /// Get a firewall by its ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_firewall(&self, id: LinodeID) -> Result<Firewall> {
+        self.get(&format!("networking/firewalls/{id}")).await
+    }
+
+    /// Create a firewall.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_firewall(&self, firewall: &CreateFirewall) -> Result<Firewall> {
+        let firewall: Firewall = self.post("networking/firewalls", firewall).await?;
+        tracing::debug!("Created firewall {} ({})", firewall.label, firewall.id);
+        Ok(firewall)
+    }
+
+    /// Update a firewall's label, status or tags.
+    #[tracing::instrument(skip(self))]
+    pub async fn update_firewall(&self, id: LinodeID, update: &UpdateFirewall) -> Result<Firewall> {
+        self.put(&format!("networking/firewalls/{id}"), update)
+            .await
+    }
+
+    /// Delete a firewall.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_firewall(&self, id: LinodeID) -> Result<()> {
+        self.delete::<Empty>(&format!("networking/firewalls/{id}"))
+            .await?;
+        tracing::debug!("Deleted firewall {id}");
+        Ok(())
+    }
+
+    /// Get the rules for a firewall.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_firewall_rules(&self, id: LinodeID) -> Result<FirewallRules> {
+        self.get(&format!("networking/firewalls/{id}/rules")).await
+    }
+
+    /// Replace all of the rules for a firewall.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_firewall_rules(
+        &self,
+        id: LinodeID,
+        rules: &FirewallRules,
+    ) -> Result<FirewallRules> {
+        self.put(&format!("networking/firewalls/{id}/rules"), rules)
+            .await
+    }
+
+    /// List the devices attached to a firewall.
+    #[tracing::instrument(skip(self))]
+    pub fn list_firewall_devices(&self, id: LinodeID) -> Paginated<FirewallDevice> {
+        self.get_paginated(&format!("networking/firewalls/{id}/devices"))
+    }
+
+    /// Attach a Linode instance or NodeBalancer to a firewall.
+    #[tracing::instrument(skip(self))]
+    pub async fn attach_firewall_device(
+        &self,
+        firewall: LinodeID,
+        kind: FirewallDeviceType,
+        entity: LinodeID,
+    ) -> Result<FirewallDevice> {
+        let device = AttachFirewallDevice { id: entity, kind };
+        let device: FirewallDevice = self
+            .post(&format!("networking/firewalls/{firewall}/devices"), &device)
+            .await?;
+        tracing::debug!("Attached {:?} {entity} to firewall {firewall}", kind);
+        Ok(device)
+    }
+
+    /// Detach a device from a firewall, using the ID of the attachment.
+    #[tracing::instrument(skip(self))]
+    pub async fn detach_firewall_device(&self, firewall: LinodeID, device: LinodeID) -> Result<()> {
+        self.delete::<Empty>(&format!("networking/firewalls/{firewall}/devices/{device}"))
+            .await?;
+        tracing::debug!("Detached device {device} from firewall {firewall}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn firewall_rules() {
+        let rules = FirewallRules {
+            inbound: vec![FirewallRule::accept_tcp("22,443").label("ssh-https")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&rules).unwrap(),
+            serde_json::json!({
+                "inbound": [{
+                    "action": "ACCEPT",
+                    "protocol": "TCP",
+                    "ports": "22,443",
+                    "addresses": {"ipv4": ["0.0.0.0/0"], "ipv6": ["::/0"]},
+                    "label": "ssh-https",
+                }],
+                "inbound_policy": "DROP",
+                "outbound": [],
+                "outbound_policy": "ACCEPT",
+            })
+        );
+
+        let firewall: Firewall = serde_json::from_value(serde_json::json!({
+            "id": 123,
+            "label": "edge",
+            "status": "enabled",
+            "created": "2018-01-01T00:01:01",
+            "rules": {
+                "inbound": [{
+                    "action": "ACCEPT",
+                    "protocol": "ICMP",
+                    "addresses": {"ipv4": ["192.0.2.0/24"]},
+                }],
+                "inbound_policy": "DROP",
+                "outbound_policy": "ACCEPT",
+            },
+            "tags": ["example"],
+        }))
+        .unwrap();
+        assert_eq!(firewall.status, FirewallStatus::Enabled);
+        assert_eq!(firewall.rules.inbound[0].protocol, FirewallProtocol::Icmp);
+        assert!(firewall.rules.inbound[0].ports.is_none());
+        assert!(firewall.rules.outbound.is_empty());
+    }
+}
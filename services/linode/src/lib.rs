@@ -12,6 +12,7 @@ use api_client::response::ResponseExt as _;
 use api_client::uri::UriExtension as _;
 use api_client::ApiClient;
 use api_client::BearerAuth;
+use api_client::ErrorDecoder;
 use api_client::PaginatedData;
 use api_client::RequestBuilder;
 use api_client::Secret;
@@ -25,6 +26,14 @@ use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde::Serialize;
 
+pub mod catalog;
+pub mod event;
+pub mod failover;
+pub mod firewall;
+pub mod object_storage;
+pub mod provision;
+pub mod volume;
+
 /// Results from the Linode API can be errors or data.
 pub type Result<T, E = LinodeError> = std::result::Result<T, E>;
 
@@ -46,9 +55,9 @@ impl LinodeClient {
         let token =
             std::env::var("LINODE_API_TOKEN").expect("LINODE_API_TOKEN environment variable");
         LinodeClient {
-            inner: ApiClient::new_bearer_auth(
+            inner: ApiClient::new(
                 "https://api.linode.com/v4/".parse().unwrap(),
-                Secret::from(token),
+                BearerAuth::new(Secret::from(token)),
             ),
         }
     }
@@ -56,35 +65,34 @@ impl LinodeClient {
     /// Create a new Linode client from a configuration.
     pub fn from_config(config: &LinodeConfiguration) -> Self {
         LinodeClient {
-            inner: ApiClient::new_bearer_auth(
+            inner: ApiClient::new(
                 "https://api.linode.com/v4/".parse().unwrap(),
-                config.token.clone(),
-            ),
+                BearerAuth::new(config.token.clone()),
+            )
+            .with_error_decoder(LinodeErrorDecoder),
         }
     }
 
     /// Create a new Linode client from a token.
     pub fn new<S: Into<Cow<'static, str>>>(token: S) -> Self {
         LinodeClient {
-            inner: ApiClient::new_bearer_auth(
+            inner: ApiClient::new(
                 "https://api.linode.com/v4/".parse().unwrap(),
-                Secret::from(token.into()),
-            ),
+                BearerAuth::new(Secret::from(token.into())),
+            )
+            .with_error_decoder(LinodeErrorDecoder),
         }
     }
 
     async fn execute(&self, request: http::Request<Body>) -> Result<String> {
         let resp = self.inner.execute(request).await?;
-        let status = resp.status();
-        let body = resp.text().await.map_err(api_client::Error::ResponseBody)?;
-
-        if !status.is_success() {
-            tracing::error!("Error response from linode: {:?}", status);
-
-            let errors = serde_json::de::from_str(&body)?;
-            return Err(LinodeApiError::new(status, errors).into());
+        if !resp.status().is_success() {
+            tracing::error!("Error response from linode: {:?}", resp.status());
         }
 
+        let resp = self.inner.error_for_status(resp).await?;
+
+        let body = resp.text().await.map_err(api_client::Error::ResponseBody)?;
         Ok(body)
     }
 
@@ -125,11 +133,7 @@ impl LinodeClient {
         D: Serialize + Send,
         T: DeserializeOwned + Send + 'static,
     {
-        let request = self
-            .inner
-            .post(endpoint)
-            .json(data)
-            .map_err(api_client::Error::from)?;
+        let request = self.inner.post(endpoint).json(data)?;
         self.execute_and_deserialize(request).await
     }
 
@@ -138,11 +142,7 @@ impl LinodeClient {
         D: Serialize + Send,
         T: DeserializeOwned + Send + Sync + 'static,
     {
-        let request = self
-            .inner
-            .put(endpoint)
-            .json(data)
-            .map_err(api_client::Error::from)?;
+        let request = self.inner.put(endpoint).json(data)?;
         self.execute_and_deserialize(request).await
     }
 
@@ -162,6 +162,13 @@ impl LinodeClient {
             .map_err(|error| LinodeError::Request(api_client::Error::ResponseBody(error)))
     }
 
+    /// Get a Linode instance by its ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_linode_instance(&self, id: &LinodeID) -> Result<Instance> {
+        let instance: GetInstance = self.get(&format!("linode/instances/{id}")).await?;
+        Ok(Instance::new(instance))
+    }
+
     /// List all domains managed by Linode.
     #[tracing::instrument(skip(self))]
     pub fn list_linode_domains(&self) -> Paginated<Domain> {
@@ -211,13 +218,32 @@ impl LinodeClient {
         record: &RecordType,
         name: &SubDomain,
         target: &str,
+    ) -> Result<Record> {
+        self.create_linode_domain_record_with_ttl(
+            domain,
+            record,
+            name,
+            target,
+            Duration::from_secs(60 * 60),
+        )
+        .await
+    }
+
+    /// Create a new domain record in Linode with a TTL.
+    pub async fn create_linode_domain_record_with_ttl(
+        &self,
+        domain: &Domain,
+        record: &RecordType,
+        name: &SubDomain,
+        target: &str,
+        ttl: Duration,
     ) -> Result<Record> {
         let endpoint = format!("domains/{}/records", domain.id());
         let record = CreateDomainRecord {
             r#type: *record,
             target: target.into(),
             name: name.with_domain(domain),
-            ttl: Duration::from_secs(60 * 60),
+            ttl,
         };
 
         let record: GetDomainRecord = self.post(&endpoint, &record).await?;
@@ -251,6 +277,32 @@ impl LinodeClient {
         record: &RecordType,
         name: &SubDomain,
         target: &str,
+    ) -> Result<()> {
+        self.update_linode_domain_record(recordid, record, name, target, None)
+            .await
+    }
+
+    /// Update a domain record in Linode, also setting its TTL.
+    #[tracing::instrument(skip(self))]
+    pub async fn set_linode_domain_record_with_ttl(
+        &self,
+        recordid: &RecordID,
+        record: &RecordType,
+        name: &SubDomain,
+        target: &str,
+        ttl: Duration,
+    ) -> Result<()> {
+        self.update_linode_domain_record(recordid, record, name, target, Some(ttl))
+            .await
+    }
+
+    async fn update_linode_domain_record(
+        &self,
+        recordid: &RecordID,
+        record: &RecordType,
+        name: &SubDomain,
+        target: &str,
+        ttl: Option<Duration>,
     ) -> Result<()> {
         let domain = self.get_linode_domain_by_id(&recordid.domain()).await?;
 
@@ -259,6 +311,7 @@ impl LinodeClient {
             r#type: *record,
             target: target.into(),
             name: name.with_domain(&domain),
+            ttl,
         };
 
         let record: GetDomainRecord = self.put(&endpoint, &record).await?;
@@ -280,6 +333,7 @@ impl LinodeClient {
 
 /// Errors that can occur when interacting with the Linode API.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum LinodeError {
     /// An error returned by the Linode API.
     #[error("Linode API Error: {0}")]
@@ -287,7 +341,7 @@ pub enum LinodeError {
 
     /// An error occured while sending the HTTP request.
     #[error("Request Error: {0}")]
-    Request(#[from] api_client::error::Error),
+    Request(#[source] api_client::error::Error),
 
     /// An error occured while deserializing the response body.
     #[error(transparent)]
@@ -302,12 +356,43 @@ pub enum LinodeError {
         value: String,
     },
 
+    /// A resource did not reach a status in time.
+    #[error("Timed out waiting for {kind} {value} to be {status}")]
+    Timeout {
+        /// The resource kind
+        kind: &'static str,
+        /// The resource which was being waited for
+        value: String,
+        /// The status which was not reached
+        status: String,
+    },
+
+    /// An action which was waited for failed.
+    #[error("Event {id} ({action}) failed: {message}")]
+    EventFailed {
+        /// The event which failed
+        id: event::EventID,
+        /// The action which failed
+        action: event::EventAction,
+        /// The message from the event, if any
+        message: String,
+    },
+
     /// A request was sent for a record that does not match
     /// the domain it belongs to.
     #[error("Domain {0} does not match record {1}")]
     DomainMismatch(DomainID, RecordID),
 }
 
+impl From<api_client::error::Error> for LinodeError {
+    fn from(error: api_client::error::Error) -> Self {
+        match error.into_api_error() {
+            Ok(error) => LinodeError::ApiError(error),
+            Err(error) => LinodeError::Request(error),
+        }
+    }
+}
+
 /// A Linode API error message.
 #[derive(Debug, Clone, Deserialize)]
 pub struct ApiError {
@@ -373,6 +458,21 @@ impl fmt::Display for LinodeApiError {
 
 impl std::error::Error for LinodeApiError {}
 
+/// Decodes Linode error responses into [`LinodeApiError`].
+#[derive(Debug, Clone, Copy, Default)]
+struct LinodeErrorDecoder;
+
+impl ErrorDecoder for LinodeErrorDecoder {
+    fn decode(
+        &self,
+        status: http::StatusCode,
+        body: &[u8],
+    ) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        let errors: ErrorResponse = serde_json::from_slice(body).ok()?;
+        Some(Box::new(LinodeApiError::new(status, errors)))
+    }
+}
+
 /// Configuration for the Linode API.
 #[derive(Debug, Clone, Deserialize)]
 pub struct LinodeConfiguration {
@@ -388,7 +488,7 @@ impl LinodeConfiguration {
 }
 
 /// Newtype wrapper for IDs returned by linode, which are usize.
-#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct LinodeID(usize);
 
 impl fmt::Display for LinodeID {
@@ -549,6 +649,13 @@ struct UpdateDomainRecord {
     r#type: RecordType,
     target: String,
     name: String,
+
+    #[serde(
+        rename = "ttl_sec",
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "crate::serialize::optional_ttl"
+    )]
+    ttl: Option<std::time::Duration>,
 }
 
 /// A Linode domain record.
@@ -673,7 +780,7 @@ impl fmt::Display for RecordID {
 }
 
 /// The status of a Linode instance.
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum InstanceStatus {
     /// The instance is running.
@@ -805,6 +912,19 @@ mod serialize {
 
         serializer.serialize_u64(ttl)
     }
+
+    pub(crate) fn optional_ttl<S>(
+        ttl: &Option<std::time::Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match ttl {
+            Some(ttl) => self::ttl(ttl, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
 }
 
 /// A paginator for paged Linode API responses.
@@ -0,0 +1,306 @@
+//! Linode Object Storage buckets and access keys.
+//!
+//! Buckets created here are S3-compatible, and can be consumed by the storage
+//! crate with the credentials returned by
+//! [`LinodeClient::create_object_storage_key`].
+
+use api_client::Secret;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{Empty, LinodeClient, LinodeID, Paginated, Result};
+
+/// Linode replaces the secret key with this value everywhere except the response
+/// to creating a key.
+const REDACTED: &str = "[REDACTED]";
+
+/// An Object Storage bucket.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectStorageBucket {
+    /// The name of the bucket.
+    pub label: String,
+
+    /// The region which holds the bucket, e.g. `us-east`.
+    pub region: String,
+
+    /// The hostname used to access the bucket.
+    pub hostname: String,
+
+    /// When the bucket was created.
+    pub created: String,
+
+    /// Number of objects stored in the bucket.
+    #[serde(default)]
+    pub objects: u64,
+
+    /// Size of the bucket, in bytes.
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Access control for a new bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BucketAcl {
+    /// Only the owner may read or write.
+    Private,
+
+    /// Anyone may read, only the owner may write.
+    PublicRead,
+
+    /// Any authenticated Linode user may read, only the owner may write.
+    AuthenticatedRead,
+
+    /// Anyone may read or write.
+    PublicReadWrite,
+}
+
+/// Request to create an Object Storage bucket.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateBucket {
+    label: String,
+    region: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    acl: Option<BucketAcl>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cors_enabled: Option<bool>,
+}
+
+impl CreateBucket {
+    /// Create a bucket with this label in a region.
+    pub fn new(label: impl Into<String>, region: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            region: region.into(),
+            acl: None,
+            cors_enabled: None,
+        }
+    }
+
+    /// Set the access control for the bucket.
+    pub fn acl(mut self, acl: BucketAcl) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    /// Enable or disable CORS for the bucket.
+    pub fn cors(mut self, enabled: bool) -> Self {
+        self.cors_enabled = Some(enabled);
+        self
+    }
+}
+
+/// Permission granted to a limited access key for a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketPermission {
+    /// Read objects in the bucket.
+    ReadOnly,
+
+    /// Read and write objects in the bucket.
+    ReadWrite,
+}
+
+/// Access to a single bucket granted to a limited access key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketAccess {
+    /// The region which holds the bucket.
+    pub region: String,
+
+    /// The name of the bucket.
+    pub bucket_name: String,
+
+    /// The permission granted.
+    pub permissions: BucketPermission,
+}
+
+/// An Object Storage access key.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectStorageKey {
+    /// The ID of the key.
+    pub id: LinodeID,
+
+    /// A label for the key.
+    pub label: String,
+
+    /// The S3 access key ID.
+    pub access_key: String,
+
+    /// The S3 secret key. Linode only returns this when the key is created.
+    #[serde(default, deserialize_with = "redacted_secret")]
+    pub secret_key: Option<Secret>,
+
+    /// Whether access is limited to the buckets in `bucket_access`.
+    #[serde(default)]
+    pub limited: bool,
+
+    /// Buckets this key can access, if it is limited.
+    #[serde(default)]
+    pub bucket_access: Option<Vec<BucketAccess>>,
+}
+
+impl ObjectStorageKey {
+    /// The credentials for this key, if the secret key is known.
+    pub fn credentials(&self) -> Option<ObjectStorageCredentials> {
+        Some(ObjectStorageCredentials {
+            access_key: self.access_key.clone(),
+            secret_key: self.secret_key.clone()?,
+        })
+    }
+}
+
+/// S3 credentials for Linode Object Storage.
+#[derive(Debug, Clone)]
+pub struct ObjectStorageCredentials {
+    /// The S3 access key ID.
+    pub access_key: String,
+
+    /// The S3 secret key.
+    pub secret_key: Secret,
+}
+
+fn redacted_secret<'de, D>(deserializer: D) -> Result<Option<Secret>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secret = Option::<String>::deserialize(deserializer)?;
+    Ok(secret.filter(|secret| secret != REDACTED).map(Secret::from))
+}
+
+/// Request to create an Object Storage access key.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateKey {
+    label: String,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    bucket_access: Vec<BucketAccess>,
+}
+
+impl CreateKey {
+    /// Create a key with unrestricted access to every bucket.
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            bucket_access: Vec::new(),
+        }
+    }
+
+    /// Limit the key to a bucket. Keys with any bucket grants can only access
+    /// those buckets.
+    pub fn bucket(
+        mut self,
+        region: impl Into<String>,
+        bucket: impl Into<String>,
+        permissions: BucketPermission,
+    ) -> Self {
+        self.bucket_access.push(BucketAccess {
+            region: region.into(),
+            bucket_name: bucket.into(),
+            permissions,
+        });
+        self
+    }
+}
+
+impl LinodeClient {
+    /// List all Object Storage buckets.
+    #[tracing::instrument(skip(self))]
+    pub fn list_object_storage_buckets(&self) -> Paginated<ObjectStorageBucket> {
+        self.get_paginated("object-storage/buckets")
+    }
+
+    /// Create an Object Storage bucket.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_object_storage_bucket(
+        &self,
+        bucket: &CreateBucket,
+    ) -> Result<ObjectStorageBucket> {
+        let bucket: ObjectStorageBucket = self.post("object-storage/buckets", bucket).await?;
+        tracing::debug!("Created bucket {} in {}", bucket.label, bucket.region);
+        Ok(bucket)
+    }
+
+    /// Delete an Object Storage bucket. Linode only deletes empty buckets.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_object_storage_bucket(&self, region: &str, label: &str) -> Result<()> {
+        self.delete::<Empty>(&format!("object-storage/buckets/{region}/{label}"))
+            .await?;
+        tracing::debug!("Deleted bucket {label} in {region}");
+        Ok(())
+    }
+
+    /// List all Object Storage access keys. Secret keys are not included.
+    #[tracing::instrument(skip(self))]
+    pub fn list_object_storage_keys(&self) -> Paginated<ObjectStorageKey> {
+        self.get_paginated("object-storage/keys")
+    }
+
+    /// Create an Object Storage access key, returning its credentials.
+    ///
+    /// This is the only time Linode will reveal the secret key.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_object_storage_key(&self, key: &CreateKey) -> Result<ObjectStorageKey> {
+        let key: ObjectStorageKey = self.post("object-storage/keys", key).await?;
+        tracing::debug!("Created object storage key {} ({})", key.label, key.id);
+        Ok(key)
+    }
+
+    /// Revoke an Object Storage access key.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_object_storage_key(&self, id: LinodeID) -> Result<()> {
+        self.delete::<Empty>(&format!("object-storage/keys/{id}"))
+            .await?;
+        tracing::debug!("Deleted object storage key {id}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_secret_key() {
+        let key: ObjectStorageKey = serde_json::from_str(
+            r#"{"id": 123, "label": "backups", "access_key": "KVAKUTGBA4WTR2NSJQ81",
+                "secret_key": "[REDACTED]", "limited": false, "bucket_access": null}"#,
+        )
+        .unwrap();
+        assert!(key.secret_key.is_none());
+        assert!(key.credentials().is_none());
+
+        let key: ObjectStorageKey = serde_json::from_str(
+            r#"{"id": 123, "label": "backups", "access_key": "KVAKUTGBA4WTR2NSJQ81",
+                "secret_key": "OiA6F5r0niLs3QA2stbyq7mY5VCV7KqOzcmitmHw", "limited": true,
+                "bucket_access": [{"region": "us-east", "bucket_name": "backups",
+                                   "permissions": "read_write"}]}"#,
+        )
+        .unwrap();
+        let credentials = key.credentials().unwrap();
+        assert_eq!(credentials.access_key, "KVAKUTGBA4WTR2NSJQ81");
+        assert_eq!(
+            credentials.secret_key.revealed(),
+            "OiA6F5r0niLs3QA2stbyq7mY5VCV7KqOzcmitmHw"
+        );
+        assert_eq!(
+            key.bucket_access.unwrap()[0].permissions,
+            BucketPermission::ReadWrite
+        );
+    }
+
+    #[test]
+    fn create_key_request() {
+        let request =
+            CreateKey::new("backups").bucket("us-east", "backups", BucketPermission::ReadOnly);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "label": "backups",
+                "bucket_access": [
+                    {"region": "us-east", "bucket_name": "backups", "permissions": "read_only"}
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Creating Linode instances which bootstrap themselves.
+//!
+//! An instance can run a [`StackScript`] on its first boot, configured with
+//! values for the script's user-defined fields (UDFs), or be given cloud-init
+//! user data with [`CreateInstance::user_data`]. Cloud-init requires an image
+//! which supports Linode's metadata service.
+
+use std::collections::BTreeMap;
+
+use api_client::Secret;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use hyperdriver::Body;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{GetInstance, Instance, LinodeClient, LinodeID, Paginated, Result};
+
+/// A StackScript, which Linode runs when deploying an instance from it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StackScript {
+    /// The ID of the StackScript.
+    pub id: LinodeID,
+
+    /// The name of the StackScript.
+    pub label: String,
+
+    /// The user who owns the StackScript.
+    pub username: String,
+
+    /// A description of the StackScript.
+    #[serde(default)]
+    pub description: String,
+
+    /// Images which the StackScript can be deployed with, e.g. `linode/debian12`.
+    #[serde(default)]
+    pub images: Vec<String>,
+
+    /// Whether anyone can deploy the StackScript.
+    #[serde(default)]
+    pub is_public: bool,
+
+    /// The script itself.
+    #[serde(default)]
+    pub script: String,
+
+    /// Fields which are passed to the script when it is deployed.
+    #[serde(default)]
+    pub user_defined_fields: Vec<UserDefinedField>,
+}
+
+impl StackScript {
+    /// Required fields which have no value in `instance`.
+    pub fn missing_fields<'s>(
+        &'s self,
+        instance: &'s CreateInstance,
+    ) -> impl Iterator<Item = &'s UserDefinedField> {
+        self.user_defined_fields.iter().filter(|field| {
+            field.is_required() && !instance.stackscript_data.contains_key(&field.name)
+        })
+    }
+}
+
+/// A user-defined field (UDF) of a StackScript.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserDefinedField {
+    /// The name of the field, used as the key for its value.
+    pub name: String,
+
+    /// A human-readable label for the field.
+    pub label: String,
+
+    /// An example value.
+    #[serde(default)]
+    pub example: Option<String>,
+
+    /// The value used when none is given.
+    #[serde(default)]
+    pub default: Option<String>,
+
+    /// Comma-separated values, one of which must be given.
+    #[serde(default, rename = "oneOf")]
+    pub one_of: Option<String>,
+
+    /// Comma-separated values, any of which may be given.
+    #[serde(default, rename = "manyOf")]
+    pub many_of: Option<String>,
+}
+
+impl UserDefinedField {
+    /// Whether a value must be given, because the field has no default.
+    pub fn is_required(&self) -> bool {
+        self.default.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct InstanceMetadata {
+    /// Base64 encoded cloud-init user data.
+    user_data: String,
+}
+
+/// Request to create a Linode instance.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateInstance {
+    region: String,
+
+    #[serde(rename = "type")]
+    kind: String,
+
+    image: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "revealed_secret"
+    )]
+    root_pass: Option<Secret>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authorized_keys: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stackscript_id: Option<LinodeID>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    stackscript_data: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<InstanceMetadata>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl CreateInstance {
+    /// Create an instance of a plan type (e.g. `g6-nanode-1`) in a region, from an image.
+    pub fn new(
+        region: impl Into<String>,
+        kind: impl Into<String>,
+        image: impl Into<String>,
+    ) -> Self {
+        Self {
+            region: region.into(),
+            kind: kind.into(),
+            image: image.into(),
+            label: None,
+            root_pass: None,
+            authorized_keys: Vec::new(),
+            stackscript_id: None,
+            stackscript_data: BTreeMap::new(),
+            metadata: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Set the label of the instance. Linode generates one if this isn't set.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Set the root password. Linode requires one when deploying an image.
+    pub fn root_pass(mut self, password: impl Into<Secret>) -> Self {
+        self.root_pass = Some(password.into());
+        self
+    }
+
+    /// Add a public SSH key for the root user.
+    pub fn authorized_key(mut self, key: impl Into<String>) -> Self {
+        self.authorized_keys.push(key.into());
+        self
+    }
+
+    /// Run a StackScript when the instance first boots.
+    pub fn stackscript(mut self, id: LinodeID) -> Self {
+        self.stackscript_id = Some(id);
+        self
+    }
+
+    /// Set the value of one of the StackScript's user-defined fields.
+    pub fn stackscript_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.stackscript_data.insert(name.into(), value.into());
+        self
+    }
+
+    /// Provide cloud-init user data, e.g. a `#cloud-config` document.
+    pub fn user_data(mut self, user_data: impl AsRef<[u8]>) -> Self {
+        self.metadata = Some(InstanceMetadata {
+            user_data: BASE64_STANDARD.encode(user_data),
+        });
+        self
+    }
+
+    /// Add a tag to the instance.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+}
+
+fn revealed_secret<S>(secret: &Option<Secret>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match secret {
+        Some(secret) => serializer.serialize_str(secret.revealed()),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl LinodeClient {
+    /// List the StackScripts owned by this account.
+    ///
+    /// Public StackScripts aren't listed, as there are thousands of them, but
+    /// can be fetched by ID with [`LinodeClient::get_stackscript`].
+    #[tracing::instrument(skip(self))]
+    pub fn list_stackscripts(&self) -> Paginated<StackScript> {
+        let request = self
+            .inner
+            .get("linode/stackscripts")
+            .header("X-Filter", r#"{"mine": true}"#)
+            .body(Body::empty())
+            .build()
+            .unwrap();
+        api_client::Paginated::new(self.inner.clone(), request)
+    }
+
+    /// Get a StackScript by its ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_stackscript(&self, id: LinodeID) -> Result<StackScript> {
+        self.get(&format!("linode/stackscripts/{id}")).await
+    }
+
+    /// Create a Linode instance.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_linode_instance(&self, instance: &CreateInstance) -> Result<Instance> {
+        let instance: GetInstance = self.post("linode/instances", instance).await?;
+        let instance = Instance::new(instance);
+        tracing::debug!("Created instance {} ({})", instance.label(), instance.id());
+        Ok(instance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_instance_request() {
+        let request = CreateInstance::new("us-east", "g6-nanode-1", "linode/debian12")
+            .label("web-1")
+            .root_pass("hunter2")
+            .stackscript(LinodeID(10079))
+            .stackscript_field("hostname", "web-1")
+            .user_data("#cloud-config\n");
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "region": "us-east",
+                "type": "g6-nanode-1",
+                "image": "linode/debian12",
+                "label": "web-1",
+                "root_pass": "hunter2",
+                "stackscript_id": 10079,
+                "stackscript_data": {"hostname": "web-1"},
+                "metadata": {"user_data": "I2Nsb3VkLWNvbmZpZwo="},
+            })
+        );
+    }
+
+    #[test]
+    fn missing_fields() {
+        let script: StackScript = serde_json::from_str(
+            r#"{"id": 10079, "label": "bootstrap", "username": "emporium",
+                "images": ["linode/debian12"], "is_public": false,
+                "user_defined_fields": [
+                    {"name": "hostname", "label": "Hostname"},
+                    {"name": "role", "label": "Role", "oneOf": "web,db", "default": "web"}
+                ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            script.user_defined_fields[1].one_of.as_deref(),
+            Some("web,db")
+        );
+
+        let request =
+            CreateInstance::new("us-east", "g6-nanode-1", "linode/debian12").stackscript(script.id);
+        let missing: Vec<_> = script
+            .missing_fields(&request)
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(missing, vec!["hostname"]);
+
+        let request = request.stackscript_field("hostname", "web-1");
+        assert_eq!(script.missing_fields(&request).count(), 0);
+    }
+}
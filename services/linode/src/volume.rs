@@ -0,0 +1,296 @@
+//! Linode block storage volumes.
+//!
+//! Volumes are created, attached and resized asynchronously, so each change is
+//! accepted by the API before the volume is ready. Use
+//! [`LinodeClient::wait_for_volume_status`] to wait for a change to finish.
+
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Empty, LinodeClient, LinodeError, LinodeID, Paginated, Result};
+
+/// How often to check the status of a volume while waiting for it to change.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The ID of a Linode volume.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct VolumeID(LinodeID);
+
+impl fmt::Display for VolumeID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The status of a volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeStatus {
+    /// The volume is being created.
+    Creating,
+
+    /// The volume is ready to use.
+    Active,
+
+    /// The volume is being resized.
+    Resizing,
+
+    /// The volume's encryption key is being rotated.
+    KeyRotating,
+
+    /// The volume needs attention from Linode support.
+    ContactSupport,
+}
+
+impl fmt::Display for VolumeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            VolumeStatus::Creating => "creating",
+            VolumeStatus::Active => "active",
+            VolumeStatus::Resizing => "resizing",
+            VolumeStatus::KeyRotating => "key_rotating",
+            VolumeStatus::ContactSupport => "contact_support",
+        };
+        f.write_str(status)
+    }
+}
+
+/// A block storage volume.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Volume {
+    /// The ID of the volume.
+    pub id: VolumeID,
+
+    /// The name of the volume.
+    pub label: String,
+
+    /// The status of the volume.
+    pub status: VolumeStatus,
+
+    /// The size of the volume, in GB.
+    pub size: u32,
+
+    /// The region which holds the volume, e.g. `us-east`.
+    pub region: String,
+
+    /// The instance the volume is attached to, if any.
+    #[serde(default)]
+    pub linode_id: Option<LinodeID>,
+
+    /// The device path of the volume on the instance it is attached to.
+    #[serde(default)]
+    pub filesystem_path: String,
+
+    /// Tags on the volume.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Request to create a volume.
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateVolume {
+    label: String,
+    size: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    region: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    linode_id: Option<LinodeID>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl CreateVolume {
+    /// Create a volume with this label and size, in GB.
+    ///
+    /// The volume must either be created in a region, or attached to an instance
+    /// when it is created.
+    pub fn new(label: impl Into<String>, size: u32) -> Self {
+        Self {
+            label: label.into(),
+            size,
+            region: None,
+            linode_id: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Create the volume in a region, without attaching it.
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Attach the volume to an instance when it is created, in the instance's region.
+    pub fn linode(mut self, id: LinodeID) -> Self {
+        self.linode_id = Some(id);
+        self
+    }
+
+    /// Add a tag to the volume.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AttachVolume {
+    linode_id: LinodeID,
+    persist_across_boots: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ResizeVolume {
+    size: u32,
+}
+
+impl LinodeClient {
+    /// List all volumes.
+    #[tracing::instrument(skip(self))]
+    pub fn list_volumes(&self) -> Paginated<Volume> {
+        self.get_paginated("volumes")
+    }
+
+    /// Get a volume by its ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_volume(&self, id: VolumeID) -> Result<Volume> {
+        self.get(&format!("volumes/{id}")).await
+    }
+
+    /// Create a volume.
+    #[tracing::instrument(skip(self))]
+    pub async fn create_volume(&self, volume: &CreateVolume) -> Result<Volume> {
+        let volume: Volume = self.post("volumes", volume).await?;
+        tracing::debug!("Created volume {} ({})", volume.label, volume.id);
+        Ok(volume)
+    }
+
+    /// Attach a volume to an instance, keeping it attached when the instance reboots.
+    #[tracing::instrument(skip(self))]
+    pub async fn attach_volume(&self, id: VolumeID, instance: LinodeID) -> Result<Volume> {
+        let attach = AttachVolume {
+            linode_id: instance,
+            persist_across_boots: true,
+        };
+        let volume: Volume = self.post(&format!("volumes/{id}/attach"), &attach).await?;
+        tracing::debug!("Attached volume {id} to instance {instance}");
+        Ok(volume)
+    }
+
+    /// Detach a volume from the instance it is attached to.
+    #[tracing::instrument(skip(self))]
+    pub async fn detach_volume(&self, id: VolumeID) -> Result<()> {
+        self.post::<_, Empty>(&format!("volumes/{id}/detach"), &serde_json::json!({}))
+            .await?;
+        tracing::debug!("Detached volume {id}");
+        Ok(())
+    }
+
+    /// Resize a volume, in GB. Volumes can only grow.
+    #[tracing::instrument(skip(self))]
+    pub async fn resize_volume(&self, id: VolumeID, size: u32) -> Result<Volume> {
+        let volume: Volume = self
+            .post(&format!("volumes/{id}/resize"), &ResizeVolume { size })
+            .await?;
+        tracing::debug!("Resizing volume {id} to {size}GB");
+        Ok(volume)
+    }
+
+    /// Delete a volume. Linode only deletes detached volumes.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_volume(&self, id: VolumeID) -> Result<()> {
+        self.delete::<Empty>(&format!("volumes/{id}")).await?;
+        tracing::debug!("Deleted volume {id}");
+        Ok(())
+    }
+
+    /// Wait until a volume has a status, checking it every few seconds.
+    ///
+    /// Returns [`LinodeError::Timeout`] if the volume doesn't reach the status
+    /// within `timeout`.
+    #[tracing::instrument(skip(self))]
+    pub async fn wait_for_volume_status(
+        &self,
+        id: VolumeID,
+        status: VolumeStatus,
+        timeout: Duration,
+    ) -> Result<Volume> {
+        let wait = async {
+            loop {
+                let volume = self.get_volume(id).await?;
+                if volume.status == status {
+                    return Ok(volume);
+                }
+                tracing::trace!("Volume {id} is {}, waiting for {status}", volume.status);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| LinodeError::Timeout {
+                kind: "volume",
+                value: id.to_string(),
+                status: status.to_string(),
+            })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volume() {
+        let volume: Volume = serde_json::from_value(serde_json::json!({
+            "id": 12345,
+            "label": "my-volume",
+            "status": "active",
+            "size": 30,
+            "region": "us-east",
+            "linode_id": 67890,
+            "linode_label": "web-1",
+            "filesystem_path": "/dev/disk/by-id/scsi-0Linode_Volume_my-volume",
+            "tags": ["example"],
+            "created": "2018-01-01T00:01:01",
+            "updated": "2018-01-01T00:01:01",
+        }))
+        .unwrap();
+        assert_eq!(volume.id.to_string(), "12345");
+        assert_eq!(volume.status, VolumeStatus::Active);
+        assert_eq!(volume.linode_id, Some(LinodeID(67890)));
+
+        let volume: Volume = serde_json::from_value(serde_json::json!({
+            "id": 12345,
+            "label": "my-volume",
+            "status": "key_rotating",
+            "size": 30,
+            "region": "us-east",
+            "linode_id": null,
+        }))
+        .unwrap();
+        assert_eq!(volume.status, VolumeStatus::KeyRotating);
+        assert!(volume.linode_id.is_none());
+    }
+
+    #[test]
+    fn create_volume_request() {
+        let request = CreateVolume::new("my-volume", 30)
+            .linode(LinodeID(67890))
+            .tag("example");
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "label": "my-volume",
+                "size": 30,
+                "linode_id": 67890,
+                "tags": ["example"],
+            })
+        );
+    }
+}
@@ -1,10 +1,7 @@
 //! Fetch a single commit from a repository using the Github API.
 
-use std::sync::Arc;
-
 use api_client::response::ResponseBodyExt as _;
 use eyre::Report;
-use jaws::crypto::rsa::{self, pkcs1::DecodeRsaPrivateKey};
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -14,12 +11,12 @@ async fn main() -> eyre::Result<()> {
         let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../.github/app-key.pem");
 
         let dst = tokio::fs::read_to_string(path).await?;
-        rsa::RsaPrivateKey::from_pkcs1_pem(&dst).expect("valid pkcs8 key")
+        octocat::AppKey::from_pem(&dst).expect("valid private key")
     };
 
     let app = octocat::GithubApp::new(
         std::env::var("GITHUB_APP_ID").expect("GITHUB_APP_ID is set"),
-        Arc::new(key),
+        key,
     );
 
     let client = app
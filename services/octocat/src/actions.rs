@@ -0,0 +1,140 @@
+//! Actions API, for inspecting and managing workflow runs.
+
+use api_client::response::ResponseExt as _;
+use api_client::uri::UriExtension as _;
+use futures::stream::{BoxStream, StreamExt as _, TryStreamExt as _};
+use http::header;
+use http_body_util::BodyExt as _;
+use hyperdriver::service::ServiceExt as _;
+use hyperdriver::Body;
+
+use crate::models::actions::{WorkflowRun, WorkflowRunFilter, WorkflowRunList};
+use crate::{Error, GithubClient, ResponseError};
+
+impl GithubClient {
+    /// List the workflow runs on a repository, newest first.
+    pub fn workflow_runs(
+        &self,
+        owner: &str,
+        repo: &str,
+        filter: &WorkflowRunFilter,
+    ) -> BoxStream<'static, Result<WorkflowRun, Error>> {
+        let endpoint = match &filter.workflow {
+            Some(workflow_id) => {
+                format!("/repos/{owner}/{repo}/actions/workflows/{workflow_id}/runs?per_page=100")
+            }
+            None => format!("/repos/{owner}/{repo}/actions/runs?per_page=100"),
+        };
+
+        let endpoint = match endpoint
+            .parse::<http::Uri>()
+            .expect("valid workflow runs endpoint")
+            .append_query(filter)
+        {
+            Ok(uri) => uri.to_string(),
+            Err(error) => {
+                let error = Error::Client(api_client::Error::from(error));
+                return futures::stream::once(futures::future::ready(Err(error))).boxed();
+            }
+        };
+
+        self.paginate::<WorkflowRunList>(&endpoint)
+    }
+
+    /// Get a single workflow run.
+    #[tracing::instrument(skip(self))]
+    pub async fn workflow_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<WorkflowRun, Error> {
+        self.get_json(&format!("/repos/{owner}/{repo}/actions/runs/{run_id}"))
+            .await
+    }
+
+    /// Re-run every job in a workflow run.
+    #[tracing::instrument(skip(self))]
+    pub async fn rerun_workflow(&self, owner: &str, repo: &str, run_id: u64) -> Result<(), Error> {
+        self.send(self.post(&format!(
+            "/repos/{owner}/{repo}/actions/runs/{run_id}/rerun"
+        )))
+        .await?;
+        tracing::debug!("Re-running workflow run {run_id} on {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Re-run only the failed jobs in a workflow run.
+    #[tracing::instrument(skip(self))]
+    pub async fn rerun_failed_jobs(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<(), Error> {
+        self.send(self.post(&format!(
+            "/repos/{owner}/{repo}/actions/runs/{run_id}/rerun-failed-jobs"
+        )))
+        .await?;
+        tracing::debug!("Re-running failed jobs in workflow run {run_id} on {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Cancel a workflow run.
+    #[tracing::instrument(skip(self))]
+    pub async fn cancel_workflow(&self, owner: &str, repo: &str, run_id: u64) -> Result<(), Error> {
+        self.send(self.post(&format!(
+            "/repos/{owner}/{repo}/actions/runs/{run_id}/cancel"
+        )))
+        .await?;
+        tracing::debug!("Cancelled workflow run {run_id} on {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Download the logs for a workflow run, as a stream of bytes from a zip archive.
+    ///
+    /// Github redirects to a short-lived archive URL, which is fetched without the
+    /// installation token, since the archive host rejects it.
+    #[tracing::instrument(skip(self))]
+    pub async fn workflow_run_logs(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<BoxStream<'static, Result<bytes::Bytes, Error>>, Error> {
+        let response = self
+            .get(&format!("/repos/{owner}/{repo}/actions/runs/{run_id}/logs"))
+            .send()
+            .await?;
+
+        let response = if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned);
+            let Some(location) = location else {
+                return Err(ResponseError::from_response(response.into_response())
+                    .await
+                    .into());
+            };
+
+            let request = http::Request::get(location)
+                .body(Body::empty())
+                .expect("valid log archive request");
+            let response = self.app.client.clone().oneshot(request).await?;
+            if !response.status().is_success() {
+                return Err(ResponseError::from_response(response).await.into());
+            }
+            response
+        } else {
+            self.error_for_status(response).await?.into_response()
+        };
+
+        Ok(response
+            .into_body()
+            .into_data_stream()
+            .map_err(Error::Body)
+            .boxed())
+    }
+}
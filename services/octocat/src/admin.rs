@@ -0,0 +1,213 @@
+//! Repository administration, for bootstrapping new repositories: creating and
+//! deleting them, protecting branches, and managing deploy keys and Actions secrets.
+
+use api_client::Secret;
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use crypto_box::aead::OsRng;
+use crypto_box::PublicKey;
+use futures::stream::BoxStream;
+
+use crate::models::admin::{
+    BranchProtection, DeployKey, DeployKeyList, EncryptedSecret, NewDeployKey, NewRepository,
+    SecretsPublicKey,
+};
+use crate::models::Repository;
+use crate::{Error, GithubClient};
+
+impl GithubClient {
+    /// Create a repository in an organization.
+    #[tracing::instrument(skip(self, repository))]
+    pub async fn create_repository(
+        &self,
+        org: &str,
+        repository: &NewRepository<'_>,
+    ) -> Result<Repository, Error> {
+        let repository: Repository = self
+            .send_json(self.post(&format!("/orgs/{org}/repos")), repository)
+            .await?;
+        tracing::debug!("Created repository {}", repository.full_name);
+        Ok(repository)
+    }
+
+    /// Delete a repository. This can't be undone.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_repository(&self, owner: &str, repo: &str) -> Result<(), Error> {
+        self.send(self.delete(&format!("/repos/{owner}/{repo}")))
+            .await?;
+        tracing::debug!("Deleted repository {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Replace the protection rules for a branch.
+    #[tracing::instrument(skip(self, protection))]
+    pub async fn set_branch_protection(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        protection: &BranchProtection,
+    ) -> Result<(), Error> {
+        let _: serde_json::Value = self
+            .send_json(
+                self.put(&format!(
+                    "/repos/{owner}/{repo}/branches/{branch}/protection"
+                )),
+                protection,
+            )
+            .await?;
+        tracing::debug!("Protected branch {branch} on {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Remove all protection rules from a branch.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_branch_protection(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<(), Error> {
+        self.send(self.delete(&format!(
+            "/repos/{owner}/{repo}/branches/{branch}/protection"
+        )))
+        .await?;
+        tracing::debug!("Removed protection from branch {branch} on {owner}/{repo}");
+        Ok(())
+    }
+
+    /// List the deploy keys of a repository.
+    pub fn list_deploy_keys(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> BoxStream<'static, Result<DeployKey, Error>> {
+        self.paginate::<DeployKeyList>(&format!("/repos/{owner}/{repo}/keys?per_page=100"))
+    }
+
+    /// Add a deploy key to a repository.
+    #[tracing::instrument(skip(self, key))]
+    pub async fn add_deploy_key(
+        &self,
+        owner: &str,
+        repo: &str,
+        key: &NewDeployKey<'_>,
+    ) -> Result<DeployKey, Error> {
+        let key: DeployKey = self
+            .send_json(self.post(&format!("/repos/{owner}/{repo}/keys")), key)
+            .await?;
+        tracing::debug!("Added deploy key {} to {owner}/{repo}", key.title);
+        Ok(key)
+    }
+
+    /// Remove a deploy key from a repository.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_deploy_key(
+        &self,
+        owner: &str,
+        repo: &str,
+        key_id: u64,
+    ) -> Result<(), Error> {
+        self.send(self.delete(&format!("/repos/{owner}/{repo}/keys/{key_id}")))
+            .await?;
+        tracing::debug!("Removed deploy key {key_id} from {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Get the public key used to encrypt Actions secrets for a repository.
+    #[tracing::instrument(skip(self))]
+    pub async fn secrets_public_key(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<SecretsPublicKey, Error> {
+        self.get_json(&format!("/repos/{owner}/{repo}/actions/secrets/public-key"))
+            .await
+    }
+
+    /// Create or update an Actions secret on a repository.
+    ///
+    /// The value is encrypted with the repository's public key before it is sent,
+    /// so only Github Actions can read it.
+    #[tracing::instrument(skip(self, value))]
+    pub async fn set_repository_secret(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+        value: &Secret,
+    ) -> Result<(), Error> {
+        let key = self.secrets_public_key(owner, repo).await?;
+        let secret = EncryptedSecret {
+            encrypted_value: seal(&key, value)?,
+            key_id: &key.key_id,
+        };
+
+        let body = serde_json::to_vec(&secret)?;
+        self.send(
+            self.put(&format!("/repos/{owner}/{repo}/actions/secrets/{name}"))
+                .header(http::header::CONTENT_TYPE, "application/json")
+                .body(body),
+        )
+        .await?;
+        tracing::debug!("Set secret {name} on {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Delete an Actions secret from a repository.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_repository_secret(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+    ) -> Result<(), Error> {
+        self.send(self.delete(&format!("/repos/{owner}/{repo}/actions/secrets/{name}")))
+            .await?;
+        tracing::debug!("Deleted secret {name} from {owner}/{repo}");
+        Ok(())
+    }
+}
+
+/// Encrypt a secret value for Github with a libsodium sealed box, returning it
+/// base64 encoded.
+fn seal(key: &SecretsPublicKey, value: &Secret) -> Result<String, Error> {
+    let key = BASE64_STANDARD
+        .decode(&key.key)
+        .ok()
+        .and_then(|key| PublicKey::from_slice(&key).ok())
+        .ok_or(Error::SecretEncryption("invalid public key"))?;
+
+    let sealed = key
+        .seal(&mut OsRng, value.revealed().as_bytes())
+        .map_err(|_| Error::SecretEncryption("sealing failed"))?;
+    Ok(BASE64_STANDARD.encode(sealed))
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_box::SecretKey;
+
+    use super::*;
+
+    #[test]
+    fn seal_secret() {
+        let secret_key = SecretKey::generate(&mut OsRng);
+        let key = SecretsPublicKey {
+            key_id: "568250167242549743".into(),
+            key: BASE64_STANDARD.encode(secret_key.public_key().as_bytes()),
+        };
+
+        let sealed = seal(&key, &Secret::from("hunter2")).unwrap();
+        let sealed = BASE64_STANDARD.decode(sealed).unwrap();
+        assert_eq!(secret_key.unseal(&sealed).unwrap(), b"hunter2");
+
+        let invalid = SecretsPublicKey {
+            key: BASE64_STANDARD.encode(b"too short"),
+            ..key
+        };
+        assert!(matches!(
+            seal(&invalid, &Secret::from("hunter2")),
+            Err(Error::SecretEncryption(_))
+        ));
+    }
+}
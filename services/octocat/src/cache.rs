@@ -0,0 +1,178 @@
+//! Persistent storage for cached Github GET responses.
+//!
+//! Github does not count conditional requests answered with `304 Not Modified`
+//! against the rate limit, so keeping the last response for each URI in storage
+//! lets automation start cold against large organizations without re-fetching
+//! everything it saw on the previous run.
+//!
+//! [`StorageCache`] is a [`CacheStore`] for an [`api_client::ResponseCache`], which
+//! does the revalidation; see [`GithubClient::with_cache`](crate::GithubClient::with_cache).
+
+use api_client::{BoxFuture, CacheStore, CachedResponse};
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use camino::Utf8PathBuf;
+use http::{HeaderMap, HeaderName, HeaderValue, Uri};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use storage::StorageBucket;
+
+/// A cached response as it is kept in storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredResponse {
+    uri: String,
+    headers: Vec<(String, String)>,
+
+    /// The response body, base64 encoded.
+    body: String,
+}
+
+impl StoredResponse {
+    fn new(uri: &Uri, response: &CachedResponse) -> Self {
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_owned(), value.to_str().ok()?.to_owned()))
+            })
+            .collect();
+
+        Self {
+            uri: uri.to_string(),
+            headers,
+            body: BASE64_STANDARD.encode(response.body()),
+        }
+    }
+
+    fn response(&self) -> Option<CachedResponse> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            headers.append(
+                HeaderName::try_from(name.as_str()).ok()?,
+                HeaderValue::from_str(value).ok()?,
+            );
+        }
+        let body = BASE64_STANDARD.decode(&self.body).ok()?;
+        Some(CachedResponse::new(headers, body.into()))
+    }
+}
+
+/// A cache of Github GET responses stored in a storage bucket.
+///
+/// Cache failures are logged and otherwise ignored, so a broken cache only
+/// costs extra requests.
+#[derive(Debug, Clone)]
+pub struct StorageCache {
+    bucket: StorageBucket,
+    prefix: Utf8PathBuf,
+}
+
+impl StorageCache {
+    /// Create a cache which stores responses under `prefix` in a bucket.
+    pub fn new(bucket: StorageBucket, prefix: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            bucket,
+            prefix: prefix.into(),
+        }
+    }
+
+    fn path(&self, uri: &Uri) -> Utf8PathBuf {
+        let key = hex::encode(Sha256::digest(uri.to_string().as_bytes()));
+        self.prefix.join(format!("{key}.json"))
+    }
+
+    async fn load(&self, uri: &Uri) -> Option<CachedResponse> {
+        let path = self.path(uri);
+        let mut buf = Vec::new();
+        if let Err(error) = self.bucket.download(&path, &mut buf).await {
+            tracing::trace!(%uri, %path, "Cache miss: {error}");
+            return None;
+        }
+
+        let stored = match serde_json::from_slice::<StoredResponse>(&buf) {
+            Ok(stored) if stored.uri == uri.to_string() => stored,
+            Ok(_) => return None,
+            Err(error) => {
+                tracing::warn!(%uri, %path, "Invalid cache entry: {error}");
+                return None;
+            }
+        };
+
+        let response = stored.response();
+        if response.is_none() {
+            tracing::warn!(%uri, %path, "Invalid cache entry");
+        }
+        response
+    }
+
+    async fn store(&self, uri: &Uri, response: CachedResponse) {
+        let path = self.path(uri);
+        let data = match serde_json::to_vec(&StoredResponse::new(uri, &response)) {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::warn!(%uri, "Failed to serialize cache entry: {error}");
+                return;
+            }
+        };
+
+        if let Err(error) = self.bucket.upload(&path, &mut data.as_slice()).await {
+            tracing::warn!(%uri, %path, "Failed to store cache entry: {error}");
+        }
+    }
+
+    async fn forget(&self, uri: &Uri) {
+        let path = self.path(uri);
+        if let Err(error) = self.bucket.delete(&path).await {
+            if !error.is_not_found() {
+                tracing::warn!(%uri, %path, "Failed to remove cache entry: {error}");
+            }
+        }
+    }
+}
+
+impl CacheStore for StorageCache {
+    fn get<'a>(&'a self, uri: &'a Uri) -> BoxFuture<'a, Option<CachedResponse>> {
+        Box::pin(self.load(uri))
+    }
+
+    fn put<'a>(&'a self, uri: &'a Uri, response: CachedResponse) -> BoxFuture<'a, ()> {
+        Box::pin(self.store(uri, response))
+    }
+
+    fn remove<'a>(&'a self, uri: &'a Uri) -> BoxFuture<'a, ()> {
+        Box::pin(self.forget(uri))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header;
+    use storage::{MemoryStorage, Storage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cache_roundtrip() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["cache"]));
+        let cache = StorageCache::new(storage.bucket("cache"), "github");
+
+        let uri: Uri = "https://api.github.com/repos/octo/repo".parse().unwrap();
+        assert!(cache.get(&uri).await.is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"abc\""));
+        cache
+            .put(&uri, CachedResponse::new(headers, "{}".into()))
+            .await;
+
+        let found = cache.get(&uri).await.unwrap();
+        assert_eq!(found.body().as_ref(), b"{}");
+        assert_eq!(found.headers()[header::ETAG], "\"abc\"");
+
+        let other: Uri = "https://api.github.com/repos/octo/other".parse().unwrap();
+        assert!(cache.get(&other).await.is_none());
+
+        cache.remove(&uri).await;
+        assert!(cache.get(&uri).await.is_none());
+        cache.remove(&uri).await;
+    }
+}
@@ -0,0 +1,44 @@
+//! Commit status API, for reporting results without the full Checks API.
+
+use crate::models::commits::{CombinedStatus, CommitStatus, NewStatus};
+use crate::{Error, GithubClient};
+
+impl GithubClient {
+    /// Report a status for a commit.
+    #[tracing::instrument(skip(self, status))]
+    pub async fn create_commit_status(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+        status: &NewStatus<'_>,
+    ) -> Result<CommitStatus, Error> {
+        let status: CommitStatus = self
+            .send_json(
+                self.post(&format!("/repos/{owner}/{repo}/statuses/{sha}")),
+                status,
+            )
+            .await?;
+        tracing::debug!(
+            "Reported {:?} for {} on {owner}/{repo}@{sha}",
+            status.state,
+            status.context
+        );
+        Ok(status)
+    }
+
+    /// Get the combined status of a ref, which can be a SHA, branch or tag.
+    ///
+    /// Only the first 100 contexts are included.
+    pub async fn combined_status(
+        &self,
+        owner: &str,
+        repo: &str,
+        git_ref: &str,
+    ) -> Result<CombinedStatus, Error> {
+        self.get_json(&format!(
+            "/repos/{owner}/{repo}/commits/{git_ref}/status?per_page=100"
+        ))
+        .await
+    }
+}
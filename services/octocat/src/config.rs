@@ -1,15 +1,15 @@
 //! Configuration for Github Apps
 
-use std::{io, sync::Arc};
+use std::io;
 
 use camino::{Utf8Path, Utf8PathBuf};
-use jaws::crypto::rsa;
-use rsa::pkcs8::Error as Pkcs8Error;
-use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey};
+use secret::provider::OnePasswordProvider;
+use secret::{ProviderError, Secret, SecretProvider as _};
 use serde::Deserialize;
 use storage::Storage;
 
 use super::GithubApp;
+use crate::key::{AppKey, KeyError};
 
 /// Errors that can occur when reading a key from a file
 #[derive(Debug, thiserror::Error)]
@@ -18,13 +18,9 @@ pub enum ErrorKind {
     #[error("IO: {0}")]
     Io(#[from] io::Error),
 
-    /// Error decoding the key as PKCS8
-    #[error("PKCS8: {0}")]
-    Pkcs8(#[from] Pkcs8Error),
-
-    /// Error decoding the key as PKCS1
-    #[error("PKCS1: {0}")]
-    Pkcs1(#[from] rsa::pkcs1::Error),
+    /// Error decoding the key
+    #[error("Key: {0}")]
+    Key(#[from] KeyError),
 }
 
 /// Error reading the key from a file
@@ -45,6 +41,23 @@ pub enum AppKeyError {
     /// Error reading the key from a storage provider
     #[error("App Key from storage provider")]
     Storage(#[from] StorageError),
+
+    /// Error decoding an inline key
+    #[error("App Key from inline PEM")]
+    Pem(#[source] KeyError),
+
+    /// Error reading the key from 1Password
+    #[error("App Key from 1Password")]
+    OnePassword(#[from] OnePasswordError),
+
+    /// Error decrypting an age-encrypted key
+    #[cfg(feature = "age")]
+    #[error("App Key from age-encrypted file")]
+    Age(#[from] AgeError),
+
+    /// The app settings are not valid
+    #[error("App settings")]
+    Settings(#[from] SettingsError),
 }
 
 impl GithubApp {
@@ -55,40 +68,31 @@ impl GithubApp {
         config: &GithubAppConfig,
         storage: &Storage,
     ) -> Result<Self, AppKeyError> {
-        match &config.signing_key {
-            GithubAppKey::File(path) => {
-                let key = rsa_key_from_file(path).map_err(AppKeyError::File)?;
-                Ok(GithubApp::new(config.app_id.clone(), Arc::new(key)))
-            }
-            GithubAppKey::B2 { path, bucket } => {
-                let key = rsa_key_from_storage(storage, bucket, path).await?;
-                Ok(GithubApp::new(config.app_id.clone(), Arc::new(key)))
-            }
-        }
+        let key = match &config.signing_key {
+            GithubAppKey::File(path) => key_from_file(path).map_err(AppKeyError::File)?,
+            GithubAppKey::B2 { path, bucket } => key_from_storage(storage, bucket, path).await?,
+            GithubAppKey::Pem(pem) => AppKey::from_pem(pem.revealed()).map_err(AppKeyError::Pem)?,
+            GithubAppKey::OnePassword(reference) => key_from_one_password(reference).await?,
+            #[cfg(feature = "age")]
+            GithubAppKey::Age { path, identity } => key_from_age(path, identity).await?,
+        };
+
+        Ok(GithubApp::with_settings(
+            config.app_id.clone(),
+            key,
+            config.settings.clone(),
+        )?)
     }
 }
 
-fn rsa_key_from_file(path: &Utf8Path) -> Result<rsa::RsaPrivateKey, FileError> {
-    match rsa::RsaPrivateKey::read_pkcs1_pem_file(path).map_err(|err| FileError {
+fn key_from_file(path: &Utf8Path) -> Result<AppKey, FileError> {
+    let error = |source: ErrorKind| FileError {
         path: path.to_path_buf(),
-        source: err.into(),
-    }) {
-        Ok(key) => Ok(key),
-        Err(pkcs1_error) => {
-            let key = match rsa::RsaPrivateKey::read_pkcs8_pem_file(path).map_err(|err| FileError {
-                path: path.to_path_buf(),
-                source: err.into(),
-            }) {
-                Ok(key) => key,
-                Err(pkcs8_error) => {
-                    tracing::error!("Error reading as PKCS1: {}", pkcs1_error);
-                    tracing::error!("Error reading as PKCS8: {}", pkcs8_error);
-                    return Err(pkcs8_error);
-                }
-            };
-            Ok(key)
-        }
-    }
+        source,
+    };
+
+    let contents = std::fs::read_to_string(path).map_err(|err| error(err.into()))?;
+    AppKey::from_pem(&contents).map_err(|err| error(err.into()))
 }
 
 /// Errors that can occur when reading a key from a storage provider
@@ -106,13 +110,9 @@ pub enum StorageErrorKind {
     #[error("Encoding: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
 
-    /// Error decoding the key as PKCS8
-    #[error("PKCS8: {0}")]
-    Pkcs8(#[from] Pkcs8Error),
-
-    /// Error decoding the key as PKCS1
-    #[error("PKCS1: {0}")]
-    Pkcs1(#[from] rsa::pkcs1::Error),
+    /// Error decoding the key
+    #[error("Key: {0}")]
+    Key(#[from] KeyError),
 }
 
 /// Error from a storage provider
@@ -124,11 +124,11 @@ pub struct StorageError {
     source: StorageErrorKind,
 }
 
-async fn rsa_key_from_storage(
+async fn key_from_storage(
     storage: &Storage,
     bucket: &str,
     path: &Utf8Path,
-) -> Result<rsa::RsaPrivateKey, StorageError> {
+) -> Result<AppKey, StorageError> {
     let mut buf = Vec::new();
     storage
         .download(bucket, path, &mut buf)
@@ -145,13 +145,123 @@ async fn rsa_key_from_storage(
         source: err.into(),
     })?;
 
-    rsa::RsaPrivateKey::from_pkcs1_pem(&contents).map_err(|err| StorageError {
+    AppKey::from_pem(&contents).map_err(|err| StorageError {
         bucket: bucket.to_string(),
         path: path.to_path_buf(),
         source: err.into(),
     })
 }
 
+/// Errors that can occur when reading a key from 1Password
+#[derive(Debug, thiserror::Error)]
+pub enum OnePasswordErrorKind {
+    /// Error reading the secret with `op`
+    #[error("1Password: {0}")]
+    Provider(#[from] ProviderError),
+
+    /// Error decoding the key
+    #[error("Key: {0}")]
+    Key(#[from] KeyError),
+}
+
+/// Error reading the key from 1Password
+#[derive(Debug, thiserror::Error)]
+#[error("Reading Github Key in PEM format from {reference}")]
+pub struct OnePasswordError {
+    reference: String,
+    source: OnePasswordErrorKind,
+}
+
+async fn key_from_one_password(reference: &str) -> Result<AppKey, OnePasswordError> {
+    let error = |source: OnePasswordErrorKind| OnePasswordError {
+        reference: reference.to_owned(),
+        source,
+    };
+
+    let pem = read_one_password(reference)
+        .await
+        .map_err(|err| error(err.into()))?;
+    AppKey::from_pem(pem.revealed()).map_err(|err| error(err.into()))
+}
+
+/// Read a secret reference, e.g. `op://vault/item/field`, with the 1Password CLI.
+async fn read_one_password(reference: &str) -> Result<Secret, ProviderError> {
+    OnePasswordProvider::new().require(reference).await
+}
+
+/// Errors that can occur when decrypting an age-encrypted key
+#[cfg(feature = "age")]
+#[derive(Debug, thiserror::Error)]
+pub enum AgeErrorKind {
+    /// Error reading the encrypted key
+    #[error("IO: {0}")]
+    Io(#[from] io::Error),
+
+    /// The identity could not be loaded or parsed
+    #[error("Identity: {0}")]
+    Identity(String),
+
+    /// Error decrypting the key
+    #[error("Decrypt: {0}")]
+    Decrypt(#[from] age::DecryptError),
+
+    /// The decrypted key was not valid utf8
+    #[error("Encoding: {0}")]
+    Utf8Error(#[from] std::str::Utf8Error),
+
+    /// Error decoding the decrypted key
+    #[error("Key: {0}")]
+    Key(#[from] KeyError),
+}
+
+/// Error decrypting an age-encrypted key
+#[cfg(feature = "age")]
+#[derive(Debug, thiserror::Error)]
+#[error("Decrypting Github Key with age from {path:?}")]
+pub struct AgeError {
+    path: Utf8PathBuf,
+    source: AgeErrorKind,
+}
+
+#[cfg(feature = "age")]
+impl AgeIdentity {
+    async fn load(&self) -> Result<Secret, AgeErrorKind> {
+        match self {
+            AgeIdentity::Env(var) => Secret::from_env(var)
+                .map_err(|err| AgeErrorKind::Identity(format!("${var}: {err}"))),
+            AgeIdentity::OnePassword(reference) => read_one_password(reference)
+                .await
+                .map_err(|err| AgeErrorKind::Identity(err.to_string())),
+        }
+    }
+}
+
+/// Decrypt a key with age. The decrypted key is only held in memory, and is
+/// zeroed once it has been decoded.
+#[cfg(feature = "age")]
+async fn key_from_age(path: &Utf8Path, identity: &AgeIdentity) -> Result<AppKey, AgeError> {
+    use std::str::FromStr as _;
+
+    let error = |source: AgeErrorKind| AgeError {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let secret = identity.load().await.map_err(error)?;
+    let identity = age::x25519::Identity::from_str(secret.revealed().trim())
+        .map_err(|err| error(AgeErrorKind::Identity(err.to_owned())))?;
+
+    let ciphertext = tokio::fs::read(path)
+        .await
+        .map_err(|err| error(err.into()))?;
+    let plaintext = zeroize::Zeroizing::new(
+        age::decrypt(&identity, &ciphertext).map_err(|err| error(err.into()))?,
+    );
+
+    let pem = std::str::from_utf8(&plaintext).map_err(|err| error(err.into()))?;
+    AppKey::from_pem(pem).map_err(|err| error(err.into()))
+}
+
 /// Configuration for a Github App
 #[derive(Debug, Clone, Deserialize)]
 pub struct GithubAppConfig {
@@ -160,6 +270,120 @@ pub struct GithubAppConfig {
 
     /// App ID from Github
     pub app_id: String,
+
+    /// Timeouts and token lifetimes used by the app
+    #[serde(default)]
+    pub settings: GithubAppSettings,
+}
+
+/// Longest lifetime Github accepts for an app JWT, in seconds.
+const MAXIMUM_TOKEN_DURATION_SECONDS: u64 = 10 * 60;
+
+/// Timeouts and token lifetimes used by a Github App.
+///
+/// App JWTs are backdated by `clock_drift` seconds, and expire `token_duration`
+/// seconds from now. Github rejects tokens which live longer than 10 minutes,
+/// counting from the (backdated) issue time.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct GithubAppSettings {
+    /// Seconds to allow for clock drift between this host and Github.
+    pub clock_drift: u64,
+
+    /// Seconds for which each app JWT is valid.
+    pub token_duration: u64,
+
+    /// Seconds to wait for a connection to Github.
+    pub connect_timeout: u64,
+
+    /// Seconds to wait for a response from Github.
+    pub timeout: u64,
+
+    /// Version of the Github REST API to request.
+    pub api_version: crate::ApiVersion,
+}
+
+impl Default for GithubAppSettings {
+    fn default() -> Self {
+        Self {
+            clock_drift: crate::CLOCK_DRIFT_OFFSET_SECONDS,
+            token_duration: crate::TOKEN_DURATION_SECONDS,
+            connect_timeout: crate::CONNECT_TIMEOUT.as_secs(),
+            timeout: crate::TIMEOUT.as_secs(),
+            api_version: Default::default(),
+        }
+    }
+}
+
+impl GithubAppSettings {
+    /// Check that these settings will produce tokens Github accepts.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.token_duration <= self.clock_drift {
+            return Err(SettingsError::TokenDuration {
+                token_duration: self.token_duration,
+                clock_drift: self.clock_drift,
+            });
+        }
+
+        if self.clock_drift + self.token_duration > MAXIMUM_TOKEN_DURATION_SECONDS {
+            return Err(SettingsError::TokenLifetime {
+                lifetime: self.clock_drift + self.token_duration,
+            });
+        }
+
+        if self.timeout == 0 {
+            return Err(SettingsError::Timeout("timeout"));
+        }
+
+        if self.connect_timeout == 0 {
+            return Err(SettingsError::Timeout("connect_timeout"));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn clock_drift(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.clock_drift as i64)
+    }
+
+    pub(crate) fn token_duration(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.token_duration as i64)
+    }
+
+    pub(crate) fn connect_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.connect_timeout)
+    }
+
+    pub(crate) fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.timeout)
+    }
+}
+
+/// Invalid Github App settings
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SettingsError {
+    /// Tokens would expire before they can be used, once clock drift is accounted for.
+    #[error(
+        "token duration of {token_duration}s must be longer than clock drift of {clock_drift}s"
+    )]
+    TokenDuration {
+        /// Configured token duration, in seconds
+        token_duration: u64,
+
+        /// Configured clock drift, in seconds
+        clock_drift: u64,
+    },
+
+    /// Tokens would live longer than Github allows.
+    #[error("token lifetime of {lifetime}s exceeds Github's 10 minute limit")]
+    TokenLifetime {
+        /// Seconds between when the token is issued and when it expires
+        lifetime: u64,
+    },
+
+    /// A timeout was set to zero.
+    #[error("{0} must be greater than zero")]
+    Timeout(&'static str),
 }
 
 /// Configuration for a Github App Key source
@@ -177,4 +401,108 @@ pub enum GithubAppKey {
         /// Bucket containing the key
         bucket: String,
     },
+
+    /// The key itself, in PEM format
+    Pem(Secret),
+
+    /// Read the key from 1Password using `op read` with a secret
+    /// reference, e.g. `op://vault/item/private-key`
+    #[serde(rename = "1password")]
+    OnePassword(String),
+
+    /// Read an age-encrypted key from disk, decrypting it in memory
+    #[cfg(feature = "age")]
+    Age {
+        /// Path to the encrypted key
+        path: Utf8PathBuf,
+
+        /// Identity used to decrypt the key
+        identity: AgeIdentity,
+    },
+}
+
+/// Source of the age identity used to decrypt a Github App key
+#[cfg(feature = "age")]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgeIdentity {
+    /// Read the identity from this environment variable
+    Env(String),
+
+    /// Read the identity from 1Password using `op read` with a secret
+    /// reference, e.g. `op://vault/item/field`
+    #[serde(rename = "1password")]
+    OnePassword(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::MemoryStorage;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn app_from_config() {
+        let pem = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test/ec-p256-private-key.pem"
+        ));
+        let config: GithubAppConfig = serde_json::from_value(serde_json::json!({
+            "app_id": "1234",
+            "signing_key": {"pem": pem},
+        }))
+        .unwrap();
+
+        let storage = Storage::new(MemoryStorage::with_buckets(&["keys"]));
+        GithubApp::from_config(&config, &storage).await.unwrap();
+
+        let key: GithubAppKey =
+            serde_json::from_str(r#"{"1password": "op://infra/app/key"}"#).unwrap();
+        assert!(
+            matches!(key, GithubAppKey::OnePassword(reference) if reference == "op://infra/app/key")
+        );
+
+        let config = GithubAppConfig {
+            signing_key: GithubAppKey::Pem("not a key".into()),
+            ..config
+        };
+        assert!(matches!(
+            GithubApp::from_config(&config, &storage).await,
+            Err(AppKeyError::Pem(_))
+        ));
+    }
+
+    #[test]
+    fn validate_settings() {
+        assert!(GithubAppSettings::default().validate().is_ok());
+
+        let settings: GithubAppSettings =
+            serde_json::from_str(r#"{"clock_drift": 120, "token_duration": 480}"#).unwrap();
+        assert_eq!(settings.timeout, crate::TIMEOUT.as_secs());
+        assert!(settings.validate().is_ok());
+
+        let settings = GithubAppSettings {
+            token_duration: 600,
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.validate(),
+            Err(SettingsError::TokenLifetime { lifetime: 660 })
+        );
+
+        let settings = GithubAppSettings {
+            token_duration: 30,
+            ..Default::default()
+        };
+        assert!(matches!(
+            settings.validate(),
+            Err(SettingsError::TokenDuration { .. })
+        ));
+
+        let settings = GithubAppSettings {
+            timeout: 0,
+            ..Default::default()
+        };
+        assert_eq!(settings.validate(), Err(SettingsError::Timeout("timeout")));
+    }
 }
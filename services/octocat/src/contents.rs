@@ -0,0 +1,87 @@
+//! Contents API, for reading and committing individual files.
+
+use crate::models::contents::{Content, ContentUpdate, DeleteContent, FileUpdate, PutContent};
+use crate::models::git::GitCommit;
+use crate::{Error, GithubClient};
+
+impl GithubClient {
+    /// Get a file from a repository, at `git_ref` or the default branch.
+    ///
+    /// Returns `None` if there is nothing at the path.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_content(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        git_ref: Option<&str>,
+    ) -> Result<Option<Content>, Error> {
+        let path = path.trim_start_matches('/');
+        let mut endpoint = format!("/repos/{owner}/{repo}/contents/{path}");
+        if let Some(git_ref) = git_ref {
+            endpoint.push_str("?ref=");
+            endpoint.push_str(git_ref);
+        }
+
+        match self.get_json(&endpoint).await {
+            Ok(content) => Ok(Some(content)),
+            Err(Error::Response(error)) if error.status == http::StatusCode::NOT_FOUND => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Create or replace a file with a single commit.
+    ///
+    /// Replacing an existing file requires its current blob SHA (see [`FileUpdate::replacing`]),
+    /// so concurrent edits are rejected rather than silently overwritten.
+    #[tracing::instrument(skip(self, update), fields(message = update.message))]
+    pub async fn put_content(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        update: &FileUpdate<'_>,
+    ) -> Result<ContentUpdate, Error> {
+        let path = path.trim_start_matches('/');
+        let result: ContentUpdate = self
+            .send_json(
+                self.put(&format!("/repos/{owner}/{repo}/contents/{path}")),
+                &PutContent::from(update),
+            )
+            .await?;
+        tracing::debug!(
+            "Committed {path} to {owner}/{repo} in {}",
+            result.commit.sha
+        );
+        Ok(result)
+    }
+
+    /// Delete a file with a single commit, if its current blob SHA matches `sha`.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_content(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        message: &str,
+        sha: &str,
+        branch: Option<&str>,
+    ) -> Result<GitCommit, Error> {
+        let path = path.trim_start_matches('/');
+        let result: ContentUpdate = self
+            .send_json(
+                self.delete(&format!("/repos/{owner}/{repo}/contents/{path}")),
+                &DeleteContent {
+                    message,
+                    sha,
+                    branch,
+                },
+            )
+            .await?;
+        tracing::debug!(
+            "Deleted {path} from {owner}/{repo} in {}",
+            result.commit.sha
+        );
+        Ok(result.commit)
+    }
+}
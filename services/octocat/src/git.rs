@@ -0,0 +1,152 @@
+//! Git data API, for creating blobs, trees, commits and references without a clone.
+
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+use crate::models::git::{
+    BlobEncoding, CreateBlob, CreateReference, CreateTree, GitCommit, GitObject, NewCommit,
+    NewTreeEntry, Reference, Tree, UpdateReference,
+};
+use crate::{Error, GithubClient};
+
+impl GithubClient {
+    /// Create a blob from raw bytes.
+    #[tracing::instrument(skip(self, content), fields(size = content.len()))]
+    pub async fn create_blob(
+        &self,
+        owner: &str,
+        repo: &str,
+        content: &[u8],
+    ) -> Result<GitObject, Error> {
+        let body = CreateBlob {
+            content: BASE64_STANDARD.encode(content),
+            encoding: BlobEncoding::Base64,
+        };
+
+        self.send_json(
+            self.post(&format!("/repos/{owner}/{repo}/git/blobs")),
+            &body,
+        )
+        .await
+    }
+
+    /// Get a tree, optionally listing all of its subtrees as well.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_tree(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+        recursive: bool,
+    ) -> Result<Tree, Error> {
+        let mut endpoint = format!("/repos/{owner}/{repo}/git/trees/{sha}");
+        if recursive {
+            endpoint.push_str("?recursive=1");
+        }
+        self.get_json(&endpoint).await
+    }
+
+    /// Create a tree, optionally modifying an existing tree.
+    #[tracing::instrument(skip(self, entries), fields(entries = entries.len()))]
+    pub async fn create_tree(
+        &self,
+        owner: &str,
+        repo: &str,
+        base_tree: Option<&str>,
+        entries: &[NewTreeEntry],
+    ) -> Result<Tree, Error> {
+        let body = CreateTree {
+            base_tree,
+            tree: entries,
+        };
+
+        self.send_json(
+            self.post(&format!("/repos/{owner}/{repo}/git/trees")),
+            &body,
+        )
+        .await
+    }
+
+    /// Get a commit object.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_commit(&self, owner: &str, repo: &str, sha: &str) -> Result<GitCommit, Error> {
+        self.get_json(&format!("/repos/{owner}/{repo}/git/commits/{sha}"))
+            .await
+    }
+
+    /// Create a commit object. This does not move any references.
+    #[tracing::instrument(skip(self, commit))]
+    pub async fn create_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        commit: &NewCommit,
+    ) -> Result<GitCommit, Error> {
+        let created: GitCommit = self
+            .send_json(
+                self.post(&format!("/repos/{owner}/{repo}/git/commits")),
+                commit,
+            )
+            .await?;
+        tracing::debug!("Created commit {} in {owner}/{repo}", created.sha);
+        Ok(created)
+    }
+
+    /// Get a reference, named without the `refs/` prefix (e.g. `heads/main`).
+    #[tracing::instrument(skip(self))]
+    pub async fn get_ref(&self, owner: &str, repo: &str, name: &str) -> Result<Reference, Error> {
+        let name = name.trim_start_matches("refs/");
+        self.get_json(&format!("/repos/{owner}/{repo}/git/ref/{name}"))
+            .await
+    }
+
+    /// Create a reference, named with the `refs/` prefix (e.g. `refs/heads/feature`).
+    #[tracing::instrument(skip(self))]
+    pub async fn create_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+        sha: &str,
+    ) -> Result<Reference, Error> {
+        let body = CreateReference { name, sha };
+        let reference: Reference = self
+            .send_json(self.post(&format!("/repos/{owner}/{repo}/git/refs")), &body)
+            .await?;
+        tracing::debug!("Created {name} at {sha} in {owner}/{repo}");
+        Ok(reference)
+    }
+
+    /// Move a reference to a new commit.
+    ///
+    /// Unless `force` is set, the update must be a fast-forward.
+    #[tracing::instrument(skip(self))]
+    pub async fn update_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+        sha: &str,
+        force: bool,
+    ) -> Result<Reference, Error> {
+        let name = name.trim_start_matches("refs/");
+        let body = UpdateReference { sha, force };
+        let reference: Reference = self
+            .send_json(
+                self.patch(&format!("/repos/{owner}/{repo}/git/refs/{name}")),
+                &body,
+            )
+            .await?;
+        tracing::debug!("Updated {name} to {sha} in {owner}/{repo}");
+        Ok(reference)
+    }
+
+    /// Delete a reference.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_ref(&self, owner: &str, repo: &str, name: &str) -> Result<(), Error> {
+        let name = name.trim_start_matches("refs/");
+        self.send(self.delete(&format!("/repos/{owner}/{repo}/git/refs/{name}")))
+            .await?;
+        tracing::debug!("Deleted {name} in {owner}/{repo}");
+        Ok(())
+    }
+}
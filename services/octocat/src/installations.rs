@@ -0,0 +1,191 @@
+//! Managing the installations of a Github App, and scoping installation tokens.
+
+use std::collections::BTreeMap;
+
+use api_client::{ApiClient, BearerAuth};
+use serde::Serialize;
+
+use crate::models::{Installation, Permission};
+use crate::{Error, GithubApp, GithubErrorDecoder};
+
+/// Limits on the access granted by an installation token.
+///
+/// An empty scope grants everything the installation can access. Otherwise,
+/// the token can only access the listed repositories (if any are listed), with
+/// the listed permissions (if any are listed), which must be a subset of those
+/// granted to the installation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TokenScope {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repositories: Vec<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    repository_ids: Vec<u64>,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    permissions: BTreeMap<String, Permission>,
+}
+
+impl TokenScope {
+    /// A scope with no limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the token to a repository, by name, without the owner.
+    pub fn repository(mut self, name: impl Into<String>) -> Self {
+        self.repositories.push(name.into());
+        self
+    }
+
+    /// Limit the token to a repository, by ID.
+    pub fn repository_id(mut self, id: u64) -> Self {
+        self.repository_ids.push(id);
+        self
+    }
+
+    /// Limit the token to `level` access for `permission`, e.g. `contents`.
+    pub fn permission(mut self, permission: impl Into<String>, level: Permission) -> Self {
+        self.permissions.insert(permission.into(), level);
+        self
+    }
+
+    /// Check if this scope has no limits.
+    pub fn is_empty(&self) -> bool {
+        self.repositories.is_empty()
+            && self.repository_ids.is_empty()
+            && self.permissions.is_empty()
+    }
+}
+
+impl GithubApp {
+    /// An API client which authenticates as the app itself, rather than an installation.
+    pub(crate) fn app_client(&self) -> Result<ApiClient<BearerAuth>, Error> {
+        let token = self.authentication_token(None)?;
+        Ok(self
+            .api_client(self.client.clone(), BearerAuth::new(token))
+            .build()
+            .with_error_decoder(GithubErrorDecoder))
+    }
+
+    /// Get an installation of this app by ID.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_installation(&self, installation_id: u64) -> Result<Installation, Error> {
+        let client = self.app_client()?;
+        let response = client
+            .get(&format!("/app/installations/{installation_id}"))
+            .version(http::Version::HTTP_2)
+            .send()
+            .await?;
+        let response = client.error_for_status(response).await?;
+        let body = response.text().await.map_err(Error::Body)?;
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    /// Suspend an installation, blocking its access until it is unsuspended.
+    #[tracing::instrument(skip(self))]
+    pub async fn suspend_installation(&self, installation_id: u64) -> Result<(), Error> {
+        let client = self.app_client()?;
+        let response = client
+            .put(&format!("/app/installations/{installation_id}/suspended"))
+            .version(http::Version::HTTP_2)
+            .send()
+            .await?;
+        client.error_for_status(response).await?;
+        tracing::debug!(id=%installation_id, "Suspended installation");
+        Ok(())
+    }
+
+    /// Restore access for a suspended installation.
+    #[tracing::instrument(skip(self))]
+    pub async fn unsuspend_installation(&self, installation_id: u64) -> Result<(), Error> {
+        let client = self.app_client()?;
+        let response = client
+            .delete(&format!("/app/installations/{installation_id}/suspended"))
+            .version(http::Version::HTTP_2)
+            .send()
+            .await?;
+        client.error_for_status(response).await?;
+        tracing::debug!(id=%installation_id, "Unsuspended installation");
+        Ok(())
+    }
+
+    /// Uninstall this app from an account.
+    #[tracing::instrument(skip(self))]
+    pub async fn delete_installation(&self, installation_id: u64) -> Result<(), Error> {
+        let client = self.app_client()?;
+        let response = client
+            .delete(&format!("/app/installations/{installation_id}"))
+            .version(http::Version::HTTP_2)
+            .send()
+            .await?;
+        client.error_for_status(response).await?;
+        tracing::debug!(id=%installation_id, "Deleted installation");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderMap;
+
+    use super::*;
+
+    #[test]
+    fn token_scope() {
+        assert!(TokenScope::new().is_empty());
+
+        let scope = TokenScope::new()
+            .repository("hello")
+            .permission("contents", Permission::Read)
+            .permission("metadata", Permission::Read);
+        assert!(!scope.is_empty());
+        assert_eq!(
+            serde_json::to_value(&scope).unwrap(),
+            serde_json::json!({
+                "repositories": ["hello"],
+                "permissions": {"contents": "read", "metadata": "read"},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn manage_installation() {
+        let mut mock = api_client::mock::MockService::new();
+        mock.add(
+            "/app/installations/1",
+            http::StatusCode::OK,
+            HeaderMap::new(),
+            br#"{"id": 1, "account": {"id": 2, "login": "octocat"},
+                 "suspended_at": "2024-01-01T00:00:00Z"}"#
+                .to_vec(),
+        );
+        mock.add(
+            "/app/installations/1/suspended",
+            http::StatusCode::NO_CONTENT,
+            HeaderMap::new(),
+            Vec::new(),
+        );
+        mock.add(
+            "/app/installations/2/suspended",
+            http::StatusCode::NOT_FOUND,
+            HeaderMap::new(),
+            br#"{"message": "Not Found"}"#.to_vec(),
+        );
+
+        let app = GithubApp {
+            client: hyperdriver::service::SharedService::new(mock),
+            ..GithubApp::test()
+        };
+
+        let installation = app.get_installation(1).await.unwrap();
+        assert!(installation.is_suspended());
+
+        app.suspend_installation(1).await.unwrap();
+        app.unsuspend_installation(1).await.unwrap();
+        let error = app.suspend_installation(2).await.unwrap_err();
+        assert!(
+            matches!(error, Error::Response(error) if error.status() == http::StatusCode::NOT_FOUND)
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Issues API, for opening and triaging issues, and commenting and reacting on them.
+
+use api_client::{LinkHeaderPaginator, PaginatedList};
+use futures::stream::BoxStream;
+
+use crate::models::issues::{
+    AddAssignees, AddLabels, Comment, Issue, IssueState, Label, NewComment, NewIssue, NewReaction,
+    Reaction, ReactionContent, UpdateIssue,
+};
+use crate::{Error, GithubClient};
+
+impl GithubClient {
+    /// List the issues on a repository in a given state, including pull requests.
+    pub fn list_issues(
+        &self,
+        owner: &str,
+        repo: &str,
+        state: IssueState,
+    ) -> BoxStream<'static, Result<Issue, Error>> {
+        self.paginate::<PaginatedList<Issue, LinkHeaderPaginator>>(&format!(
+            "/repos/{owner}/{repo}/issues?state={}&per_page=100",
+            state.as_str()
+        ))
+    }
+
+    /// Get an issue by its number.
+    pub async fn get_issue(&self, owner: &str, repo: &str, number: u64) -> Result<Issue, Error> {
+        self.get_json(&format!("/repos/{owner}/{repo}/issues/{number}"))
+            .await
+    }
+
+    /// Open an issue.
+    #[tracing::instrument(skip(self, issue))]
+    pub async fn create_issue(
+        &self,
+        owner: &str,
+        repo: &str,
+        issue: &NewIssue<'_>,
+    ) -> Result<Issue, Error> {
+        let issue: Issue = self
+            .send_json(self.post(&format!("/repos/{owner}/{repo}/issues")), issue)
+            .await?;
+        tracing::debug!("Opened issue #{} on {owner}/{repo}", issue.number);
+        Ok(issue)
+    }
+
+    /// Update an issue, e.g. to close it.
+    #[tracing::instrument(skip(self, update))]
+    pub async fn update_issue(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        update: &UpdateIssue<'_>,
+    ) -> Result<Issue, Error> {
+        let issue: Issue = self
+            .send_json(
+                self.patch(&format!("/repos/{owner}/{repo}/issues/{number}")),
+                update,
+            )
+            .await?;
+        tracing::debug!("Updated issue #{number} on {owner}/{repo}");
+        Ok(issue)
+    }
+
+    /// List the comments on an issue or pull request, oldest first.
+    pub fn list_issue_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+    ) -> BoxStream<'static, Result<Comment, Error>> {
+        self.paginate::<PaginatedList<Comment, LinkHeaderPaginator>>(&format!(
+            "/repos/{owner}/{repo}/issues/{number}/comments?per_page=100"
+        ))
+    }
+
+    /// Comment on an issue or pull request.
+    #[tracing::instrument(skip(self, body))]
+    pub async fn add_issue_comment(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        body: &str,
+    ) -> Result<Comment, Error> {
+        let comment: Comment = self
+            .send_json(
+                self.post(&format!("/repos/{owner}/{repo}/issues/{number}/comments")),
+                &NewComment { body },
+            )
+            .await?;
+        tracing::debug!("Commented on #{number} on {owner}/{repo}");
+        Ok(comment)
+    }
+
+    /// Add labels to an issue or pull request, returning every label now applied.
+    ///
+    /// Labels which don't exist in the repository are created.
+    #[tracing::instrument(skip(self))]
+    pub async fn add_labels(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        labels: &[&str],
+    ) -> Result<Vec<Label>, Error> {
+        self.send_json(
+            self.post(&format!("/repos/{owner}/{repo}/issues/{number}/labels")),
+            &AddLabels { labels },
+        )
+        .await
+    }
+
+    /// Assign accounts to an issue or pull request, by login.
+    ///
+    /// Github silently ignores accounts which can't be assigned.
+    #[tracing::instrument(skip(self))]
+    pub async fn add_assignees(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        assignees: &[&str],
+    ) -> Result<Issue, Error> {
+        self.send_json(
+            self.post(&format!("/repos/{owner}/{repo}/issues/{number}/assignees")),
+            &AddAssignees { assignees },
+        )
+        .await
+    }
+
+    /// React to an issue or pull request.
+    ///
+    /// Reacting twice with the same content returns the existing reaction.
+    #[tracing::instrument(skip(self))]
+    pub async fn add_issue_reaction(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        content: ReactionContent,
+    ) -> Result<Reaction, Error> {
+        self.send_json(
+            self.post(&format!("/repos/{owner}/{repo}/issues/{number}/reactions")),
+            &NewReaction { content },
+        )
+        .await
+    }
+
+    /// React to a comment on an issue or pull request.
+    ///
+    /// Reacting twice with the same content returns the existing reaction.
+    #[tracing::instrument(skip(self))]
+    pub async fn add_comment_reaction(
+        &self,
+        owner: &str,
+        repo: &str,
+        comment_id: u64,
+        content: ReactionContent,
+    ) -> Result<Reaction, Error> {
+        self.send_json(
+            self.post(&format!(
+                "/repos/{owner}/{repo}/issues/comments/{comment_id}/reactions"
+            )),
+            &NewReaction { content },
+        )
+        .await
+    }
+}
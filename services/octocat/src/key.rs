@@ -0,0 +1,127 @@
+//! Private keys used to sign Github App JWTs.
+
+use std::fmt;
+
+use jaws::crypto::{ecdsa, p256, rsa};
+use rsa::pkcs1::DecodeRsaPrivateKey as _;
+use rsa::pkcs8::{self, der};
+use rsa::sha2::Sha256;
+
+/// Errors that can occur when decoding a Github App key.
+#[derive(Debug, thiserror::Error)]
+pub enum KeyError {
+    /// Error decoding the PEM envelope
+    #[error("PEM: {0}")]
+    Pem(#[source] der::Error),
+
+    /// Error decoding the key as PKCS8
+    #[error("PKCS8: {0}")]
+    Pkcs8(#[from] pkcs8::Error),
+
+    /// Error decoding the key as PKCS1
+    #[error("PKCS1: {0}")]
+    Pkcs1(#[from] rsa::pkcs1::Error),
+
+    /// Error decoding the key as SEC1
+    #[error("SEC1: {0}")]
+    Sec1(#[from] p256::elliptic_curve::Error),
+
+    /// The PEM label is not a supported private key type
+    #[error("Unsupported PEM label: {0}")]
+    Label(String),
+
+    /// The PKCS8 key uses an unsupported algorithm
+    #[error("Unsupported key algorithm: {0}")]
+    Algorithm(pkcs8::ObjectIdentifier),
+}
+
+/// A private key used to sign JWTs for a Github App.
+///
+/// The JWT algorithm is chosen by the kind of key: RSA keys sign with `RS256`,
+/// and ECDSA P-256 keys sign with `ES256`.
+#[derive(Clone)]
+pub enum AppKey {
+    /// An RSA key, signing with PKCS#1 v1.5 and SHA-256.
+    Rsa(Box<rsa::pkcs1v15::SigningKey<Sha256>>),
+
+    /// An ECDSA key on the P-256 curve.
+    P256(ecdsa::SigningKey<p256::NistP256>),
+}
+
+impl fmt::Debug for AppKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("AppKey").field(&self.algorithm()).finish()
+    }
+}
+
+impl AppKey {
+    /// Decode a key from PEM.
+    ///
+    /// Accepts PKCS1 (`RSA PRIVATE KEY`), SEC1 (`EC PRIVATE KEY`) and
+    /// PKCS8 (`PRIVATE KEY`) encodings.
+    pub fn from_pem(pem: &str) -> Result<Self, KeyError> {
+        let (label, der) =
+            der::pem::decode_vec(pem.as_bytes()).map_err(|err| KeyError::Pem(err.into()))?;
+        match label {
+            "RSA PRIVATE KEY" => Ok(rsa::RsaPrivateKey::from_pkcs1_der(&der)?.into()),
+            "EC PRIVATE KEY" => Ok(p256::SecretKey::from_sec1_der(&der)?.into()),
+            "PRIVATE KEY" => Self::from_pkcs8_der(&der),
+            _ => Err(KeyError::Label(label.to_owned())),
+        }
+    }
+
+    /// Decode a PKCS8 key from DER.
+    pub fn from_pkcs8_der(der: &[u8]) -> Result<Self, KeyError> {
+        let info = pkcs8::PrivateKeyInfo::try_from(der)?;
+        match info.algorithm.oid {
+            rsa::pkcs1::ALGORITHM_OID => Ok(rsa::RsaPrivateKey::try_from(info)?.into()),
+            p256::elliptic_curve::ALGORITHM_OID => Ok(p256::SecretKey::try_from(info)?.into()),
+            oid => Err(KeyError::Algorithm(oid)),
+        }
+    }
+
+    /// The JWT algorithm used when signing with this key.
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            AppKey::Rsa(_) => "RS256",
+            AppKey::P256(_) => "ES256",
+        }
+    }
+}
+
+impl From<rsa::RsaPrivateKey> for AppKey {
+    fn from(key: rsa::RsaPrivateKey) -> Self {
+        AppKey::Rsa(Box::new(rsa::pkcs1v15::SigningKey::new(key)))
+    }
+}
+
+impl From<p256::SecretKey> for AppKey {
+    fn from(key: p256::SecretKey) -> Self {
+        AppKey::P256(key.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_keys() {
+        let key = AppKey::from_pkcs8_der(include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test/rsa-2048-private-key.pk8"
+        )))
+        .unwrap();
+        assert_eq!(key.algorithm(), "RS256");
+
+        let key = AppKey::from_pem(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test/ec-p256-private-key.pem"
+        )))
+        .unwrap();
+        assert_eq!(key.algorithm(), "ES256");
+
+        let pem = "-----BEGIN PUBLIC KEY-----\nAAAA\n-----END PUBLIC KEY-----\n";
+        assert!(matches!(AppKey::from_pem(pem), Err(KeyError::Label(_))));
+    }
+}
@@ -0,0 +1,435 @@
+//! Git LFS batch API support.
+//!
+//! Objects are transferred with the [basic transfer adapter][basic], using the
+//! installation token to authenticate against the LFS server that Github hosts
+//! for each repository.
+//!
+//! [basic]: https://github.com/git-lfs/git-lfs/blob/main/docs/api/basic-transfers.md
+
+use std::collections::HashMap;
+
+use api_client::response::ResponseBodyExt as _;
+use api_client::Secret;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt as _;
+use http::header;
+use http_body_util::BodyExt as _;
+use hyperdriver::service::ServiceExt as _;
+use hyperdriver::Body;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt as _};
+
+use crate::{Error, GithubClient, ResponseError};
+
+const LFS_MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
+
+/// Errors specific to Git LFS transfers.
+#[derive(Debug, thiserror::Error)]
+pub enum LfsError {
+    /// The LFS server rejected an individual object in a batch.
+    #[error("LFS object {oid}: {message} ({code})")]
+    Object {
+        /// Object ID (SHA-256 hex digest).
+        oid: String,
+
+        /// HTTP-like status code for the object.
+        code: u16,
+
+        /// Error message from the server.
+        message: String,
+    },
+
+    /// The server did not provide an action needed to transfer the object.
+    #[error("LFS object {oid}: missing {action} action")]
+    MissingAction {
+        /// Object ID (SHA-256 hex digest).
+        oid: String,
+
+        /// The action which was expected.
+        action: &'static str,
+    },
+
+    /// The downloaded object did not match its pointer.
+    #[error("LFS object {oid}: content does not match (got {size} bytes, sha256 {actual})")]
+    Integrity {
+        /// Expected object ID (SHA-256 hex digest).
+        oid: String,
+
+        /// Actual SHA-256 hex digest of the downloaded content.
+        actual: String,
+
+        /// Number of bytes downloaded.
+        size: u64,
+    },
+}
+
+/// The direction of an LFS batch request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// Request actions to download objects.
+    Download,
+
+    /// Request actions to upload objects.
+    Upload,
+}
+
+/// A pointer to an LFS object, identified by its SHA-256 digest and size.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pointer {
+    /// Object ID (SHA-256 hex digest).
+    pub oid: String,
+
+    /// Object size in bytes.
+    pub size: u64,
+}
+
+impl Pointer {
+    /// Create a pointer for some in-memory content.
+    pub fn from_bytes(content: &[u8]) -> Self {
+        Self {
+            oid: hex::encode(Sha256::digest(content)),
+            size: content.len() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BatchRequest<'a> {
+    operation: Operation,
+    transfers: &'a [&'a str],
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    git_ref: Option<RefName<'a>>,
+    objects: &'a [Pointer],
+    hash_algo: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct RefName<'a> {
+    name: &'a str,
+}
+
+/// Response to an LFS batch request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchResponse {
+    /// The transfer adapter selected by the server.
+    #[serde(default)]
+    pub transfer: Option<String>,
+
+    /// Actions for each requested object.
+    pub objects: Vec<BatchObject>,
+}
+
+/// A single object in an LFS batch response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchObject {
+    /// The object this entry refers to.
+    #[serde(flatten)]
+    pub pointer: Pointer,
+
+    /// Actions available for this object. Empty if no transfer is required,
+    /// e.g. when uploading an object the server already has.
+    #[serde(default)]
+    pub actions: HashMap<String, Action>,
+
+    /// Error for this object, if the server rejected it.
+    #[serde(default)]
+    pub error: Option<ObjectError>,
+}
+
+impl BatchObject {
+    /// Get an action by name (`download`, `upload` or `verify`).
+    pub fn action(&self, name: &str) -> Option<&Action> {
+        self.actions.get(name)
+    }
+
+    fn check(&self) -> Result<(), LfsError> {
+        match &self.error {
+            Some(error) => Err(LfsError::Object {
+                oid: self.pointer.oid.clone(),
+                code: error.code,
+                message: error.message.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Error for a single object in an LFS batch response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectError {
+    /// HTTP-like status code.
+    pub code: u16,
+
+    /// Error message.
+    pub message: String,
+}
+
+/// An action to transfer an object.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Action {
+    /// URL to send the transfer request to.
+    pub href: String,
+
+    /// Headers which must be sent with the transfer request.
+    #[serde(default)]
+    pub header: HashMap<String, String>,
+
+    /// When the action expires.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Action {
+    fn request(&self, method: http::Method) -> http::request::Builder {
+        self.header.iter().fold(
+            http::Request::builder().method(method).uri(&self.href),
+            |builder, (name, value)| builder.header(name, value),
+        )
+    }
+}
+
+/// Git LFS client for a single repository.
+#[derive(Debug, Clone)]
+pub struct LfsRepository {
+    client: GithubClient,
+    endpoint: String,
+    git_ref: Option<String>,
+}
+
+impl LfsRepository {
+    pub(crate) fn new(client: GithubClient, owner: &str, repo: &str) -> Self {
+        Self {
+            client,
+            endpoint: format!("https://github.com/{owner}/{repo}.git/info/lfs/objects/batch"),
+            git_ref: None,
+        }
+    }
+
+    /// Scope batch requests to a git reference (e.g. `refs/heads/main`).
+    pub fn with_ref(mut self, git_ref: impl Into<String>) -> Self {
+        self.git_ref = Some(git_ref.into());
+        self
+    }
+
+    async fn send(&self, req: http::Request<Body>) -> Result<http::Response<Body>, Error> {
+        let resp = self.client.app.client.clone().oneshot(req).await?;
+
+        if !resp.status().is_success() {
+            let error = ResponseError::from_response(resp).await;
+            return Err(Error::Response(error));
+        }
+
+        Ok(resp)
+    }
+
+    fn token(&self) -> Secret {
+        self.client.token()
+    }
+
+    /// Request transfer actions for a set of objects.
+    #[tracing::instrument(skip(self, objects), fields(endpoint = %self.endpoint, objects = objects.len()))]
+    pub async fn batch(
+        &self,
+        operation: Operation,
+        objects: &[Pointer],
+    ) -> Result<BatchResponse, Error> {
+        let body = serde_json::to_vec(&BatchRequest {
+            operation,
+            transfers: &["basic"],
+            git_ref: self.git_ref.as_deref().map(|name| RefName { name }),
+            objects,
+            hash_algo: "sha256",
+        })?;
+
+        let req = http::Request::post(&self.endpoint)
+            .header(header::ACCEPT, LFS_MEDIA_TYPE)
+            .header(header::CONTENT_TYPE, LFS_MEDIA_TYPE)
+            .header(
+                header::AUTHORIZATION,
+                api_client::basic_auth("x-access-token", Some(self.token().revealed())),
+            )
+            .body(Body::from(body))
+            .map_err(|error| Error::Client(error.into()))?;
+
+        let resp = self.send(req).await?;
+        let batch: BatchResponse = resp.json().await.map_err(Error::Body)?;
+        tracing::debug!("LFS batch returned {} objects", batch.objects.len());
+        Ok(batch)
+    }
+
+    /// Download a single object, writing its content to `writer`.
+    ///
+    /// The content is checked against the object's size and digest as it is written.
+    pub async fn download<W>(&self, pointer: &Pointer, writer: &mut W) -> Result<(), Error>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let batch = self
+            .batch(Operation::Download, std::slice::from_ref(pointer))
+            .await?;
+        let object = find_object(batch, pointer)?;
+        let action = object
+            .action("download")
+            .ok_or_else(|| LfsError::MissingAction {
+                oid: pointer.oid.clone(),
+                action: "download",
+            })?;
+
+        let req = action
+            .request(http::Method::GET)
+            .body(Body::empty())
+            .map_err(|error| Error::Client(error.into()))?;
+        let mut body = self.send(req).await?.into_body();
+
+        let mut hasher = Sha256::new();
+        let mut size = 0u64;
+        while let Some(frame) = body.frame().await {
+            let frame = frame.map_err(Error::Body)?;
+            if let Ok(data) = frame.into_data() {
+                hasher.update(&data);
+                size += data.len() as u64;
+                writer.write_all(&data).await?;
+            }
+        }
+        writer.flush().await?;
+
+        let actual = hex::encode(hasher.finalize());
+        if actual != pointer.oid || size != pointer.size {
+            return Err(LfsError::Integrity {
+                oid: pointer.oid.clone(),
+                actual,
+                size,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Upload a single object, streaming its content from `reader`.
+    ///
+    /// Returns `false` if the server already had the object and no upload was needed.
+    pub async fn upload<R>(&self, pointer: &Pointer, reader: R) -> Result<bool, Error>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let batch = self
+            .batch(Operation::Upload, std::slice::from_ref(pointer))
+            .await?;
+        let object = find_object(batch, pointer)?;
+
+        let Some(action) = object.action("upload") else {
+            tracing::debug!(oid = %pointer.oid, "LFS object already present");
+            return Ok(false);
+        };
+
+        let stream = tokio_util::io::ReaderStream::new(reader).map_ok(http_body::Frame::data);
+        let req = action
+            .request(http::Method::PUT)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .header(header::CONTENT_LENGTH, pointer.size)
+            .body(Body::new(http_body_util::StreamBody::new(stream)))
+            .map_err(|error| Error::Client(error.into()))?;
+        self.send(req).await?;
+
+        if let Some(verify) = object.action("verify") {
+            let req = verify
+                .request(http::Method::POST)
+                .header(header::ACCEPT, LFS_MEDIA_TYPE)
+                .header(header::CONTENT_TYPE, LFS_MEDIA_TYPE)
+                .body(Body::from(serde_json::to_vec(pointer)?))
+                .map_err(|error| Error::Client(error.into()))?;
+            self.send(req).await?;
+        }
+
+        tracing::debug!(oid = %pointer.oid, size = pointer.size, "Uploaded LFS object");
+        Ok(true)
+    }
+}
+
+fn find_object(batch: BatchResponse, pointer: &Pointer) -> Result<BatchObject, Error> {
+    let object = batch
+        .objects
+        .into_iter()
+        .find(|object| object.pointer.oid == pointer.oid)
+        .ok_or_else(|| LfsError::Object {
+            oid: pointer.oid.clone(),
+            code: 404,
+            message: "object missing from batch response".into(),
+        })?;
+    object.check()?;
+    Ok(object)
+}
+
+impl GithubClient {
+    /// Get a Git LFS client for a repository.
+    pub fn lfs(&self, owner: &str, repo: &str) -> LfsRepository {
+        LfsRepository::new(self.clone(), owner, repo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pointer_from_bytes() {
+        let pointer = Pointer::from_bytes(b"hello world");
+        assert_eq!(
+            pointer.oid,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(pointer.size, 11);
+    }
+
+    #[test]
+    fn parse_batch_response() {
+        let batch: BatchResponse = serde_json::from_str(
+            r#"{
+                "transfer": "basic",
+                "objects": [
+                    {
+                        "oid": "1111111",
+                        "size": 123,
+                        "authenticated": true,
+                        "actions": {
+                            "download": {
+                                "href": "https://some-download.com",
+                                "header": {"Key": "value"},
+                                "expires_at": "2016-11-10T15:29:07Z"
+                            }
+                        }
+                    },
+                    {
+                        "oid": "2222222",
+                        "size": 1,
+                        "error": {"code": 404, "message": "Object does not exist"}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let download = batch.objects[0].action("download").unwrap();
+        assert_eq!(download.href, "https://some-download.com");
+        assert_eq!(download.header["Key"], "value");
+        assert!(batch.objects[0].check().is_ok());
+
+        let error = batch.objects[1].check().unwrap_err();
+        assert!(matches!(error, LfsError::Object { code: 404, .. }));
+    }
+
+    #[test]
+    fn malformed_action() {
+        let action: Action = serde_json::from_str(
+            r#"{"href": "https://example.com/a b", "header": {"Key": "line\nbreak"}}"#,
+        )
+        .unwrap();
+        assert!(action
+            .request(http::Method::GET)
+            .body(Body::empty())
+            .is_err());
+    }
+}
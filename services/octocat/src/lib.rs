@@ -6,36 +6,69 @@ use std::path::PathBuf;
 use std::process::Output;
 use std::sync::{Arc, RwLock};
 
-use api_client::response::ResponseBodyExt;
-use api_client::{ApiClient, RequestExt, Secret};
-
-use http::HeaderValue;
+use api_client::response::{ResponseBodyExt, ResponseExt as _};
+use api_client::{
+    ApiClient, ApiClientBuilder, Authentication, ErrorDecoder, LinkHeaderPaginator, Paginated,
+    PaginatedList, Paginator, RequestExt, Secret,
+};
+
+use futures::stream::{self, BoxStream, StreamExt as _, TryStreamExt as _};
+use http::{HeaderName, HeaderValue};
 use hyperdriver::client::conn::transport::tcp::TcpTransportConfig;
 use hyperdriver::service::ServiceExt as _;
 use jaws::claims::{Claims, RegisteredClaims};
-use jaws::crypto::{rsa, signature};
+use jaws::crypto::{ecdsa, p256, rsa, signature};
 use jaws::token::{Token, TokenFormattingError, TokenSigningError};
 
 use http::header;
 use hyperdriver::{Body, Client};
-use models::InstallationAccess;
-use rsa::sha2::Sha256;
+use models::repos::RepositoryList;
+use models::{
+    Installation, InstallationAccess, Permission, PullRequest, PullRequestState, Repository,
+};
+use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
+use tower_http::set_header::SetRequestHeaderLayer;
 
+mod actions;
+mod admin;
+pub mod cache;
+mod commits;
 pub mod config;
+mod contents;
+mod git;
+mod installations;
+mod issues;
+pub mod key;
+pub mod lfs;
+mod manifest;
 pub mod models;
-
-pub use crate::config::GithubAppConfig;
-
-const CLOCK_DRIFT_OFFSET_SECONDS: i64 = 60;
-const TOKEN_DURATION_SECONDS: i64 = 5 * 60;
+mod ratelimit;
+mod refresh;
+mod releases;
+pub mod version;
+
+pub use crate::cache::StorageCache;
+pub use crate::config::{GithubAppConfig, GithubAppSettings, SettingsError};
+pub use crate::installations::TokenScope;
+pub use crate::key::AppKey;
+pub use crate::manifest::ManifestApp;
+use crate::ratelimit::SecondaryRateLimitLayer;
+use crate::refresh::{InstallationAuth, InstallationToken, TokenRefreshLayer};
+pub use crate::version::{ApiVersion, ApiVersionExt, Deprecation};
+use crate::version::{DeprecationLayer, DeprecationState};
+
+const CLOCK_DRIFT_OFFSET_SECONDS: u64 = 60;
+const TOKEN_DURATION_SECONDS: u64 = 5 * 60;
 const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+const RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+const RETRY_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(120);
 const GITHUB_ACCEPT: &str = "application/vnd.github+json";
-const GITHUB_API_VERSION: &str = "2022-11-28";
-const GITHUB_API_VERSION_HEADER: &str = "X-GitHub-Api-Version";
+const GITHUB_API_VERSION_HEADER: &str = "x-github-api-version";
 const GITHUB_BASE: &str = "https://api.github.com/";
-const GITHUB_LIST_INSTALLATIONS: &str = "https://api.github.com/app/installations";
+const GITHUB_LIST_INSTALLATIONS: &str = "/app/installations?per_page=100";
+const USER_AGENT: &str = "automoton-octocat/0.1.0";
 
 /// Errors that can occur when using the Github client.
 #[derive(Debug, Error)]
@@ -64,9 +97,42 @@ pub enum Error {
     #[error("IO: {0}")]
     IO(#[from] std::io::Error),
 
+    /// An error occured while transferring Git LFS objects.
+    #[error("LFS: {0}")]
+    Lfs(#[from] lfs::LfsError),
+
     /// An error occured when encoding or decoding data from the OS
     #[error("Encoding: {0}")]
     OsEncoding(#[from] std::string::FromUtf8Error),
+
+    /// An error occured while building a request or reading a response.
+    #[error("Client: {0}")]
+    Client(#[source] api_client::Error),
+
+    /// A secret could not be encrypted with a repository's public key.
+    #[error("Encrypting secret: {0}")]
+    SecretEncryption(&'static str),
+
+    /// Github's secondary rate limit was exceeded, and retrying didn't help.
+    ///
+    /// Callers should wait for `retry_after` (or at least a minute, if Github
+    /// didn't say) before making more requests.
+    #[error("Secondary rate limit exceeded")]
+    SecondaryRateLimit {
+        /// How long Github asked clients to wait, from the `Retry-After` header.
+        retry_after: Option<std::time::Duration>,
+    },
+}
+
+impl From<api_client::Error> for Error {
+    fn from(error: api_client::Error) -> Self {
+        match error.into_api_error() {
+            Ok(error) => Error::Response(error),
+            Err(api_client::Error::Request(error)) => Error::Request(error),
+            Err(api_client::Error::ResponseBody(error)) => Error::Body(error),
+            Err(error) => Error::Client(error),
+        }
+    }
 }
 
 impl From<TokenSigningError> for Error {
@@ -96,6 +162,20 @@ pub struct ResponseError {
 }
 
 impl ResponseError {
+    /// The HTTP status code of the response.
+    pub fn status(&self) -> http::StatusCode {
+        self.status
+    }
+
+    /// Check if this is a secondary rate limit response, which Github sends as a
+    /// 403 or 429 with a message explaining the limit.
+    fn is_secondary_rate_limit(&self) -> bool {
+        matches!(
+            self.status,
+            http::StatusCode::FORBIDDEN | http::StatusCode::TOO_MANY_REQUESTS
+        ) && ratelimit::is_secondary_rate_limit(&self.body)
+    }
+
     async fn from_response(response: http::Response<Body>) -> Self {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
@@ -103,6 +183,21 @@ impl ResponseError {
     }
 }
 
+/// Keeps the body of every Github error response as a [`ResponseError`].
+#[derive(Debug, Clone, Copy, Default)]
+struct GithubErrorDecoder;
+
+impl ErrorDecoder for GithubErrorDecoder {
+    fn decode(
+        &self,
+        status: http::StatusCode,
+        body: &[u8],
+    ) -> Option<Box<dyn std::error::Error + Send + Sync>> {
+        let body = String::from_utf8_lossy(body).into_owned();
+        Some(Box::new(ResponseError { status, body }))
+    }
+}
+
 #[derive(Clone)]
 struct GithubCredentialHelperSettings {
     credentials: PathBuf,
@@ -232,11 +327,17 @@ impl Drop for GithubCredentialsHelper {
 
 /// A Github client that can be used to make requests against the Github API
 /// using an oAuth application and a specific installation.
+///
+/// The installation token is refreshed automatically when it is about to expire,
+/// or when Github rejects it, so long-lived clients don't need to call
+/// [`GithubClient::refresh`].
 #[derive(Debug, Clone)]
 pub struct GithubClient {
     app: GithubApp,
-    client: ApiClient<InstallationAccess>,
+    client: ApiClient<InstallationAuth>,
+    token: Arc<InstallationToken>,
     id: u64,
+    api_version: ApiVersion,
 }
 
 impl GithubClient {
@@ -245,41 +346,266 @@ impl GithubClient {
         client: hyperdriver::client::SharedClientService<Body, Body>,
         installation: InstallationAccess,
         id: u64,
+        scope: TokenScope,
     ) -> Self {
+        let token = Arc::new(InstallationToken::new(app.clone(), id, scope, installation));
+        let api_version = app.settings().api_version;
+        let client = app
+            .api_client(client, InstallationAuth::new(token.clone()))
+            .layer(TokenRefreshLayer::new(token.clone()))
+            .layer(DeprecationLayer::new(app.deprecation.clone()))
+            .build()
+            .with_error_decoder(GithubErrorDecoder);
+
         Self {
             app,
-            client: ApiClient::new_with_inner_service(
-                GITHUB_BASE.parse().unwrap(),
-                installation,
-                client,
-            ),
+            client,
+            token,
             id,
+            api_version,
         }
     }
 
-    fn from_app(app: GithubApp, installation: InstallationAccess, id: u64) -> Self {
+    fn from_app(
+        app: GithubApp,
+        installation: InstallationAccess,
+        id: u64,
+        scope: TokenScope,
+    ) -> Self {
         let client = app.client.clone();
-        Self::new(app, client, installation, id)
+        Self::new(app, client, installation, id, scope)
+    }
+
+    /// Revalidate GET requests from this client against a response cache.
+    ///
+    /// Use a [`StorageCache`] to keep responses between runs.
+    pub fn with_cache(mut self, cache: api_client::ResponseCache) -> Self {
+        self.client = self.client.with_response_cache(cache);
+        self
+    }
+
+    /// Use a different Github API version for every request from this client.
+    ///
+    /// Single requests can override this with [`ApiVersionExt::api_version`].
+    pub fn with_api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// The Github API version used for requests from this client.
+    pub fn api_version(&self) -> ApiVersion {
+        self.api_version
+    }
+
+    /// The most recent warning from Github that a requested API version is deprecated.
+    pub fn deprecation(&self) -> Option<Deprecation> {
+        self.app.deprecation()
     }
 
     /// Build a GET request against a Github endpoint.
     pub fn get(&self, endpoint: &str) -> api_client::RequestBuilder {
-        self.client.get(endpoint).version(http::Version::HTTP_2)
+        self.client
+            .get(endpoint)
+            .version(http::Version::HTTP_2)
+            .api_version(self.api_version)
+    }
+
+    /// GET a Github endpoint and deserialize the JSON response.
+    ///
+    /// When a cache is configured, previous responses are revalidated with a
+    /// conditional request and reused if Github responds with `304 Not Modified`.
+    #[tracing::instrument(skip(self))]
+    pub async fn get_json<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T, Error> {
+        let body = self.send(self.get(endpoint)).await?.text().await;
+        Ok(serde_json::from_str(&body.map_err(Error::Body)?)?)
+    }
+
+    /// Stream every item from a paginated Github list endpoint, following `Link` headers.
+    fn paginate<P>(&self, endpoint: &str) -> BoxStream<'static, Result<P::Item, Error>>
+    where
+        P: Paginator + DeserializeOwned + Send + 'static,
+        P::Item: DeserializeOwned + Send + 'static,
+    {
+        let request = self
+            .get(endpoint)
+            .body(Body::empty())
+            .build()
+            .expect("valid paginated request");
+
+        Paginated::<_, P::Item, P>::new(self.client.clone(), request)
+            .map_err(Error::Body)
+            .boxed()
+    }
+
+    /// List the repositories this installation can access.
+    pub fn repositories(&self) -> BoxStream<'static, Result<Repository, Error>> {
+        self.paginate::<RepositoryList>("/installation/repositories?per_page=100")
+    }
+
+    /// List the pull requests on a repository in a given state.
+    pub fn pull_requests(
+        &self,
+        owner: &str,
+        repo: &str,
+        state: PullRequestState,
+    ) -> BoxStream<'static, Result<PullRequest, Error>> {
+        self.paginate::<PaginatedList<PullRequest, LinkHeaderPaginator>>(&format!(
+            "/repos/{owner}/{repo}/pulls?state={}&per_page=100",
+            state.as_str()
+        ))
     }
 
     /// Build a POST request against a Github endpoint.
     pub fn post(&self, endpoint: &str) -> api_client::RequestBuilder {
-        self.client.post(endpoint).version(http::Version::HTTP_2)
+        self.client
+            .post(endpoint)
+            .version(http::Version::HTTP_2)
+            .api_version(self.api_version)
+    }
+
+    /// Build a PATCH request against a Github endpoint.
+    pub fn patch(&self, endpoint: &str) -> api_client::RequestBuilder {
+        self.client
+            .patch(endpoint)
+            .version(http::Version::HTTP_2)
+            .api_version(self.api_version)
+    }
+
+    /// Build a PUT request against a Github endpoint.
+    pub fn put(&self, endpoint: &str) -> api_client::RequestBuilder {
+        self.client
+            .put(endpoint)
+            .version(http::Version::HTTP_2)
+            .api_version(self.api_version)
+    }
+
+    /// Build a DELETE request against a Github endpoint.
+    pub fn delete(&self, endpoint: &str) -> api_client::RequestBuilder {
+        self.client
+            .delete(endpoint)
+            .version(http::Version::HTTP_2)
+            .api_version(self.api_version)
+    }
+
+    /// Send a request, returning an error if the response was not successful.
+    async fn send(
+        &self,
+        request: api_client::RequestBuilder,
+    ) -> Result<api_client::response::Response, Error> {
+        let response = request.send().await?;
+        self.error_for_status(response).await
+    }
+
+    /// Return an error for a response with a non-success status, recognizing
+    /// secondary rate limits.
+    async fn error_for_status(
+        &self,
+        response: api_client::response::Response,
+    ) -> Result<api_client::response::Response, Error> {
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+            .map(std::time::Duration::from_secs);
+
+        match self
+            .client
+            .error_for_status(response)
+            .await
+            .map_err(Error::from)
+        {
+            Err(Error::Response(error)) if error.is_secondary_rate_limit() => {
+                tracing::warn!(?retry_after, "Github secondary rate limit exceeded");
+                Err(Error::SecondaryRateLimit { retry_after })
+            }
+            result => result,
+        }
+    }
+
+    /// Send a JSON body and deserialize the JSON response.
+    async fn send_json<B, T>(
+        &self,
+        request: api_client::RequestBuilder,
+        body: &B,
+    ) -> Result<T, Error>
+    where
+        B: Serialize,
+        T: DeserializeOwned,
+    {
+        let body = serde_json::to_vec(body)?;
+        let response = self
+            .send(
+                request
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body),
+            )
+            .await?;
+        let text = response.text().await.map_err(Error::Body)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Trigger a `repository_dispatch` event on a repository.
+    ///
+    /// The payload is made available to workflows as `github.event.client_payload`.
+    #[tracing::instrument(skip(self, payload))]
+    pub async fn repository_dispatch<P: Serialize>(
+        &self,
+        owner: &str,
+        repo: &str,
+        event_type: &str,
+        payload: P,
+    ) -> Result<(), Error> {
+        let body = serde_json::to_vec(&models::actions::RepositoryDispatch {
+            event_type,
+            client_payload: payload,
+        })?;
+
+        self.send(
+            self.post(&format!("/repos/{owner}/{repo}/dispatches"))
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body),
+        )
+        .await?;
+
+        tracing::debug!("Dispatched {event_type} to {owner}/{repo}");
+        Ok(())
+    }
+
+    /// Trigger a `workflow_dispatch` event for a workflow.
+    ///
+    /// The workflow can be identified by its numeric ID or its file name (e.g. `ci.yml`).
+    #[tracing::instrument(skip(self, inputs))]
+    pub async fn workflow_dispatch<I: Serialize>(
+        &self,
+        owner: &str,
+        repo: &str,
+        workflow_id: &str,
+        git_ref: &str,
+        inputs: I,
+    ) -> Result<(), Error> {
+        let body = serde_json::to_vec(&models::actions::WorkflowDispatch { git_ref, inputs })?;
+
+        self.send(
+            self.post(&format!(
+                "/repos/{owner}/{repo}/actions/workflows/{workflow_id}/dispatches"
+            ))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body),
+        )
+        .await?;
+
+        tracing::debug!("Dispatched workflow {workflow_id} on {owner}/{repo}@{git_ref}");
+        Ok(())
     }
 
     /// Check if the authentication token is expired.
     pub fn is_expired(&self) -> bool {
-        self.client.auth().is_expired()
+        self.token.current().is_expired()
     }
 
     /// Get the authentication token.
     pub fn token(&self) -> Secret {
-        self.client.auth().token.clone()
+        self.token.current().token.clone()
     }
 
     /// Set up git credentials for this installation with a token.
@@ -288,14 +614,54 @@ impl GithubClient {
         GithubCredentialsHelper::new(path, &self.token()).await
     }
 
-    /// refresh the authentication token.
+    /// Refresh the authentication token now, even if it hasn't expired.
     pub async fn refresh(&self) -> Result<(), Error> {
-        let installation = self.app.installation_token(self.id).await?;
-        self.client.refresh_auth(installation);
+        self.token.refresh().await?;
         Ok(())
     }
 }
 
+/// Filter applied to the installations of a Github App.
+///
+/// All conditions must match for an installation to be included.
+#[derive(Debug, Clone, Default)]
+pub struct InstallationFilter {
+    account: Option<String>,
+    permissions: Vec<(String, Permission)>,
+}
+
+impl InstallationFilter {
+    /// Create a filter which matches every installation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match installations on the account with this login (case-insensitive).
+    pub fn account(mut self, login: impl Into<String>) -> Self {
+        self.account = Some(login.into());
+        self
+    }
+
+    /// Only match installations which grant at least `level` access for `permission`.
+    pub fn permission(mut self, permission: impl Into<String>, level: Permission) -> Self {
+        self.permissions.push((permission.into(), level));
+        self
+    }
+
+    /// Check if an installation matches this filter.
+    pub fn matches(&self, installation: &Installation) -> bool {
+        if let Some(login) = &self.account {
+            if !installation.account.login.eq_ignore_ascii_case(login) {
+                return false;
+            }
+        }
+
+        self.permissions
+            .iter()
+            .all(|(permission, level)| installation.has_permission(permission, *level))
+    }
+}
+
 #[derive(Debug)]
 struct TokenCache {
     secret: Secret,
@@ -318,78 +684,168 @@ impl TokenCache {
 #[derive(Debug, Clone)]
 pub struct GithubApp {
     app_id: String,
-    secret: Arc<rsa::RsaPrivateKey>,
+    secret: Arc<AppKey>,
     token: Arc<RwLock<Option<TokenCache>>>,
+    settings: GithubAppSettings,
     client: hyperdriver::client::SharedClientService<Body, Body>,
+    deprecation: DeprecationState,
 }
 
 impl GithubApp {
     /// Create a new Github App client
-    pub fn new(app_id: String, secret: Arc<rsa::RsaPrivateKey>) -> Self {
+    ///
+    /// The key may be an RSA or ECDSA P-256 key, which sign JWTs with `RS256` or `ES256`.
+    pub fn new(app_id: String, secret: impl Into<AppKey>) -> Self {
+        Self::build(app_id, secret.into(), GithubAppSettings::default())
+    }
+
+    /// Create a new Github App client with custom timeouts and token lifetimes.
+    pub fn with_settings(
+        app_id: String,
+        secret: impl Into<AppKey>,
+        settings: GithubAppSettings,
+    ) -> Result<Self, SettingsError> {
+        settings.validate()?;
+        Ok(Self::build(app_id, secret.into(), settings))
+    }
+
+    fn build(app_id: String, secret: AppKey, settings: GithubAppSettings) -> Self {
         let mut tcp = TcpTransportConfig::default();
-        tcp.connect_timeout = Some(CONNECT_TIMEOUT);
+        tcp.connect_timeout = Some(settings.connect_timeout());
 
         let client = Client::builder()
+            .with_tcp(tcp)
             .layer(
-                tower_http::set_header::SetRequestHeaderLayer::if_not_present(
-                    header::ACCEPT,
-                    GITHUB_ACCEPT.parse::<HeaderValue>().unwrap(),
-                ),
-            )
-            .layer(
-                tower_http::set_header::SetRequestHeaderLayer::if_not_present(
-                    GITHUB_API_VERSION_HEADER.parse().unwrap(),
-                    GITHUB_API_VERSION.parse::<HeaderValue>().unwrap(),
-                ),
+                tower::ServiceBuilder::new()
+                    .layer(tower::retry::RetryLayer::new(api_client::Backoff::new(
+                        RETRY_DELAY,
+                        2,
+                        RETRY_MAX_DELAY,
+                    )))
+                    .layer(SecondaryRateLimitLayer),
             )
-            .with_tcp(tcp)
             .with_default_tls()
             .with_auto_http()
-            .with_user_agent("automoton-octocat/0.1.0".to_owned())
-            .with_timeout(TIMEOUT)
+            .with_user_agent(USER_AGENT.to_owned())
+            .with_timeout(settings.timeout())
             .build_service();
 
         Self {
             app_id,
-            secret,
+            secret: Arc::new(secret),
             token: Default::default(),
+            settings,
             client,
+            deprecation: Default::default(),
         }
     }
 
-    /// List all installations for this app
-    pub async fn installations(&self) -> Result<Vec<crate::models::Installation>, Error> {
-        let req = http::Request::get(GITHUB_LIST_INSTALLATIONS)
+    /// The timeouts and token lifetimes used by this app.
+    pub fn settings(&self) -> &GithubAppSettings {
+        &self.settings
+    }
+
+    /// Start an API client which sends requests through `client`, usually this
+    /// app's connection pool, with the headers Github expects on every request.
+    fn api_client<A>(
+        &self,
+        client: hyperdriver::client::SharedClientService<Body, Body>,
+        authentication: A,
+    ) -> ApiClientBuilder<A>
+    where
+        A: Authentication + Send + Sync + 'static,
+    {
+        ApiClient::builder(GITHUB_BASE.parse().unwrap(), authentication)
+            .with_inner_service(client)
+            .layer(SetRequestHeaderLayer::if_not_present(
+                header::ACCEPT,
+                HeaderValue::from_static(GITHUB_ACCEPT),
+            ))
+            .layer(SetRequestHeaderLayer::if_not_present(
+                HeaderName::from_static(GITHUB_API_VERSION_HEADER),
+                self.settings.api_version.header_value(),
+            ))
+    }
+
+    /// Start a request which is sent directly through this app's connection pool,
+    /// with the headers [`GithubApp::api_client`] would add.
+    fn request(&self, method: http::Method, uri: String) -> http::request::Builder {
+        http::Request::builder()
+            .method(method)
+            .uri(uri)
             .version(http::Version::HTTP_2)
-            .bearer_auth(self.authentication_token(None)?.revealed())
-            .body(Body::empty())
-            .unwrap();
+            .header(header::ACCEPT, HeaderValue::from_static(GITHUB_ACCEPT))
+            .header(
+                HeaderName::from_static(GITHUB_API_VERSION_HEADER),
+                self.settings.api_version.header_value(),
+            )
+    }
 
-        let resp = self.client.clone().oneshot(req).await?;
+    /// The most recent warning from Github that a requested API version is deprecated,
+    /// seen by any installation client of this app.
+    pub fn deprecation(&self) -> Option<Deprecation> {
+        self.deprecation.read().unwrap().clone()
+    }
 
-        if !resp.status().is_success() {
-            let error = ResponseError::from_response(resp).await;
-            return Err(Error::Response(error));
-        }
+    /// List all installations for this app
+    ///
+    /// Installations are fetched lazily, one page at a time, following the `Link`
+    /// header that Github sends with each page.
+    pub fn installations(&self) -> BoxStream<'static, Result<Installation, Error>> {
+        let client = match self.app_client() {
+            Ok(client) => client,
+            Err(error) => return stream::once(futures::future::ready(Err(error))).boxed(),
+        };
+        let request = client
+            .get(GITHUB_LIST_INSTALLATIONS)
+            .version(http::Version::HTTP_2)
+            .body(Body::empty())
+            .build()
+            .expect("valid installations request");
 
-        let contents: Vec<crate::models::Installation> = resp.json().await.map_err(Error::Body)?;
+        Paginated::<_, Installation, PaginatedList<Installation, LinkHeaderPaginator>>::new(
+            client, request,
+        )
+        .map_err(Error::Body)
+        .boxed()
+    }
 
-        tracing::debug!(app = self.app_id, "Found {} installations", contents.len());
+    /// List installations for this app which match a filter.
+    pub fn installations_matching(
+        &self,
+        filter: InstallationFilter,
+    ) -> BoxStream<'static, Result<Installation, Error>> {
+        self.installations()
+            .try_filter(move |installation| futures::future::ready(filter.matches(installation)))
+            .boxed()
+    }
 
-        Ok(contents)
+    /// Find the installation of this app for an account (user or organization).
+    pub async fn find_installation(&self, owner: &str) -> Result<Option<Installation>, Error> {
+        let mut installations =
+            self.installations_matching(InstallationFilter::new().account(owner));
+        installations.try_next().await
     }
 
-    /// Get an authentication token for an installation
+    /// Get an authentication token for an installation, limited to a scope
     pub(crate) async fn installation_token(
         &self,
         installation_id: u64,
+        scope: &TokenScope,
     ) -> Result<InstallationAccess, Error> {
-        let req = http::Request::post(format!(
-            "https://api.github.com/app/installations/{installation_id}/access_tokens"
-        ))
-        .version(http::Version::HTTP_2)
-        .bearer_auth(self.authentication_token(None)?.revealed())
-        .body(Body::empty())
+        let req = self
+            .request(
+                http::Method::POST,
+                format!("https://api.github.com/app/installations/{installation_id}/access_tokens"),
+            )
+            .bearer_auth(self.authentication_token(None)?.revealed());
+
+        let req = if scope.is_empty() {
+            req.body(Body::empty())
+        } else {
+            req.header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(serde_json::to_vec(scope)?))
+        }
         .unwrap();
 
         let resp = self.client.clone().oneshot(req).await?;
@@ -417,15 +873,18 @@ impl GithubApp {
         user: &str,
         repository: &str,
     ) -> Result<GithubClient, Error> {
-        let req = http::Request::get(format!(
-            "https://api.github.com/repos/{user}/{repository}/installation",
-            user = user,
-            repository = repository
-        ))
-        .version(http::Version::HTTP_2)
-        .bearer_auth(self.authentication_token(None)?.revealed())
-        .body(Body::empty())
-        .unwrap();
+        let req = self
+            .request(
+                http::Method::GET,
+                format!(
+                    "https://api.github.com/repos/{user}/{repository}/installation",
+                    user = user,
+                    repository = repository
+                ),
+            )
+            .bearer_auth(self.authentication_token(None)?.revealed())
+            .body(Body::empty())
+            .unwrap();
 
         let resp = self.client.clone().oneshot(req).await?;
 
@@ -438,16 +897,31 @@ impl GithubApp {
         let installation: crate::models::Installation = serde_json::from_str(&body)?;
         tracing::debug!(id=%installation.id, "Got installation for repo {user}/{repository}");
 
-        let token = self.installation_token(installation.id).await?;
+        let scope = TokenScope::new();
+        let token = self.installation_token(installation.id, &scope).await?;
 
-        Ok(GithubClient::from_app(self, token, installation.id))
+        Ok(GithubClient::from_app(self, token, installation.id, scope))
     }
 
     /// Get a github client with an installation token.
     #[tracing::instrument(skip(self))]
     pub async fn installation(self, installation_id: u64) -> Result<GithubClient, Error> {
-        let access = self.installation_token(installation_id).await?;
-        Ok(GithubClient::from_app(self, access, installation_id))
+        self.installation_with_scope(installation_id, TokenScope::new())
+            .await
+    }
+
+    /// Get a github client with an installation token limited to some repositories
+    /// or permissions, so that each operation can use the least access it needs.
+    ///
+    /// Refreshed tokens have the same scope.
+    #[tracing::instrument(skip(self))]
+    pub async fn installation_with_scope(
+        self,
+        installation_id: u64,
+        scope: TokenScope,
+    ) -> Result<GithubClient, Error> {
+        let access = self.installation_token(installation_id, &scope).await?;
+        Ok(GithubClient::from_app(self, access, installation_id, scope))
     }
 
     /// Get an authentication token for the Github App specific to an installation
@@ -469,8 +943,8 @@ impl GithubApp {
         // Grab the lock now so that only one cache update occurs
         let mut guard = self.token.write().unwrap();
 
-        let issued_at = now - chrono::Duration::seconds(CLOCK_DRIFT_OFFSET_SECONDS);
-        let expire_at = now + chrono::Duration::seconds(TOKEN_DURATION_SECONDS);
+        let issued_at = now - self.settings.clock_drift();
+        let expire_at = now + self.settings.token_duration();
 
         let claims: Claims<(), &str> = Claims {
             registered: RegisteredClaims {
@@ -483,17 +957,24 @@ impl GithubApp {
         };
 
         let jwt = Token::compact((), claims);
-        let algorihm: rsa::pkcs1v15::SigningKey<Sha256> =
-            rsa::pkcs1v15::SigningKey::new((*self.secret).clone());
-        let token =
-            jwt.sign::<rsa::pkcs1v15::SigningKey<Sha256>, rsa::pkcs1v15::Signature>(&algorihm)?;
-
-        let encoded_token: Secret = token.rendered()?.into();
-        tracing::debug!(app = self.app_id, "Created a new Github App",);
+        let encoded_token: Secret = match &*self.secret {
+            AppKey::Rsa(key) => jwt
+                .sign::<_, rsa::pkcs1v15::Signature>(key.as_ref())?
+                .rendered()?,
+            AppKey::P256(key) => jwt
+                .sign::<_, ecdsa::Signature<p256::NistP256>>(key)?
+                .rendered()?,
+        }
+        .into();
+        tracing::debug!(
+            app = self.app_id,
+            alg = self.secret.algorithm(),
+            "Created a new Github App",
+        );
         tracing::trace!(app = self.app_id, jwt=%encoded_token.revealed(), "Github App JWT");
         let cache = TokenCache::new(
             encoded_token.clone(),
-            expire_at - chrono::Duration::seconds(CLOCK_DRIFT_OFFSET_SECONDS),
+            expire_at - self.settings.clock_drift(),
         );
         *guard = Some(cache);
 
@@ -504,12 +985,10 @@ impl GithubApp {
 #[cfg(test)]
 mod tests {
 
-    use rsa::pkcs8::DecodePrivateKey;
-
     use super::*;
 
     impl GithubApp {
-        fn test() -> Self {
+        pub(crate) fn test() -> Self {
             let key = {
                 include_bytes!(concat!(
                     env!("CARGO_MANIFEST_DIR"),
@@ -519,16 +998,90 @@ mod tests {
 
             GithubApp {
                 app_id: "1235".into(),
-                secret: Arc::new(rsa::RsaPrivateKey::from_pkcs8_der(key).unwrap()),
+                secret: Arc::new(AppKey::from_pkcs8_der(key).unwrap()),
                 token: Default::default(),
+                settings: Default::default(),
                 client: Client::builder()
                     .with_auto_http()
                     .with_tcp(Default::default())
                     .build_service(),
+                deprecation: Default::default(),
             }
         }
     }
 
+    fn installation(login: &str, permissions: &[(&str, Permission)]) -> Installation {
+        Installation {
+            id: 1,
+            account: models::Account {
+                title: None,
+                id: 2,
+                login: login.into(),
+            },
+            permissions: permissions
+                .iter()
+                .map(|(name, level)| (name.to_string(), *level))
+                .collect(),
+            suspended_at: None,
+        }
+    }
+
+    #[test]
+    fn filter_installations() {
+        let installation = installation(
+            "Octo-Org",
+            &[
+                ("contents", Permission::Write),
+                ("metadata", Permission::Read),
+            ],
+        );
+
+        assert!(InstallationFilter::new().matches(&installation));
+        assert!(InstallationFilter::new()
+            .account("octo-org")
+            .matches(&installation));
+        assert!(!InstallationFilter::new()
+            .account("other-org")
+            .matches(&installation));
+        assert!(InstallationFilter::new()
+            .permission("contents", Permission::Read)
+            .permission("metadata", Permission::Read)
+            .matches(&installation));
+        assert!(!InstallationFilter::new()
+            .permission("metadata", Permission::Write)
+            .matches(&installation));
+        assert!(!InstallationFilter::new()
+            .permission("issues", Permission::Read)
+            .matches(&installation));
+    }
+
+    #[test]
+    fn parse_link_header() {
+        let mut headers = http::HeaderMap::new();
+        assert_eq!(LinkHeaderPaginator::link(&headers, "next"), None);
+
+        headers.insert(
+            header::LINK,
+            HeaderValue::from_static(
+                r#"<https://api.github.com/app/installations?page=1>; rel="prev", <https://api.github.com/app/installations?page=3>; rel="next", <https://api.github.com/app/installations?page=5>; rel="last""#,
+            ),
+        );
+        assert_eq!(
+            LinkHeaderPaginator::link(&headers, "next")
+                .map(|uri| uri.to_string())
+                .as_deref(),
+            Some("https://api.github.com/app/installations?page=3")
+        );
+
+        headers.insert(
+            header::LINK,
+            HeaderValue::from_static(
+                r#"<https://api.github.com/app/installations?page=1>; rel="first""#,
+            ),
+        );
+        assert_eq!(LinkHeaderPaginator::link(&headers, "next"), None);
+    }
+
     #[test]
     fn create_authentication_token() {
         use chrono::TimeZone;
@@ -545,4 +1098,194 @@ mod tests {
             .trim()
         )
     }
+
+    #[test]
+    fn create_ec_authentication_token() {
+        let key = AppKey::from_pem(include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test/ec-p256-private-key.pem"
+        )))
+        .unwrap();
+        let app = GithubApp {
+            secret: Arc::new(key),
+            ..GithubApp::test()
+        };
+
+        let token = app.authentication_token(None).unwrap();
+        assert_eq!(token.revealed().split('.').count(), 3);
+    }
+
+    #[tokio::test]
+    async fn secondary_rate_limit() {
+        let mut mock = api_client::mock::MockService::new();
+        let mut headers = http::HeaderMap::new();
+        headers.insert(header::RETRY_AFTER, HeaderValue::from_static("60"));
+        mock.add(
+            "/repos/octocat/hello/git/commits/abc",
+            http::StatusCode::FORBIDDEN,
+            headers,
+            br#"{"message": "You have exceeded a secondary rate limit."}"#.to_vec(),
+        );
+        mock.add(
+            "/repos/octocat/hello/git/commits/def",
+            http::StatusCode::FORBIDDEN,
+            http::HeaderMap::new(),
+            br#"{"message": "Resource not accessible by integration"}"#.to_vec(),
+        );
+
+        let installation = InstallationAccess {
+            token: Secret::from("token"),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+        };
+        let client = GithubClient::new(
+            GithubApp::test(),
+            hyperdriver::service::SharedService::new(mock),
+            installation,
+            1,
+            TokenScope::new(),
+        );
+
+        let error = client
+            .get_commit("octocat", "hello", "abc")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::SecondaryRateLimit {
+                retry_after: Some(delay)
+            } if delay == std::time::Duration::from_secs(60)
+        ));
+
+        let error = client
+            .get_commit("octocat", "hello", "def")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Response(_)));
+    }
+
+    #[tokio::test]
+    async fn cache_keyed_by_request_uri() {
+        let mut mock = api_client::mock::MockService::new();
+        let mut headers = http::HeaderMap::new();
+        headers.insert(header::ETAG, HeaderValue::from_static("\"v1\""));
+        mock.add(
+            "/repos/octocat/hello/issues/1",
+            http::StatusCode::OK,
+            headers,
+            br#"{"number": 1}"#.to_vec(),
+        );
+
+        let store = api_client::MemoryCacheStore::default();
+        let client = || {
+            let installation = InstallationAccess {
+                token: Secret::from("token"),
+                expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+            };
+            GithubClient::new(
+                GithubApp::test(),
+                hyperdriver::service::SharedService::new(mock.clone()),
+                installation,
+                1,
+                TokenScope::new(),
+            )
+            .with_cache(api_client::ResponseCache::with_store(store.clone()))
+        };
+        let github = client();
+        let enterprise = client();
+        enterprise
+            .client
+            .set_base("https://github.example.com/".parse().unwrap());
+
+        for client in [&github, &enterprise] {
+            let issue: serde_json::Value = client
+                .get_json("/repos/octocat/hello/issues/1")
+                .await
+                .unwrap();
+            assert_eq!(issue["number"], 1);
+        }
+
+        assert_eq!(store.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn refresh_rejected_token() {
+        let mut mock = api_client::mock::MockService::new();
+        mock.add(
+            "/app/installations/1/access_tokens",
+            http::StatusCode::CREATED,
+            http::HeaderMap::new(),
+            br#"{"token": "fresh", "expires_at": "2099-01-01T00:00:00Z"}"#.to_vec(),
+        );
+        mock.add(
+            "/repos/octocat/hello/git/commits/abc",
+            http::StatusCode::UNAUTHORIZED,
+            http::HeaderMap::new(),
+            br#"{"message": "Bad credentials"}"#.to_vec(),
+        );
+        let service = hyperdriver::service::SharedService::new(mock);
+
+        let installation = InstallationAccess {
+            token: Secret::from("stale"),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+        };
+        let app = GithubApp {
+            client: service.clone(),
+            ..GithubApp::test()
+        };
+        let client = GithubClient::new(app, service, installation, 1, TokenScope::new());
+
+        let error = client
+            .get_commit("octocat", "hello", "abc")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, Error::Response(error) if error.status() == http::StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(client.token().revealed(), "fresh");
+        assert!(!client.is_expired());
+    }
+
+    #[tokio::test]
+    async fn api_version_deprecation() {
+        let mut mock = api_client::mock::MockService::new();
+        let mut headers = http::HeaderMap::new();
+        headers.insert("deprecation", HeaderValue::from_static("@1767225600"));
+        headers.insert(
+            "sunset",
+            HeaderValue::from_static("Thu, 31 Dec 2026 00:00:00 GMT"),
+        );
+        mock.add(
+            "/repos/octocat/hello/git/commits/abc",
+            http::StatusCode::NOT_FOUND,
+            headers,
+            br#"{"message": "Not Found"}"#.to_vec(),
+        );
+
+        let installation = InstallationAccess {
+            token: Secret::from("token"),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(1),
+        };
+        let client = GithubClient::new(
+            GithubApp::test(),
+            hyperdriver::service::SharedService::new(mock),
+            installation,
+            1,
+            TokenScope::new(),
+        )
+        .with_api_version(ApiVersion::V2022_11_28);
+        assert!(client.deprecation().is_none());
+
+        client
+            .get_commit("octocat", "hello", "abc")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            client.deprecation(),
+            Some(Deprecation {
+                version: Some("2022-11-28".into()),
+                deprecated: "@1767225600".into(),
+                sunset: Some("Thu, 31 Dec 2026 00:00:00 GMT".into()),
+            })
+        );
+    }
 }
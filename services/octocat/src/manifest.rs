@@ -0,0 +1,105 @@
+//! Registering a Github App from a manifest.
+//!
+//! In the manifest flow, a user submits an app manifest to Github, which creates
+//! the app and redirects back with a temporary code. [`ManifestApp::convert`]
+//! exchanges that code, within an hour, for the new app's credentials.
+
+use api_client::response::ResponseBodyExt as _;
+use api_client::{ApiClient, Secret};
+use http::{header, HeaderName, HeaderValue};
+use serde::Deserialize;
+
+use crate::key::{AppKey, KeyError};
+use crate::{
+    ApiVersion, Error, GithubApp, GithubErrorDecoder, GITHUB_ACCEPT, GITHUB_API_VERSION_HEADER,
+    GITHUB_BASE, USER_AGENT,
+};
+
+/// Credentials for a Github App created from a manifest.
+///
+/// Github only returns these once, so they should be stored before the app is used.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestApp {
+    /// The app ID, used as the issuer of app JWTs.
+    pub id: u64,
+
+    /// The URL-friendly name of the app.
+    pub slug: String,
+
+    /// The name of the app.
+    pub name: String,
+
+    /// The OAuth client ID.
+    pub client_id: String,
+
+    /// The OAuth client secret.
+    pub client_secret: Secret,
+
+    /// The secret used to sign webhook payloads, if the manifest configured webhooks.
+    #[serde(default)]
+    pub webhook_secret: Option<Secret>,
+
+    /// The app's private key, in PEM format.
+    pub pem: Secret,
+}
+
+impl ManifestApp {
+    /// Exchange the temporary code from the manifest flow for the new app's credentials.
+    #[tracing::instrument(skip(code))]
+    pub async fn convert(code: &str) -> Result<Self, Error> {
+        let client = ApiClient::builder(GITHUB_BASE.parse().unwrap(), ())
+            .with_header(header::ACCEPT, HeaderValue::from_static(GITHUB_ACCEPT))
+            .with_header(
+                HeaderName::from_static(GITHUB_API_VERSION_HEADER),
+                ApiVersion::default().header_value(),
+            )
+            .with_user_agent(USER_AGENT)
+            .expect("valid user agent")
+            .build()
+            .with_error_decoder(GithubErrorDecoder);
+
+        let response = client
+            .post(&format!("/app-manifests/{code}/conversions"))
+            .send()
+            .await?;
+        let response = client.error_for_status(response).await?;
+        let body = response.text().await.map_err(Error::Body)?;
+        let app: ManifestApp = serde_json::from_str(&body)?;
+        tracing::debug!(id = app.id, slug = %app.slug, "Created Github App from manifest");
+        Ok(app)
+    }
+
+    /// A client for the new app.
+    pub fn app(&self) -> Result<GithubApp, KeyError> {
+        let key = AppKey::from_pem(self.pem.revealed())?;
+        Ok(GithubApp::new(self.id.to_string(), key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_app() {
+        let pem = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test/ec-p256-private-key.pem"
+        ));
+        let app: ManifestApp = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "slug": "octoapp",
+            "node_id": "MDxOkludGVncmF0aW9uMQ==",
+            "name": "Octocat App",
+            "client_id": "Iv1.8a61f9b3a7aba766",
+            "client_secret": "1726be1638095a19edd134c77bde3aa2ece1e5d8",
+            "webhook_secret": null,
+            "pem": pem,
+        }))
+        .unwrap();
+        assert!(app.webhook_secret.is_none());
+
+        let app = app.app().unwrap();
+        assert!(app.authentication_token(None).is_ok());
+    }
+}
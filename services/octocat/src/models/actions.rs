@@ -0,0 +1,304 @@
+//! Github Actions data models.
+
+use api_client::{LinkHeaderPaginator, PaginationInfo, Paginator};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Request body to trigger a `repository_dispatch` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepositoryDispatch<'a, P> {
+    /// The name of the event, matched against `on.repository_dispatch.types` in workflows.
+    pub event_type: &'a str,
+
+    /// Arbitrary JSON payload, available to workflows as `github.event.client_payload`.
+    pub client_payload: P,
+}
+
+/// Request body to trigger a `workflow_dispatch` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkflowDispatch<'a, I> {
+    /// The git reference (branch or tag) to run the workflow on.
+    #[serde(rename = "ref")]
+    pub git_ref: &'a str,
+
+    /// Inputs declared by the workflow's `on.workflow_dispatch.inputs`.
+    pub inputs: I,
+}
+
+/// Status of a workflow run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunStatus {
+    /// The run is waiting for a runner.
+    Queued,
+
+    /// The run is executing.
+    InProgress,
+
+    /// The run has finished, see its [`WorkflowRunConclusion`].
+    Completed,
+
+    /// The run is waiting for a deployment protection rule.
+    Waiting,
+
+    /// The run has been requested, but not yet queued.
+    Requested,
+
+    /// The run is waiting on a concurrency group.
+    Pending,
+
+    /// Any other status Github reports.
+    #[serde(other)]
+    Unknown,
+}
+
+/// Outcome of a completed workflow run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowRunConclusion {
+    /// Every job succeeded.
+    Success,
+
+    /// At least one job failed.
+    Failure,
+
+    /// The run was cancelled.
+    Cancelled,
+
+    /// The run was skipped.
+    Skipped,
+
+    /// The run exceeded its time limit.
+    TimedOut,
+
+    /// The run requires manual approval.
+    ActionRequired,
+
+    /// The run finished without success or failure.
+    Neutral,
+
+    /// The run became stale before it finished.
+    Stale,
+
+    /// Any other conclusion Github reports.
+    #[serde(other)]
+    Unknown,
+}
+
+/// A single run of a Github Actions workflow.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkflowRun {
+    /// Run ID.
+    pub id: u64,
+
+    /// Name of the workflow.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// ID of the workflow which was run.
+    pub workflow_id: u64,
+
+    /// Sequential number of this run of the workflow.
+    pub run_number: u64,
+
+    /// Attempt number, incremented when the run is re-run.
+    #[serde(default)]
+    pub run_attempt: Option<u64>,
+
+    /// The event which triggered the run, e.g. `push` or `workflow_dispatch`.
+    pub event: String,
+
+    /// Branch the run was triggered on.
+    #[serde(default)]
+    pub head_branch: Option<String>,
+
+    /// Commit the run was triggered on.
+    pub head_sha: String,
+
+    /// Current status of the run.
+    pub status: WorkflowRunStatus,
+
+    /// Outcome of the run, once it has completed.
+    #[serde(default)]
+    pub conclusion: Option<WorkflowRunConclusion>,
+
+    /// Link to the run on Github.
+    pub html_url: String,
+
+    /// When the run was created.
+    pub created_at: DateTime<Utc>,
+
+    /// When the run was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WorkflowRun {
+    /// Check if the run has completed.
+    pub fn is_completed(&self) -> bool {
+        self.status == WorkflowRunStatus::Completed
+    }
+}
+
+/// Filters for listing workflow runs. Empty filters match every run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorkflowRunFilter {
+    #[serde(skip)]
+    pub(crate) workflow: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    branch: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<WorkflowRunStatus>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    head_sha: Option<String>,
+}
+
+impl WorkflowRunFilter {
+    /// Create a filter which matches every run.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match runs of a workflow, by numeric ID or file name (e.g. `ci.yml`).
+    pub fn workflow(mut self, workflow_id: impl Into<String>) -> Self {
+        self.workflow = Some(workflow_id.into());
+        self
+    }
+
+    /// Only match runs triggered on a branch.
+    pub fn branch(mut self, branch: impl Into<String>) -> Self {
+        self.branch = Some(branch.into());
+        self
+    }
+
+    /// Only match runs triggered by an event, e.g. `push`.
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Only match runs with a status.
+    pub fn status(mut self, status: WorkflowRunStatus) -> Self {
+        self.status = Some(status);
+        self
+    }
+
+    /// Only match runs for a commit.
+    pub fn head_sha(mut self, sha: impl Into<String>) -> Self {
+        self.head_sha = Some(sha.into());
+        self
+    }
+}
+
+/// A page of workflow runs.
+#[derive(Debug, Deserialize)]
+pub(crate) struct WorkflowRunList {
+    workflow_runs: Vec<WorkflowRun>,
+
+    #[serde(skip)]
+    paginate: LinkHeaderPaginator,
+}
+
+impl PaginationInfo for WorkflowRunList {
+    fn pages(&self) -> Option<usize> {
+        self.paginate.pages()
+    }
+
+    fn page(&self) -> Option<usize> {
+        self.paginate.page()
+    }
+
+    fn next(
+        &self,
+        req: http::Request<hyperdriver::Body>,
+    ) -> Option<http::Request<hyperdriver::Body>> {
+        self.paginate.next(req)
+    }
+
+    fn update_from_headers(&mut self, headers: &http::HeaderMap) {
+        self.paginate.update_from_headers(headers)
+    }
+}
+
+impl Paginator for WorkflowRunList {
+    type Item = WorkflowRun;
+
+    fn items(&mut self) -> Vec<Self::Item> {
+        std::mem::take(&mut self.workflow_runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use api_client::uri::UriExtension as _;
+
+    use super::*;
+
+    #[test]
+    fn parse_workflow_run_list() {
+        let mut list: WorkflowRunList = serde_json::from_str(
+            r#"{
+                "total_count": 2,
+                "workflow_runs": [
+                    {
+                        "id": 30433642,
+                        "name": "Build",
+                        "workflow_id": 159038,
+                        "run_number": 562,
+                        "run_attempt": 1,
+                        "event": "push",
+                        "head_branch": "main",
+                        "head_sha": "acb5820ced9479c074f688cc328bf03f341a511d",
+                        "status": "completed",
+                        "conclusion": "timed_out",
+                        "html_url": "https://github.com/octo-org/octo-repo/actions/runs/30433642",
+                        "created_at": "2020-01-22T19:33:08Z",
+                        "updated_at": "2020-01-22T19:33:08Z"
+                    },
+                    {
+                        "id": 30433643,
+                        "workflow_id": 159038,
+                        "run_number": 563,
+                        "event": "workflow_dispatch",
+                        "head_sha": "acb5820ced9479c074f688cc328bf03f341a511d",
+                        "status": "in_progress",
+                        "conclusion": null,
+                        "html_url": "https://github.com/octo-org/octo-repo/actions/runs/30433643",
+                        "created_at": "2020-01-22T19:34:08Z",
+                        "updated_at": "2020-01-22T19:34:08Z"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let runs = list.items();
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].is_completed());
+        assert_eq!(runs[0].conclusion, Some(WorkflowRunConclusion::TimedOut));
+        assert_eq!(runs[1].status, WorkflowRunStatus::InProgress);
+        assert_eq!(runs[1].conclusion, None);
+    }
+
+    #[test]
+    fn workflow_run_filter_query() {
+        let filter = WorkflowRunFilter::new()
+            .workflow("ci.yml")
+            .branch("main")
+            .status(WorkflowRunStatus::InProgress);
+        let uri = "/runs?per_page=100"
+            .parse::<http::Uri>()
+            .unwrap()
+            .append_query(&filter)
+            .unwrap();
+        assert_eq!(
+            uri.to_string(),
+            "/runs?per_page=100&branch=main&status=in_progress"
+        );
+    }
+}
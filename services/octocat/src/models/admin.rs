@@ -0,0 +1,228 @@
+//! Repository administration data models.
+
+use api_client::{LinkHeaderPaginator, PaginatedList};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A page of deploy keys.
+pub(crate) type DeployKeyList = PaginatedList<DeployKey, LinkHeaderPaginator>;
+
+/// Request body to create a repository.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewRepository<'a> {
+    name: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+
+    private: bool,
+    auto_init: bool,
+}
+
+impl<'a> NewRepository<'a> {
+    /// Create a private, empty repository.
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            description: None,
+            private: true,
+            auto_init: false,
+        }
+    }
+
+    /// Set the repository description.
+    pub fn description(mut self, description: &'a str) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    /// Make the repository public.
+    pub fn public(mut self) -> Self {
+        self.private = false;
+        self
+    }
+
+    /// Create the default branch with an initial commit containing a README.
+    pub fn auto_init(mut self) -> Self {
+        self.auto_init = true;
+        self
+    }
+}
+
+/// Status checks which must pass before merging to a protected branch.
+#[derive(Debug, Clone, Serialize)]
+struct RequiredStatusChecks {
+    strict: bool,
+    contexts: Vec<String>,
+}
+
+/// Reviews required before merging to a protected branch.
+#[derive(Debug, Clone, Serialize)]
+struct RequiredReviews {
+    required_approving_review_count: u8,
+    dismiss_stale_reviews: bool,
+}
+
+/// Protection rules for a branch, which replace any existing rules.
+///
+/// Rules which aren't set are disabled.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BranchProtection {
+    required_status_checks: Option<RequiredStatusChecks>,
+    enforce_admins: bool,
+    required_pull_request_reviews: Option<RequiredReviews>,
+
+    /// Github requires this field, but push restrictions are only available for
+    /// organization repositories, so they are always disabled.
+    restrictions: (),
+
+    required_linear_history: bool,
+    allow_force_pushes: bool,
+    allow_deletions: bool,
+}
+
+impl BranchProtection {
+    /// Protection which only blocks force pushes and deletion of the branch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require status checks with these names to pass before merging. If `strict`,
+    /// branches must also be up to date with the protected branch.
+    pub fn require_status_checks<I, S>(mut self, strict: bool, contexts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.required_status_checks = Some(RequiredStatusChecks {
+            strict,
+            contexts: contexts.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    /// Require approving reviews before merging, dismissing approvals when new
+    /// commits are pushed.
+    pub fn require_reviews(mut self, count: u8) -> Self {
+        self.required_pull_request_reviews = Some(RequiredReviews {
+            required_approving_review_count: count,
+            dismiss_stale_reviews: true,
+        });
+        self
+    }
+
+    /// Apply these rules to repository administrators too.
+    pub fn enforce_admins(mut self) -> Self {
+        self.enforce_admins = true;
+        self
+    }
+
+    /// Reject merge commits on the branch.
+    pub fn require_linear_history(mut self) -> Self {
+        self.required_linear_history = true;
+        self
+    }
+}
+
+/// An SSH key with access to a single repository.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeployKey {
+    /// Deploy key ID.
+    pub id: u64,
+
+    /// The public key.
+    pub key: String,
+
+    /// A name for the key.
+    pub title: String,
+
+    /// Whether the key can only read from the repository.
+    pub read_only: bool,
+
+    /// When the key was added.
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Request body to add a deploy key.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewDeployKey<'a> {
+    title: &'a str,
+    key: &'a str,
+    read_only: bool,
+}
+
+impl<'a> NewDeployKey<'a> {
+    /// Add a read-only deploy key with a name and public key.
+    pub fn new(title: &'a str, key: &'a str) -> Self {
+        Self {
+            title,
+            key,
+            read_only: true,
+        }
+    }
+
+    /// Allow the key to push to the repository.
+    pub fn writable(mut self) -> Self {
+        self.read_only = false;
+        self
+    }
+}
+
+/// The public key used to encrypt Actions secrets for a repository.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SecretsPublicKey {
+    /// Key ID, sent with each secret encrypted with the key.
+    pub key_id: String,
+
+    /// The base64 encoded Curve25519 public key.
+    pub key: String,
+}
+
+/// Request body to create or update an Actions secret.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct EncryptedSecret<'a> {
+    pub(crate) encrypted_value: String,
+    pub(crate) key_id: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn branch_protection() {
+        assert_eq!(
+            serde_json::to_value(BranchProtection::new()).unwrap(),
+            serde_json::json!({
+                "required_status_checks": null,
+                "enforce_admins": false,
+                "required_pull_request_reviews": null,
+                "restrictions": null,
+                "required_linear_history": false,
+                "allow_force_pushes": false,
+                "allow_deletions": false,
+            })
+        );
+
+        let protection = BranchProtection::new()
+            .require_status_checks(true, ["ci"])
+            .require_reviews(1)
+            .enforce_admins();
+        assert_eq!(
+            serde_json::to_value(protection).unwrap(),
+            serde_json::json!({
+                "required_status_checks": {"strict": true, "contexts": ["ci"]},
+                "enforce_admins": true,
+                "required_pull_request_reviews": {
+                    "required_approving_review_count": 1,
+                    "dismiss_stale_reviews": true,
+                },
+                "restrictions": null,
+                "required_linear_history": false,
+                "allow_force_pushes": false,
+                "allow_deletions": false,
+            })
+        );
+    }
+}
@@ -1,8 +1,10 @@
-//! Commit data models.
+//! Commit and commit status data models.
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::Account;
+
 /// A commit object.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Commit {
@@ -32,3 +34,176 @@ pub struct AuthorCommitDetails {
     /// The date of the commit.
     pub date: DateTime<Utc>,
 }
+
+/// The state of a commit status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusState {
+    /// The check could not run.
+    Error,
+
+    /// The check failed.
+    Failure,
+
+    /// The check is running, or hasn't reported yet.
+    Pending,
+
+    /// The check passed.
+    Success,
+}
+
+/// A status reported for a commit, e.g. by a CI system.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommitStatus {
+    /// Status ID.
+    pub id: u64,
+
+    /// The state of the status.
+    pub state: StatusState,
+
+    /// The label which identifies the reporter, e.g. `ci/build`.
+    pub context: String,
+
+    /// A short description of the status.
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// Link to details of the status, e.g. a build log.
+    #[serde(default)]
+    pub target_url: Option<String>,
+
+    /// The account which reported the status.
+    #[serde(default)]
+    pub creator: Option<Account>,
+
+    /// When the status was first reported.
+    pub created_at: DateTime<Utc>,
+
+    /// When the status was last updated.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The combined status of a commit, from the latest status for each context.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CombinedStatus {
+    /// The combined state: `failure` if any context failed or errored, `pending`
+    /// if any context is pending or there are no statuses, and `success` otherwise.
+    pub state: StatusState,
+
+    /// The SHA of the commit.
+    pub sha: String,
+
+    /// The number of contexts which reported a status.
+    pub total_count: u64,
+
+    /// The latest status for each context.
+    #[serde(default)]
+    pub statuses: Vec<CommitStatus>,
+}
+
+impl CombinedStatus {
+    /// Find the latest status for a context.
+    pub fn status(&self, context: &str) -> Option<&CommitStatus> {
+        self.statuses
+            .iter()
+            .find(|status| status.context == context)
+    }
+}
+
+/// Request body to create a commit status.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewStatus<'a> {
+    state: StatusState,
+    context: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_url: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+}
+
+impl<'a> NewStatus<'a> {
+    /// Report a status for a context, e.g. `ci/build`.
+    ///
+    /// Each context has one current status, so reporting the same context again
+    /// replaces its previous status.
+    pub fn new(state: StatusState, context: &'a str) -> Self {
+        Self {
+            state,
+            context,
+            target_url: None,
+            description: None,
+        }
+    }
+
+    /// Link to details of the status, e.g. a build log.
+    pub fn target_url(mut self, url: &'a str) -> Self {
+        self.target_url = Some(url);
+        self
+    }
+
+    /// Set a short description of the status.
+    pub fn description(mut self, description: &'a str) -> Self {
+        self.description = Some(description);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_combined_status() {
+        let combined: CombinedStatus = serde_json::from_str(
+            r#"{
+                "state": "failure",
+                "sha": "6dcb09b5b57875f334f61aebed695e2e4193db5e",
+                "total_count": 2,
+                "statuses": [
+                    {
+                        "id": 1,
+                        "state": "success",
+                        "description": "Build has completed successfully",
+                        "target_url": "https://ci.example.com/1000/output",
+                        "context": "continuous-integration/jenkins",
+                        "created_at": "2012-07-20T01:19:13Z",
+                        "updated_at": "2012-07-20T01:19:13Z"
+                    },
+                    {
+                        "id": 2,
+                        "state": "failure",
+                        "description": "Testing has failed",
+                        "target_url": null,
+                        "context": "security/brakeman",
+                        "creator": {"login": "octocat", "id": 1},
+                        "created_at": "2012-08-20T01:19:13Z",
+                        "updated_at": "2012-08-20T01:19:13Z"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(combined.state, StatusState::Failure);
+        let status = combined.status("security/brakeman").unwrap();
+        assert_eq!(status.state, StatusState::Failure);
+        assert!(status.target_url.is_none());
+        assert!(combined.status("missing").is_none());
+    }
+
+    #[test]
+    fn new_status() {
+        let status =
+            NewStatus::new(StatusState::Pending, "ci/build").target_url("https://ci.example.com/1");
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({
+                "state": "pending",
+                "context": "ci/build",
+                "target_url": "https://ci.example.com/1",
+            })
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Repository contents data models.
+
+use base64::prelude::{Engine as _, BASE64_STANDARD};
+use serde::{Deserialize, Serialize};
+
+use super::git::{GitCommit, Signature};
+
+/// The kind of item at a path in a repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    /// A regular file.
+    File,
+
+    /// A directory.
+    Dir,
+
+    /// A symbolic link.
+    Symlink,
+
+    /// A git submodule.
+    Submodule,
+}
+
+/// A file (or other item) in a repository.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Content {
+    /// The kind of item.
+    #[serde(rename = "type")]
+    pub kind: ContentType,
+
+    /// File name.
+    pub name: String,
+
+    /// Path within the repository.
+    pub path: String,
+
+    /// The blob SHA, required to update or delete the file.
+    pub sha: String,
+
+    /// Size in bytes.
+    #[serde(default)]
+    pub size: u64,
+
+    /// Encoding of `content`, usually `base64`.
+    #[serde(default)]
+    pub encoding: Option<String>,
+
+    /// Encoded file content. Github omits this for directories and large files.
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+impl Content {
+    /// Decode the file content.
+    ///
+    /// Returns `None` if Github did not include the content in the response.
+    pub fn decode(&self) -> Option<Result<Vec<u8>, base64::DecodeError>> {
+        let content = self.content.as_deref()?;
+        match self.encoding.as_deref() {
+            Some("base64") | None => {
+                // Github wraps base64 content at 60 characters.
+                let content: String = content.split_whitespace().collect();
+                Some(BASE64_STANDARD.decode(content))
+            }
+            Some(_) => Some(Ok(content.as_bytes().to_vec())),
+        }
+    }
+}
+
+/// A change to a single file, committed directly through the contents API.
+#[derive(Debug, Clone)]
+pub struct FileUpdate<'a> {
+    /// The commit message.
+    pub message: &'a str,
+
+    /// New content of the file.
+    pub content: &'a [u8],
+
+    /// The blob SHA of the file being replaced. Required when the file exists,
+    /// and the update is rejected if the file has changed since.
+    pub sha: Option<&'a str>,
+
+    /// The branch to commit to, defaults to the repository's default branch.
+    pub branch: Option<&'a str>,
+
+    /// The committer, defaults to the installation.
+    pub committer: Option<Signature>,
+}
+
+impl<'a> FileUpdate<'a> {
+    /// Create an update which writes `content` with a commit message.
+    pub fn new(message: &'a str, content: &'a [u8]) -> Self {
+        Self {
+            message,
+            content,
+            sha: None,
+            branch: None,
+            committer: None,
+        }
+    }
+
+    /// Only replace the file if its current blob SHA matches.
+    pub fn replacing(mut self, sha: &'a str) -> Self {
+        self.sha = Some(sha);
+        self
+    }
+
+    /// Commit to a branch other than the default.
+    pub fn on_branch(mut self, branch: &'a str) -> Self {
+        self.branch = Some(branch);
+        self
+    }
+
+    /// Set the committer.
+    pub fn with_committer(mut self, committer: Signature) -> Self {
+        self.committer = Some(committer);
+        self
+    }
+}
+
+/// Request body to create or update a file.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PutContent<'a> {
+    pub(crate) message: &'a str,
+    pub(crate) content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) sha: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) branch: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) committer: Option<&'a Signature>,
+}
+
+impl<'a> From<&'a FileUpdate<'a>> for PutContent<'a> {
+    fn from(update: &'a FileUpdate<'a>) -> Self {
+        Self {
+            message: update.message,
+            content: BASE64_STANDARD.encode(update.content),
+            sha: update.sha,
+            branch: update.branch,
+            committer: update.committer.as_ref(),
+        }
+    }
+}
+
+/// Request body to delete a file.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DeleteContent<'a> {
+    pub(crate) message: &'a str,
+    pub(crate) sha: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) branch: Option<&'a str>,
+}
+
+/// The result of changing a file through the contents API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContentUpdate {
+    /// The file after the change, or `None` if it was deleted.
+    pub content: Option<Content>,
+
+    /// The commit which made the change.
+    pub commit: GitCommit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_wrapped_content() {
+        let content: Content = serde_json::from_str(
+            r#"{
+                "type": "file",
+                "encoding": "base64",
+                "size": 21,
+                "name": "config.toml",
+                "path": "config/config.toml",
+                "content": "W3NlcnZlcl0KcG9ydCA9IDgw\nODAK\n",
+                "sha": "3d21ec53a331a6f037a91c368710b99387d012c1"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(content.kind, ContentType::File);
+        assert_eq!(
+            content.decode().unwrap().unwrap(),
+            b"[server]\nport = 8080\n"
+        );
+    }
+
+    #[test]
+    fn serialize_file_update() {
+        let update = FileUpdate::new("Update config", b"hello").replacing("abc123");
+        assert_eq!(
+            serde_json::to_value(PutContent::from(&update)).unwrap(),
+            serde_json::json!({
+                "message": "Update config",
+                "content": "aGVsbG8=",
+                "sha": "abc123",
+            })
+        );
+    }
+}
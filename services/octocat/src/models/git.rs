@@ -0,0 +1,361 @@
+//! Git data models, for reading and writing objects directly through the API.
+
+use chrono::{DateTime, Utc};
+use serde::ser::SerializeMap as _;
+use serde::{Deserialize, Serialize};
+
+/// The type of a git object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectType {
+    /// File contents.
+    Blob,
+
+    /// A directory listing.
+    Tree,
+
+    /// A commit.
+    Commit,
+
+    /// An annotated tag.
+    Tag,
+}
+
+/// A reference to a git object by its SHA.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GitObject {
+    /// The SHA of the object.
+    pub sha: String,
+
+    /// The type of the object, when Github reports it.
+    #[serde(rename = "type", default)]
+    pub kind: Option<ObjectType>,
+
+    /// API URL for the object.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// Encoding of blob content sent to the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BlobEncoding {
+    /// Content is a UTF-8 string.
+    #[serde(rename = "utf-8")]
+    Utf8,
+
+    /// Content is base64 encoded bytes.
+    #[serde(rename = "base64")]
+    Base64,
+}
+
+/// Request body to create a blob.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CreateBlob {
+    pub(crate) content: String,
+    pub(crate) encoding: BlobEncoding,
+}
+
+/// File mode of an entry in a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TreeMode {
+    /// A regular file.
+    #[serde(rename = "100644")]
+    File,
+
+    /// An executable file.
+    #[serde(rename = "100755")]
+    Executable,
+
+    /// A subdirectory (tree).
+    #[serde(rename = "040000")]
+    Subdirectory,
+
+    /// A submodule (commit).
+    #[serde(rename = "160000")]
+    Submodule,
+
+    /// A symbolic link.
+    #[serde(rename = "120000")]
+    Symlink,
+}
+
+/// An entry in an existing tree.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TreeEntry {
+    /// Path of the entry, relative to the tree.
+    pub path: String,
+
+    /// File mode of the entry.
+    pub mode: TreeMode,
+
+    /// The type of object the entry points to.
+    #[serde(rename = "type")]
+    pub kind: ObjectType,
+
+    /// The SHA of the object the entry points to.
+    pub sha: String,
+
+    /// Size of the blob, for blob entries.
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// A git tree.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tree {
+    /// The SHA of the tree.
+    pub sha: String,
+
+    /// Entries in the tree.
+    pub tree: Vec<TreeEntry>,
+
+    /// Whether Github truncated a recursive listing.
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// Where the content of a new tree entry comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeSource {
+    /// An existing object, by SHA.
+    Sha(String),
+
+    /// File content, which Github will store as a new blob.
+    Content(String),
+
+    /// Remove the path from the base tree.
+    Delete,
+}
+
+/// An entry to write when creating a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewTreeEntry {
+    /// Path of the entry, relative to the tree.
+    pub path: String,
+
+    /// File mode of the entry.
+    pub mode: TreeMode,
+
+    /// The type of object the entry points to.
+    pub kind: ObjectType,
+
+    /// The content of the entry.
+    pub source: TreeSource,
+}
+
+impl NewTreeEntry {
+    /// A regular file pointing at an existing blob.
+    pub fn blob(path: impl Into<String>, sha: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            mode: TreeMode::File,
+            kind: ObjectType::Blob,
+            source: TreeSource::Sha(sha.into()),
+        }
+    }
+
+    /// A regular file with UTF-8 content.
+    pub fn file(path: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            mode: TreeMode::File,
+            kind: ObjectType::Blob,
+            source: TreeSource::Content(content.into()),
+        }
+    }
+
+    /// Delete a file from the base tree.
+    pub fn delete(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            mode: TreeMode::File,
+            kind: ObjectType::Blob,
+            source: TreeSource::Delete,
+        }
+    }
+
+    /// Set the file mode of the entry.
+    pub fn with_mode(mut self, mode: TreeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+// Github rejects entries which set both `sha` and `content`, and deletes
+// entries which set `sha` to null, so the source can't use a derived format.
+impl Serialize for NewTreeEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(4))?;
+        map.serialize_entry("path", &self.path)?;
+        map.serialize_entry("mode", &self.mode)?;
+        map.serialize_entry("type", &self.kind)?;
+        match &self.source {
+            TreeSource::Sha(sha) => map.serialize_entry("sha", sha)?,
+            TreeSource::Content(content) => map.serialize_entry("content", content)?,
+            TreeSource::Delete => map.serialize_entry("sha", &None::<String>)?,
+        }
+        map.end()
+    }
+}
+
+/// Request body to create a tree.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CreateTree<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) base_tree: Option<&'a str>,
+    pub(crate) tree: &'a [NewTreeEntry],
+}
+
+/// The author or committer of a commit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    /// Name of the person.
+    pub name: String,
+
+    /// Email address of the person.
+    pub email: String,
+
+    /// When the commit was authored or committed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// A commit to create.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewCommit {
+    /// The commit message.
+    pub message: String,
+
+    /// The SHA of the tree for this commit.
+    pub tree: String,
+
+    /// SHAs of the parent commits.
+    pub parents: Vec<String>,
+
+    /// The author, defaults to the installation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<Signature>,
+
+    /// The committer, defaults to the author.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer: Option<Signature>,
+}
+
+impl NewCommit {
+    /// Create a commit of `tree` on top of a single parent.
+    pub fn new(
+        message: impl Into<String>,
+        tree: impl Into<String>,
+        parent: impl Into<String>,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            tree: tree.into(),
+            parents: vec![parent.into()],
+            author: None,
+            committer: None,
+        }
+    }
+
+    /// Set the author of the commit.
+    pub fn with_author(mut self, author: Signature) -> Self {
+        self.author = Some(author);
+        self
+    }
+}
+
+/// A commit, as returned by the git data API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GitCommit {
+    /// The SHA of the commit.
+    pub sha: String,
+
+    /// The commit message.
+    pub message: String,
+
+    /// The tree for this commit.
+    pub tree: GitObject,
+
+    /// The parent commits.
+    pub parents: Vec<GitObject>,
+
+    /// The author of the commit.
+    pub author: Signature,
+
+    /// The committer of the commit.
+    pub committer: Signature,
+}
+
+/// A git reference, such as a branch or tag.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Reference {
+    /// The fully qualified name of the reference, e.g. `refs/heads/main`.
+    #[serde(rename = "ref")]
+    pub name: String,
+
+    /// The object the reference points to.
+    pub object: GitObject,
+}
+
+/// Request body to create a reference.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CreateReference<'a> {
+    #[serde(rename = "ref")]
+    pub(crate) name: &'a str,
+    pub(crate) sha: &'a str,
+}
+
+/// Request body to move a reference.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UpdateReference<'a> {
+    pub(crate) sha: &'a str,
+    pub(crate) force: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn serialize_tree_entries() {
+        let entries = [
+            NewTreeEntry::blob("a.txt", "abc123"),
+            NewTreeEntry::file("bin/run", "#!/bin/sh").with_mode(TreeMode::Executable),
+            NewTreeEntry::delete("old.txt"),
+        ];
+
+        assert_eq!(
+            serde_json::to_value(&entries).unwrap(),
+            json!([
+                {"path": "a.txt", "mode": "100644", "type": "blob", "sha": "abc123"},
+                {"path": "bin/run", "mode": "100755", "type": "blob", "content": "#!/bin/sh"},
+                {"path": "old.txt", "mode": "100644", "type": "blob", "sha": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_reference() {
+        let reference: Reference = serde_json::from_str(
+            r#"{
+                "ref": "refs/heads/featureA",
+                "node_id": "MDM6UmVmcmVmcy9oZWFkcy9mZWF0dXJlQQ==",
+                "url": "https://api.github.com/repos/octocat/Hello-World/git/refs/heads/featureA",
+                "object": {
+                    "type": "commit",
+                    "sha": "aa218f56b14c9653891f9e74264a383fa43fefbd",
+                    "url": "https://api.github.com/repos/octocat/Hello-World/git/commits/aa218f56b14c9653891f9e74264a383fa43fefbd"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(reference.name, "refs/heads/featureA");
+        assert_eq!(reference.object.kind, Some(ObjectType::Commit));
+    }
+}
@@ -0,0 +1,417 @@
+//! Issue, comment and reaction data models.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Account;
+
+/// The state of an issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueState {
+    /// The issue is open.
+    Open,
+
+    /// The issue is closed.
+    Closed,
+
+    /// Any state, only used when listing issues.
+    All,
+}
+
+impl IssueState {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            IssueState::Open => "open",
+            IssueState::Closed => "closed",
+            IssueState::All => "all",
+        }
+    }
+}
+
+/// A Github issue.
+///
+/// Github treats every pull request as an issue, so issue lists include pull
+/// requests as well. Use [`Issue::is_pull_request`] to tell them apart.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Issue {
+    /// Issue ID.
+    pub id: u64,
+
+    /// Issue number within the repository.
+    pub number: u64,
+
+    /// Issue title.
+    pub title: String,
+
+    /// Issue description, in markdown.
+    #[serde(default)]
+    pub body: Option<String>,
+
+    /// Issue state.
+    pub state: IssueState,
+
+    /// The account which opened the issue.
+    pub user: Account,
+
+    /// Labels applied to the issue.
+    #[serde(default)]
+    pub labels: Vec<Label>,
+
+    /// Accounts assigned to the issue.
+    #[serde(default)]
+    pub assignees: Vec<Account>,
+
+    /// The number of comments on the issue.
+    #[serde(default)]
+    pub comments: u64,
+
+    /// Link to the issue on Github.
+    pub html_url: String,
+
+    /// When the issue was opened.
+    pub created_at: DateTime<Utc>,
+
+    /// When the issue was last updated.
+    pub updated_at: DateTime<Utc>,
+
+    /// When the issue was closed, if it is closed.
+    #[serde(default)]
+    pub closed_at: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pull_request: Option<serde::de::IgnoredAny>,
+}
+
+impl Issue {
+    /// Check if the issue is a pull request.
+    pub fn is_pull_request(&self) -> bool {
+        self.pull_request.is_some()
+    }
+
+    /// Check if a label is applied to the issue.
+    pub fn has_label(&self, name: &str) -> bool {
+        self.labels.iter().any(|label| label.name == name)
+    }
+}
+
+/// A label which can be applied to issues.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Label {
+    /// Label ID.
+    pub id: u64,
+
+    /// Label name.
+    pub name: String,
+
+    /// Label color, as a hex code without the leading `#`.
+    pub color: String,
+
+    /// Short description of the label.
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// A comment on an issue or pull request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Comment {
+    /// Comment ID.
+    pub id: u64,
+
+    /// The comment, in markdown.
+    pub body: String,
+
+    /// The account which wrote the comment.
+    pub user: Account,
+
+    /// Link to the comment on Github.
+    pub html_url: String,
+
+    /// When the comment was written.
+    pub created_at: DateTime<Utc>,
+
+    /// When the comment was last edited.
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The emoji used for a reaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReactionContent {
+    /// 👍
+    #[serde(rename = "+1")]
+    ThumbsUp,
+
+    /// 👎
+    #[serde(rename = "-1")]
+    ThumbsDown,
+
+    /// 😄
+    Laugh,
+
+    /// 😕
+    Confused,
+
+    /// ❤️
+    Heart,
+
+    /// 🎉
+    Hooray,
+
+    /// 🚀
+    Rocket,
+
+    /// 👀
+    Eyes,
+}
+
+/// A reaction to an issue or comment.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Reaction {
+    /// Reaction ID.
+    pub id: u64,
+
+    /// The emoji used for the reaction.
+    pub content: ReactionContent,
+
+    /// The account which reacted.
+    pub user: Account,
+
+    /// When the reaction was added.
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request body to create an issue.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewIssue<'a> {
+    title: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<&'a str>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assignees: Vec<&'a str>,
+}
+
+impl<'a> NewIssue<'a> {
+    /// Create an issue with a title.
+    pub fn new(title: &'a str) -> Self {
+        Self {
+            title,
+            body: None,
+            labels: Vec::new(),
+            assignees: Vec::new(),
+        }
+    }
+
+    /// Set the issue description.
+    pub fn body(mut self, body: &'a str) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Apply a label to the issue. The label is created if it doesn't exist.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    /// Assign the issue to an account, by login.
+    pub fn assignee(mut self, login: &'a str) -> Self {
+        self.assignees.push(login);
+        self
+    }
+}
+
+/// Request body to update an issue. Only the fields which are set are changed.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UpdateIssue<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<IssueState>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<Vec<&'a str>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignees: Option<Vec<&'a str>>,
+}
+
+impl<'a> UpdateIssue<'a> {
+    /// Create an update which changes nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change the issue title.
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    /// Change the issue description.
+    pub fn body(mut self, body: &'a str) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Close the issue.
+    pub fn close(mut self) -> Self {
+        self.state = Some(IssueState::Closed);
+        self
+    }
+
+    /// Reopen the issue.
+    pub fn reopen(mut self) -> Self {
+        self.state = Some(IssueState::Open);
+        self
+    }
+
+    /// Replace the labels on the issue.
+    pub fn labels(mut self, labels: &[&'a str]) -> Self {
+        self.labels = Some(labels.to_vec());
+        self
+    }
+
+    /// Replace the accounts assigned to the issue.
+    pub fn assignees(mut self, logins: &[&'a str]) -> Self {
+        self.assignees = Some(logins.to_vec());
+        self
+    }
+}
+
+/// Request body to comment on an issue.
+#[derive(Debug, Serialize)]
+pub(crate) struct NewComment<'a> {
+    pub(crate) body: &'a str,
+}
+
+/// Request body to add labels to an issue.
+#[derive(Debug, Serialize)]
+pub(crate) struct AddLabels<'a> {
+    pub(crate) labels: &'a [&'a str],
+}
+
+/// Request body to add assignees to an issue.
+#[derive(Debug, Serialize)]
+pub(crate) struct AddAssignees<'a> {
+    pub(crate) assignees: &'a [&'a str],
+}
+
+/// Request body to react to an issue or comment.
+#[derive(Debug, Serialize)]
+pub(crate) struct NewReaction {
+    pub(crate) content: ReactionContent,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_issue() {
+        let issue: Issue = serde_json::from_str(
+            r#"{
+                "id": 1,
+                "number": 1347,
+                "title": "Found a bug",
+                "body": "I'm having a problem with this.",
+                "state": "open",
+                "user": {"login": "octocat", "id": 1},
+                "labels": [
+                    {
+                        "id": 208045946,
+                        "name": "bug",
+                        "description": "Something isn't working",
+                        "color": "f29513",
+                        "default": true
+                    }
+                ],
+                "assignees": [{"login": "hubot", "id": 2}],
+                "comments": 0,
+                "html_url": "https://github.com/octocat/Hello-World/issues/1347",
+                "created_at": "2011-04-22T13:33:48Z",
+                "updated_at": "2011-04-22T13:33:48Z",
+                "closed_at": null
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(issue.state, IssueState::Open);
+        assert!(issue.has_label("bug"));
+        assert_eq!(issue.assignees[0].login, "hubot");
+        assert!(!issue.is_pull_request());
+    }
+
+    #[test]
+    fn parse_pull_request_issue() {
+        let issue: Issue = serde_json::from_str(
+            r#"{
+                "id": 2,
+                "number": 1348,
+                "title": "Fix the bug",
+                "state": "closed",
+                "user": {"login": "octocat", "id": 1},
+                "html_url": "https://github.com/octocat/Hello-World/pull/1348",
+                "created_at": "2011-04-22T13:33:48Z",
+                "updated_at": "2011-04-23T13:33:48Z",
+                "closed_at": "2011-04-23T13:33:48Z",
+                "pull_request": {
+                    "url": "https://api.github.com/repos/octocat/Hello-World/pulls/1348"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert!(issue.is_pull_request());
+        assert!(issue.closed_at.is_some());
+        assert!(issue.labels.is_empty());
+    }
+
+    #[test]
+    fn issue_requests() {
+        let issue = NewIssue::new("Found a bug")
+            .label("bug")
+            .assignee("octocat");
+        assert_eq!(
+            serde_json::to_value(&issue).unwrap(),
+            serde_json::json!({
+                "title": "Found a bug",
+                "labels": ["bug"],
+                "assignees": ["octocat"],
+            })
+        );
+
+        let update = UpdateIssue::new().close().labels(&[]);
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            serde_json::json!({
+                "state": "closed",
+                "labels": [],
+            })
+        );
+    }
+
+    #[test]
+    fn reaction_content() {
+        let reaction = NewReaction {
+            content: ReactionContent::ThumbsUp,
+        };
+        assert_eq!(
+            serde_json::to_value(&reaction).unwrap(),
+            serde_json::json!({"content": "+1"})
+        );
+        assert_eq!(
+            serde_json::from_str::<ReactionContent>(r#""hooray""#).unwrap(),
+            ReactionContent::Hooray
+        );
+    }
+}
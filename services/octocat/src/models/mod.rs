@@ -1,25 +1,73 @@
 //! Github API object models.
 
 use api_client::{Authentication, RequestExt, Secret};
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+pub mod actions;
+pub mod admin;
 pub mod commits;
+pub mod contents;
+pub mod git;
+pub mod issues;
+pub mod releases;
+pub mod repos;
 
-pub use commits::Commit;
+pub use commits::{CombinedStatus, Commit, CommitStatus, StatusState};
+pub use repos::{PullRequest, PullRequestRef, PullRequestState, Repository};
 
 /// Github API response for a single installation.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Installation {
     /// Installation ID.
     pub id: u64,
 
     /// Account associated with the installation.
     pub account: Account,
+
+    /// Permissions granted to the installation, keyed by permission name (e.g. `contents`).
+    #[serde(default)]
+    pub permissions: BTreeMap<String, Permission>,
+
+    /// When the installation was suspended, if it is suspended.
+    #[serde(default)]
+    pub suspended_at: Option<DateTime<Utc>>,
+}
+
+impl Installation {
+    /// Check if the installation is suspended, in which case it can't be used.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended_at.is_some()
+    }
+
+    /// Check if the installation grants at least `level` access for `permission`.
+    pub fn has_permission(&self, permission: &str, level: Permission) -> bool {
+        self.permissions
+            .get(permission)
+            .is_some_and(|granted| *granted >= level)
+    }
+}
+
+/// Access level granted for a single installation permission.
+///
+/// Levels are ordered, so that `Write` satisfies a requirement for `Read`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Read-only access.
+    Read,
+
+    /// Read and write access.
+    Write,
+
+    /// Administrative access.
+    Admin,
 }
 
 /// Account associated with an installation.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Account {
     /// Installation title
     pub title: Option<String>,
@@ -0,0 +1,202 @@
+//! Release and release asset data models.
+
+use api_client::{LinkHeaderPaginator, PaginatedList};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A page of releases.
+pub(crate) type ReleaseList = PaginatedList<Release, LinkHeaderPaginator>;
+
+/// A Github release.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    /// Release ID.
+    pub id: u64,
+
+    /// The tag the release is attached to.
+    pub tag_name: String,
+
+    /// The release title.
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// The release notes, in markdown.
+    #[serde(default)]
+    pub body: Option<String>,
+
+    /// Whether the release is an unpublished draft.
+    pub draft: bool,
+
+    /// Whether the release is marked as a pre-release.
+    pub prerelease: bool,
+
+    /// Link to the release on Github.
+    pub html_url: String,
+
+    /// URL template for uploading assets, e.g.
+    /// `https://uploads.github.com/repos/octocat/hello/releases/1/assets{?name,label}`.
+    pub upload_url: String,
+
+    /// When the release was created.
+    pub created_at: DateTime<Utc>,
+
+    /// When the release was published, if it isn't a draft.
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+
+    /// Files attached to the release.
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+impl Release {
+    /// The URL to upload assets to, without the URI template suffix.
+    pub fn upload_endpoint(&self) -> &str {
+        self.upload_url
+            .split_once('{')
+            .map_or(self.upload_url.as_str(), |(url, _)| url)
+    }
+
+    /// Find an asset attached to this release by name.
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// A file attached to a release.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    /// Asset ID.
+    pub id: u64,
+
+    /// File name of the asset.
+    pub name: String,
+
+    /// Short description shown instead of the file name.
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// Media type of the asset.
+    pub content_type: String,
+
+    /// Size of the asset, in bytes.
+    pub size: u64,
+
+    /// Whether the asset has been fully `uploaded`.
+    pub state: String,
+
+    /// Public URL for downloading the asset.
+    pub browser_download_url: String,
+}
+
+/// Request body to create a release.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewRelease<'a> {
+    tag_name: &'a str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_commitish: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<&'a str>,
+
+    draft: bool,
+    prerelease: bool,
+    generate_release_notes: bool,
+}
+
+impl<'a> NewRelease<'a> {
+    /// Create a published release for a tag. The tag is created from the default
+    /// branch if it doesn't already exist.
+    pub fn new(tag_name: &'a str) -> Self {
+        Self {
+            tag_name,
+            target_commitish: None,
+            name: None,
+            body: None,
+            draft: false,
+            prerelease: false,
+            generate_release_notes: false,
+        }
+    }
+
+    /// Create the tag from this branch or commit SHA, if it doesn't exist.
+    pub fn target(mut self, commitish: &'a str) -> Self {
+        self.target_commitish = Some(commitish);
+        self
+    }
+
+    /// Set the release title.
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Set the release notes.
+    pub fn body(mut self, body: &'a str) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Create the release as an unpublished draft.
+    pub fn draft(mut self) -> Self {
+        self.draft = true;
+        self
+    }
+
+    /// Mark the release as a pre-release.
+    pub fn prerelease(mut self) -> Self {
+        self.prerelease = true;
+        self
+    }
+
+    /// Have Github generate release notes from merged pull requests.
+    pub fn generate_release_notes(mut self) -> Self {
+        self.generate_release_notes = true;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upload_endpoint() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "id": 1,
+                "tag_name": "v1.0.0",
+                "name": "v1.0.0",
+                "draft": false,
+                "prerelease": false,
+                "html_url": "https://github.com/octocat/Hello-World/releases/v1.0.0",
+                "upload_url": "https://uploads.github.com/repos/octocat/Hello-World/releases/1/assets{?name,label}",
+                "created_at": "2013-02-27T19:35:32Z",
+                "published_at": "2013-02-27T19:35:32Z",
+                "assets": [
+                    {
+                        "id": 1,
+                        "name": "example.zip",
+                        "label": "short description",
+                        "content_type": "application/zip",
+                        "size": 1024,
+                        "state": "uploaded",
+                        "browser_download_url": "https://github.com/octocat/Hello-World/releases/download/v1.0.0/example.zip"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            release.upload_endpoint(),
+            "https://uploads.github.com/repos/octocat/Hello-World/releases/1/assets"
+        );
+        assert_eq!(release.asset("example.zip").unwrap().size, 1024);
+        assert!(release.asset("missing.zip").is_none());
+    }
+}
@@ -0,0 +1,175 @@
+//! Repository and pull request data models.
+
+use api_client::{LinkHeaderPaginator, PaginationInfo, Paginator};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Account;
+
+/// A Github repository.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Repository {
+    /// Repository ID.
+    pub id: u64,
+
+    /// Repository name, without the owner.
+    pub name: String,
+
+    /// Full repository name, as `owner/name`.
+    pub full_name: String,
+
+    /// The account which owns the repository.
+    pub owner: Account,
+
+    /// Whether the repository is private.
+    #[serde(default)]
+    pub private: bool,
+
+    /// Whether the repository is archived.
+    #[serde(default)]
+    pub archived: bool,
+
+    /// The default branch of the repository.
+    #[serde(default)]
+    pub default_branch: Option<String>,
+}
+
+/// A page of repositories accessible to an installation.
+#[derive(Debug, Deserialize)]
+pub(crate) struct RepositoryList {
+    repositories: Vec<Repository>,
+
+    #[serde(skip)]
+    paginate: LinkHeaderPaginator,
+}
+
+impl PaginationInfo for RepositoryList {
+    fn pages(&self) -> Option<usize> {
+        self.paginate.pages()
+    }
+
+    fn page(&self) -> Option<usize> {
+        self.paginate.page()
+    }
+
+    fn next(
+        &self,
+        req: http::Request<hyperdriver::Body>,
+    ) -> Option<http::Request<hyperdriver::Body>> {
+        self.paginate.next(req)
+    }
+
+    fn update_from_headers(&mut self, headers: &http::HeaderMap) {
+        self.paginate.update_from_headers(headers)
+    }
+}
+
+impl Paginator for RepositoryList {
+    type Item = Repository;
+
+    fn items(&mut self) -> Vec<Self::Item> {
+        std::mem::take(&mut self.repositories)
+    }
+}
+
+/// The state of a pull request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullRequestState {
+    /// The pull request is open.
+    Open,
+
+    /// The pull request is closed (or merged).
+    Closed,
+
+    /// Any state, only used when listing pull requests.
+    All,
+}
+
+impl PullRequestState {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            PullRequestState::Open => "open",
+            PullRequestState::Closed => "closed",
+            PullRequestState::All => "all",
+        }
+    }
+}
+
+/// A Github pull request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullRequest {
+    /// Pull request ID.
+    pub id: u64,
+
+    /// Pull request number within the repository.
+    pub number: u64,
+
+    /// Pull request title.
+    pub title: String,
+
+    /// Pull request state.
+    pub state: PullRequestState,
+
+    /// Whether the pull request is a draft.
+    #[serde(default)]
+    pub draft: bool,
+
+    /// The account which opened the pull request.
+    pub user: Account,
+
+    /// The branch the changes are on.
+    pub head: PullRequestRef,
+
+    /// The branch the changes would be merged into.
+    pub base: PullRequestRef,
+
+    /// When the pull request was merged, if it was.
+    #[serde(default)]
+    pub merged_at: Option<DateTime<Utc>>,
+}
+
+/// A branch reference for a pull request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullRequestRef {
+    /// The branch name.
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+
+    /// The commit SHA at the tip of the branch.
+    pub sha: String,
+
+    /// A label for the branch, as `owner:branch`.
+    pub label: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_repository_list() {
+        let mut list: RepositoryList = serde_json::from_str(
+            r#"{
+                "total_count": 1,
+                "repositories": [
+                    {
+                        "id": 1296269,
+                        "name": "Hello-World",
+                        "full_name": "octocat/Hello-World",
+                        "owner": {"login": "octocat", "id": 1},
+                        "private": false,
+                        "default_branch": "main"
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let repos = list.items();
+        assert_eq!(repos.len(), 1);
+        assert_eq!(repos[0].full_name, "octocat/Hello-World");
+        assert_eq!(repos[0].owner.login, "octocat");
+        assert!(list.paginate.next_link().is_none());
+    }
+}
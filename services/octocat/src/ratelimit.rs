@@ -0,0 +1,148 @@
+//! Recognizing Github's secondary rate limits before responses are retried.
+//!
+//! Github doesn't always send `Retry-After` with a secondary rate limit, and
+//! asks clients to wait at least a minute when it is missing. The limit is only
+//! described in the response body, which retry policies can't read, so this
+//! layer adds the header for them.
+
+use std::task::{Context, Poll};
+
+use api_client::BoxFuture;
+use bytes::Bytes;
+use http::{header, HeaderValue, StatusCode};
+use http_body_util::BodyExt as _;
+use hyperdriver::Body;
+use tower::{Layer, Service};
+
+/// How long to wait after a secondary rate limit without a `Retry-After` header.
+const SECONDARY_RATE_LIMIT_DELAY: &str = "60";
+
+/// Check whether an error response body describes a secondary rate limit.
+pub(crate) fn is_secondary_rate_limit(body: &str) -> bool {
+    body.contains("secondary rate limit")
+}
+
+/// Adds a `Retry-After` header to secondary rate limit responses which don't have one.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SecondaryRateLimitLayer;
+
+impl<S> Layer<S> for SecondaryRateLimitLayer {
+    type Service = SecondaryRateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SecondaryRateLimit { inner }
+    }
+}
+
+/// A service which adds a `Retry-After` header to secondary rate limit responses.
+#[derive(Debug, Clone)]
+pub(crate) struct SecondaryRateLimit<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for SecondaryRateLimit<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<Body>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let response = future.await?;
+            if !matches!(
+                response.status(),
+                StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+            ) || response.headers().contains_key(header::RETRY_AFTER)
+                || response
+                    .headers()
+                    .get("x-ratelimit-remaining")
+                    .is_some_and(|remaining| remaining == "0")
+            {
+                return Ok(response);
+            }
+
+            // Error bodies are small, so they can be buffered to look for the limit.
+            let (mut parts, body) = response.into_parts();
+            let body = match body.collect().await {
+                Ok(collected) => collected.to_bytes(),
+                Err(error) => {
+                    tracing::debug!("Reading Github error response: {error}");
+                    Bytes::new()
+                }
+            };
+
+            if is_secondary_rate_limit(&String::from_utf8_lossy(&body)) {
+                parts.headers.insert(
+                    header::RETRY_AFTER,
+                    HeaderValue::from_static(SECONDARY_RATE_LIMIT_DELAY),
+                );
+            }
+            Ok(http::Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use api_client::mock::MockService;
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    async fn retry_after(mock: &MockService, path: &str) -> Option<HeaderValue> {
+        let response = SecondaryRateLimitLayer
+            .layer(mock.clone())
+            .oneshot(
+                http::Request::get(format!("http://example.com{path}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response.headers().get(header::RETRY_AFTER).cloned()
+    }
+
+    #[tokio::test]
+    async fn secondary_rate_limit_delay() {
+        let mut mock = MockService::new();
+        mock.add(
+            "/limited",
+            StatusCode::FORBIDDEN,
+            http::HeaderMap::new(),
+            br#"{"message": "You have exceeded a secondary rate limit."}"#.to_vec(),
+        );
+        let mut headers = http::HeaderMap::new();
+        headers.insert(header::RETRY_AFTER, HeaderValue::from_static("5"));
+        mock.add(
+            "/retry-after",
+            StatusCode::FORBIDDEN,
+            headers,
+            br#"{"message": "You have exceeded a secondary rate limit."}"#.to_vec(),
+        );
+        mock.add(
+            "/forbidden",
+            StatusCode::FORBIDDEN,
+            http::HeaderMap::new(),
+            br#"{"message": "Resource not accessible by integration"}"#.to_vec(),
+        );
+
+        assert_eq!(
+            retry_after(&mock, "/limited").await,
+            Some(HeaderValue::from_static(SECONDARY_RATE_LIMIT_DELAY))
+        );
+        assert_eq!(
+            retry_after(&mock, "/retry-after").await,
+            Some(HeaderValue::from_static("5"))
+        );
+        assert_eq!(retry_after(&mock, "/forbidden").await, None);
+    }
+}
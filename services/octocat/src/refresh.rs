@@ -0,0 +1,212 @@
+//! Automatic refresh of installation access tokens.
+//!
+//! Installation tokens expire after an hour. [`TokenRefreshLayer`] sits below the
+//! authentication layer of a [`GithubClient`](crate::GithubClient), and replaces the
+//! token before sending a request when it is about to expire, or after Github
+//! rejects it with `401 Unauthorized`, in which case the request is retried once.
+//! Concurrent requests share a single refresh.
+
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use api_client::{Authentication, BoxFuture};
+use arc_swap::ArcSwap;
+use http::header;
+use hyperdriver::service::ServiceExt as _;
+use hyperdriver::Body;
+use tower::{Layer, Service};
+
+use crate::models::InstallationAccess;
+use crate::{Error, GithubApp, TokenScope};
+
+/// The current access token for an installation, shared by a client and its middleware.
+#[derive(Debug)]
+pub(crate) struct InstallationToken {
+    app: GithubApp,
+    id: u64,
+
+    /// The scope of every token fetched, so refreshed tokens have the same access.
+    scope: TokenScope,
+    access: ArcSwap<InstallationAccess>,
+    refreshing: tokio::sync::Mutex<()>,
+}
+
+impl InstallationToken {
+    pub(crate) fn new(
+        app: GithubApp,
+        id: u64,
+        scope: TokenScope,
+        access: InstallationAccess,
+    ) -> Self {
+        Self {
+            app,
+            id,
+            scope,
+            access: ArcSwap::new(Arc::new(access)),
+            refreshing: Default::default(),
+        }
+    }
+
+    /// The current access token.
+    pub(crate) fn current(&self) -> Arc<InstallationAccess> {
+        self.access.load_full()
+    }
+
+    /// Check if a token expires within the clock drift allowance.
+    fn expires_soon(&self, access: &InstallationAccess) -> bool {
+        access.expires_at - self.app.settings().clock_drift() < chrono::Utc::now()
+    }
+
+    /// Fetch a new access token, unconditionally.
+    pub(crate) async fn refresh(&self) -> Result<Arc<InstallationAccess>, Error> {
+        let _guard = self.refreshing.lock().await;
+        self.fetch().await
+    }
+
+    /// Replace a stale access token, unless another request already has.
+    async fn replace(&self, stale: &InstallationAccess) -> Result<Arc<InstallationAccess>, Error> {
+        let _guard = self.refreshing.lock().await;
+        let current = self.current();
+        if current.token.revealed() != stale.token.revealed() {
+            return Ok(current);
+        }
+        self.fetch().await
+    }
+
+    async fn fetch(&self) -> Result<Arc<InstallationAccess>, Error> {
+        let access = Arc::new(self.app.installation_token(self.id, &self.scope).await?);
+        self.access.store(access.clone());
+        Ok(access)
+    }
+}
+
+/// Authenticates requests with the current token of an installation.
+#[derive(Debug, Clone)]
+pub(crate) struct InstallationAuth(Arc<InstallationToken>);
+
+impl InstallationAuth {
+    pub(crate) fn new(token: Arc<InstallationToken>) -> Self {
+        Self(token)
+    }
+}
+
+impl Authentication for InstallationAuth {
+    fn authenticate<B>(&self, req: http::Request<B>) -> http::Request<B> {
+        self.0.current().authenticate(req)
+    }
+}
+
+/// A layer which refreshes installation tokens which are expired or rejected.
+#[derive(Debug, Clone)]
+pub(crate) struct TokenRefreshLayer {
+    token: Arc<InstallationToken>,
+}
+
+impl TokenRefreshLayer {
+    pub(crate) fn new(token: Arc<InstallationToken>) -> Self {
+        Self { token }
+    }
+}
+
+impl<S> Layer<S> for TokenRefreshLayer {
+    type Service = TokenRefresh<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TokenRefresh {
+            inner,
+            token: self.token.clone(),
+        }
+    }
+}
+
+/// A service which refreshes installation tokens which are expired or rejected.
+#[derive(Debug, Clone)]
+pub(crate) struct TokenRefresh<S> {
+    inner: S,
+    token: Arc<InstallationToken>,
+}
+
+impl<S> Service<http::Request<Body>> for TokenRefresh<S>
+where
+    S: Service<
+            http::Request<Body>,
+            Response = http::Response<Body>,
+            Error = hyperdriver::client::Error,
+        > + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = hyperdriver::client::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<Body>) -> Self::Future {
+        // Use the service which was driven to readiness, and leave a fresh clone behind.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let token = self.token.clone();
+
+        Box::pin(async move {
+            let mut access = token.current();
+            if token.expires_soon(&access) {
+                match token.replace(&access).await {
+                    Ok(refreshed) => {
+                        tracing::debug!(expires = %refreshed.expires_at, "Refreshed installation token");
+                        access = refreshed;
+                    }
+                    Err(error) => tracing::warn!("Failed to refresh installation token: {error}"),
+                }
+            }
+
+            // The token may have been refreshed since the request was authenticated.
+            authorize(&mut req, &access);
+
+            let retry = try_clone_request(&req);
+            let response = inner.call(req).await?;
+            if response.status() != http::StatusCode::UNAUTHORIZED {
+                return Ok(response);
+            }
+
+            let Some(mut retry) = retry else {
+                return Ok(response);
+            };
+
+            match token.replace(&access).await {
+                Ok(refreshed) => {
+                    tracing::debug!("Retrying request with a refreshed installation token");
+                    authorize(&mut retry, &refreshed);
+                    inner.oneshot(retry).await
+                }
+                Err(error) => {
+                    tracing::warn!("Failed to refresh rejected installation token: {error}");
+                    Ok(response)
+                }
+            }
+        })
+    }
+}
+
+/// Replace the authorization header on a request.
+fn authorize(req: &mut http::Request<Body>, access: &InstallationAccess) {
+    let mut value = access
+        .token
+        .bearer()
+        .expect("bearer token is a valid HTTP header value");
+    value.set_sensitive(true);
+    req.headers_mut().insert(header::AUTHORIZATION, value);
+}
+
+fn try_clone_request(req: &http::Request<Body>) -> Option<http::Request<Body>> {
+    let body = req.body().try_clone()?;
+    let mut next = http::Request::new(body);
+    *next.method_mut() = req.method().clone();
+    *next.uri_mut() = req.uri().clone();
+    *next.version_mut() = req.version();
+    *next.headers_mut() = req.headers().clone();
+    Some(next)
+}
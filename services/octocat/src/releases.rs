@@ -0,0 +1,84 @@
+//! Releases API, for publishing releases and uploading their assets.
+
+use api_client::response::ResponseBodyExt as _;
+use api_client::uri::UriExtension as _;
+use futures::stream::BoxStream;
+use futures::TryStreamExt as _;
+use http::header;
+use hyperdriver::Body;
+use tokio::io::AsyncRead;
+
+use crate::models::releases::{NewRelease, Release, ReleaseAsset, ReleaseList};
+use crate::{Error, GithubClient};
+
+impl GithubClient {
+    /// List the releases on a repository, newest first.
+    pub fn list_releases(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> BoxStream<'static, Result<Release, Error>> {
+        self.paginate::<ReleaseList>(&format!("/repos/{owner}/{repo}/releases?per_page=100"))
+    }
+
+    /// Create a release.
+    #[tracing::instrument(skip(self, release))]
+    pub async fn create_release(
+        &self,
+        owner: &str,
+        repo: &str,
+        release: &NewRelease<'_>,
+    ) -> Result<Release, Error> {
+        let release: Release = self
+            .send_json(
+                self.post(&format!("/repos/{owner}/{repo}/releases")),
+                release,
+            )
+            .await?;
+        tracing::debug!("Created release {} on {owner}/{repo}", release.tag_name);
+        Ok(release)
+    }
+
+    /// Upload an asset to a release, streaming its content from `reader`.
+    ///
+    /// Github requires the size of the asset up front, and `reader` must produce
+    /// exactly `size` bytes.
+    #[tracing::instrument(skip(self, release, reader), fields(release = release.id))]
+    pub async fn upload_release_asset<R>(
+        &self,
+        release: &Release,
+        name: &str,
+        content_type: &str,
+        size: u64,
+        reader: R,
+    ) -> Result<ReleaseAsset, Error>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let uri = release
+            .upload_endpoint()
+            .parse::<http::Uri>()
+            .map_err(|error| Error::Client(http::Error::from(error).into()))?
+            .append_query(&[("name", name)])
+            .map_err(|error| Error::Client(error.into()))?;
+
+        let stream = tokio_util::io::ReaderStream::new(reader).map_ok(http_body::Frame::data);
+        let request = http::Request::post(uri)
+            .version(http::Version::HTTP_2)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_LENGTH, size)
+            .body(Body::new(http_body_util::StreamBody::new(stream)))
+            .map_err(|error| Error::Client(error.into()))?;
+
+        let response = self.client.execute(request).await?;
+        let response = self.error_for_status(response).await?;
+        let asset: ReleaseAsset = response.json().await.map_err(Error::Body)?;
+        tracing::debug!(
+            "Uploaded {} ({} bytes) to release {}",
+            asset.name,
+            asset.size,
+            release.id
+        );
+        Ok(asset)
+    }
+}
@@ -0,0 +1,227 @@
+//! Versions of the Github REST API, and the deprecation warnings Github sends for them.
+//!
+//! Every request carries an `X-GitHub-Api-Version` header. The version defaults to
+//! [`GithubAppSettings::api_version`](crate::GithubAppSettings::api_version), can be
+//! changed for a whole client with [`GithubClient::with_api_version`](crate::GithubClient::with_api_version),
+//! and for a single request with [`ApiVersionExt::api_version`].
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+
+use api_client::BoxFuture;
+use http::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use tower::{Layer, Service};
+
+use crate::GITHUB_API_VERSION_HEADER;
+
+/// The header Github sends when a requested API version is deprecated.
+const DEPRECATION_HEADER: &str = "deprecation";
+
+/// The header Github sends with the date a deprecated API version will stop working.
+const SUNSET_HEADER: &str = "sunset";
+
+/// A version of the Github REST API.
+///
+/// Only versions this crate has been tested against are listed, so moving to a
+/// new version is a deliberate change here, rather than a string in a config file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub enum ApiVersion {
+    /// The `2022-11-28` version, the first dated version of the API.
+    #[default]
+    V2022_11_28,
+}
+
+impl ApiVersion {
+    /// All versions supported by this crate, oldest first.
+    pub const SUPPORTED: &'static [ApiVersion] = &[ApiVersion::V2022_11_28];
+
+    /// The value of the `X-GitHub-Api-Version` header for this version.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiVersion::V2022_11_28 => "2022-11-28",
+        }
+    }
+
+    pub(crate) fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_static(self.as_str())
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An API version which this crate does not support.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unsupported Github API version {0:?}")]
+pub struct UnsupportedApiVersion(String);
+
+impl FromStr for ApiVersion {
+    type Err = UnsupportedApiVersion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::SUPPORTED
+            .iter()
+            .find(|version| version.as_str() == s)
+            .copied()
+            .ok_or_else(|| UnsupportedApiVersion(s.to_owned()))
+    }
+}
+
+impl TryFrom<String> for ApiVersion {
+    type Error = UnsupportedApiVersion;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Select the Github API version for a single request.
+pub trait ApiVersionExt {
+    /// Send this request with a specific API version, replacing the client's default.
+    fn api_version(self, version: ApiVersion) -> Self;
+}
+
+impl ApiVersionExt for api_client::RequestBuilder {
+    fn api_version(mut self, version: ApiVersion) -> Self {
+        if let Some(headers) = self.headers_mut() {
+            headers.insert(
+                HeaderName::from_static(GITHUB_API_VERSION_HEADER),
+                version.header_value(),
+            );
+        }
+        self
+    }
+}
+
+/// A warning from Github that a requested API version is deprecated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The API version which was requested, if the request said.
+    pub version: Option<String>,
+
+    /// When the version was deprecated, from the `Deprecation` header.
+    pub deprecated: String,
+
+    /// When the version will stop working, from the `Sunset` header.
+    pub sunset: Option<String>,
+}
+
+impl Deprecation {
+    fn from_headers(version: Option<String>, headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+
+        Some(Self {
+            version,
+            deprecated: header(DEPRECATION_HEADER)?,
+            sunset: header(SUNSET_HEADER),
+        })
+    }
+}
+
+/// The most recent deprecation warning seen by a client.
+pub(crate) type DeprecationState = Arc<RwLock<Option<Deprecation>>>;
+
+/// A layer which records deprecation warnings sent by Github.
+#[derive(Debug, Clone)]
+pub(crate) struct DeprecationLayer {
+    state: DeprecationState,
+}
+
+impl DeprecationLayer {
+    pub(crate) fn new(state: DeprecationState) -> Self {
+        Self { state }
+    }
+}
+
+impl<S> Layer<S> for DeprecationLayer {
+    type Service = DeprecationWarnings<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeprecationWarnings {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// A service which records deprecation warnings sent by Github.
+#[derive(Debug, Clone)]
+pub(crate) struct DeprecationWarnings<S> {
+    inner: S,
+    state: DeprecationState,
+}
+
+impl<S, B, R> Service<http::Request<B>> for DeprecationWarnings<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<R>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let version = req
+            .headers()
+            .get(GITHUB_API_VERSION_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let state = self.state.clone();
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let response = future.await?;
+            if let Some(deprecation) = Deprecation::from_headers(version, response.headers()) {
+                let mut current = state.write().unwrap();
+                if current.as_ref() != Some(&deprecation) {
+                    tracing::warn!(
+                        version = ?deprecation.version,
+                        sunset = ?deprecation.sunset,
+                        "Github API version is deprecated since {}",
+                        deprecation.deprecated
+                    );
+                    *current = Some(deprecation);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_api_version() {
+        assert_eq!(
+            "2022-11-28".parse::<ApiVersion>(),
+            Ok(ApiVersion::V2022_11_28)
+        );
+        assert!("2021-01-01".parse::<ApiVersion>().is_err());
+
+        let version: ApiVersion = serde_json::from_str(r#""2022-11-28""#).unwrap();
+        assert_eq!(version, ApiVersion::default());
+        assert!(serde_json::from_str::<ApiVersion>(r#""latest""#).is_err());
+
+        for version in ApiVersion::SUPPORTED {
+            assert_eq!(version.as_str().parse::<ApiVersion>(), Ok(*version));
+        }
+    }
+}
@@ -81,17 +81,29 @@ impl TailscaleClient {
     }
 }
 
+/// A device on the tailscale network
 #[derive(Debug, Clone, Deserialize)]
 pub struct Device {
-    // name: String,
+    /// Unique ID of the device
+    #[serde(default)]
+    pub id: String,
+
+    /// Fully qualified name of the device on the tailnet
+    #[serde(default)]
+    pub name: String,
+
+    /// Tailscale addresses assigned to the device
     pub addresses: Vec<IpAddr>,
 }
 
+/// Errors from the tailscale API
 #[derive(Debug, Error)]
 pub enum TailscaleAPIError {
+    /// The request could not be sent
     #[error("Request error: {0}")]
     RequestError(#[source] hyperdriver::client::Error),
 
+    /// The response body could not be read or decoded
     #[error("Response error: {0}")]
     BodyError(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
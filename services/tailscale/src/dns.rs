@@ -0,0 +1,134 @@
+//! Split-horizon DNS records for hosts on a tailnet.
+//!
+//! Each host gets records under its public name, pointing at its public
+//! addresses, and under an internal subdomain, pointing at its tailnet
+//! addresses, e.g. `web.example.com` and `web.internal.example.com`. The
+//! planned records are reconciled with a Linode domain by
+//! [`DnsRecords`](emporium_reconcile::dns::DnsRecords).
+
+use std::net::IpAddr;
+
+use emporium_reconcile::dns::DesiredRecord;
+use linode::{RecordType, SubDomain};
+
+use crate::TailscaleAddress;
+
+/// The addresses of a single host
+#[derive(Debug, Clone)]
+pub struct HostAddresses {
+    name: String,
+    tailnet: TailscaleAddress,
+    public: Vec<IpAddr>,
+}
+
+impl HostAddresses {
+    /// A host with a name within the domain, e.g. `web`, and its tailnet addresses
+    pub fn new(name: impl Into<String>, tailnet: TailscaleAddress) -> Self {
+        Self {
+            name: name.into(),
+            tailnet,
+            public: Vec::new(),
+        }
+    }
+
+    /// Add a public address, e.g. from [`get_host_ip_address`](crate::get_host_ip_address)
+    pub fn with_public(mut self, address: IpAddr) -> Self {
+        self.public.push(address);
+        self
+    }
+}
+
+/// Plans public and tailnet-internal records for a set of hosts
+#[derive(Debug, Clone)]
+pub struct SplitHorizon {
+    internal: String,
+}
+
+impl SplitHorizon {
+    /// Put tailnet records under an internal subdomain, e.g. `internal`
+    pub fn new(internal: impl Into<String>) -> Self {
+        Self {
+            internal: internal.into(),
+        }
+    }
+
+    /// The name of a host's tailnet records
+    pub fn internal_name(&self, host: &str) -> SubDomain {
+        match SubDomain::from(host) {
+            SubDomain::Root => SubDomain::from(self.internal.as_str()),
+            host => SubDomain::from(format!("{host}.{}", self.internal)),
+        }
+    }
+
+    /// The records every host should have
+    ///
+    /// Hosts without public addresses only get tailnet records.
+    pub fn plan<'h, I>(&self, hosts: I) -> Vec<DesiredRecord>
+    where
+        I: IntoIterator<Item = &'h HostAddresses>,
+    {
+        let mut records = Vec::new();
+        for host in hosts {
+            for address in &host.public {
+                records.push(record(host.name.as_str(), *address));
+            }
+
+            let internal = self.internal_name(&host.name);
+            records.push(record(internal.clone(), IpAddr::V4(*host.tailnet.v4())));
+            records.push(record(internal, IpAddr::V6(*host.tailnet.v6())));
+        }
+        records
+    }
+}
+
+fn record(name: impl Into<SubDomain>, address: IpAddr) -> DesiredRecord {
+    let kind = match address {
+        IpAddr::V4(_) => RecordType::A,
+        IpAddr::V6(_) => RecordType::AAAA,
+    };
+    DesiredRecord::new(kind, name, address.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    fn tailnet(host: u8) -> TailscaleAddress {
+        TailscaleAddress::new(
+            Ipv4Addr::new(100, 64, 0, host),
+            Ipv6Addr::new(0xfd7a, 0x115c, 0xa1e0, 0, 0, 0, 0, host.into()),
+        )
+    }
+
+    #[test]
+    fn plan_split_horizon() {
+        let hosts = [
+            HostAddresses::new("web", tailnet(1))
+                .with_public(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+                .with_public("2001:db8::1".parse().unwrap()),
+            HostAddresses::new("db", tailnet(2)),
+            HostAddresses::new("@", tailnet(3)),
+        ];
+
+        let records: Vec<_> = SplitHorizon::new("internal")
+            .plan(&hosts)
+            .into_iter()
+            .map(|record| (record.kind, record.name.to_string(), record.target))
+            .collect();
+
+        let expected = [
+            (RecordType::A, "web", "192.0.2.1"),
+            (RecordType::AAAA, "web", "2001:db8::1"),
+            (RecordType::A, "web.internal", "100.64.0.1"),
+            (RecordType::AAAA, "web.internal", "fd7a:115c:a1e0::1"),
+            (RecordType::A, "db.internal", "100.64.0.2"),
+            (RecordType::AAAA, "db.internal", "fd7a:115c:a1e0::2"),
+            (RecordType::A, "internal", "100.64.0.3"),
+            (RecordType::AAAA, "internal", "fd7a:115c:a1e0::3"),
+        ]
+        .map(|(kind, name, target)| (kind, name.to_owned(), target.to_owned()));
+        assert_eq!(records, expected);
+    }
+}
@@ -9,17 +9,24 @@ use eyre::Context;
 use eyre::{eyre, Report, Result};
 
 mod client;
+#[cfg(feature = "linode")]
+pub mod dns;
 
-pub use self::client::{TailscaleClient, TailscaleConfiguration};
+pub use self::client::{Device, TailscaleAPIError, TailscaleClient, TailscaleConfiguration};
 
 /// A tailscale host address with both V4 and V6 addresses
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TailscaleAddress {
     v4: Ipv4Addr,
     v6: Ipv6Addr,
 }
 
 impl TailscaleAddress {
+    /// Create an address from the V4 and V6 addresses of a host
+    pub fn new(v4: Ipv4Addr, v6: Ipv6Addr) -> Self {
+        Self { v4, v6 }
+    }
+
     /// Get the V4 address
     pub fn v4(&self) -> &Ipv4Addr {
         &self.v4
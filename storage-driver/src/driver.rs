@@ -8,9 +8,14 @@ use tokio::io::{self, AsyncWriteExt};
 use tracing::Instrument;
 
 use crate::error::StorageError;
+use crate::progress::{Progress, ProgressReader, ProgressWriter};
+use crate::range::{ByteRange, RangeWriter};
 use camino::Utf8Path;
 use chrono::{DateTime, Utc};
 
+/// Size of the buffer between the download and upload halves of a streamed copy.
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
 /// A reader stream for file contents.
 pub type Reader<'r> = dyn io::AsyncBufRead + Unpin + Send + Sync + 'r;
 
@@ -30,6 +35,29 @@ pub struct Metadata {
     pub created: DateTime<Utc>,
 }
 
+/// Optional operations which a storage driver supports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// The driver implements [`Driver::upload_if_not_exists`] atomically.
+    pub conditional_writes: bool,
+}
+
+impl Capabilities {
+    /// Set whether the driver supports conditional writes.
+    pub fn with_conditional_writes(mut self, conditional_writes: bool) -> Self {
+        self.conditional_writes = conditional_writes;
+        self
+    }
+
+    /// The capabilities supported by both `self` and `other`.
+    pub fn intersection(self, other: Capabilities) -> Capabilities {
+        Capabilities {
+            conditional_writes: self.conditional_writes && other.conditional_writes,
+        }
+    }
+}
+
 /// A storage driver, which provides the ability to interact with a storage backend.
 #[async_trait::async_trait]
 pub trait Driver: fmt::Debug {
@@ -39,9 +67,25 @@ pub trait Driver: fmt::Debug {
     /// The Uri of the driver.
     fn scheme(&self) -> &str;
 
+    /// The optional operations which this driver supports.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
     /// Delete a file from the storage, by path.
     async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError>;
 
+    /// Delete several files from the storage, by path.
+    ///
+    /// Drivers which can delete in bulk should override this, the default
+    /// deletes each file in turn.
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        for remote in remotes {
+            self.delete(bucket, remote).await?;
+        }
+        Ok(())
+    }
+
     /// Get the metadata for a file, by path.
     async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError>;
 
@@ -53,6 +97,27 @@ pub trait Driver: fmt::Debug {
         reader: &mut Reader<'_>,
     ) -> Result<(), StorageError>;
 
+    /// Upload a file to the storage, only if no file exists at that path.
+    ///
+    /// The check and the write happen atomically, so when two writers race, only
+    /// one succeeds. The other gets an error for which
+    /// [`StorageError::is_already_exists`] is true.
+    ///
+    /// Drivers which can't make this guarantee return an error for which
+    /// [`StorageError::is_unsupported`] is true, see [`Capabilities::conditional_writes`].
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        let _ = (bucket, remote, reader);
+        Err(StorageError::unsupported(
+            self.name(),
+            "upload_if_not_exists",
+        ))
+    }
+
     /// Download a file from storage, into a writer stream.
     async fn download(
         &self,
@@ -61,6 +126,105 @@ pub trait Driver: fmt::Debug {
         writer: &mut Writer<'_>,
     ) -> Result<(), StorageError>;
 
+    /// Download part of a file from storage, into a writer stream.
+    ///
+    /// Drivers which can fetch part of a file should override this, the default
+    /// downloads the whole file and discards the bytes outside the range.
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let range = if range.is_bounded() {
+            range.resolve(u64::MAX)
+        } else {
+            range.resolve(self.metadata(bucket, remote).await?.size)
+        };
+
+        let mut writer = RangeWriter::new(writer, range);
+        self.download(bucket, remote, &mut writer).await
+    }
+
+    /// Upload a file to the storage, reporting progress as it is uploaded.
+    ///
+    /// The default reports bytes as they are read from `reader`. Drivers which
+    /// buffer uploads should override this to report bytes once they are stored.
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        let mut reader = ProgressReader::new(reader, progress);
+        self.upload(bucket, remote, &mut reader).await
+    }
+
+    /// Download a file from storage, reporting progress as it is written to `writer`.
+    ///
+    /// If `progress` doesn't know the size of the file, it is fetched first.
+    async fn download_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        let sized;
+        let progress = match progress.total() {
+            Some(_) => progress,
+            None => {
+                sized = progress
+                    .clone()
+                    .with_total(self.metadata(bucket, remote).await?.size);
+                &sized
+            }
+        };
+
+        let mut writer = ProgressWriter::new(writer, progress);
+        self.download(bucket, remote, &mut writer).await
+    }
+
+    /// Copy a file to another path in the same bucket.
+    ///
+    /// Drivers which can copy without transferring the contents through the
+    /// client should override this, the default streams a download of `from`
+    /// into an upload to `to`.
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        let (mut writer, reader) = io::duplex(COPY_BUFFER_SIZE);
+        let mut reader = io::BufReader::new(reader);
+
+        // Dropping the writer on error would look like the end of the file to
+        // the upload, but `try_join` returns before the upload is polled again.
+        let download = async move {
+            self.download(bucket, from, &mut writer).await?;
+            writer
+                .shutdown()
+                .await
+                .wrap_err("shutdown copy stream")
+                .map_err(StorageError::with(self.name()))
+        };
+        let upload = self.upload(bucket, to, &mut reader);
+
+        futures::future::try_join(download, upload).await?;
+        Ok(())
+    }
+
+    /// Move a file to another path in the same bucket.
+    ///
+    /// The default copies the file, then deletes the original.
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.copy(bucket, from, to).await?;
+        self.delete(bucket, from).await
+    }
+
     /// Donwload a file from storage, into a local file.
     async fn download_file(
         &self,
@@ -201,11 +365,30 @@ where
         forward_uri!(self.driver.upload(url, reader)).await
     }
 
+    /// Upload a file to the storage, only if no file exists at that path.
+    pub async fn upload_if_not_exists(
+        &self,
+        url: &Uri,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        forward_uri!(self.driver.upload_if_not_exists(url, reader)).await
+    }
+
     /// Download a file from storage, into a writer stream.
     pub async fn download(&self, url: &Uri, writer: &mut Writer<'_>) -> Result<(), StorageError> {
         forward_uri!(self.driver.download(url, writer)).await
     }
 
+    /// Download part of a file from storage, into a writer stream.
+    pub async fn download_range(
+        &self,
+        url: &Uri,
+        range: ByteRange,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        forward_uri!(self.driver.download_range(url, range, writer)).await
+    }
+
     /// Donwload a file from storage, into a local file.
     pub async fn download_file(&self, url: &Uri, local: &Utf8Path) -> Result<(), StorageError> {
         forward_uri!(self.driver.download_file(url, local)).await
@@ -378,10 +561,18 @@ where
         self.deref().scheme()
     }
 
+    fn capabilities(&self) -> Capabilities {
+        self.deref().capabilities()
+    }
+
     async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError> {
         self.deref().delete(bucket, remote).await
     }
 
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        self.deref().delete_many(bucket, remotes).await
+    }
+
     async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
         self.deref().metadata(bucket, remote).await
     }
@@ -395,6 +586,17 @@ where
         self.deref().upload(bucket, remote, reader).await
     }
 
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        self.deref()
+            .upload_if_not_exists(bucket, remote, reader)
+            .await
+    }
+
     async fn download(
         &self,
         bucket: &str,
@@ -404,6 +606,55 @@ where
         self.deref().download(bucket, remote, writer).await
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        self.deref()
+            .download_range(bucket, remote, range, writer)
+            .await
+    }
+
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        self.deref()
+            .upload_with_progress(bucket, remote, reader, progress)
+            .await
+    }
+
+    async fn download_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        self.deref()
+            .download_with_progress(bucket, remote, writer, progress)
+            .await
+    }
+
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.deref().copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.deref().rename(bucket, from, to).await
+    }
+
     async fn list(
         &self,
         bucket: &str,
@@ -427,10 +678,18 @@ where
         (*self).scheme()
     }
 
+    fn capabilities(&self) -> Capabilities {
+        (*self).capabilities()
+    }
+
     async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError> {
         self.delete(bucket, remote).await
     }
 
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        (*self).delete_many(bucket, remotes).await
+    }
+
     async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
         self.metadata(bucket, remote).await
     }
@@ -444,6 +703,15 @@ where
         self.upload(bucket, remote, reader).await
     }
 
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        (*self).upload_if_not_exists(bucket, remote, reader).await
+    }
+
     async fn download(
         &self,
         bucket: &str,
@@ -453,6 +721,53 @@ where
         self.download(bucket, remote, writer).await
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        (*self).download_range(bucket, remote, range, writer).await
+    }
+
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        (*self)
+            .upload_with_progress(bucket, remote, reader, progress)
+            .await
+    }
+
+    async fn download_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        (*self)
+            .download_with_progress(bucket, remote, writer, progress)
+            .await
+    }
+
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        (*self).copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        (*self).rename(bucket, from, to).await
+    }
+
     async fn list(
         &self,
         bucket: &str,
@@ -1,6 +1,24 @@
+use std::io;
+
+use camino::Utf8PathBuf;
 use eyre::Report;
 use thiserror::Error;
 
+/// The file does not exist in the storage backend.
+#[derive(Debug, Error)]
+#[error("{0} not found")]
+struct NotFound(Utf8PathBuf);
+
+/// The file already exists in the storage backend.
+#[derive(Debug, Error)]
+#[error("{0} already exists")]
+struct AlreadyExists(Utf8PathBuf);
+
+/// The storage backend does not support an operation.
+#[derive(Debug, Error)]
+#[error("{0} is not supported")]
+struct Unsupported(&'static str);
+
 /// Generic error returned from a downstream
 /// implementation.
 #[derive(Debug, Error)]
@@ -22,6 +40,58 @@ impl StorageError {
         }
     }
 
+    /// Create a new storage error for a file which does not exist.
+    pub fn not_found<P: Into<Utf8PathBuf>>(engine: &'static str, path: P) -> Self {
+        Self::new(engine, NotFound(path.into()))
+    }
+
+    /// Check whether this error was caused by a file which does not exist.
+    ///
+    /// This recognizes errors created with [`StorageError::not_found`], and
+    /// IO errors of kind [`io::ErrorKind::NotFound`].
+    pub fn is_not_found(&self) -> bool {
+        self.caused_by::<NotFound>(io::ErrorKind::NotFound)
+    }
+
+    /// Create a new storage error for a file which already exists.
+    pub fn already_exists<P: Into<Utf8PathBuf>>(engine: &'static str, path: P) -> Self {
+        Self::new(engine, AlreadyExists(path.into()))
+    }
+
+    /// Check whether this error was caused by a file which already exists.
+    ///
+    /// This recognizes errors created with [`StorageError::already_exists`], and
+    /// IO errors of kind [`io::ErrorKind::AlreadyExists`].
+    pub fn is_already_exists(&self) -> bool {
+        self.caused_by::<AlreadyExists>(io::ErrorKind::AlreadyExists)
+    }
+
+    /// Create a new storage error for an operation the storage engine does not support.
+    pub fn unsupported(engine: &'static str, operation: &'static str) -> Self {
+        Self::new(engine, Unsupported(operation))
+    }
+
+    /// Check whether this error was caused by an unsupported operation.
+    ///
+    /// This recognizes errors created with [`StorageError::unsupported`], and
+    /// IO errors of kind [`io::ErrorKind::Unsupported`].
+    pub fn is_unsupported(&self) -> bool {
+        self.caused_by::<Unsupported>(io::ErrorKind::Unsupported)
+    }
+
+    /// Check whether any cause of this error is an `M`, or an IO error of `kind`.
+    fn caused_by<M>(&self, kind: io::ErrorKind) -> bool
+    where
+        M: std::error::Error + 'static,
+    {
+        self.error.chain().any(|cause| {
+            cause.is::<M>()
+                || cause
+                    .downcast_ref::<io::Error>()
+                    .is_some_and(|err| err.kind() == kind)
+        })
+    }
+
     /// Return a boxed closure that creates a new storage error from a downstream
     /// error, using the provided storage engine name.
     pub fn with<E>(engine: &'static str) -> Box<dyn FnOnce(E) -> StorageError>
@@ -34,3 +104,33 @@ impl StorageError {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use eyre::WrapErr as _;
+
+    use super::*;
+
+    #[test]
+    fn not_found() {
+        assert!(StorageError::not_found("test", "some/file").is_not_found());
+
+        let err: Result<(), _> = Err(io::Error::from(io::ErrorKind::NotFound));
+        let err = err.wrap_err("metadata").unwrap_err();
+        assert!(StorageError::new("test", err).is_not_found());
+
+        let err = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(!StorageError::new("test", err).is_not_found());
+    }
+
+    #[test]
+    fn already_exists_and_unsupported() {
+        let err = StorageError::already_exists("test", "some/file");
+        assert!(err.is_already_exists());
+        assert!(!err.is_not_found());
+
+        let err = StorageError::unsupported("test", "upload_if_not_exists");
+        assert!(err.is_unsupported());
+        assert!(!err.is_already_exists());
+    }
+}
@@ -5,10 +5,15 @@
 
 mod driver;
 mod error;
+mod progress;
+mod range;
 
+pub use driver::Capabilities;
 pub use driver::Driver;
 pub use driver::DriverUri;
 pub use driver::Metadata;
 pub use driver::Reader;
 pub use driver::Writer;
 pub use error::StorageError;
+pub use progress::{Progress, TransferProgress};
+pub use range::ByteRange;
@@ -0,0 +1,223 @@
+use std::{
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tokio::io;
+
+use crate::{Reader, Writer};
+
+/// Progress of an upload or download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferProgress {
+    /// Number of bytes transferred so far.
+    pub transferred: u64,
+
+    /// Size of the file, if it is known.
+    pub total: Option<u64>,
+}
+
+type ProgressCallback = Arc<dyn Fn(TransferProgress) + Send + Sync>;
+
+/// A callback which receives the progress of an upload or download.
+///
+/// The callback may be called often, e.g. for every chunk read from an upload,
+/// so it should be cheap.
+#[derive(Clone)]
+pub struct Progress {
+    callback: ProgressCallback,
+    total: Option<u64>,
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("total", &self.total)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Progress {
+    /// Call `callback` as bytes are transferred.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(TransferProgress) + Send + Sync + 'static,
+    {
+        Self {
+            callback: Arc::new(callback),
+            total: None,
+        }
+    }
+
+    /// Set the size of the file, e.g. for an upload from a reader of known length.
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// The size of the file, if it is known.
+    pub fn total(&self) -> Option<u64> {
+        self.total
+    }
+
+    /// Report that `transferred` bytes have been transferred so far.
+    pub fn report(&self, transferred: u64) {
+        (self.callback)(TransferProgress {
+            transferred,
+            total: self.total,
+        });
+    }
+}
+
+/// A reader which reports progress as bytes are read from it.
+pub(crate) struct ProgressReader<'r, 'a> {
+    inner: &'r mut Reader<'a>,
+    progress: &'r Progress,
+    transferred: u64,
+}
+
+impl<'r, 'a> ProgressReader<'r, 'a> {
+    pub(crate) fn new(inner: &'r mut Reader<'a>, progress: &'r Progress) -> Self {
+        Self {
+            inner,
+            progress,
+            transferred: 0,
+        }
+    }
+
+    fn advance(&mut self, amount: usize) {
+        if amount > 0 {
+            self.transferred += amount as u64;
+            self.progress.report(self.transferred);
+        }
+    }
+}
+
+impl io::AsyncRead for ProgressReader<'_, '_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        std::task::ready!(Pin::new(&mut *this.inner).poll_read(cx, buf))?;
+        this.advance(buf.filled().len() - filled);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl io::AsyncBufRead for ProgressReader<'_, '_> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8], io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        Pin::new(&mut *this.inner).consume(amt);
+        this.advance(amt);
+    }
+}
+
+/// A writer which reports progress as bytes are written to it.
+pub(crate) struct ProgressWriter<'r, 'w> {
+    inner: &'r mut Writer<'w>,
+    progress: &'r Progress,
+    transferred: u64,
+}
+
+impl<'r, 'w> ProgressWriter<'r, 'w> {
+    pub(crate) fn new(inner: &'r mut Writer<'w>, progress: &'r Progress) -> Self {
+        Self {
+            inner,
+            progress,
+            transferred: 0,
+        }
+    }
+}
+
+impl io::AsyncWrite for ProgressWriter<'_, '_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let written = std::task::ready!(Pin::new(&mut *this.inner).poll_write(cx, buf))?;
+        if written > 0 {
+            this.transferred += written as u64;
+            this.progress.report(this.transferred);
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    use super::*;
+
+    fn recorder() -> (Progress, Arc<Mutex<Vec<TransferProgress>>>) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let progress = Progress::new({
+            let reports = reports.clone();
+            move |progress| reports.lock().unwrap().push(progress)
+        });
+        (progress, reports)
+    }
+
+    #[tokio::test]
+    async fn read_progress() {
+        let (progress, reports) = recorder();
+        let progress = progress.with_total(10);
+
+        let mut data = tokio::io::BufReader::with_capacity(4, &b"abcdefghij"[..]);
+        let mut output = Vec::new();
+        ProgressReader::new(&mut data, &progress)
+            .read_to_end(&mut output)
+            .await
+            .unwrap();
+        assert_eq!(output, b"abcdefghij");
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(
+            reports.last(),
+            Some(&TransferProgress {
+                transferred: 10,
+                total: Some(10)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn write_progress() {
+        let (progress, reports) = recorder();
+
+        let mut output = Vec::new();
+        {
+            let mut writer = ProgressWriter::new(&mut output, &progress);
+            for chunk in [&b"ab"[..], b"cdef", b""] {
+                writer.write_all(chunk).await.unwrap();
+            }
+        }
+        assert_eq!(output, b"abcdef");
+
+        let reports = reports.lock().unwrap();
+        let transferred: Vec<_> = reports.iter().map(|p| p.transferred).collect();
+        assert_eq!(transferred, vec![2, 6]);
+        assert!(reports.iter().all(|p| p.total.is_none()));
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    fmt,
+    ops::{Range, RangeFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::io;
+
+use crate::Writer;
+
+/// A range of bytes within a file, for partial downloads.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ByteRange {
+    /// The bytes from `start` up to, but not including, `end`.
+    Bounded(Range<u64>),
+
+    /// The bytes from an offset to the end of the file.
+    Offset(u64),
+
+    /// The last `n` bytes of the file.
+    Last(u64),
+}
+
+impl ByteRange {
+    /// Resolve this range against the size of a file.
+    ///
+    /// The range is clamped to the file, so it may be empty.
+    pub fn resolve(&self, size: u64) -> Range<u64> {
+        match self {
+            ByteRange::Bounded(range) => {
+                let end = range.end.min(size);
+                range.start.min(end)..end
+            }
+            ByteRange::Offset(start) => (*start).min(size)..size,
+            ByteRange::Last(n) => size.saturating_sub(*n)..size,
+        }
+    }
+
+    /// Check whether the range can be resolved without knowing the size of the file.
+    pub fn is_bounded(&self) -> bool {
+        matches!(self, ByteRange::Bounded(_))
+    }
+
+    /// Check whether the range contains no bytes, regardless of the size of the file.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ByteRange::Bounded(range) => range.is_empty(),
+            ByteRange::Offset(_) => false,
+            ByteRange::Last(n) => *n == 0,
+        }
+    }
+}
+
+impl From<Range<u64>> for ByteRange {
+    fn from(range: Range<u64>) -> Self {
+        ByteRange::Bounded(range)
+    }
+}
+
+impl From<RangeFrom<u64>> for ByteRange {
+    fn from(range: RangeFrom<u64>) -> Self {
+        ByteRange::Offset(range.start)
+    }
+}
+
+/// Formats the range as the value of an HTTP `Range` header.
+///
+/// Empty ranges can't be represented in a `Range` header, so callers should
+/// check [`ByteRange::is_empty`] first.
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteRange::Bounded(range) => {
+                write!(f, "bytes={}-{}", range.start, range.end.saturating_sub(1))
+            }
+            ByteRange::Offset(start) => write!(f, "bytes={start}-"),
+            ByteRange::Last(n) => write!(f, "bytes=-{n}"),
+        }
+    }
+}
+
+/// A writer which passes on only the bytes in a range, and discards the rest.
+pub(crate) struct RangeWriter<'r, 'w> {
+    inner: &'r mut Writer<'w>,
+    range: Range<u64>,
+    position: u64,
+}
+
+impl<'r, 'w> RangeWriter<'r, 'w> {
+    pub(crate) fn new(inner: &'r mut Writer<'w>, range: Range<u64>) -> Self {
+        Self {
+            inner,
+            range,
+            position: 0,
+        }
+    }
+}
+
+impl io::AsyncWrite for RangeWriter<'_, '_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let end = this.position + buf.len() as u64;
+
+        if end <= this.range.start || this.position >= this.range.end {
+            this.position = end;
+            return Poll::Ready(Ok(buf.len()));
+        }
+
+        let skip = this.range.start.saturating_sub(this.position) as usize;
+        let take = (this.range.end.min(end) - this.position) as usize;
+        let written =
+            std::task::ready!(Pin::new(&mut *this.inner).poll_write(cx, &buf[skip..take]))?;
+
+        this.position += (skip + written) as u64;
+        Poll::Ready(Ok(skip + written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt as _;
+
+    use super::*;
+
+    #[test]
+    fn resolve_and_format() {
+        assert_eq!(ByteRange::from(2..5).resolve(4), 2..4);
+        assert_eq!(ByteRange::from(6..).resolve(4), 4..4);
+        let reversed = Range { start: 5, end: 3 };
+        assert_eq!(ByteRange::from(reversed).resolve(4), 3..3);
+        assert_eq!(ByteRange::Last(3).resolve(10), 7..10);
+        assert_eq!(ByteRange::Last(30).resolve(10), 0..10);
+
+        assert_eq!(ByteRange::from(2..5).to_string(), "bytes=2-4");
+        assert_eq!(ByteRange::from(6..).to_string(), "bytes=6-");
+        assert_eq!(ByteRange::Last(3).to_string(), "bytes=-3");
+    }
+
+    #[tokio::test]
+    async fn write_range() {
+        let mut output = Vec::new();
+        {
+            let mut writer = RangeWriter::new(&mut output, 3..9);
+            for chunk in [&b"ab"[..], b"cdef", b"ghijkl", b"mn"] {
+                writer.write_all(chunk).await.unwrap();
+            }
+            writer.flush().await.unwrap();
+        }
+        assert_eq!(output, b"defghi");
+    }
+}
@@ -0,0 +1,8 @@
+//! Deprecated storage items, re-exported from their old paths until the next
+//! release, when they are removed.
+//!
+//! Driver and configuration changes which would otherwise break callers, such as
+//! new listing types, land alongside a shim here which adapts the old interface
+//! to the new one, marked `#[deprecated]` with a note naming the replacement.
+//!
+//! Nothing is deprecated in this release.
@@ -0,0 +1,166 @@
+//! Deleting every file under a prefix.
+
+use std::fmt;
+use std::sync::Arc;
+
+use camino::Utf8Path;
+use futures::{StreamExt as _, TryStreamExt as _};
+
+use crate::{ArcDriver, StorageError};
+
+/// Default number of delete requests in flight at once.
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Largest number of files handed to [`Driver::delete_many`](crate::Driver::delete_many) at once.
+const MAX_BATCH_SIZE: usize = 100;
+
+/// Progress of a prefix delete, reported after each batch of files is deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeleteProgress {
+    /// Number of files deleted so far.
+    pub deleted: usize,
+
+    /// Number of files found under the prefix.
+    pub total: usize,
+}
+
+type ProgressCallback = Arc<dyn Fn(DeleteProgress) + Send + Sync>;
+
+/// Options for [`Storage::delete_prefix_with`](crate::Storage::delete_prefix_with).
+#[derive(Clone)]
+pub struct DeletePrefix {
+    concurrency: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl fmt::Debug for DeletePrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeletePrefix")
+            .field("concurrency", &self.concurrency)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl Default for DeletePrefix {
+    fn default() -> Self {
+        Self {
+            concurrency: DEFAULT_CONCURRENCY,
+            progress: None,
+        }
+    }
+}
+
+impl DeletePrefix {
+    /// Create the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of delete requests in flight at once (8 by default).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Call `progress` after each batch of files is deleted.
+    pub fn with_progress<F>(mut self, progress: F) -> Self
+    where
+        F: Fn(DeleteProgress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+/// Delete every file under `prefix`, returning the number of files deleted.
+///
+/// The prefix is matched by whole path components, so deleting `a/b` leaves
+/// `a/bc` alone.
+pub(crate) async fn delete_prefix(
+    driver: &ArcDriver,
+    bucket: &str,
+    prefix: &Utf8Path,
+    options: &DeletePrefix,
+) -> Result<usize, StorageError> {
+    let paths: Vec<String> = driver
+        .list(bucket, Some(prefix))
+        .await?
+        .into_iter()
+        .filter(|path| Utf8Path::new(path).starts_with(prefix))
+        .collect();
+
+    let total = paths.len();
+    if total == 0 {
+        return Ok(0);
+    }
+
+    // Small deletes are spread across the concurrency limit, large ones are
+    // split into batches which drivers may be able to delete in bulk.
+    let batch_size = total.div_ceil(options.concurrency).clamp(1, MAX_BATCH_SIZE);
+    tracing::debug!(%prefix, "Deleting {total} files from {bucket} in batches of {batch_size}");
+
+    let mut deleted = 0;
+    let mut batches = futures::stream::iter(paths.chunks(batch_size))
+        .map(|batch| async move {
+            let remotes: Vec<&Utf8Path> = batch.iter().map(Utf8Path::new).collect();
+            driver.delete_many(bucket, &remotes).await?;
+            Ok::<_, StorageError>(batch.len())
+        })
+        .buffer_unordered(options.concurrency);
+
+    while let Some(count) = batches.try_next().await? {
+        deleted += count;
+        if let Some(progress) = &options.progress {
+            progress(DeleteProgress { deleted, total });
+        }
+    }
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::{MemoryStorage, Storage};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn delete_prefix_with_progress() {
+        let storage = Storage::new(MemoryStorage::with_buckets(&["bucket"]));
+        for path in ["a/b/1", "a/b/2", "a/b/c/3", "a/bc", "d/4"] {
+            storage
+                .upload("bucket", Utf8Path::new(path), &mut &b"data"[..])
+                .await
+                .unwrap();
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let recorder = reports.clone();
+        let options = DeletePrefix::new()
+            .with_concurrency(2)
+            .with_progress(move |progress| recorder.lock().unwrap().push(progress));
+
+        let deleted = storage
+            .delete_prefix_with("bucket", Utf8Path::new("a/b"), &options)
+            .await
+            .unwrap();
+        assert_eq!(deleted, 3);
+
+        let mut remaining = storage.list("bucket", None).await.unwrap();
+        remaining.sort();
+        assert_eq!(remaining, vec!["a/bc", "d/4"]);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports.last(),
+            Some(&DeleteProgress {
+                deleted: 3,
+                total: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Client-side encryption for any storage driver, using [age](https://age-encryption.org).
+//!
+//! Files are encrypted before they are handed to the inner driver, and decrypted
+//! after they are downloaded, so the storage backend only ever sees ciphertext.
+//! The age header, which carries the wrapped file key and nonce, is stored at
+//! the start of each object.
+
+use std::str::FromStr as _;
+
+use camino::Utf8Path;
+use eyre::{eyre, WrapErr};
+use futures::AsyncWriteExt as _;
+use secret::Secret;
+use serde::Deserialize;
+use tokio::io::{AsyncWriteExt as _, BufReader, DuplexStream};
+use tokio_util::compat::{
+    FuturesAsyncReadCompatExt as _, TokioAsyncReadCompatExt as _, TokioAsyncWriteCompatExt as _,
+};
+
+use storage_driver::{ByteRange, Capabilities, Driver, Metadata, Reader, StorageError, Writer};
+
+const ENCRYPTED_STORAGE_NAME: &str = "encrypted";
+
+/// Buffer size of the pipe between encryption and the inner driver.
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// Number of bytes read from the start of an object to find the end of its age header.
+const HEADER_PROBE: u64 = 4 * 1024;
+
+/// Size of the payload nonce, which follows the age header.
+const NONCE_SIZE: u64 = 16;
+
+/// Size of each plaintext chunk in the age payload.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Size of the authentication tag added to each chunk.
+const TAG_SIZE: u64 = 16;
+
+/// Where to find the age identity used to encrypt and decrypt files.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdentitySource {
+    /// Read the identity from an environment variable.
+    Env(String),
+
+    /// Read the identity from 1Password with `op read`, e.g. `op://vault/item/field`.
+    OnePassword(String),
+}
+
+impl IdentitySource {
+    /// Load the identity.
+    pub async fn load(&self) -> Result<Secret, StorageError> {
+        match self {
+            IdentitySource::Env(var) => Secret::from_env(var)
+                .wrap_err_with(|| format!("read age identity from ${var}"))
+                .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME)),
+            IdentitySource::OnePassword(reference) => {
+                let output = tokio::process::Command::new("op")
+                    .args(["read", "--no-newline", reference])
+                    .output()
+                    .await
+                    .wrap_err("run op")
+                    .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+                if !output.status.success() {
+                    return Err(StorageError::new(
+                        ENCRYPTED_STORAGE_NAME,
+                        eyre!(
+                            "op read {reference}: {}",
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        ),
+                    ));
+                }
+
+                let identity = String::from_utf8(output.stdout)
+                    .wrap_err("age identity is not utf-8")
+                    .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+                Ok(identity.into())
+            }
+        }
+    }
+}
+
+/// A driver which encrypts files with age before storing them in an inner driver.
+///
+/// Files are encrypted and decrypted as they are streamed to and from the inner
+/// driver. Metadata reports the size of the decrypted file, which is worked out
+/// from the size of the encrypted file and its age header.
+pub struct EncryptedDriver<D> {
+    inner: D,
+    identity: age::x25519::Identity,
+    recipient: age::x25519::Recipient,
+}
+
+impl<D: std::fmt::Debug> std::fmt::Debug for EncryptedDriver<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedDriver")
+            .field("inner", &self.inner)
+            .field("recipient", &self.recipient.to_string())
+            .finish()
+    }
+}
+
+impl<D> EncryptedDriver<D> {
+    /// Wrap a driver, encrypting files to the age identity (`AGE-SECRET-KEY-1...`) in a secret.
+    pub fn new(inner: D, identity: &Secret) -> Result<Self, StorageError> {
+        let identity =
+            age::x25519::Identity::from_str(identity.revealed().trim()).map_err(|err| {
+                StorageError::new(ENCRYPTED_STORAGE_NAME, eyre!("age identity: {err}"))
+            })?;
+        let recipient = identity.to_public();
+
+        Ok(Self {
+            inner,
+            identity,
+            recipient,
+        })
+    }
+
+    /// The public key which files are encrypted to.
+    pub fn recipient(&self) -> String {
+        self.recipient.to_string()
+    }
+
+    /// The wrapped driver.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    /// Encrypt the contents of an upload into `output`, closing it when done.
+    async fn encrypt(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+        output: DuplexStream,
+    ) -> Result<(), StorageError> {
+        let encryptor = age::Encryptor::with_recipients(std::iter::once(
+            &self.recipient as &dyn age::Recipient,
+        ))
+        .wrap_err_with(|| format!("encrypt {bucket}/{remote}"))
+        .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+
+        let mut ciphertext = encryptor
+            .wrap_async_output(output.compat_write())
+            .await
+            .wrap_err("write age header")
+            .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+        futures::io::copy_buf(reader.compat(), &mut ciphertext)
+            .await
+            .wrap_err_with(|| format!("encrypt {bucket}/{remote}"))
+            .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+
+        // Closing the stream writes the final chunk, and closes the pipe.
+        ciphertext
+            .close()
+            .await
+            .wrap_err_with(|| format!("encrypt {bucket}/{remote}"))
+            .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))
+    }
+
+    /// Decrypt the ciphertext read from `input` into `writer`.
+    async fn decrypt(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        input: DuplexStream,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let decryptor = age::Decryptor::new_async_buffered(BufReader::new(input).compat())
+            .await
+            .wrap_err_with(|| format!("decrypt {bucket}/{remote}"))
+            .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+        let plaintext = decryptor
+            .decrypt_async(std::iter::once(&self.identity as &dyn age::Identity))
+            .wrap_err_with(|| format!("decrypt {bucket}/{remote}"))
+            .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+
+        tokio::io::copy(&mut plaintext.compat(), writer)
+            .await
+            .wrap_err_with(|| format!("decrypt {bucket}/{remote}"))
+            .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))?;
+        Ok(())
+    }
+}
+
+/// The size of the plaintext in an age file, given the start of the file and its total size.
+fn plaintext_size(start: &[u8], size: u64) -> Option<u64> {
+    // The header ends with the MAC line, `--- <mac>\n`, and is followed by the nonce.
+    let mac = start.windows(5).position(|window| window == b"\n--- ")?;
+    let header = mac + start[mac + 1..].iter().position(|&b| b == b'\n')? + 2;
+
+    let payload = size.checked_sub(header as u64 + NONCE_SIZE)?;
+    let chunks = payload.div_ceil(CHUNK_SIZE + TAG_SIZE).max(1);
+    payload.checked_sub(chunks * TAG_SIZE)
+}
+
+#[async_trait::async_trait]
+impl<D> Driver for EncryptedDriver<D>
+where
+    D: Driver + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        ENCRYPTED_STORAGE_NAME
+    }
+
+    fn scheme(&self) -> &str {
+        self.inner.scheme()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError> {
+        self.inner.delete(bucket, remote).await
+    }
+
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        self.inner.delete_many(bucket, remotes).await
+    }
+
+    async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
+        let metadata = self.inner.metadata(bucket, remote).await?;
+
+        let mut start = Vec::new();
+        self.inner
+            .download_range(bucket, remote, ByteRange::from(0..HEADER_PROBE), &mut start)
+            .await?;
+        let size = plaintext_size(&start, metadata.size).ok_or_else(|| {
+            StorageError::new(
+                ENCRYPTED_STORAGE_NAME,
+                eyre!("{bucket}/{remote} is not an age encrypted file"),
+            )
+        })?;
+
+        Ok(Metadata { size, ..metadata })
+    }
+
+    async fn upload(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        let (input, output) = tokio::io::duplex(PIPE_SIZE);
+        let mut ciphertext = BufReader::new(input);
+        futures::future::try_join(
+            self.encrypt(bucket, remote, reader, output),
+            self.inner.upload(bucket, remote, &mut ciphertext),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        let (input, output) = tokio::io::duplex(PIPE_SIZE);
+        let mut ciphertext = BufReader::new(input);
+        futures::future::try_join(
+            self.encrypt(bucket, remote, reader, output),
+            self.inner
+                .upload_if_not_exists(bucket, remote, &mut ciphertext),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn download(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let (mut ciphertext, input) = tokio::io::duplex(PIPE_SIZE);
+        let download = async move {
+            self.inner.download(bucket, remote, &mut ciphertext).await?;
+            ciphertext
+                .shutdown()
+                .await
+                .wrap_err("close decryption pipe")
+                .map_err(StorageError::with(ENCRYPTED_STORAGE_NAME))
+        };
+
+        futures::future::try_join(download, self.decrypt(bucket, remote, input, writer)).await?;
+        Ok(())
+    }
+
+    /// Files are encrypted independently of their path, so the ciphertext is copied as is.
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.inner.copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.inner.rename(bucket, from, to).await
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: Option<&Utf8Path>,
+    ) -> Result<Vec<String>, StorageError> {
+        self.inner.list(bucket, prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use age::secrecy::ExposeSecret as _;
+
+    use crate::MemoryStorage;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn encrypt_roundtrip() {
+        let key = age::x25519::Identity::generate();
+        let secret = Secret::from(key.to_string().expose_secret().to_owned());
+
+        let memory = Arc::new(MemoryStorage::with_buckets(&["bucket"]));
+        let driver = EncryptedDriver::new(memory.clone(), &secret).unwrap();
+        let remote = Utf8Path::new("backup.txt");
+
+        driver
+            .upload("bucket", remote, &mut &b"frobulator"[..])
+            .await
+            .unwrap();
+
+        let mut stored = Vec::new();
+        memory
+            .download("bucket", remote, &mut stored)
+            .await
+            .unwrap();
+        assert!(stored.starts_with(b"age-encryption.org/v1"));
+        assert!(!stored.windows(10).any(|window| window == b"frobulator"));
+
+        let mut plaintext = Vec::new();
+        driver
+            .download("bucket", remote, &mut plaintext)
+            .await
+            .unwrap();
+        assert_eq!(plaintext, b"frobulator");
+
+        let other = age::x25519::Identity::generate();
+        let other = Secret::from(other.to_string().expose_secret().to_owned());
+        let wrong = EncryptedDriver::new(memory, &other).unwrap();
+        assert!(wrong
+            .download("bucket", remote, &mut Vec::new())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn plaintext_metadata_and_ranges() {
+        let key = age::x25519::Identity::generate();
+        let secret = Secret::from(key.to_string().expose_secret().to_owned());
+
+        let memory = MemoryStorage::with_buckets(&["bucket"]);
+        let driver = EncryptedDriver::new(memory, &secret).unwrap();
+
+        for size in [0usize, 10, 64 * 1024, 200 * 1024] {
+            let remote = Utf8Path::new("data.bin");
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            driver
+                .upload("bucket", remote, &mut data.as_slice())
+                .await
+                .unwrap();
+
+            let metadata = driver.metadata("bucket", remote).await.unwrap();
+            assert_eq!(metadata.size, size as u64);
+
+            let mut tail = Vec::new();
+            driver
+                .download_range("bucket", remote, ByteRange::Last(100), &mut tail)
+                .await
+                .unwrap();
+            assert_eq!(tail, &data[size.saturating_sub(100)..]);
+        }
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! Configuration and unification for the storage backends.
 
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
 
 use camino::Utf8Path;
@@ -11,6 +13,11 @@ use camino::Utf8PathBuf;
 use eyre::Context;
 use serde::Deserialize;
 
+#[doc(hidden)]
+pub mod compat;
+mod delete;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
 #[cfg(feature = "local")]
 pub(crate) mod local;
 
@@ -19,10 +26,15 @@ pub mod multi;
 pub(crate) mod memory;
 #[cfg(feature = "tmp")]
 pub(crate) mod temp;
+pub mod throttle;
 
+pub use delete::{DeletePrefix, DeleteProgress};
+#[cfg(feature = "encryption")]
+#[doc(inline)]
+pub use encrypted::EncryptedDriver;
 #[cfg(feature = "local")]
 #[doc(inline)]
-pub use local::LocalDriver;
+pub use local::{Durability, LocalDriver};
 
 #[doc(inline)]
 pub use memory::MemoryStorage;
@@ -31,13 +43,18 @@ use storage_driver::DriverUri;
 #[cfg(feature = "tmp")]
 #[doc(inline)]
 pub use temp::TempDriver;
+#[doc(inline)]
+pub use throttle::{RateLimit, ThrottledDriver};
 
 #[doc(inline)]
-pub use storage_driver::{Driver, Metadata, StorageError};
+pub use storage_driver::{
+    ByteRange, Capabilities, Driver, Metadata, Progress, StorageError, TransferProgress,
+};
 
 /// Configuration for the storage backend, used to create a [`Storage`] instance.
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
 pub enum StorageConfig {
     /// In-memory storage backend.
     Memory {
@@ -50,6 +67,10 @@ pub enum StorageConfig {
     Local {
         /// The path to the local storage directory.
         path: Utf8PathBuf,
+
+        /// How durable uploads should be before they are reported as complete.
+        #[serde(default)]
+        durability: Durability,
     },
 
     /// Temporary storage backend.
@@ -58,9 +79,18 @@ pub enum StorageConfig {
 
     /// Backblaze B2 storage backend.
     #[cfg(feature = "b2")]
-    B2(b2_client::B2ApplicationKey),
+    B2 {
+        /// The application key used to access B2.
+        #[serde(flatten)]
+        key: b2_client::B2ApplicationKey,
+
+        /// Settings used when uploading files.
+        #[serde(default)]
+        uploads: b2_client::UploadSettings,
+    },
 
-    /// Backblaze B2 storage backend, using environment variables for configuration.
+    /// Backblaze B2 storage backend, using environment variables for configuration
+    /// and the default upload settings.
     #[cfg(feature = "b2")]
     #[serde(alias = "b2env")]
     B2Env,
@@ -68,6 +98,32 @@ pub enum StorageConfig {
     /// Backblaze B2 storage backend, using multiple accounts to access multiple buckets.
     #[cfg(feature = "b2")]
     B2Multi(b2_client::B2MultiConfig),
+
+    /// Route buckets to several storage backends.
+    Multi(Vec<multi::StorageRoute>),
+
+    /// Encrypt files with age before storing them in another backend.
+    #[cfg(feature = "encryption")]
+    Encrypted {
+        /// Where to find the age identity.
+        identity: encrypted::IdentitySource,
+
+        /// The backend which stores the encrypted files.
+        storage: Box<StorageConfig>,
+    },
+
+    /// Limit the bandwidth used by another backend.
+    Throttled {
+        /// The limit, in bytes per second.
+        bytes_per_second: u64,
+
+        /// Limit each transfer separately, rather than all transfers together.
+        #[serde(default)]
+        per_transfer: bool,
+
+        /// The backend to limit.
+        storage: Box<StorageConfig>,
+    },
 }
 
 impl StorageConfig {
@@ -77,17 +133,20 @@ impl StorageConfig {
         let client: Storage = match self {
             StorageConfig::Memory { bucket } => MemoryStorage::with_buckets(&[&bucket]).into(),
             #[cfg(feature = "local")]
-            StorageConfig::Local { path } => LocalDriver::new(path).into(),
+            StorageConfig::Local { path, durability } => {
+                LocalDriver::new(path).with_durability(durability).into()
+            }
             #[cfg(feature = "tmp")]
             StorageConfig::Temp => TempDriver::new()
                 .map_err(StorageError::with("Temp"))?
                 .into(),
             #[cfg(feature = "b2")]
-            StorageConfig::B2(app) => app
+            StorageConfig::B2 { key, uploads } => key
                 .client()
                 .await
                 .context("authenticating b2 client")
                 .map_err(StorageError::with("B2"))?
+                .with_upload_settings(uploads)
                 .into(),
             #[cfg(feature = "b2")]
             StorageConfig::B2Env => b2_client::B2ApplicationKey::from_env()
@@ -100,9 +159,35 @@ impl StorageConfig {
                 .into(),
             #[cfg(feature = "b2")]
             StorageConfig::B2Multi(config) => config.client().into(),
+            StorageConfig::Multi(routes) => multi::MultiStorage::from_routes(routes).await?.into(),
+            #[cfg(feature = "encryption")]
+            StorageConfig::Encrypted { identity, storage } => {
+                let inner = storage.build_boxed().await?;
+                let identity = identity.load().await?;
+                EncryptedDriver::new(inner.driver, &identity)?.into()
+            }
+            StorageConfig::Throttled {
+                bytes_per_second,
+                per_transfer,
+                storage,
+            } => {
+                let limit = if per_transfer {
+                    RateLimit::per_transfer(bytes_per_second)
+                } else {
+                    RateLimit::new(bytes_per_second)
+                };
+                storage.build_boxed().await?.with_rate_limit(limit)
+            }
         };
         Ok(client)
     }
+
+    /// Build a [`Storage`] instance behind a boxed future, so that configurations can nest.
+    pub(crate) fn build_boxed(
+        self,
+    ) -> Pin<Box<dyn Future<Output = Result<Storage, StorageError>> + Send>> {
+        Box::pin(self.build())
+    }
 }
 
 use tokio::io;
@@ -142,6 +227,18 @@ impl Storage {
         self.driver.scheme()
     }
 
+    /// The optional operations supported by this driver.
+    pub fn capabilities(&self) -> Capabilities {
+        self.driver.capabilities()
+    }
+
+    /// Limit the bandwidth of uploads and downloads through this client.
+    ///
+    /// Clones of the returned client share the limit, as do its bucket clients.
+    pub fn with_rate_limit(self, limit: RateLimit) -> Self {
+        Storage::new(ThrottledDriver::new(self.driver, limit))
+    }
+
     /// Get a bucket-specific storage client.
     pub fn bucket<S: Into<String>>(&self, bucket: S) -> StorageBucket {
         StorageBucket {
@@ -176,6 +273,44 @@ impl Storage {
         Ok(())
     }
 
+    /// Download a file to a writer, reporting progress as it is written.
+    #[tracing::instrument(skip(self, writer, progress), fields(driver=self.driver.name()))]
+    pub async fn download_with_progress<'d, W>(
+        &'d self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut W,
+        progress: &Progress,
+    ) -> Result<(), StorageError>
+    where
+        W: io::AsyncWrite + Unpin + Send + Sync + 'd,
+    {
+        tracing::trace!(%remote, "Downloading from: {bucket}/{remote}");
+        self.driver
+            .download_with_progress(bucket, remote, writer, progress)
+            .await
+    }
+
+    /// Download part of a file to a writer.
+    #[tracing::instrument(skip(self, range, writer), fields(driver=self.driver.name()))]
+    pub async fn download_range<'d, W>(
+        &'d self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: impl Into<ByteRange>,
+        writer: &mut W,
+    ) -> Result<(), StorageError>
+    where
+        W: io::AsyncWrite + Unpin + Send + Sync + 'd,
+    {
+        let range = range.into();
+        tracing::trace!(%remote, %range, "Downloading part of: {bucket}/{remote}");
+        self.driver
+            .download_range(bucket, remote, range, writer)
+            .await?;
+        Ok(())
+    }
+
     /// Upload a file from a reader.
     #[tracing::instrument(skip(self, reader), fields(driver=self.driver.name(), bucket))]
     pub async fn upload<'d, R>(
@@ -192,6 +327,45 @@ impl Storage {
         Ok(())
     }
 
+    /// Upload a file from a reader, reporting progress as it is uploaded.
+    ///
+    /// Set [`Progress::with_total`] if the size of the upload is known.
+    #[tracing::instrument(skip(self, reader, progress), fields(driver=self.driver.name(), bucket))]
+    pub async fn upload_with_progress<'d, R>(
+        &'d self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut R,
+        progress: &Progress,
+    ) -> Result<(), StorageError>
+    where
+        R: io::AsyncBufRead + Unpin + Send + Sync + 'd,
+    {
+        tracing::trace!(%remote, "Uploading to: {bucket}/{remote}");
+        self.driver
+            .upload_with_progress(bucket, remote, reader, progress)
+            .await
+    }
+
+    /// Upload a file from a reader, only if no file exists at that path.
+    ///
+    /// See [`Driver::upload_if_not_exists`] for the errors this returns.
+    #[tracing::instrument(skip(self, reader), fields(driver=self.driver.name(), bucket))]
+    pub async fn upload_if_not_exists<'d, R>(
+        &'d self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut R,
+    ) -> Result<(), StorageError>
+    where
+        R: io::AsyncBufRead + Unpin + Send + Sync + 'd,
+    {
+        tracing::trace!(%remote, "Uploading if not exists to: {bucket}/{remote}");
+        self.driver
+            .upload_if_not_exists(bucket, remote, reader)
+            .await
+    }
+
     /// Upload a file from a local path.
     pub async fn upload_file(
         &self,
@@ -230,6 +404,51 @@ impl Storage {
         self.driver.delete(bucket, path).await
     }
 
+    /// Copy a file to another path in the same bucket.
+    ///
+    /// Drivers which support it copy the file without downloading it, see [`Driver::copy`].
+    #[tracing::instrument(skip(self), fields(driver=self.driver.name()))]
+    pub async fn copy(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.driver.copy(bucket, from, to).await
+    }
+
+    /// Move a file to another path in the same bucket.
+    #[tracing::instrument(skip(self), fields(driver=self.driver.name()))]
+    pub async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.driver.rename(bucket, from, to).await
+    }
+
+    /// Delete every file under a prefix, returning the number of files deleted.
+    pub async fn delete_prefix(
+        &self,
+        bucket: &str,
+        prefix: &Utf8Path,
+    ) -> Result<usize, StorageError> {
+        self.delete_prefix_with(bucket, prefix, &DeletePrefix::default())
+            .await
+    }
+
+    /// Delete every file under a prefix, with options for concurrency and progress.
+    #[tracing::instrument(skip(self, options), fields(driver=self.driver.name()))]
+    pub async fn delete_prefix_with(
+        &self,
+        bucket: &str,
+        prefix: &Utf8Path,
+        options: &DeletePrefix,
+    ) -> Result<usize, StorageError> {
+        delete::delete_prefix(&self.driver, bucket, prefix, options).await
+    }
+
     /// Get a storage driver which accepts URIs.
     pub fn uri(&self) -> DriverUri<ArcDriver> {
         DriverUri::new(self.driver.clone())
@@ -266,6 +485,42 @@ impl StorageBucket {
         Ok(())
     }
 
+    /// Download a file to a writer, reporting progress as it is written.
+    #[tracing::instrument(skip(self, writer, progress), fields(driver=self.driver.name()))]
+    pub async fn download_with_progress<'d, W>(
+        &'d self,
+        remote: &Utf8Path,
+        writer: &mut W,
+        progress: &Progress,
+    ) -> Result<(), StorageError>
+    where
+        W: io::AsyncWrite + Unpin + Send + Sync + 'd,
+    {
+        tracing::trace!(%remote, "Downloading from: {}/{remote}", self.bucket);
+        self.driver
+            .download_with_progress(&self.bucket, remote, writer, progress)
+            .await
+    }
+
+    /// Download part of a file to a writer.
+    #[tracing::instrument(skip(self, range, writer), fields(driver=self.driver.name()))]
+    pub async fn download_range<'d, W>(
+        &'d self,
+        remote: &Utf8Path,
+        range: impl Into<ByteRange>,
+        writer: &mut W,
+    ) -> Result<(), StorageError>
+    where
+        W: io::AsyncWrite + Unpin + Send + Sync + 'd,
+    {
+        let range = range.into();
+        tracing::trace!(%remote, %range, "Downloading part of: {}/{remote}", self.bucket);
+        self.driver
+            .download_range(&self.bucket, remote, range, writer)
+            .await?;
+        Ok(())
+    }
+
     /// Upload a file from a reader.
     #[tracing::instrument(skip(self, reader), fields(driver=self.driver.name(), bucket=self.bucket))]
     pub async fn upload<'d, R>(
@@ -281,6 +536,43 @@ impl StorageBucket {
         Ok(())
     }
 
+    /// Upload a file from a reader, reporting progress as it is uploaded.
+    ///
+    /// Set [`Progress::with_total`] if the size of the upload is known.
+    #[tracing::instrument(skip(self, reader, progress), fields(driver=self.driver.name(), bucket=self.bucket))]
+    pub async fn upload_with_progress<'d, R>(
+        &'d self,
+        remote: &Utf8Path,
+        reader: &mut R,
+        progress: &Progress,
+    ) -> Result<(), StorageError>
+    where
+        R: io::AsyncBufRead + Unpin + Send + Sync + 'd,
+    {
+        tracing::trace!(%remote, "Uploading to: {}/{remote}", self.bucket);
+        self.driver
+            .upload_with_progress(&self.bucket, remote, reader, progress)
+            .await
+    }
+
+    /// Upload a file from a reader, only if no file exists at that path.
+    ///
+    /// See [`Driver::upload_if_not_exists`] for the errors this returns.
+    #[tracing::instrument(skip(self, reader), fields(driver=self.driver.name(), bucket=self.bucket))]
+    pub async fn upload_if_not_exists<'d, R>(
+        &'d self,
+        remote: &Utf8Path,
+        reader: &mut R,
+    ) -> Result<(), StorageError>
+    where
+        R: io::AsyncBufRead + Unpin + Send + Sync + 'd,
+    {
+        tracing::trace!(%remote, "Uploading if not exists to: {}/{remote}", self.bucket);
+        self.driver
+            .upload_if_not_exists(&self.bucket, remote, reader)
+            .await
+    }
+
     /// Upload a file from a local path.
     pub async fn upload_file(
         &self,
@@ -310,4 +602,139 @@ impl StorageBucket {
     pub async fn delete(&self, path: &Utf8Path) -> Result<(), StorageError> {
         self.driver.delete(&self.bucket, path).await
     }
+
+    /// Copy a file to another path in this bucket.
+    ///
+    /// Drivers which support it copy the file without downloading it, see [`Driver::copy`].
+    #[tracing::instrument(skip(self), fields(driver=self.driver.name(), bucket=self.bucket))]
+    pub async fn copy(&self, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.driver.copy(&self.bucket, from, to).await
+    }
+
+    /// Move a file to another path in this bucket.
+    #[tracing::instrument(skip(self), fields(driver=self.driver.name(), bucket=self.bucket))]
+    pub async fn rename(&self, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.driver.rename(&self.bucket, from, to).await
+    }
+
+    /// Delete every file under a prefix, returning the number of files deleted.
+    pub async fn delete_prefix(&self, prefix: &Utf8Path) -> Result<usize, StorageError> {
+        self.delete_prefix_with(prefix, &DeletePrefix::default())
+            .await
+    }
+
+    /// Delete every file under a prefix, with options for concurrency and progress.
+    #[tracing::instrument(skip(self, options), fields(driver=self.driver.name(), bucket=self.bucket))]
+    pub async fn delete_prefix_with(
+        &self,
+        prefix: &Utf8Path,
+        options: &DeletePrefix,
+    ) -> Result<usize, StorageError> {
+        delete::delete_prefix(&self.driver, &self.bucket, prefix, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use storage_driver::{Reader, Writer};
+
+    use super::*;
+
+    /// Reports upload progress once the whole file is stored, like drivers which upload in parts.
+    #[derive(Debug)]
+    struct StoredProgress(MemoryStorage);
+
+    #[async_trait::async_trait]
+    impl Driver for StoredProgress {
+        fn name(&self) -> &'static str {
+            "stored-progress"
+        }
+
+        fn scheme(&self) -> &str {
+            self.0.scheme()
+        }
+
+        async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError> {
+            self.0.delete(bucket, remote).await
+        }
+
+        async fn metadata(
+            &self,
+            bucket: &str,
+            remote: &Utf8Path,
+        ) -> Result<Metadata, StorageError> {
+            self.0.metadata(bucket, remote).await
+        }
+
+        async fn upload(
+            &self,
+            bucket: &str,
+            remote: &Utf8Path,
+            reader: &mut Reader<'_>,
+        ) -> Result<(), StorageError> {
+            self.0.upload(bucket, remote, reader).await
+        }
+
+        async fn upload_with_progress(
+            &self,
+            bucket: &str,
+            remote: &Utf8Path,
+            reader: &mut Reader<'_>,
+            progress: &Progress,
+        ) -> Result<(), StorageError> {
+            self.0.upload(bucket, remote, reader).await?;
+            progress.report(self.0.metadata(bucket, remote).await?.size);
+            Ok(())
+        }
+
+        async fn download(
+            &self,
+            bucket: &str,
+            remote: &Utf8Path,
+            writer: &mut Writer<'_>,
+        ) -> Result<(), StorageError> {
+            self.0.download(bucket, remote, writer).await
+        }
+
+        async fn list(
+            &self,
+            bucket: &str,
+            prefix: Option<&Utf8Path>,
+        ) -> Result<Vec<String>, StorageError> {
+            self.0.list(bucket, prefix).await
+        }
+    }
+
+    #[tokio::test]
+    async fn driver_upload_progress() {
+        let storage = Storage::new(StoredProgress(MemoryStorage::with_buckets(&["bucket"])));
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let progress = {
+            let reports = reports.clone();
+            Progress::new(move |p| reports.lock().unwrap().push(p.transferred))
+        };
+
+        let data = vec![7u8; 100_000];
+        storage
+            .upload_with_progress(
+                "bucket",
+                Utf8Path::new("file"),
+                &mut data.as_slice(),
+                &progress,
+            )
+            .await
+            .unwrap();
+        assert_eq!(*reports.lock().unwrap(), vec![100_000]);
+
+        reports.lock().unwrap().clear();
+        storage
+            .bucket("bucket")
+            .upload_with_progress(Utf8Path::new("other"), &mut data.as_slice(), &progress)
+            .await
+            .unwrap();
+        assert_eq!(*reports.lock().unwrap(), vec![100_000]);
+    }
 }
@@ -1,20 +1,56 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use camino::{Utf8Path, Utf8PathBuf};
 use eyre::Context;
-use tokio::io::AsyncWriteExt;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt};
 use tracing::instrument;
 
-use storage_driver::{Driver, Metadata, Reader, StorageError, Writer};
+use storage_driver::{ByteRange, Capabilities, Driver, Metadata, Reader, StorageError, Writer};
+
+/// Suffix used for files which are still being written.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// How hard the local driver works to make uploads survive a crash.
+///
+/// Uploads are always written to a temporary file and renamed into place, so
+/// readers never observe a partially written file. The durability level controls
+/// whether data is flushed to disk before the upload is reported as complete.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Durability {
+    /// Rename into place without syncing to disk.
+    #[default]
+    Atomic,
+
+    /// Sync file contents to disk before renaming into place.
+    SyncFile,
+
+    /// Sync file contents, then sync the parent directory after the rename,
+    /// so the new directory entry is durable as well.
+    SyncAll,
+}
 
 /// A storage driver that stores files on the local filesystem.
 #[derive(Debug)]
 pub struct LocalDriver {
     root: Utf8PathBuf,
+    durability: Durability,
 }
 
 impl LocalDriver {
     /// Create a new `LocalDriver` instance, storing files in the given directory.
     pub fn new(root: Utf8PathBuf) -> Self {
-        Self { root }
+        Self {
+            root,
+            durability: Durability::default(),
+        }
+    }
+
+    /// Set the durability level used when uploading files.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
     }
 
     fn path(&self, bucket: &str, remote: &Utf8Path) -> Utf8PathBuf {
@@ -23,6 +59,121 @@ impl LocalDriver {
         path.push(remote);
         path
     }
+
+    /// A unique path next to `path` to write a partial upload to.
+    fn partial_path(path: &Utf8Path) -> Utf8PathBuf {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!(
+            ".{}.{}-{n}{PARTIAL_SUFFIX}",
+            path.file_name().unwrap_or_default(),
+            std::process::id()
+        );
+        path.with_file_name(name)
+    }
+
+    /// Check whether a file name is one made by [`LocalDriver::partial_path`].
+    fn is_partial(name: &str) -> bool {
+        let Some(stem) = name
+            .strip_prefix('.')
+            .and_then(|name| name.strip_suffix(PARTIAL_SUFFIX))
+        else {
+            return false;
+        };
+
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        stem.rsplit_once('.')
+            .and_then(|(_, unique)| unique.split_once('-'))
+            .is_some_and(|(pid, n)| digits(pid) && digits(n))
+    }
+
+    /// Write an upload to a partial file, then move it into place.
+    ///
+    /// Without `overwrite`, the partial file is hard linked into place, which
+    /// fails atomically if a file already exists there.
+    async fn put(
+        &self,
+        bucket: &str,
+        name: &Utf8Path,
+        local: &mut Reader<'_>,
+        overwrite: bool,
+    ) -> Result<(), StorageError> {
+        let remote = self.path(bucket, name);
+
+        tokio::fs::create_dir_all(&remote.parent().unwrap())
+            .await
+            .context("create_dir_all")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        let partial = Self::partial_path(&remote);
+        if let Err(err) = self.write_partial(&partial, local).await {
+            if let Err(error) = tokio::fs::remove_file(&partial).await {
+                tracing::warn!(%partial, "Failed to remove partial upload: {error}");
+            }
+            return Err(StorageError::new(self.name(), err));
+        }
+
+        if overwrite {
+            if let Err(err) = tokio::fs::rename(&partial, &remote).await {
+                if let Err(error) = tokio::fs::remove_file(&partial).await {
+                    tracing::warn!(%partial, "Failed to remove partial upload: {error}");
+                }
+                return Err(err)
+                    .context("rename into place")
+                    .map_err(|err| StorageError::new(self.name(), err));
+            }
+        } else {
+            let linked = tokio::fs::hard_link(&partial, &remote).await;
+            if let Err(error) = tokio::fs::remove_file(&partial).await {
+                tracing::warn!(%partial, "Failed to remove partial upload: {error}");
+            }
+
+            match linked {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    return Err(StorageError::already_exists(self.name(), name));
+                }
+                Err(err) => {
+                    return Err(err)
+                        .context("link into place")
+                        .map_err(|err| StorageError::new(self.name(), err));
+                }
+            }
+        }
+
+        if self.durability == Durability::SyncAll {
+            let parent = remote.parent().unwrap();
+            tokio::fs::File::open(parent)
+                .await
+                .context("open parent directory")
+                .map_err(|err| StorageError::new(self.name(), err))?
+                .sync_all()
+                .await
+                .context("sync parent directory")
+                .map_err(|err| StorageError::new(self.name(), err))?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_partial(&self, partial: &Utf8Path, local: &mut Reader<'_>) -> eyre::Result<()> {
+        let mut writer = tokio::io::BufWriter::new(
+            tokio::fs::File::create(partial)
+                .await
+                .context("local: open partial file")?,
+        );
+
+        tokio::io::copy(local, &mut writer).await.context("copy")?;
+        writer.flush().await.context("flush writer")?;
+
+        if self.durability != Durability::Atomic {
+            writer.get_ref().sync_all().await.context("sync file")?;
+        }
+
+        writer.shutdown().await.context("shutdown writer")?;
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -35,6 +186,10 @@ impl Driver for LocalDriver {
         "local"
     }
 
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default().with_conditional_writes(true)
+    }
+
     async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
         let remote = self.path(bucket, remote);
         let metadata = tokio::fs::metadata(remote)
@@ -66,32 +221,51 @@ impl Driver for LocalDriver {
         remote: &Utf8Path,
         local: &mut Reader<'_>,
     ) -> Result<(), StorageError> {
-        let remote = self.path(bucket, remote);
+        self.put(bucket, remote, local, true).await
+    }
 
-        tokio::fs::create_dir_all(&remote.parent().unwrap())
-            .await
-            .context("create_dir_all")
-            .map_err(|err| StorageError::new(self.name(), err))?;
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        self.put(bucket, remote, local, false).await
+    }
 
-        let mut writer = tokio::io::BufWriter::new(
-            tokio::fs::File::create(&remote)
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        let source = self.path(bucket, from);
+        let mut reader = tokio::io::BufReader::new(
+            tokio::fs::File::open(&source)
                 .await
-                .context("local: open remote file")
+                .context("open source file")
                 .map_err(|err| StorageError::new(self.name(), err))?,
         );
 
-        tokio::io::copy(local, &mut writer)
+        self.put(bucket, to, &mut reader, true).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        let source = self.path(bucket, from);
+        let destination = self.path(bucket, to);
+
+        tokio::fs::create_dir_all(&destination.parent().unwrap())
             .await
-            .context("copy")
+            .context("create_dir_all")
             .map_err(|err| StorageError::new(self.name(), err))?;
 
-        writer
-            .shutdown()
+        tokio::fs::rename(&source, &destination)
             .await
-            .context("shutdown writer")
+            .context("rename")
             .map_err(|err| StorageError::new(self.name(), err))?;
         Ok(())
     }
+
     async fn download(
         &self,
         bucket: &str,
@@ -121,6 +295,47 @@ impl Driver for LocalDriver {
         Ok(())
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        local: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let remote = self.path(bucket, remote);
+
+        let mut file = tokio::fs::File::open(&remote)
+            .await
+            .context("open remote file")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+        let size = file
+            .metadata()
+            .await
+            .context("remote file metadata")
+            .map_err(|err| StorageError::new(self.name(), err))?
+            .len();
+
+        let range = range.resolve(size);
+        file.seek(std::io::SeekFrom::Start(range.start))
+            .await
+            .context("seek remote file")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        let mut reader = tokio::io::BufReader::new(file).take(range.end - range.start);
+        tokio::io::copy(&mut reader, local)
+            .await
+            .context("copy")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        local
+            .flush()
+            .await
+            .context("flush writer")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        Ok(())
+    }
+
     #[instrument(skip(self), "local::list", level = "debug", fields(bucket=%bucket, prefix=%prefix.as_ref().map(|p| p.as_str()).unwrap_or("")))]
     async fn list(
         &self,
@@ -187,6 +402,8 @@ fn visit(path: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> eyre::Result<()> {
         let entry = entry?;
         if entry.file_type()?.is_dir() {
             visit(entry.path(), files)?;
+        } else if LocalDriver::is_partial(entry.file_name()) {
+            tracing::trace!("Skipping partial upload: {}", entry.path());
         } else {
             tracing::trace!("Found file: {}", entry.path());
             files.push(entry.path().to_owned())
@@ -195,3 +412,168 @@ fn visit(path: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn driver(durability: Durability) -> (tempfile::TempDir, LocalDriver) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(dir.path()).unwrap().to_owned();
+        (dir, LocalDriver::new(root).with_durability(durability))
+    }
+
+    #[tokio::test]
+    async fn upload_is_atomic() {
+        for durability in [
+            Durability::Atomic,
+            Durability::SyncFile,
+            Durability::SyncAll,
+        ] {
+            let (_dir, driver) = driver(durability);
+            let remote = Utf8Path::new("some/file.txt");
+
+            driver
+                .upload("bucket", remote, &mut &b"first"[..])
+                .await
+                .unwrap();
+            driver
+                .upload("bucket", remote, &mut &b"second"[..])
+                .await
+                .unwrap();
+
+            let mut buf = Vec::new();
+            driver.download("bucket", remote, &mut buf).await.unwrap();
+            assert_eq!(buf, b"second");
+
+            assert_eq!(
+                driver.list("bucket", None).await.unwrap(),
+                vec![remote.to_string()]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_if_not_exists() {
+        let (_dir, driver) = driver(Durability::Atomic);
+        let remote = Utf8Path::new("tags/latest");
+        assert!(driver.capabilities().conditional_writes);
+
+        driver
+            .upload_if_not_exists("bucket", remote, &mut &b"first"[..])
+            .await
+            .unwrap();
+        let err = driver
+            .upload_if_not_exists("bucket", remote, &mut &b"second"[..])
+            .await
+            .unwrap_err();
+        assert!(err.is_already_exists());
+
+        let mut buf = Vec::new();
+        driver.download("bucket", remote, &mut buf).await.unwrap();
+        assert_eq!(buf, b"first");
+
+        assert_eq!(
+            driver.list("bucket", None).await.unwrap(),
+            vec![remote.to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn copy_and_rename() {
+        let (_dir, driver) = driver(Durability::Atomic);
+
+        driver
+            .upload("bucket", Utf8Path::new("a.txt"), &mut &b"contents"[..])
+            .await
+            .unwrap();
+        driver
+            .copy(
+                "bucket",
+                Utf8Path::new("a.txt"),
+                Utf8Path::new("copies/b.txt"),
+            )
+            .await
+            .unwrap();
+        driver
+            .rename(
+                "bucket",
+                Utf8Path::new("a.txt"),
+                Utf8Path::new("moved/c.txt"),
+            )
+            .await
+            .unwrap();
+
+        let mut files = driver.list("bucket", None).await.unwrap();
+        files.sort();
+        assert_eq!(files, vec!["copies/b.txt", "moved/c.txt"]);
+
+        let mut buf = Vec::new();
+        driver
+            .download("bucket", Utf8Path::new("moved/c.txt"), &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"contents");
+    }
+
+    #[tokio::test]
+    async fn partial_uploads_are_hidden() {
+        let (_dir, driver) = driver(Durability::Atomic);
+        let remote = driver.path("bucket", Utf8Path::new("file.txt"));
+        tokio::fs::create_dir_all(remote.parent().unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(LocalDriver::partial_path(&remote), b"partial")
+            .await
+            .unwrap();
+
+        assert!(driver.list("bucket", None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_rename_removes_partial() {
+        let (_dir, driver) = driver(Durability::Atomic);
+        let remote = driver.path("bucket", Utf8Path::new("file.txt"));
+
+        // A non-empty directory can't be replaced by a file.
+        tokio::fs::create_dir_all(remote.join("child"))
+            .await
+            .unwrap();
+        driver
+            .upload("bucket", Utf8Path::new("file.txt"), &mut &b"contents"[..])
+            .await
+            .unwrap_err();
+
+        let entries: Vec<_> = std::fs::read_dir(remote.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["file.txt"]);
+    }
+
+    #[tokio::test]
+    async fn list_skips_only_partial_uploads() {
+        let (_dir, driver) = driver(Durability::Atomic);
+        let remote = driver.path("bucket", Utf8Path::new("file.txt"));
+        tokio::fs::create_dir_all(remote.parent().unwrap())
+            .await
+            .unwrap();
+
+        for name in ["notes.partial", ".notes.partial", ".notes.1-x.partial"] {
+            driver
+                .upload("bucket", Utf8Path::new(name), &mut &b"contents"[..])
+                .await
+                .unwrap();
+        }
+        let partial = LocalDriver::partial_path(&remote);
+        tokio::fs::write(&partial, b"partial").await.unwrap();
+        assert!(LocalDriver::is_partial(partial.file_name().unwrap()));
+
+        let mut files = driver.list("bucket", None).await.unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![".notes.1-x.partial", ".notes.partial", "notes.partial"]
+        );
+    }
+}
@@ -5,9 +5,9 @@ use chrono::{DateTime, Utc};
 use eyre::{eyre, Context};
 use tokio::{io::AsyncWriteExt, sync::RwLock};
 
-use storage_driver::{Driver, Metadata, Reader, StorageError, Writer};
+use storage_driver::{ByteRange, Capabilities, Driver, Metadata, Reader, StorageError, Writer};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct MemoryFileItem {
     created: DateTime<Utc>,
     data: Vec<u8>,
@@ -66,6 +66,134 @@ impl MemoryStorage {
         let mut buckets = self.buckets.write().await;
         buckets.insert(bucket, HashMap::new());
     }
+
+    /// Read the contents of an upload into memory.
+    async fn read_upload(&self, local: &mut Reader<'_>) -> Result<Vec<u8>, StorageError> {
+        let mut buf = Vec::new();
+
+        tokio::io::copy(local, &mut buf)
+            .await
+            .context("copy")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        buf.shutdown()
+            .await
+            .context("shutdown writer")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        Ok(buf)
+    }
+
+    /// Write a snapshot of all buckets to a tar archive.
+    ///
+    /// Each bucket is a top-level directory in the archive, and each file is stored
+    /// at `<bucket>/<path>`. Creation times are preserved with one second resolution.
+    ///
+    /// The files are copied before the archive is written, so other tasks can use the
+    /// storage while `writer` blocks.
+    #[cfg(feature = "snapshot")]
+    pub async fn export<W: std::io::Write>(&self, writer: W) -> Result<W, StorageError> {
+        let buckets: Vec<(String, Vec<(Utf8PathBuf, MemoryFileItem)>)> = {
+            let buckets = self.buckets.read().await;
+            buckets
+                .iter()
+                .map(|(name, bucket)| {
+                    let files = bucket
+                        .iter()
+                        .map(|(path, item)| (path.clone(), item.clone()))
+                        .collect();
+                    (name.clone(), files)
+                })
+                .collect()
+        };
+
+        let mut archive = tar::Builder::new(writer);
+        for (name, bucket) in &buckets {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_mode(0o755);
+            header.set_size(0);
+            archive
+                .append_data(&mut header, name, std::io::empty())
+                .with_context(|| format!("export bucket {name}"))
+                .map_err(|err| StorageError::new(self.name(), err))?;
+
+            for (path, item) in bucket {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(item.data.len() as u64);
+                header.set_mtime(item.created.timestamp().max(0) as u64);
+                archive
+                    .append_data(&mut header, Utf8Path::new(name).join(path), &*item.data)
+                    .with_context(|| format!("export {name}/{path}"))
+                    .map_err(|err| StorageError::new(self.name(), err))?;
+            }
+        }
+
+        archive
+            .into_inner()
+            .context("finish archive")
+            .map_err(|err| StorageError::new(self.name(), err))
+    }
+
+    /// Load a `MemoryStorage` instance from a tar archive written by [`MemoryStorage::export`].
+    #[cfg(feature = "snapshot")]
+    pub fn import<R: std::io::Read>(reader: R) -> Result<Self, StorageError> {
+        use std::io::Read as _;
+
+        let mut buckets: HashMap<String, HashMap<Utf8PathBuf, MemoryFileItem>> = HashMap::new();
+        let mut archive = tar::Archive::new(reader);
+
+        let entries = archive
+            .entries()
+            .context("read archive")
+            .map_err(StorageError::with("memory"))?;
+
+        for entry in entries {
+            let mut entry = entry
+                .context("read archive entry")
+                .map_err(StorageError::with("memory"))?;
+
+            let path = entry
+                .path()
+                .context("archive entry path")
+                .map_err(StorageError::with("memory"))?;
+            let path = Utf8PathBuf::try_from(path.into_owned())
+                .context("archive entry path")
+                .map_err(StorageError::with("memory"))?;
+
+            let mut components = path.components();
+            let Some(bucket) = components.next() else {
+                continue;
+            };
+            let bucket = buckets.entry(bucket.as_str().to_owned()).or_default();
+
+            if entry.header().entry_type().is_dir() {
+                continue;
+            }
+
+            let remote = components.as_path().to_owned();
+            let created = entry
+                .header()
+                .mtime()
+                .ok()
+                .and_then(|mtime| DateTime::from_timestamp(mtime as i64, 0))
+                .unwrap_or_else(Utc::now);
+
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .with_context(|| format!("read {path}"))
+                .map_err(StorageError::with("memory"))?;
+
+            bucket.insert(remote, MemoryFileItem { created, data });
+        }
+
+        Ok(Self {
+            buckets: RwLock::new(buckets),
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -78,6 +206,10 @@ impl Driver for MemoryStorage {
         "memory"
     }
 
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default().with_conditional_writes(true)
+    }
+
     async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
         let buckets = self.buckets.read().await;
         let bucket = buckets
@@ -86,8 +218,7 @@ impl Driver for MemoryStorage {
             .map_err(|err| StorageError::new(self.name(), err))?;
         Ok(bucket
             .get(remote)
-            .ok_or(eyre!("Path Not found: {remote}"))
-            .map_err(|err| StorageError::new(self.name(), err))?
+            .ok_or_else(|| StorageError::not_found(self.name(), remote))?
             .into())
     }
 
@@ -108,20 +239,28 @@ impl Driver for MemoryStorage {
         remote: &Utf8Path,
         local: &mut Reader<'_>,
     ) -> Result<(), StorageError> {
-        let mut buf = Vec::new();
+        let buf = self.read_upload(local).await?;
 
-        tokio::io::copy(local, &mut buf)
-            .await
-            .context("copy")
-            .map_err(|err| StorageError::new(self.name(), err))?;
+        let mut buckets = self.buckets.write().await;
+        let bucket = buckets.entry(bucket.to_string()).or_default();
+        bucket.insert(remote.to_owned(), buf.into());
 
-        buf.shutdown()
-            .await
-            .context("shutdown writer")
-            .map_err(|err| StorageError::new(self.name(), err))?;
+        Ok(())
+    }
+
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        let buf = self.read_upload(local).await?;
 
         let mut buckets = self.buckets.write().await;
         let bucket = buckets.entry(bucket.to_string()).or_default();
+        if bucket.contains_key(remote) {
+            return Err(StorageError::already_exists(self.name(), remote));
+        }
         bucket.insert(remote.to_owned(), buf.into());
 
         Ok(())
@@ -158,6 +297,41 @@ impl Driver for MemoryStorage {
         Ok(())
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        local: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let buckets = self.buckets.read().await;
+        let bucket = buckets
+            .get(bucket)
+            .ok_or(eyre!("Bucket Not found: {bucket}"))
+            .map_err(|err| StorageError::new(self.name(), err))?;
+        let data = bucket
+            .get(remote)
+            .ok_or(eyre!("Path Not found: {remote}"))
+            .map_err(|err| StorageError::new(self.name(), err))?
+            .as_ref();
+
+        let range = range.resolve(data.len() as u64);
+        let mut buf = &data[range.start as usize..range.end as usize];
+
+        tokio::io::copy(&mut buf, local)
+            .await
+            .context("copy")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        local
+            .flush()
+            .await
+            .context("flush")
+            .map_err(|err| StorageError::new(self.name(), err))?;
+
+        Ok(())
+    }
+
     async fn list(
         &self,
         bucket: &str,
@@ -185,3 +359,93 @@ impl Driver for MemoryStorage {
         Ok(paths)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn copy_and_rename() {
+        let storage = MemoryStorage::with_buckets(&["bucket"]);
+
+        // Larger than the buffer between the download and the upload.
+        let contents: Vec<u8> = (0..200_000u32).map(|n| n as u8).collect();
+        storage
+            .upload("bucket", Utf8Path::new("a.bin"), &mut &contents[..])
+            .await
+            .unwrap();
+
+        storage
+            .copy("bucket", Utf8Path::new("a.bin"), Utf8Path::new("b.bin"))
+            .await
+            .unwrap();
+        storage
+            .rename("bucket", Utf8Path::new("a.bin"), Utf8Path::new("c.bin"))
+            .await
+            .unwrap();
+
+        let mut files = storage.list("bucket", None).await.unwrap();
+        files.sort();
+        assert_eq!(files, vec!["b.bin", "c.bin"]);
+
+        for name in ["b.bin", "c.bin"] {
+            let mut buf = Vec::new();
+            storage
+                .download("bucket", Utf8Path::new(name), &mut buf)
+                .await
+                .unwrap();
+            assert_eq!(buf, contents);
+        }
+    }
+
+    #[tokio::test]
+    async fn copy_missing_file() {
+        let storage = MemoryStorage::with_buckets(&["bucket"]);
+        storage
+            .copy("bucket", Utf8Path::new("a.bin"), Utf8Path::new("b.bin"))
+            .await
+            .unwrap_err();
+        assert!(storage.list("bucket", None).await.unwrap().is_empty());
+    }
+
+    #[cfg(feature = "snapshot")]
+    #[tokio::test]
+    async fn export_import_roundtrip() {
+        let storage = MemoryStorage::with_buckets(&["empty"]);
+        storage
+            .upload(
+                "bucket",
+                Utf8Path::new("some/file.txt"),
+                &mut &b"hello world"[..],
+            )
+            .await
+            .unwrap();
+
+        let archive = storage.export(Vec::new()).await.unwrap();
+        let restored = MemoryStorage::import(&archive[..]).unwrap();
+
+        assert!(restored.list("empty", None).await.unwrap().is_empty());
+        assert_eq!(
+            restored.list("bucket", None).await.unwrap(),
+            vec!["some/file.txt".to_owned()]
+        );
+
+        let mut buf = Vec::new();
+        restored
+            .download("bucket", Utf8Path::new("some/file.txt"), &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"hello world");
+
+        let original = storage
+            .metadata("bucket", Utf8Path::new("some/file.txt"))
+            .await
+            .unwrap();
+        let metadata = restored
+            .metadata("bucket", Utf8Path::new("some/file.txt"))
+            .await
+            .unwrap();
+        assert_eq!(metadata.size, original.size);
+        assert_eq!(metadata.created.timestamp(), original.created.timestamp());
+    }
+}
@@ -1,5 +1,8 @@
 //! A storage backend that can use multiple drivers based on the URI scheme,
 //! and possibly the bucket.
+//!
+//! [`MultiStorage`] is also a [`Driver`] itself, routing requests by bucket name,
+//! so that a single [`Storage`] handle can serve buckets from several backends.
 
 #![allow(clippy::needless_pass_by_ref_mut)]
 use std::collections::HashMap;
@@ -7,10 +10,13 @@ use std::collections::HashMap;
 use camino::Utf8Path;
 use eyre::eyre;
 use http::Uri;
-use storage_driver::{Driver, DriverUri, Metadata, StorageError};
+use serde::Deserialize;
+use storage_driver::{
+    ByteRange, Capabilities, Driver, DriverUri, Metadata, Progress, Reader, StorageError, Writer,
+};
 use tokio::io;
 
-use crate::Storage;
+use crate::{Storage, StorageConfig};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Key {
@@ -18,11 +24,44 @@ struct Key {
     bucket: Option<String>,
 }
 
+/// A bucket name, or a bucket prefix when it ends with `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BucketPattern {
+    Exact(String),
+    Prefix(String),
+}
+
+impl BucketPattern {
+    fn new(pattern: &str) -> Self {
+        match pattern.strip_suffix('*') {
+            Some(prefix) => BucketPattern::Prefix(prefix.to_owned()),
+            None => BucketPattern::Exact(pattern.to_owned()),
+        }
+    }
+}
+
+/// Configuration for a single route in a [`MultiStorage`] backend.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StorageRoute {
+    /// The bucket served by this backend. A trailing `*` matches all buckets
+    /// with that prefix. When omitted, the backend serves every bucket which no
+    /// other route matches, and every URI with its scheme. Only one route may
+    /// omit its bucket.
+    #[serde(default)]
+    pub bucket: Option<String>,
+
+    /// The backend to use for this route.
+    pub storage: StorageConfig,
+}
+
 /// A storage backend that can use multiple drivers based on the URI scheme,
 /// and possibly the bucket.
 #[derive(Debug, Default)]
 pub struct MultiStorage {
     drivers: HashMap<Key, Storage>,
+    buckets: Vec<(BucketPattern, Storage)>,
+    fallback: Option<Storage>,
 }
 
 macro_rules! forward_driver {
@@ -64,7 +103,87 @@ impl MultiStorage {
     pub fn new() -> Self {
         Self {
             drivers: HashMap::new(),
+            buckets: Vec::new(),
+            fallback: None,
+        }
+    }
+
+    /// Build a `MultiStorage` instance from a list of routes.
+    pub async fn from_routes(routes: Vec<StorageRoute>) -> Result<Self, StorageError> {
+        let mut multi = Self::new();
+        for route in routes {
+            let storage = route.storage.build_boxed().await?;
+            match route.bucket {
+                Some(bucket) => multi.insert_bucket(&bucket, storage),
+                None if multi.fallback.is_some() => {
+                    return Err(StorageError::new(
+                        "multi driver",
+                        eyre!("Only one storage route may omit its bucket"),
+                    ));
+                }
+                None => {
+                    multi.insert(storage.scheme().to_owned(), None, storage.clone());
+                    multi.fallback = Some(storage);
+                }
+            }
+        }
+        Ok(multi)
+    }
+
+    fn insert(&mut self, scheme: String, bucket: Option<String>, storage: Storage) {
+        assert_ne!(scheme, "file");
+        self.drivers.insert(Key { scheme, bucket }, storage);
+    }
+
+    fn insert_bucket(&mut self, pattern: &str, storage: Storage) {
+        let pattern = BucketPattern::new(pattern);
+        if let BucketPattern::Exact(bucket) = &pattern {
+            self.insert(
+                storage.scheme().to_owned(),
+                Some(bucket.clone()),
+                storage.clone(),
+            );
+        }
+        self.buckets.retain(|(existing, _)| existing != &pattern);
+        self.buckets.push((pattern, storage));
+    }
+
+    /// Add a new driver to the storage backend, serving a single bucket.
+    ///
+    /// If `bucket` ends with `*`, the driver serves all buckets with that prefix.
+    /// Exact bucket names take precedence over prefixes, and longer prefixes take
+    /// precedence over shorter ones.
+    pub fn add_bucket<D>(&mut self, bucket: &str, driver: D)
+    where
+        D: Driver + Send + Sync + 'static,
+    {
+        self.insert_bucket(bucket, driver.into());
+    }
+
+    /// Get the driver which serves a bucket, ignoring URI schemes.
+    pub fn get_bucket(&self, bucket: &str) -> Option<&Storage> {
+        let mut best: Option<(usize, &Storage)> = None;
+        for (pattern, storage) in &self.buckets {
+            match pattern {
+                BucketPattern::Exact(name) if name == bucket => return Some(storage),
+                BucketPattern::Prefix(prefix)
+                    if bucket.starts_with(prefix.as_str())
+                        && !matches!(best, Some((len, _)) if len >= prefix.len()) =>
+                {
+                    best = Some((prefix.len(), storage));
+                }
+                _ => {}
+            }
         }
+        best.map(|(_, storage)| storage)
+    }
+
+    /// The driver which serves a bucket, falling back to the route without a bucket.
+    fn route(&self, bucket: &str) -> Result<&Storage, StorageError> {
+        self.get_bucket(bucket)
+            .or(self.fallback.as_ref())
+            .ok_or_else(|| eyre!("No driver for bucket {bucket}"))
+            .map_err(|err| StorageError::new("multi driver", err))
     }
 
     /// Add a new driver to the storage backend, applicable to all URIs with the
@@ -101,6 +220,10 @@ impl MultiStorage {
             return Ok(Some(s));
         }
 
+        if let Some(s) = bucket.and_then(|bucket| self.get_bucket(bucket)) {
+            return Ok(Some(s));
+        }
+
         Ok(None)
     }
 
@@ -146,6 +269,163 @@ impl MultiStorage {
     }
 }
 
+#[async_trait::async_trait]
+impl Driver for MultiStorage {
+    fn name(&self) -> &'static str {
+        "multi"
+    }
+
+    fn scheme(&self) -> &str {
+        "multi"
+    }
+
+    /// Only the capabilities supported by every backend which serves buckets.
+    fn capabilities(&self) -> Capabilities {
+        self.buckets
+            .iter()
+            .map(|(_, storage)| storage)
+            .chain(self.fallback.as_ref())
+            .map(|storage| storage.driver.capabilities())
+            .reduce(Capabilities::intersection)
+            .unwrap_or_default()
+    }
+
+    async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError> {
+        self.route(bucket)?.driver.delete(bucket, remote).await
+    }
+
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .delete_many(bucket, remotes)
+            .await
+    }
+
+    async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
+        self.route(bucket)?.driver.metadata(bucket, remote).await
+    }
+
+    async fn upload(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .upload(bucket, remote, reader)
+            .await
+    }
+
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .upload_with_progress(bucket, remote, reader, progress)
+            .await
+    }
+
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .upload_if_not_exists(bucket, remote, reader)
+            .await
+    }
+
+    async fn download(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .download(bucket, remote, writer)
+            .await
+    }
+
+    async fn download_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .download_with_progress(bucket, remote, writer, progress)
+            .await
+    }
+
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .download_range(bucket, remote, range, writer)
+            .await
+    }
+
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.route(bucket)?.driver.copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?.driver.rename(bucket, from, to).await
+    }
+
+    async fn download_file(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .download_file(bucket, remote, local)
+            .await
+    }
+
+    async fn upload_file(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.route(bucket)?
+            .driver
+            .upload_file(bucket, remote, local)
+            .await
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: Option<&Utf8Path>,
+    ) -> Result<Vec<String>, StorageError> {
+        self.route(bucket)?.driver.list(bucket, prefix).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use http::Uri;
@@ -203,4 +483,141 @@ mod tests {
     //     assert_eq!(uri.host(), None);
     //     assert_eq!(uri.path(), "/path/to/file");
     // }
+
+    mod routing {
+        use camino::Utf8Path;
+
+        use crate::{MemoryStorage, StorageConfig};
+
+        use super::super::*;
+
+        #[tokio::test]
+        async fn route_by_bucket() {
+            let mut multi = MultiStorage::new();
+            multi.add_bucket("logs", MemoryStorage::with_buckets(&["logs"]));
+            multi.add_bucket("backup-*", MemoryStorage::new());
+            multi.add_bucket("backup-db-*", MemoryStorage::new());
+
+            assert!(multi.get_bucket("logs").is_some());
+            assert!(multi.get_bucket("logs-old").is_none());
+            assert!(multi.get_bucket("backup-www").is_some());
+            assert!(!std::ptr::eq(
+                multi.get_bucket("backup-www").unwrap(),
+                multi.get_bucket("backup-db-main").unwrap()
+            ));
+
+            let uri: Uri = "memory://logs/file.txt".parse().unwrap();
+            assert!(multi.get(&uri).unwrap().is_some());
+
+            let storage = crate::Storage::new(multi);
+            storage
+                .upload(
+                    "backup-db-main",
+                    Utf8Path::new("dump.sql"),
+                    &mut &b"data"[..],
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                storage.list("backup-db-main", None).await.unwrap(),
+                vec!["dump.sql".to_owned()]
+            );
+            assert!(storage.list("backup-www", None).await.is_err());
+            assert!(storage.list("unknown", None).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn from_config() {
+            let multi = MultiStorage::from_routes(vec![
+                StorageRoute {
+                    bucket: Some("first".into()),
+                    storage: StorageConfig::Memory {
+                        bucket: "first".into(),
+                    },
+                },
+                StorageRoute {
+                    bucket: Some("second".into()),
+                    storage: StorageConfig::Memory {
+                        bucket: "second".into(),
+                    },
+                },
+            ])
+            .await
+            .unwrap();
+
+            let storage = crate::Storage::new(multi);
+            assert!(storage.list("first", None).await.unwrap().is_empty());
+            assert!(storage.list("second", None).await.unwrap().is_empty());
+            assert!(storage.list("third", None).await.is_err());
+        }
+
+        #[tokio::test]
+        async fn fallback_route() {
+            let multi = MultiStorage::from_routes(vec![
+                StorageRoute {
+                    bucket: Some("first".into()),
+                    storage: StorageConfig::Memory {
+                        bucket: "first".into(),
+                    },
+                },
+                StorageRoute {
+                    bucket: None,
+                    storage: StorageConfig::Memory {
+                        bucket: "other".into(),
+                    },
+                },
+            ])
+            .await
+            .unwrap();
+
+            let uri: Uri = "memory://other/file.txt".parse().unwrap();
+            assert!(multi.get(&uri).unwrap().is_some());
+
+            let storage = crate::Storage::new(multi);
+            assert!(storage.capabilities().conditional_writes);
+            storage
+                .upload("other", Utf8Path::new("file.txt"), &mut &b"data"[..])
+                .await
+                .unwrap();
+            assert_eq!(
+                storage.list("other", None).await.unwrap(),
+                vec!["file.txt".to_owned()]
+            );
+            assert!(storage.list("first", None).await.unwrap().is_empty());
+
+            let fallback = || StorageRoute {
+                bucket: None,
+                storage: StorageConfig::Memory {
+                    bucket: "other".into(),
+                },
+            };
+            assert!(MultiStorage::from_routes(vec![fallback(), fallback()])
+                .await
+                .is_err());
+        }
+
+        #[tokio::test]
+        async fn conditional_writes() {
+            let mut multi = MultiStorage::new();
+            multi.add_bucket("tags", MemoryStorage::with_buckets(&["tags"]));
+
+            let storage = crate::Storage::new(multi);
+            assert!(storage.capabilities().conditional_writes);
+
+            let remote = Utf8Path::new("latest");
+            storage
+                .upload_if_not_exists("tags", remote, &mut &b"first"[..])
+                .await
+                .unwrap();
+            let err = storage
+                .upload_if_not_exists("tags", remote, &mut &b"second"[..])
+                .await
+                .unwrap_err();
+            assert!(err.is_already_exists());
+
+            let mut buf = Vec::new();
+            storage.download("tags", remote, &mut buf).await.unwrap();
+            assert_eq!(buf, b"first");
+        }
+    }
 }
@@ -3,7 +3,7 @@ use eyre::WrapErr;
 use tempfile::TempDir;
 
 use crate::local::LocalDriver;
-use storage_driver::{Driver, Metadata, Reader, StorageError, Writer};
+use storage_driver::{ByteRange, Capabilities, Driver, Metadata, Reader, StorageError, Writer};
 
 /// A storage driver that stores files in a temporary directory.
 #[derive(Debug)]
@@ -44,6 +44,10 @@ impl Driver for TempDriver {
         "tmp"
     }
 
+    fn capabilities(&self) -> Capabilities {
+        self.driver.capabilities()
+    }
+
     async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
         self.driver.metadata(bucket, remote).await
     }
@@ -52,6 +56,10 @@ impl Driver for TempDriver {
         self.driver.delete(bucket, remote).await
     }
 
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        self.driver.delete_many(bucket, remotes).await
+    }
+
     async fn upload(
         &self,
         bucket: &str,
@@ -60,6 +68,17 @@ impl Driver for TempDriver {
     ) -> Result<(), StorageError> {
         self.driver.upload(bucket, remote, local).await
     }
+
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        local: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        self.driver
+            .upload_if_not_exists(bucket, remote, local)
+            .await
+    }
     async fn download(
         &self,
         bucket: &str,
@@ -69,6 +88,31 @@ impl Driver for TempDriver {
         self.driver.download(bucket, remote, local).await
     }
 
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        local: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        self.driver
+            .download_range(bucket, remote, range, local)
+            .await
+    }
+
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.driver.copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.driver.rename(bucket, from, to).await
+    }
+
     async fn list(
         &self,
         bucket: &str,
@@ -0,0 +1,424 @@
+//! Bandwidth limits for any storage driver.
+//!
+//! Uploads and downloads are throttled with a token bucket, which allows a burst
+//! of up to one second's worth of bytes before slowing down to the limit.
+
+use std::future::Future as _;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use camino::Utf8Path;
+use tokio::io::{self, AsyncBufRead as _};
+use tokio::time::{Instant, Sleep};
+
+use storage_driver::{
+    ByteRange, Capabilities, Driver, Metadata, Progress, Reader, StorageError, Writer,
+};
+
+const THROTTLED_STORAGE_NAME: &str = "throttled";
+
+/// Smallest number of bytes worth waiting for, so that slow transfers don't wake
+/// up for every byte.
+const MIN_CHUNK: u64 = 8 * 1024;
+
+/// A token bucket, refilled at a fixed rate.
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(bytes_per_second: u64) -> Self {
+        let rate = bytes_per_second.max(1) as f64;
+        Self {
+            rate,
+            capacity: rate,
+            tokens: rate,
+            updated: Instant::now(),
+        }
+    }
+
+    /// The number of bytes which can be sent now, or how long to wait before
+    /// enough bytes can be sent.
+    fn available(&mut self) -> Result<usize, Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+
+        let wanted = self.capacity.min(MIN_CHUNK as f64);
+        if self.tokens >= wanted {
+            Ok(self.tokens as usize)
+        } else {
+            let wait = Duration::from_secs_f64((wanted - self.tokens) / self.rate);
+            Err(wait.max(Duration::from_millis(1)))
+        }
+    }
+
+    /// Spend `amount` bytes. Concurrent transfers may overdraw the bucket, which
+    /// then makes every transfer sharing it wait longer.
+    fn take(&mut self, amount: usize) {
+        self.tokens -= amount as f64;
+    }
+}
+
+type SharedBucket = Arc<Mutex<Bucket>>;
+
+/// A bandwidth limit for uploads and downloads.
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    bytes_per_second: u64,
+    shared: Option<SharedBucket>,
+}
+
+impl RateLimit {
+    /// Limit all transfers through this limit, together, to `bytes_per_second`.
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            shared: Some(Arc::new(Mutex::new(Bucket::new(bytes_per_second)))),
+        }
+    }
+
+    /// Limit each transfer to `bytes_per_second`, however many run at once.
+    pub fn per_transfer(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            shared: None,
+        }
+    }
+
+    /// The limit, in bytes per second.
+    pub fn bytes_per_second(&self) -> u64 {
+        self.bytes_per_second
+    }
+
+    /// The bucket a new transfer draws from.
+    fn bucket(&self) -> SharedBucket {
+        match &self.shared {
+            Some(bucket) => bucket.clone(),
+            None => Arc::new(Mutex::new(Bucket::new(self.bytes_per_second))),
+        }
+    }
+}
+
+/// Waits for a bucket to allow more bytes.
+struct Throttle {
+    bucket: SharedBucket,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Throttle {
+    fn new(limit: &RateLimit) -> Self {
+        Self {
+            bucket: limit.bucket(),
+            sleep: None,
+        }
+    }
+
+    fn poll_available(&mut self, cx: &mut Context<'_>) -> Poll<usize> {
+        loop {
+            if let Some(sleep) = &mut self.sleep {
+                std::task::ready!(sleep.as_mut().poll(cx));
+                self.sleep = None;
+            }
+
+            match self.bucket.lock().unwrap().available() {
+                Ok(available) => return Poll::Ready(available),
+                Err(wait) => self.sleep = Some(Box::pin(tokio::time::sleep(wait))),
+            }
+        }
+    }
+
+    fn take(&self, amount: usize) {
+        self.bucket.lock().unwrap().take(amount);
+    }
+}
+
+/// A reader which only produces bytes as fast as a rate limit allows.
+struct ThrottledReader<'r, 'a> {
+    inner: &'r mut Reader<'a>,
+    throttle: Throttle,
+}
+
+impl io::AsyncBufRead for ThrottledReader<'_, '_> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8], io::Error>> {
+        let this = self.get_mut();
+        let buf = std::task::ready!(Pin::new(&mut *this.inner).poll_fill_buf(cx))?;
+        if buf.is_empty() {
+            // Don't wait for the bucket just to report the end of the reader.
+            return Poll::Ready(Ok(buf));
+        }
+        let available = std::task::ready!(this.throttle.poll_available(cx));
+        Poll::Ready(Ok(&buf[..buf.len().min(available)]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        Pin::new(&mut *this.inner).consume(amt);
+        this.throttle.take(amt);
+    }
+}
+
+impl io::AsyncRead for ThrottledReader<'_, '_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut io::ReadBuf<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        let available = std::task::ready!(self.as_mut().poll_fill_buf(cx))?;
+        let amount = available.len().min(buf.remaining());
+        buf.put_slice(&available[..amount]);
+        self.consume(amount);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A writer which only accepts bytes as fast as a rate limit allows.
+struct ThrottledWriter<'r, 'w> {
+    inner: &'r mut Writer<'w>,
+    throttle: Throttle,
+}
+
+impl io::AsyncWrite for ThrottledWriter<'_, '_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let available = std::task::ready!(this.throttle.poll_available(cx));
+        let written = std::task::ready!(
+            Pin::new(&mut *this.inner).poll_write(cx, &buf[..buf.len().min(available)])
+        )?;
+        this.throttle.take(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut *self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// A driver which limits the bandwidth of uploads and downloads through an inner driver.
+#[derive(Debug)]
+pub struct ThrottledDriver<D> {
+    inner: D,
+    limit: RateLimit,
+}
+
+impl<D> ThrottledDriver<D> {
+    /// Wrap a driver, limiting its transfers to `limit`.
+    pub fn new(inner: D, limit: RateLimit) -> Self {
+        Self { inner, limit }
+    }
+
+    /// The wrapped driver.
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    fn reader<'r, 'a>(&self, inner: &'r mut Reader<'a>) -> ThrottledReader<'r, 'a> {
+        ThrottledReader {
+            inner,
+            throttle: Throttle::new(&self.limit),
+        }
+    }
+
+    fn writer<'r, 'w>(&self, inner: &'r mut Writer<'w>) -> ThrottledWriter<'r, 'w> {
+        ThrottledWriter {
+            inner,
+            throttle: Throttle::new(&self.limit),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<D> Driver for ThrottledDriver<D>
+where
+    D: Driver + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        THROTTLED_STORAGE_NAME
+    }
+
+    fn scheme(&self) -> &str {
+        self.inner.scheme()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+
+    async fn delete(&self, bucket: &str, remote: &Utf8Path) -> Result<(), StorageError> {
+        self.inner.delete(bucket, remote).await
+    }
+
+    async fn delete_many(&self, bucket: &str, remotes: &[&Utf8Path]) -> Result<(), StorageError> {
+        self.inner.delete_many(bucket, remotes).await
+    }
+
+    async fn metadata(&self, bucket: &str, remote: &Utf8Path) -> Result<Metadata, StorageError> {
+        self.inner.metadata(bucket, remote).await
+    }
+
+    async fn upload(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        let mut reader = self.reader(reader);
+        self.inner.upload(bucket, remote, &mut reader).await
+    }
+
+    async fn upload_if_not_exists(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+    ) -> Result<(), StorageError> {
+        let mut reader = self.reader(reader);
+        self.inner
+            .upload_if_not_exists(bucket, remote, &mut reader)
+            .await
+    }
+
+    async fn upload_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        reader: &mut Reader<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        let mut reader = self.reader(reader);
+        self.inner
+            .upload_with_progress(bucket, remote, &mut reader, progress)
+            .await
+    }
+
+    async fn download(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let mut writer = self.writer(writer);
+        self.inner.download(bucket, remote, &mut writer).await
+    }
+
+    async fn download_range(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        range: ByteRange,
+        writer: &mut Writer<'_>,
+    ) -> Result<(), StorageError> {
+        let mut writer = self.writer(writer);
+        self.inner
+            .download_range(bucket, remote, range, &mut writer)
+            .await
+    }
+
+    async fn download_with_progress(
+        &self,
+        bucket: &str,
+        remote: &Utf8Path,
+        writer: &mut Writer<'_>,
+        progress: &Progress,
+    ) -> Result<(), StorageError> {
+        let mut writer = self.writer(writer);
+        self.inner
+            .download_with_progress(bucket, remote, &mut writer, progress)
+            .await
+    }
+
+    /// Copies are made by the inner driver, which doesn't send file contents through
+    /// the client when it can copy server-side, so they aren't throttled.
+    async fn copy(&self, bucket: &str, from: &Utf8Path, to: &Utf8Path) -> Result<(), StorageError> {
+        self.inner.copy(bucket, from, to).await
+    }
+
+    async fn rename(
+        &self,
+        bucket: &str,
+        from: &Utf8Path,
+        to: &Utf8Path,
+    ) -> Result<(), StorageError> {
+        self.inner.rename(bucket, from, to).await
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: Option<&Utf8Path>,
+    ) -> Result<Vec<String>, StorageError> {
+        self.inner.list(bucket, prefix).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MemoryStorage;
+
+    use super::*;
+
+    async fn upload_twice(limit: RateLimit) -> Duration {
+        let driver = ThrottledDriver::new(MemoryStorage::with_buckets(&["bucket"]), limit);
+        let data = vec![0u8; 32 * 1024];
+
+        let start = Instant::now();
+        for remote in ["a", "b"] {
+            driver
+                .upload("bucket", Utf8Path::new(remote), &mut data.as_slice())
+                .await
+                .unwrap();
+        }
+        start.elapsed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_limit() {
+        // The first 32KiB is a burst, the second is limited to 32KiB/s.
+        let elapsed = upload_twice(RateLimit::new(32 * 1024)).await;
+        assert!(elapsed >= Duration::from_millis(990), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(1100), "{elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn per_transfer_limit() {
+        let elapsed = upload_twice(RateLimit::per_transfer(32 * 1024)).await;
+        assert!(elapsed < Duration::from_millis(10), "{elapsed:?}");
+
+        let elapsed = upload_twice(RateLimit::per_transfer(16 * 1024)).await;
+        assert!(elapsed >= Duration::from_millis(1990), "{elapsed:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn throttled_download() {
+        let memory = MemoryStorage::with_buckets(&["bucket"]);
+        memory
+            .upload("bucket", Utf8Path::new("a"), &mut &[1u8; 24 * 1024][..])
+            .await
+            .unwrap();
+        let driver = ThrottledDriver::new(memory, RateLimit::new(8 * 1024));
+
+        let start = Instant::now();
+        let mut output = Vec::new();
+        driver
+            .download("bucket", Utf8Path::new("a"), &mut output)
+            .await
+            .unwrap();
+        assert_eq!(output.len(), 24 * 1024);
+        assert!(start.elapsed() >= Duration::from_millis(1990));
+    }
+}
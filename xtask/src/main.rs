@@ -0,0 +1,220 @@
+//! Development tasks for the emporium workspace, run with `cargo xtask`.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+use clap::{Parser, Subcommand};
+use eyre::{eyre, WrapErr as _};
+
+mod matrix;
+
+use crate::matrix::MATRIX;
+
+/// Development tasks for the emporium workspace.
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(subcommand)]
+    command: Task,
+
+    /// Print the commands which would be run, without running them.
+    #[arg(long, global = true)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Task {
+    /// Build and test the workspace with all features, then each combination
+    /// in the feature matrix, then check the workspace with the MSRV toolchain.
+    Ci {
+        /// Skip the MSRV check, e.g. when that toolchain is not installed.
+        #[arg(long)]
+        skip_msrv: bool,
+    },
+
+    /// Build and test each combination in the feature matrix.
+    Features,
+
+    /// Check the workspace with the MSRV toolchain.
+    Msrv,
+}
+
+/// A cargo command, run from the root of the workspace.
+#[derive(Debug)]
+struct Step {
+    name: String,
+    toolchain: Option<String>,
+    args: Vec<OsString>,
+}
+
+impl Step {
+    fn new<S: Into<String>>(name: S, args: &[&str]) -> Self {
+        Self {
+            name: name.into(),
+            toolchain: None,
+            args: args.iter().map(OsString::from).collect(),
+        }
+    }
+
+    fn with_toolchain(mut self, toolchain: &str) -> Self {
+        self.toolchain = Some(toolchain.to_owned());
+        self
+    }
+
+    fn with_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    fn command(&self, root: &Path) -> Command {
+        let mut command = match &self.toolchain {
+            // Toolchain overrides only work through the rustup proxy.
+            Some(toolchain) => {
+                let mut command = Command::new("cargo");
+                command.arg(format!("+{toolchain}"));
+                command
+            }
+            None => Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into())),
+        };
+        command.current_dir(root).args(&self.args);
+        command
+    }
+
+    fn run(&self, root: &Path, dry_run: bool) -> eyre::Result<bool> {
+        eprintln!("==> {self}");
+        if dry_run {
+            return Ok(true);
+        }
+
+        let status = self
+            .command(root)
+            .status()
+            .wrap_err_with(|| format!("run {self}"))?;
+        Ok(status.success())
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: cargo", self.name)?;
+        if let Some(toolchain) = &self.toolchain {
+            write!(f, " +{toolchain}")?;
+        }
+        for arg in &self.args {
+            write!(f, " {}", arg.to_string_lossy())?;
+        }
+        Ok(())
+    }
+}
+
+/// The root of the workspace, which contains this crate.
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask is inside the workspace")
+        .to_path_buf()
+}
+
+/// The minimum supported rust version, from `workspace.package.rust-version`.
+fn msrv(root: &Path) -> eyre::Result<String> {
+    let path = root.join("Cargo.toml");
+    let manifest: toml::Table = std::fs::read_to_string(&path)
+        .wrap_err_with(|| format!("read {}", path.display()))?
+        .parse()
+        .wrap_err_with(|| format!("parse {}", path.display()))?;
+
+    manifest
+        .get("workspace")
+        .and_then(|workspace| workspace.get("package"))
+        .and_then(|package| package.get("rust-version"))
+        .and_then(toml::Value::as_str)
+        .map(str::to_owned)
+        .ok_or_else(|| eyre!("{} has no workspace.package.rust-version", path.display()))
+}
+
+fn workspace_steps() -> Vec<Step> {
+    vec![
+        Step::new(
+            "workspace",
+            &["check", "--workspace", "--all-targets", "--all-features"],
+        ),
+        Step::new("workspace", &["test", "--workspace", "--all-features"]),
+    ]
+}
+
+fn feature_steps() -> Vec<Step> {
+    MATRIX
+        .iter()
+        .map(|combination| {
+            Step::new(
+                format!("{} with {}", combination.package, combination.features),
+                &[
+                    "test",
+                    "--package",
+                    combination.package,
+                    "--target-dir",
+                    "target/xtask",
+                ],
+            )
+            .with_args(combination.features.args())
+        })
+        .collect()
+}
+
+fn msrv_steps(root: &Path) -> eyre::Result<Vec<Step>> {
+    let msrv = msrv(root)?;
+    Ok(vec![Step::new(
+        format!("MSRV {msrv}"),
+        &[
+            "check",
+            "--workspace",
+            "--all-targets",
+            "--all-features",
+            "--target-dir",
+            "target/msrv",
+        ],
+    )
+    .with_toolchain(&msrv)])
+}
+
+fn main() -> eyre::Result<ExitCode> {
+    let args = Args::parse();
+    let root = workspace_root();
+
+    let steps = match args.command {
+        Task::Ci { skip_msrv } => {
+            let mut steps = workspace_steps();
+            steps.extend(feature_steps());
+            if !skip_msrv {
+                steps.extend(msrv_steps(&root)?);
+            }
+            steps
+        }
+        Task::Features => feature_steps(),
+        Task::Msrv => msrv_steps(&root)?,
+    };
+
+    // Run every step, so one broken combination doesn't hide another.
+    let mut failed = Vec::new();
+    for step in &steps {
+        if !step.run(&root, args.dry_run)? {
+            failed.push(step);
+        }
+    }
+
+    if failed.is_empty() {
+        eprintln!("{} steps passed", steps.len());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    eprintln!("{} of {} steps failed:", failed.len(), steps.len());
+    for step in failed {
+        eprintln!("  {step}");
+    }
+    Ok(ExitCode::FAILURE)
+}
@@ -0,0 +1,165 @@
+//! The feature combinations which are built and tested in CI.
+//!
+//! Feature-gated code is only compiled when its feature is enabled, so each
+//! optional backend is tested on its own, as well as without any optional
+//! features, and in the combinations the facade crates use together.
+
+use std::fmt;
+
+/// Which features to enable for a package.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Features {
+    /// The default features.
+    Default,
+
+    /// No features at all, including the defaults.
+    None,
+
+    /// Only these features, without the defaults.
+    Only(&'static [&'static str]),
+}
+
+impl Features {
+    /// Arguments to pass to cargo to select these features.
+    pub(crate) fn args(&self) -> Vec<String> {
+        match self {
+            Features::Default => Vec::new(),
+            Features::None => vec!["--no-default-features".into()],
+            Features::Only(features) => vec![
+                "--no-default-features".into(),
+                "--features".into(),
+                features.join(","),
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Features::Default => f.write_str("default features"),
+            Features::None => f.write_str("no features"),
+            Features::Only(features) => write!(f, "features {}", features.join(",")),
+        }
+    }
+}
+
+/// A package, built with a set of features.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Combination {
+    /// The name of the package.
+    pub(crate) package: &'static str,
+
+    /// The features to enable.
+    pub(crate) features: Features,
+}
+
+const fn combination(package: &'static str, features: Features) -> Combination {
+    Combination { package, features }
+}
+
+/// Every combination of features which is tested, in addition to the whole
+/// workspace with all features enabled.
+pub(crate) const MATRIX: &[Combination] = &[
+    // Each storage backend alone.
+    combination("storage", Features::None),
+    combination("storage", Features::Only(&["b2"])),
+    combination("storage", Features::Only(&["local"])),
+    combination("storage", Features::Only(&["tmp"])),
+    combination("storage", Features::Only(&["snapshot"])),
+    combination("storage", Features::Only(&["encryption"])),
+    combination("storage", Features::Default),
+    combination("api-client", Features::None),
+    combination("api-client", Features::Only(&["compression"])),
+    combination("octocat", Features::None),
+    combination("octocat", Features::Only(&["age"])),
+    combination("tailscale", Features::None),
+    combination("tailscale", Features::Only(&["linode"])),
+    // Facades, with each service alone, and as they are used together.
+    combination("emporium-inventory", Features::None),
+    combination("emporium-inventory", Features::Only(&["b2"])),
+    combination("emporium-inventory", Features::Only(&["linode"])),
+    combination("emporium-inventory", Features::Only(&["octocat"])),
+    combination("emporium-inventory", Features::Only(&["tailscale"])),
+    combination("emporium-reconcile", Features::None),
+    combination("emporium-reconcile", Features::Only(&["b2"])),
+    combination("emporium-reconcile", Features::Only(&["linode"])),
+    combination("emporium-reconcile", Features::Only(&["b2", "linode"])),
+    combination("emporium-report", Features::None),
+    combination("emporium-report", Features::Only(&["b2"])),
+    combination("emporium-report", Features::Only(&["bookshelf"])),
+    combination("emporium-report", Features::Only(&["octocat"])),
+    combination("emporium-report", Features::Only(&["b2", "bookshelf"])),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// Map package names to their manifests, for every member of the workspace.
+    fn manifests() -> BTreeMap<String, toml::Table> {
+        let root = crate::workspace_root();
+        let workspace: toml::Table =
+            toml::from_str(&std::fs::read_to_string(root.join("Cargo.toml")).unwrap()).unwrap();
+
+        workspace["workspace"]["members"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| {
+                let path = root.join(member.as_str().unwrap()).join("Cargo.toml");
+                let manifest: toml::Table =
+                    toml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+                let name = manifest["package"]["name"].as_str().unwrap().to_owned();
+                (name, manifest)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matrix_matches_manifests() {
+        let manifests = manifests();
+
+        for combination in MATRIX {
+            let manifest = manifests
+                .get(combination.package)
+                .unwrap_or_else(|| panic!("{} is not a workspace member", combination.package));
+
+            if let Features::Only(features) = combination.features {
+                for feature in features {
+                    assert!(
+                        manifest
+                            .get("features")
+                            .and_then(|features| features.get(*feature))
+                            .is_some(),
+                        "{} has no feature {feature}",
+                        combination.package
+                    );
+                }
+            }
+        }
+
+        // Every feature of every package should be tested on its own.
+        for (package, manifest) in &manifests {
+            let Some(features) = manifest.get("features").and_then(toml::Value::as_table) else {
+                continue;
+            };
+
+            for feature in features
+                .keys()
+                .filter(|feature| feature.as_str() != "default")
+            {
+                let tested = MATRIX.iter().any(|combination| {
+                    combination.package == package
+                        && matches!(combination.features, Features::Only([only]) if *only == feature.as_str())
+                });
+                assert!(
+                    tested,
+                    "{package} feature {feature} is not tested on its own"
+                );
+            }
+        }
+    }
+}